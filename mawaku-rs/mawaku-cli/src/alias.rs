@@ -0,0 +1,127 @@
+use crate::RunStatus;
+use clap::Subcommand;
+use log::{error, info};
+use mawaku_config::{Config, load_or_init, update};
+use mawaku_utils::trimmed_or_none;
+
+/// Actions of the `alias` subcommand.
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+pub(crate) enum AliasCommand {
+    /// Store NAME as a short name for LOCATION, replacing any alias with the
+    /// same name.
+    Add { name: String, location: String },
+    /// Print every alias and the location it expands to.
+    List,
+    /// Forget the alias NAME.
+    Remove { name: String },
+}
+
+/// Store `location` under `name`, dropping aliases whose names differ only in
+/// case.
+pub(crate) fn add_alias(config: &mut Config, name: &str, location: &str) {
+    remove_alias(config, name);
+    config
+        .aliases
+        .insert(name.to_string(), location.to_string());
+}
+
+/// Remove the alias `name`, matched ignoring case. Returns whether one existed.
+pub(crate) fn remove_alias(config: &mut Config, name: &str) -> bool {
+    let before = config.aliases.len();
+    config
+        .aliases
+        .retain(|alias, _| !alias.trim().eq_ignore_ascii_case(name.trim()));
+    config.aliases.len() != before
+}
+
+/// One `name = location` line per alias, sorted by name.
+pub(crate) fn alias_lines(config: &Config) -> Vec<String> {
+    config
+        .aliases
+        .iter()
+        .map(|(name, location)| format!("{name} = {location}"))
+        .collect()
+}
+
+/// Replace each location naming an alias with its stored location, noting
+/// every expansion in `infos`. Other locations pass through unchanged.
+pub(crate) fn expand(
+    config: &Config,
+    locations: Vec<String>,
+    infos: &mut Vec<String>,
+) -> Vec<String> {
+    locations
+        .into_iter()
+        .map(|location| match config.alias(&location) {
+            Some(expanded) => {
+                infos.push(format!(
+                    "Expanded location alias `{}` to \"{expanded}\".",
+                    location.trim()
+                ));
+                expanded.to_string()
+            }
+            None => location,
+        })
+        .collect()
+}
+
+pub(crate) fn run(command: &AliasCommand) -> RunStatus {
+    let outcome = match load_or_init() {
+        Ok(outcome) => outcome,
+        Err(error) => {
+            error!("failed to load Mawaku configuration ({error}).");
+            return RunStatus::ConfigFailure;
+        }
+    };
+
+    match command {
+        AliasCommand::List => {
+            let lines = alias_lines(&outcome.config);
+            if lines.is_empty() {
+                info!("No location aliases in {}.", outcome.path.display());
+            }
+            for line in lines {
+                println!("{line}");
+            }
+            RunStatus::Success
+        }
+        AliasCommand::Add { name, location } => {
+            let (Some(name), Some(location)) =
+                (trimmed_or_none(Some(name)), trimmed_or_none(Some(location)))
+            else {
+                error!("an alias needs a non-empty name and location.");
+                return RunStatus::ConfigFailure;
+            };
+            match update(&outcome.path, |config| add_alias(config, name, location)) {
+                Ok(()) => {
+                    info!("Alias `{name}` now expands to \"{location}\".");
+                    RunStatus::Success
+                }
+                Err(error) => {
+                    error!("failed to store the alias ({error}).");
+                    RunStatus::ConfigFailure
+                }
+            }
+        }
+        AliasCommand::Remove { name } => {
+            let mut removed = false;
+            let result = update(&outcome.path, |config| {
+                removed = remove_alias(config, name);
+            });
+            match result {
+                Ok(()) if removed => {
+                    info!("Removed alias `{}`.", name.trim());
+                    RunStatus::Success
+                }
+                Ok(()) => {
+                    error!("no alias named `{}`.", name.trim());
+                    RunStatus::ConfigFailure
+                }
+                Err(error) => {
+                    error!("failed to remove the alias ({error}).");
+                    RunStatus::ConfigFailure
+                }
+            }
+        }
+    }
+}
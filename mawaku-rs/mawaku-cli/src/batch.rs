@@ -0,0 +1,138 @@
+use crate::{RunContext, SceneOutcome, build_caption, failures, render_scene};
+use failures::FailedScene;
+use log::{error, info, warn};
+use mawaku_gemini::{ImageGenerator, SeasonPreset, TextGenerator};
+use mawaku_utils::{ImageNameContext, trimmed_or_none};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::thread;
+
+/// Render every scene with at most `concurrency` scenes in flight. Workers
+/// share the generators, and so the client's connection pool. Entries are
+/// `None` when a worker panicked before finishing that scene.
+pub(crate) fn render(
+    images: &dyn ImageGenerator,
+    text: &dyn TextGenerator,
+    scenes: &[(RunContext, ImageNameContext)],
+    concurrency: usize,
+) -> Vec<Option<SceneOutcome>> {
+    let next = AtomicUsize::new(0);
+    let workers = concurrency.clamp(1, scenes.len().max(1));
+    let mut outcomes: Vec<Option<SceneOutcome>> = scenes.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some((context, image_name_context)) = scenes.get(index) else {
+                            break;
+                        };
+                        info!("Rendering {}...", context.location);
+                        let outcome =
+                            render_scene(images, text, context, image_name_context, false);
+                        finished.push((index, outcome));
+                    }
+                    finished
+                })
+            })
+            .collect();
+
+        // Join every worker so a panic in one is reported rather than
+        // propagated when the scope ends.
+        for handle in handles {
+            match handle.join() {
+                Ok(finished) => {
+                    for (index, outcome) in finished {
+                        outcomes[index] = Some(outcome);
+                    }
+                }
+                Err(_) => error!("a batch worker panicked; its scenes were not finished."),
+            }
+        }
+    });
+
+    outcomes
+}
+
+/// A copy of `context` narrowed to a single location for batch rendering.
+pub(crate) fn scene_context(context: &RunContext, location: &str) -> RunContext {
+    let mut scene = context.clone();
+    scene.location = location.to_string();
+    scene.locations = vec![location.to_string()];
+    if scene.caption.is_some() {
+        scene.caption = Some(build_caption(
+            location,
+            scene.season.as_deref(),
+            scene.time_of_day.as_deref(),
+            scene.weather.as_deref(),
+        ));
+    }
+    scene
+}
+
+/// A batch scene context using the flags recorded for `scene`. Season and
+/// time of day fall back to the values `context` resolved, so configured
+/// defaults still apply to scenes recorded without them.
+pub(crate) fn recorded_scene_context(context: &RunContext, scene: &FailedScene) -> RunContext {
+    let season_preset = scene.season_preset();
+    let (season, season_fragment) = match trimmed_or_none(scene.season.as_deref()) {
+        Some(season) => (
+            Some(season.to_string()),
+            season_preset.map(SeasonPreset::fragment),
+        ),
+        None => match season_preset {
+            Some(preset) => (Some(preset.as_str().to_string()), Some(preset.fragment())),
+            None => (context.season.clone(), context.season_fragment),
+        },
+    };
+    let flagged = RunContext {
+        season,
+        season_fragment,
+        time_of_day: trimmed_or_none(scene.time_of_day.as_deref())
+            .map(str::to_string)
+            .or_else(|| context.time_of_day.clone()),
+        weather: scene.weather.clone(),
+        theme: scene.theme(),
+        ..context.clone()
+    };
+    scene_context(&flagged, &scene.location)
+}
+
+/// Update the failures log after a batch: scenes that saved no image are
+/// recorded for `--retry-failed`, and the others are cleared.
+pub(crate) fn record_failures(
+    path: &Path,
+    scenes: &[FailedScene],
+    outcomes: &[Option<SceneOutcome>],
+) {
+    let failed: Vec<FailedScene> = scenes
+        .iter()
+        .zip(outcomes)
+        .filter(|(_, outcome)| outcome.as_ref().is_none_or(|o| o.saved_paths().is_empty()))
+        .map(|(scene, _)| scene.clone())
+        .collect();
+    match failures::record(path, scenes, &failed) {
+        Ok(()) if !failed.is_empty() => info!(
+            "Recorded {} failed scene(s) in {}; rerun them with --retry-failed.",
+            failed.len(),
+            path.display()
+        ),
+        Ok(()) => {}
+        Err(error) => warn!("failed to update {} ({error}).", path.display()),
+    }
+}
+
+pub(crate) fn format_line(location: &str, outcome: Option<&SceneOutcome>) -> String {
+    match outcome {
+        Some(outcome) if !outcome.saved_paths().is_empty() => {
+            let count = outcome.saved_files().len();
+            let images = if count == 1 { "image" } else { "images" };
+            format!("{location}: {count} {images} saved")
+        }
+        Some(outcome) if outcome.offline => format!("{location}: skipped (offline)"),
+        _ => format!("{location}: failed"),
+    }
+}
@@ -0,0 +1,52 @@
+use crate::RunContext;
+use mawaku_config::{Config, cache_directory};
+use mawaku_gemini::{ImageCache, PersonGeneration, PlaceDescriptionCache};
+use std::time::Duration;
+
+/// The image cache key: the exact prompt plus every request parameter that
+/// shapes the images.
+pub(crate) fn image_key(context: &RunContext, prompt: &str, aspect_ratio: Option<&str>) -> String {
+    let mut parameters = vec![
+        aspect_ratio,
+        context.image_model.as_deref(),
+        context.negative_prompt.as_deref(),
+        context.person_generation.map(PersonGeneration::as_str),
+    ];
+    // Only seeded requests add a part, so unseeded keys stay unchanged.
+    let seed = context.seed.map(|seed| seed.to_string());
+    if seed.is_some() {
+        parameters.push(seed.as_deref());
+    }
+    ImageCache::key(prompt, &parameters)
+}
+
+pub(crate) fn description_key(context: &RunContext) -> String {
+    PlaceDescriptionCache::key(
+        &context.location,
+        context.season.as_deref(),
+        context.time_of_day.as_deref(),
+        context.weather.as_deref(),
+    )
+}
+
+/// The image cache when enabled by `--cache-images` or `cache.images`.
+pub(crate) fn image_cache(config: &Config, flag: bool) -> Option<ImageCache> {
+    if !flag && !config.cache.images {
+        return None;
+    }
+
+    cache_directory()
+        .ok()
+        .map(|dir| ImageCache::new(dir.join("images")))
+}
+
+pub(crate) fn description_cache(config: &Config) -> Option<PlaceDescriptionCache> {
+    let ttl_secs = config.cache.description_ttl_secs;
+    if ttl_secs == 0 {
+        return None;
+    }
+
+    cache_directory()
+        .ok()
+        .map(|dir| PlaceDescriptionCache::new(dir, Duration::from_secs(ttl_secs)))
+}
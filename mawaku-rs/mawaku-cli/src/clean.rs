@@ -0,0 +1,272 @@
+use crate::RunStatus;
+use clap::Args;
+use log::{error, info, warn};
+use mawaku_config::load_or_init;
+use mawaku_utils::{
+    DEFAULT_FILE_NAME_PREFIX, DEFAULT_RANDOM_SUFFIX_LENGTH, DEFAULT_SUFFIX_ALPHABET,
+    parse_image_name,
+};
+use std::fs;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+
+/// Extensions Mawaku writes generated images with.
+const IMAGE_EXTENSIONS: &[&str] = &["png", "jpg", "webp", "gif", "bin"];
+
+/// Extension of the raw payloads written by `--save-raw`.
+const RAW_PAYLOAD_EXTENSION: &str = "b64";
+
+/// Options of the `clean` subcommand.
+#[derive(Args, Debug, Clone, PartialEq, Eq)]
+#[group(required = true, multiple = true, args = ["older_than", "keep"])]
+pub(crate) struct CleanArgs {
+    /// Remove images older than this age, e.g. `30d`, `12h`, `2w`.
+    #[arg(long = "older-than", value_name = "AGE", value_parser = parse_age)]
+    pub(crate) older_than: Option<Duration>,
+    /// Keep only the N most recent images. Combined with `--older-than`, an
+    /// image must match both to be removed.
+    #[arg(long, value_name = "N")]
+    pub(crate) keep: Option<usize>,
+    /// File name prefix to match instead of the configured `file_name_prefix`.
+    #[arg(long, value_name = "PREFIX", value_parser = crate::parse_file_name_prefix)]
+    pub(crate) prefix: Option<String>,
+    /// Directory to clean instead of the configured `image_output_dir`.
+    #[arg(long, value_name = "DIR")]
+    pub(crate) dir: Option<PathBuf>,
+    /// List what would be removed without deleting anything.
+    #[arg(long = "dry-run")]
+    pub(crate) dry_run: bool,
+    /// Delete without asking for confirmation.
+    #[arg(short, long)]
+    pub(crate) yes: bool,
+}
+
+/// A file recognized as a Mawaku prediction by its name.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct GeneratedImage {
+    pub(crate) path: PathBuf,
+    pub(crate) modified: SystemTime,
+    /// The `--save-raw` payload saved next to the image, removed with it.
+    pub(crate) raw_path: Option<PathBuf>,
+}
+
+/// Parse an age such as `30d`: a whole number followed by `m` (minutes), `h`,
+/// `d`, or `w`.
+pub(crate) fn parse_age(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let invalid = || format!("invalid age `{value}` (expected e.g. 30d, 12h, or 2w)");
+    let unit_start = value
+        .find(|ch: char| !ch.is_ascii_digit())
+        .unwrap_or(value.len());
+    let (amount, unit) = value.split_at(unit_start);
+    let amount: u64 = amount.parse().map_err(|_| invalid())?;
+    let unit_secs = match unit {
+        "m" => 60,
+        "h" => 60 * 60,
+        "d" => 24 * 60 * 60,
+        "w" => 7 * 24 * 60 * 60,
+        _ => return Err(invalid()),
+    };
+    Ok(Duration::from_secs(amount.saturating_mul(unit_secs)))
+}
+
+/// Whether `path` is named like an image Mawaku saved with exactly `prefix`,
+/// e.g. `mawaku-hakone-p1-AB3KZ.png`. Prefixes are single words, so images of
+/// another prefix never match.
+pub(crate) fn is_generated_image(path: &Path, prefix: &str) -> bool {
+    has_extension(path, IMAGE_EXTENSIONS) && has_generated_stem(path, prefix)
+}
+
+/// Whether `path` is a `--save-raw` payload of an image saved with `prefix`.
+fn is_raw_payload(path: &Path, prefix: &str) -> bool {
+    has_extension(path, &[RAW_PAYLOAD_EXTENSION]) && has_generated_stem(path, prefix)
+}
+
+fn has_extension(path: &Path, extensions: &[&str]) -> bool {
+    path.extension()
+        .and_then(|extension| extension.to_str())
+        .is_some_and(|extension| extensions.contains(&extension))
+}
+
+fn has_generated_stem(path: &Path, prefix: &str) -> bool {
+    path.file_stem()
+        .and_then(|stem| stem.to_str())
+        .and_then(parse_image_name)
+        .is_some_and(|parsed| parsed.prefix == prefix && is_generated_suffix(&parsed.suffix))
+}
+
+/// Whether `suffix` could come from the name builder, so user files such as
+/// `mawaku-trip-p1-final.png` are left alone.
+fn is_generated_suffix(suffix: &str) -> bool {
+    suffix.len() == DEFAULT_RANDOM_SUFFIX_LENGTH
+        && suffix
+            .bytes()
+            .all(|byte| DEFAULT_SUFFIX_ALPHABET.contains(&byte))
+}
+
+/// Every image generated with `prefix` in `dir` and in its `YYYY/MM/DD` date
+/// partitions. Raw payloads are attached to the image they belong to, or
+/// listed on their own when that image was never saved. Other files and
+/// directories are never touched.
+pub(crate) fn generated_images(dir: &Path, prefix: &str) -> io::Result<Vec<GeneratedImage>> {
+    let mut images = Vec::new();
+    collect_generated_images(dir, prefix, 0, &mut images)?;
+    Ok(images)
+}
+
+fn collect_generated_images(
+    dir: &Path,
+    prefix: &str,
+    depth: usize,
+    images: &mut Vec<GeneratedImage>,
+) -> io::Result<()> {
+    let first = images.len();
+    let mut raw_payloads = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let path = entry.path();
+        let file_type = entry.file_type()?;
+        if file_type.is_dir() {
+            let is_date_partition = entry
+                .file_name()
+                .to_str()
+                .is_some_and(|name| name.chars().all(|ch| ch.is_ascii_digit()));
+            if depth < 3 && is_date_partition {
+                collect_generated_images(&path, prefix, depth + 1, images)?;
+            }
+        } else if file_type.is_file() && is_generated_image(&path, prefix) {
+            images.push(GeneratedImage {
+                modified: entry.metadata()?.modified()?,
+                path,
+                raw_path: None,
+            });
+        } else if file_type.is_file() && is_raw_payload(&path, prefix) {
+            raw_payloads.push((path, entry.metadata()?.modified()?));
+        }
+    }
+
+    for (raw_path, modified) in raw_payloads {
+        let image = images[first..]
+            .iter_mut()
+            .find(|image| image.path.with_extension(RAW_PAYLOAD_EXTENSION) == raw_path);
+        match image {
+            Some(image) => image.raw_path = Some(raw_path),
+            None => images.push(GeneratedImage {
+                path: raw_path,
+                modified,
+                raw_path: None,
+            }),
+        }
+    }
+    Ok(())
+}
+
+/// The images to remove: those older than `older_than` and beyond the `keep`
+/// most recent. A criterion that is `None` matches every image.
+pub(crate) fn select_for_removal(
+    mut images: Vec<GeneratedImage>,
+    older_than: Option<Duration>,
+    keep: Option<usize>,
+    now: SystemTime,
+) -> Vec<GeneratedImage> {
+    images.sort_by(|a, b| {
+        b.modified
+            .cmp(&a.modified)
+            .then_with(|| a.path.cmp(&b.path))
+    });
+    images
+        .into_iter()
+        .enumerate()
+        .filter(|(rank, image)| {
+            let beyond_keep = keep.is_none_or(|keep| *rank >= keep);
+            let too_old = older_than.is_none_or(|age| {
+                now.duration_since(image.modified)
+                    .is_ok_and(|elapsed| elapsed > age)
+            });
+            beyond_keep && too_old
+        })
+        .map(|(_, image)| image)
+        .collect()
+}
+
+/// Run `mawaku clean`.
+pub(crate) fn run(args: &CleanArgs) -> RunStatus {
+    let config = load_or_init().map(|outcome| outcome.config);
+    let dir = match (args.dir.clone(), &config) {
+        (Some(dir), _) => dir,
+        (None, Ok(config)) => PathBuf::from(&config.image_output_dir),
+        (None, Err(error)) => {
+            error!("failed to load Mawaku configuration ({error}).");
+            return RunStatus::ConfigFailure;
+        }
+    };
+    let prefix = match (&args.prefix, &config) {
+        (Some(prefix), _) => prefix.clone(),
+        (None, Ok(config)) => config.file_name_prefix(),
+        (None, Err(_)) => DEFAULT_FILE_NAME_PREFIX.to_string(),
+    };
+
+    let images = match generated_images(&dir, &prefix) {
+        Ok(images) => images,
+        Err(error) => {
+            error!("failed to read {} ({error}).", dir.display());
+            return RunStatus::ApiFailure;
+        }
+    };
+    let doomed = select_for_removal(images, args.older_than, args.keep, SystemTime::now());
+    if doomed.is_empty() {
+        info!("No generated images to remove in {}.", dir.display());
+        return RunStatus::Success;
+    }
+
+    for image in &doomed {
+        println!("{}", image.path.display());
+        if let Some(raw_path) = &image.raw_path {
+            println!("{}", raw_path.display());
+        }
+    }
+    if args.dry_run {
+        info!("Dry run: {} image(s) would be removed.", doomed.len());
+        return RunStatus::Success;
+    }
+    if !args.yes && !confirm(doomed.len()) {
+        info!("Nothing removed.");
+        return RunStatus::Success;
+    }
+
+    let mut failures = 0;
+    for image in &doomed {
+        let mut removed = true;
+        for path in std::iter::once(&image.path).chain(&image.raw_path) {
+            if let Err(error) = fs::remove_file(path) {
+                warn!("failed to remove {} ({error}).", path.display());
+                removed = false;
+            }
+        }
+        if !removed {
+            failures += 1;
+        }
+    }
+    info!(
+        "Removed {} of {} image(s) from {}.",
+        doomed.len() - failures,
+        doomed.len(),
+        dir.display()
+    );
+    if failures == 0 {
+        RunStatus::Success
+    } else {
+        RunStatus::ApiFailure
+    }
+}
+
+fn confirm(count: usize) -> bool {
+    eprint!("Delete {count} image(s)? [y/N] ");
+    let _ = io::stderr().flush();
+    let mut answer = String::new();
+    if io::stdin().lock().read_line(&mut answer).is_err() {
+        return false;
+    }
+    matches!(answer.trim().to_ascii_lowercase().as_str(), "y" | "yes")
+}
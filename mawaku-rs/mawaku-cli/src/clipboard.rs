@@ -0,0 +1,29 @@
+/// Put `text` on the system clipboard. Fails on headless systems where no
+/// clipboard is available.
+#[cfg(feature = "clipboard")]
+pub(crate) fn copy(text: &str) -> Result<(), String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|error| error.to_string())?;
+    clipboard
+        .set_text(text.to_string())
+        .map_err(|error| error.to_string())
+}
+
+/// Read the text currently on the system clipboard.
+#[cfg(feature = "clipboard")]
+pub(crate) fn paste() -> Result<String, String> {
+    let mut clipboard = arboard::Clipboard::new().map_err(|error| error.to_string())?;
+    clipboard.get_text().map_err(|error| error.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub(crate) fn copy(_text: &str) -> Result<(), String> {
+    Err(UNSUPPORTED.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+pub(crate) fn paste() -> Result<String, String> {
+    Err(UNSUPPORTED.to_string())
+}
+
+#[cfg(not(feature = "clipboard"))]
+const UNSUPPORTED: &str = "clipboard support requires building Mawaku with `--features clipboard`";
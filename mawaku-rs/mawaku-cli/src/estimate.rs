@@ -0,0 +1,152 @@
+use crate::{AspectRatioArg, RunContext};
+use mawaku_gemini::{
+    AVATAR_ASPECT_RATIO, DESCRIPTION_INPUT_TOKENS_ESTIMATE, DESCRIPTION_OUTPUT_TOKENS_ESTIMATE,
+    GeminiClientOptions, ORIENTATION_INPUT_TOKENS_ESTIMATE, ORIENTATION_OUTPUT_TOKENS_ESTIMATE,
+    PriceTable,
+};
+use std::collections::BTreeSet;
+
+/// What a run would request from Gemini and roughly what it would cost, for
+/// `--estimate`.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct CostEstimate {
+    pub(crate) currency: String,
+    pub(crate) image_requests: u64,
+    pub(crate) images: u64,
+    pub(crate) text_requests: u64,
+    pub(crate) cost: f64,
+    /// Models without a price; their requests are left out of `cost`.
+    pub(crate) unpriced_models: BTreeSet<String>,
+}
+
+impl CostEstimate {
+    /// Estimate rendering every scene in `scenes` with `options`, assuming
+    /// nothing is served from a cache.
+    pub(crate) fn new(
+        table: &PriceTable,
+        options: &GeminiClientOptions,
+        scenes: &[RunContext],
+    ) -> Self {
+        let mut estimate = Self {
+            currency: table.currency.clone(),
+            image_requests: 0,
+            images: 0,
+            text_requests: 0,
+            cost: 0.0,
+            unpriced_models: BTreeSet::new(),
+        };
+        for scene in scenes {
+            estimate.add_scene(table, options, scene);
+        }
+        estimate
+    }
+
+    fn add_scene(&mut self, table: &PriceTable, options: &GeminiClientOptions, scene: &RunContext) {
+        // A seed sweep describes the place once and renders it per seed.
+        let seeds = scene.seed_sweep.map_or(1, |sweep| sweep.len());
+        // Without a location there is nothing to describe.
+        if !scene.location.is_empty() && !scene.skip_description {
+            let output_tokens = scene
+                .max_output_tokens
+                .map_or(DESCRIPTION_OUTPUT_TOKENS_ESTIMATE, u64::from);
+            self.add_text_request(
+                table,
+                &options.text_model,
+                DESCRIPTION_INPUT_TOKENS_ESTIMATE,
+                output_tokens,
+            );
+        }
+        let aspect_ratio = match scene.aspect_ratio {
+            Some(AspectRatioArg::Fixed(ratio)) => Some(ratio),
+            Some(AspectRatioArg::Auto) => {
+                self.add_text_request(
+                    table,
+                    &options.text_model,
+                    ORIENTATION_INPUT_TOKENS_ESTIMATE,
+                    ORIENTATION_OUTPUT_TOKENS_ESTIMATE,
+                );
+                None
+            }
+            None => options.aspect_ratio.as_deref(),
+        };
+        for _ in 0..seeds {
+            self.add_image_requests(table, options, scene, aspect_ratio);
+            if scene.also_avatar {
+                self.add_image_requests(table, options, scene, Some(AVATAR_ASPECT_RATIO));
+            }
+        }
+    }
+
+    /// One image request, plus one upscale request per image when upscaling.
+    fn add_image_requests(
+        &mut self,
+        table: &PriceTable,
+        options: &GeminiClientOptions,
+        scene: &RunContext,
+        aspect_ratio: Option<&str>,
+    ) {
+        self.add_image_request(
+            table,
+            &options.image_model,
+            options.sample_count,
+            aspect_ratio,
+        );
+        if scene.upscale.is_some() {
+            for _ in 0..options.sample_count {
+                self.image_requests += 1;
+                self.add_cost(
+                    &options.upscale_model,
+                    table.image_cost(&options.upscale_model, 1, aspect_ratio),
+                );
+            }
+        }
+    }
+
+    fn add_image_request(
+        &mut self,
+        table: &PriceTable,
+        model: &str,
+        sample_count: u32,
+        aspect_ratio: Option<&str>,
+    ) {
+        self.image_requests += 1;
+        self.images += u64::from(sample_count);
+        self.add_cost(model, table.image_cost(model, sample_count, aspect_ratio));
+    }
+
+    fn add_text_request(
+        &mut self,
+        table: &PriceTable,
+        model: &str,
+        input_tokens: u64,
+        output_tokens: u64,
+    ) {
+        self.text_requests += 1;
+        self.add_cost(model, table.text_cost(model, input_tokens, output_tokens));
+    }
+
+    fn add_cost(&mut self, model: &str, cost: Option<f64>) {
+        match cost {
+            Some(cost) => self.cost += cost,
+            None => {
+                self.unpriced_models.insert(model.to_string());
+            }
+        }
+    }
+
+    pub(crate) fn lines(&self) -> Vec<String> {
+        let mut lines = vec![
+            format!("Estimated cost: {:.2} {}", self.cost, self.currency),
+            format!(
+                "{} image request(s) for {} image(s), {} text request(s).",
+                self.image_requests, self.images, self.text_requests
+            ),
+        ];
+        for model in &self.unpriced_models {
+            lines.push(format!(
+                "No price is known for {model}; its requests are not included. Add it under [pricing] in the configuration."
+            ));
+        }
+        lines
+    }
+}
@@ -0,0 +1,116 @@
+use crate::{Cli, RunContext};
+use mawaku_gemini::{SeasonPreset, Theme};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File in the image output directory listing batch scenes that saved no
+/// image, one JSON object per line.
+pub(crate) const FAILURES_LOG_FILE_NAME: &str = "failures.log";
+
+/// A batch scene and the flags it was rendered with, so `--retry-failed`
+/// can render it again exactly as requested.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct FailedScene {
+    pub(crate) location: String,
+    pub(crate) season: Option<String>,
+    pub(crate) season_preset: Option<String>,
+    pub(crate) time_of_day: Option<String>,
+    pub(crate) weather: Option<String>,
+    pub(crate) theme: Option<String>,
+}
+
+impl FailedScene {
+    pub(crate) fn from_cli(cli: &Cli, location: &str) -> Self {
+        Self {
+            location: location.to_string(),
+            season: cli.season.clone(),
+            season_preset: cli.season_preset.map(|preset| preset.to_string()),
+            time_of_day: cli.time_of_day.clone(),
+            weather: cli.weather.clone(),
+            theme: cli.theme.map(|theme| theme.to_string()),
+        }
+    }
+
+    /// The preset, or `None` when it is unset or no longer parses.
+    pub(crate) fn season_preset(&self) -> Option<SeasonPreset> {
+        self.season_preset
+            .as_deref()
+            .and_then(|preset| preset.parse().ok())
+    }
+
+    /// The theme, or `None` when it is unset or no longer parses.
+    pub(crate) fn theme(&self) -> Option<Theme> {
+        self.theme.as_deref().and_then(|theme| theme.parse().ok())
+    }
+
+    /// This scene with the season, time of day, weather, and theme that
+    /// `context` resolved for it, including configured defaults.
+    pub(crate) fn resolved(&self, context: &RunContext) -> Self {
+        Self {
+            season: context.season.clone(),
+            time_of_day: context.time_of_day.clone(),
+            weather: context.weather.clone(),
+            theme: context.theme.map(|theme| theme.to_string()),
+            ..self.clone()
+        }
+    }
+}
+
+pub(crate) fn log_path(output_dir: &Path) -> PathBuf {
+    output_dir.join(FAILURES_LOG_FILE_NAME)
+}
+
+/// The scenes recorded at `path`, skipping lines that no longer parse. A
+/// missing log means nothing failed.
+pub(crate) fn load(path: &Path) -> io::Result<Vec<FailedScene>> {
+    let contents = match fs::read_to_string(path) {
+        Ok(contents) => contents,
+        Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(error) => return Err(error),
+    };
+    Ok(contents
+        .lines()
+        .filter(|line| !line.trim().is_empty())
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect())
+}
+
+/// Write `scenes` to `path`, removing the log once nothing is left.
+pub(crate) fn save(path: &Path, scenes: &[FailedScene]) -> io::Result<()> {
+    if scenes.is_empty() {
+        return match fs::remove_file(path) {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error),
+            _ => Ok(()),
+        };
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let mut contents = String::new();
+    for scene in scenes {
+        contents.push_str(&serde_json::to_string(scene)?);
+        contents.push('\n');
+    }
+    fs::write(path, contents)
+}
+
+/// Update the log at `path` after rendering `attempted`: entries for those
+/// scenes are cleared, then the ones in `failed` are appended. Scenes from
+/// earlier batches that were not attempted are kept.
+pub(crate) fn record(
+    path: &Path,
+    attempted: &[FailedScene],
+    failed: &[FailedScene],
+) -> io::Result<()> {
+    let mut scenes = load(path)?;
+    scenes.retain(|scene| !attempted.contains(scene));
+    for scene in failed {
+        if !scenes.contains(scene) {
+            scenes.push(scene.clone());
+        }
+    }
+    save(path, &scenes)
+}
@@ -0,0 +1,69 @@
+use crate::Cli;
+use mawaku_utils::trimmed_or_none;
+use std::io::{self, BufRead, IsTerminal, Write};
+
+/// Ask for the scene flags missing from `cli` on the terminal, for
+/// `--interactive`.
+pub(crate) fn run(cli: &mut Cli) -> Result<(), String> {
+    let is_terminal = io::stdin().is_terminal();
+    fill_missing(cli, &mut io::stdin().lock(), &mut io::stderr(), is_terminal)
+}
+
+/// Prompt on `output` and read answers from `input` for the location, season,
+/// and time of day that `cli` does not set yet. The location must not be
+/// blank; blank optional answers leave the flag unset.
+///
+/// When `is_terminal` is false, fails at the first question instead of
+/// asking, so scripts get an error rather than a hanging prompt. Nothing is
+/// checked when every flag is already set.
+pub(crate) fn fill_missing(
+    cli: &mut Cli,
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    is_terminal: bool,
+) -> Result<(), String> {
+    let needs_answers = cli.location.is_empty()
+        || (cli.season.is_none() && cli.season_preset.is_none())
+        || cli.time_of_day.is_none();
+    if needs_answers && !is_terminal {
+        return Err(
+            "--interactive needs a terminal; pass --location (and optionally --season and --time-of-day) instead."
+                .to_string(),
+        );
+    }
+
+    if cli.location.is_empty() {
+        let location = loop {
+            match ask(input, output, "Location")? {
+                Some(location) => break location,
+                None => {
+                    let _ = writeln!(output, "A location is required.");
+                }
+            }
+        };
+        cli.location = vec![location];
+    }
+    if cli.season.is_none() && cli.season_preset.is_none() {
+        cli.season = ask(input, output, "Season (optional)")?;
+    }
+    if cli.time_of_day.is_none() {
+        cli.time_of_day = ask(input, output, "Time of day (optional)")?;
+    }
+    Ok(())
+}
+
+/// Print `label` and read one line. Returns `None` for a blank answer.
+fn ask(
+    input: &mut impl BufRead,
+    output: &mut impl Write,
+    label: &str,
+) -> Result<Option<String>, String> {
+    let _ = write!(output, "{label}: ");
+    let _ = output.flush();
+    let mut answer = String::new();
+    match input.read_line(&mut answer) {
+        Ok(0) => Err("no answer entered; stopping.".to_string()),
+        Ok(_) => Ok(trimmed_or_none(Some(&answer)).map(str::to_string)),
+        Err(error) => Err(format!("failed to read the answer ({error}).")),
+    }
+}
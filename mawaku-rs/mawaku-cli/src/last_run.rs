@@ -0,0 +1,121 @@
+use crate::{AspectRatioArg, Cli};
+use log::warn;
+use mawaku_config::last_run_path;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::io;
+use std::path::Path;
+
+/// Scene flags of the previous invocation, stored as `last_run.json` in the
+/// config directory. Only scene parameters are kept; nothing secret is
+/// written.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(default)]
+pub(crate) struct LastRun {
+    pub(crate) location: Vec<String>,
+    pub(crate) season: Option<String>,
+    pub(crate) season_preset: Option<String>,
+    pub(crate) time_of_day: Option<String>,
+    pub(crate) weather: Option<String>,
+    pub(crate) theme: Option<String>,
+    pub(crate) aspect_ratio: Option<String>,
+    pub(crate) upscale: Option<String>,
+    pub(crate) label: bool,
+    pub(crate) contact_sheet: bool,
+    pub(crate) embed_metadata: bool,
+    pub(crate) batch: bool,
+}
+
+impl LastRun {
+    pub(crate) fn from_cli(cli: &Cli) -> Self {
+        Self {
+            location: cli.location.clone(),
+            season: cli.season.clone(),
+            season_preset: cli.season_preset.map(|preset| preset.to_string()),
+            time_of_day: cli.time_of_day.clone(),
+            weather: cli.weather.clone(),
+            theme: cli.theme.map(|theme| theme.to_string()),
+            aspect_ratio: cli.aspect_ratio.map(|ratio| ratio.to_string()),
+            upscale: cli.upscale.map(|factor| factor.to_string()),
+            label: cli.label,
+            contact_sheet: cli.contact_sheet,
+            embed_metadata: cli.embed_metadata,
+            batch: cli.batch,
+        }
+    }
+
+    /// Fill in every value `cli` leaves unset. Flags given on the command
+    /// line win: a recorded switch stays on unless its `--no-*` form is
+    /// passed. Stored values that no longer parse are dropped.
+    pub(crate) fn apply_to(self, cli: &mut Cli) {
+        if cli.location.is_empty() {
+            cli.location = self.location;
+        }
+        // `--season` and `--season-preset` are mutually exclusive, so either
+        // one on the command line replaces both stored values.
+        if cli.season.is_none() && cli.season_preset.is_none() {
+            cli.season = self.season;
+            cli.season_preset = self.season_preset.and_then(|preset| preset.parse().ok());
+        }
+        cli.time_of_day = cli.time_of_day.take().or(self.time_of_day);
+        cli.weather = cli.weather.take().or(self.weather);
+        cli.theme = cli
+            .theme
+            .or_else(|| self.theme.and_then(|theme| theme.parse().ok()));
+        cli.aspect_ratio = cli.aspect_ratio.or_else(|| {
+            self.aspect_ratio
+                .and_then(|ratio| ratio.parse::<AspectRatioArg>().ok())
+        });
+        cli.upscale = cli
+            .upscale
+            .or_else(|| self.upscale.and_then(|factor| factor.parse().ok()));
+        cli.label = !cli.no_label && (cli.label || self.label);
+        cli.contact_sheet = !cli.no_contact_sheet && (cli.contact_sheet || self.contact_sheet);
+        cli.embed_metadata = !cli.no_embed_metadata && (cli.embed_metadata || self.embed_metadata);
+        cli.batch = !cli.no_batch && (cli.batch || self.batch) && !cli.location.is_empty();
+    }
+}
+
+pub(crate) fn load(path: &Path) -> io::Result<LastRun> {
+    let contents = fs::read_to_string(path)?;
+    Ok(serde_json::from_str(&contents)?)
+}
+
+pub(crate) fn save(path: &Path, last_run: &LastRun) -> io::Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    let serialized = serde_json::to_string_pretty(last_run)?;
+    fs::write(path, serialized)
+}
+
+/// Preload `cli` with the previous run's flags for `--repeat-last`.
+pub(crate) fn preload(cli: &mut Cli) {
+    let path = match last_run_path() {
+        Ok(path) => path,
+        Err(error) => {
+            warn!("cannot locate the previous run ({error}); using only the given flags.");
+            return;
+        }
+    };
+    match load(&path) {
+        Ok(last_run) => last_run.apply_to(cli),
+        Err(error) if error.kind() == io::ErrorKind::NotFound => {
+            warn!("no previous run recorded yet; using only the given flags.");
+        }
+        Err(error) => warn!(
+            "failed to read {} ({error}); using only the given flags.",
+            path.display()
+        ),
+    }
+}
+
+/// Record the scene flags of this run for a later `--repeat-last`.
+pub(crate) fn remember(cli: &Cli) {
+    let result = last_run_path()
+        .map_err(io::Error::other)
+        .and_then(|path| save(&path, &LastRun::from_cli(cli)));
+    if let Err(error) = result {
+        warn!("failed to record this run for --repeat-last ({error}).");
+    }
+}
@@ -0,0 +1,103 @@
+use log::{Level, LevelFilter, Log, Metadata, Record};
+use mawaku_utils::format_iso8601;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Verbosity accepted by `--log-level`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum LogLevel {
+    Error,
+    Warn,
+    #[default]
+    Info,
+    Debug,
+}
+
+impl LogLevel {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Error => "error",
+            Self::Warn => "warn",
+            Self::Info => "info",
+            Self::Debug => "debug",
+        }
+    }
+
+    pub(crate) fn filter(self) -> LevelFilter {
+        match self {
+            Self::Error => LevelFilter::Error,
+            Self::Warn => LevelFilter::Warn,
+            Self::Info => LevelFilter::Info,
+            Self::Debug => LevelFilter::Debug,
+        }
+    }
+}
+
+impl std::fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for LogLevel {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "error" => Ok(Self::Error),
+            "warn" | "warning" => Ok(Self::Warn),
+            "info" => Ok(Self::Info),
+            "debug" => Ok(Self::Debug),
+            other => Err(format!(
+                "unsupported log level `{other}` (expected error, warn, info, or debug)"
+            )),
+        }
+    }
+}
+
+/// Writes `<timestamp> <LEVEL> <message>` lines to stderr.
+struct StderrLogger;
+
+static LOGGER: StderrLogger = StderrLogger;
+static WARNINGS: AtomicUsize = AtomicUsize::new(0);
+
+impl Log for StderrLogger {
+    fn enabled(&self, metadata: &Metadata<'_>) -> bool {
+        metadata.level() <= log::max_level()
+    }
+
+    fn log(&self, record: &Record<'_>) {
+        if record.level() <= Level::Warn {
+            WARNINGS.fetch_add(1, Ordering::Relaxed);
+        }
+        if self.enabled(record.metadata()) {
+            eprintln!("{}", format_record(record));
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+pub(crate) fn format_record(record: &Record<'_>) -> String {
+    let secs = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default();
+    format!(
+        "{} {:<5} {}",
+        format_iso8601(secs),
+        record.level(),
+        record.args()
+    )
+}
+
+/// Install the stderr logger at `level`. Later calls only adjust the level.
+pub(crate) fn init(level: LogLevel) {
+    let _ = log::set_logger(&LOGGER);
+    log::set_max_level(level.filter());
+}
+
+/// Number of warnings and errors logged so far.
+pub(crate) fn warning_count() -> usize {
+    WARNINGS.load(Ordering::Relaxed)
+}
@@ -1,256 +1,2121 @@
-use clap::Parser;
-use mawaku_config::{Config, DEFAULT_PROMPT, load_or_init};
+use clap::{ArgGroup, Parser, Subcommand};
+use log::{error, info, warn};
+use mawaku_config::{Config, PricingConfig, SUPPORTED_ASPECT_RATIOS, load_or_init, update};
 use mawaku_gemini::{
-    GeminiError, PlaceDescription, PredictResponse, craft_prompt, generate_image,
-    generate_place_description,
+    AVATAR_ASPECT_RATIO, BASE_URL_ENV_VAR, DEFAULT_ASPECT_RATIO, GeminiClient, GeminiClientOptions,
+    GeminiError, HarmCategory, ImageCache, ImageGenerator, MAX_TOKENS_FINISH_REASON, ModelInfo,
+    PersonGeneration, PlaceDescription, PlaceDescriptionCache, PredictResponse, PriceTable,
+    RequestDumper, SafetySetting, SafetyThreshold, SeasonPreset, TextGenerator, Theme,
+    UpscaleFactor, build_avatar_prompt, build_structured_prompt, craft_prompt_with_locations,
+    fill_prompt_template, join_locations,
+};
+use mawaku_image::{
+    AspectRatio, DEFAULT_JPEG_QUALITY, ImageDimensions, ImageFormat, ImageSaveError,
+    SaveImageOptions, SavedImage, save_base64_image, save_contact_sheet,
 };
-use mawaku_image::{SaveImageOptions, save_base64_image};
 use mawaku_utils::{
-    DEFAULT_FILE_NAME_PREFIX, ImageNameBuilder, ImageNameContext, format_context_line,
-    list_or_unspecified, trimmed_or_none,
+    ImageNameContext, build_image_name_context, existing_file_stems, format_iso8601, prefix_slug,
+    today_partition, trimmed_or_none, unix_timestamp_secs,
 };
+use std::collections::HashMap;
 use std::env;
 use std::io::{self, Write};
-use std::path::PathBuf;
+use std::num::NonZeroU32;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::thread;
 use std::time::{Duration, Instant};
 
+mod alias;
+mod batch;
+mod cache;
+mod clean;
+mod clipboard;
+mod estimate;
+mod failures;
+mod interactive;
+mod last_run;
+mod logging;
+mod manifest;
+mod progress;
+mod retry;
+mod seed_sweep;
+
+use estimate::CostEstimate;
+use failures::FailedScene;
+use logging::LogLevel;
+use manifest::Manifest;
+use progress::{ProgressEvent, ProgressFormat, SpinnerStyle};
+use seed_sweep::{DEFAULT_SEED_SWEEP_LIMIT, SeedSweep};
+
+/// Scenes rendered at once in `--batch` mode unless `--concurrency` is given.
+const DEFAULT_CONCURRENCY: u16 = 2;
+/// Predictions decoded and written at once, so large samples do not thrash
+/// a slow disk.
+const MAX_CONCURRENT_SAVES: usize = 4;
+/// File name component that tells `--also-avatar` images apart.
+const AVATAR_FILE_NAME_COMPONENT: &str = "avatar";
+/// `User-Agent` identifying this build of the CLI to proxies and to Google.
+const USER_AGENT: &str = concat!("mawaku-cli/", env!("CARGO_PKG_VERSION"));
+/// File name component of blurred images saved next to `--keep-sharp`
+/// originals.
+const BLUR_FILE_NAME_COMPONENT: &str = "blur";
 const GEMINI_KEY_WARNING_PREFIX: &str =
-    "Warning: Gemini API key environment variable is missing. Export it before running Mawaku: ";
+    "Gemini API key environment variable is missing. Export it before running Mawaku: ";
 
 /// Mawaku CLI entry point.
 ///
 /// Mawaku will translate natural language scene descriptions into
 /// prompts for background generators such as Google Imagen or OpenAI's DALL-E.
-#[derive(Parser, Debug, Clone)]
+#[derive(Parser, Debug, Clone, Default)]
 #[command(
     name = "mawaku",
     author,
     version,
     about = "Generate video-call backgrounds by describing a place.",
-    long_about = None
+    long_about = None,
+    group(ArgGroup::new("batch_locations").args(["location", "repeat_last"]).multiple(true))
 )]
 struct Cli {
-    /// Location that should anchor the generated background.
-    #[arg(long, value_name = "LOCATION")]
-    location: String,
+    #[command(subcommand)]
+    command: Option<Command>,
+    /// Location that should anchor the generated background. Repeat the flag
+    /// to blend several places into one scene. When omitted, Mawaku renders
+    /// a generic cosy workspace.
+    #[arg(long, value_name = "LOCATION", global = true)]
+    location: Vec<String>,
+    /// Use the text on the clipboard as the location. Requires building
+    /// with `--features clipboard`.
+    #[arg(
+        long = "location-from-clipboard",
+        global = true,
+        conflicts_with = "location"
+    )]
+    location_from_clipboard: bool,
+    /// Ask on the terminal for the location, season, and time of day when
+    /// their flags are not given.
+    #[arg(long, conflicts_with = "retry_failed")]
+    interactive: bool,
     /// Optional season that informs the ambience of the scene.
-    #[arg(long, value_name = "SEASON")]
+    #[arg(long, value_name = "SEASON", global = true)]
     season: Option<String>,
+    /// Curated season (spring, summer, autumn, winter) that adds a carefully
+    /// worded seasonal description to the prompt. Alternative to `--season`.
+    #[arg(
+        long = "season-preset",
+        value_name = "PRESET",
+        conflicts_with = "season",
+        global = true
+    )]
+    season_preset: Option<SeasonPreset>,
     /// Optional time of day to tailor the lighting of the scene.
-    #[arg(long = "time-of-day", value_name = "TIME")]
+    #[arg(long = "time-of-day", value_name = "TIME", global = true)]
     time_of_day: Option<String>,
+    /// Optional weather conditions visible through the window.
+    #[arg(long, value_name = "WEATHER", global = true)]
+    weather: Option<String>,
+    /// Curated interior style appended to the prompt and the file names:
+    /// `cozy`, `minimalist`, `industrial`, `scandinavian`, or `japandi`.
+    #[arg(long, value_name = "THEME", global = true)]
+    theme: Option<Theme>,
+    /// Render each `--location` as its own scene instead of blending them.
+    /// Needs `--location`, or `--repeat-last` to take the recorded ones.
+    #[arg(long, requires = "batch_locations")]
+    batch: bool,
+    /// Turn off a `--batch` recorded by the previous run under
+    /// `--repeat-last`.
+    #[arg(long = "no-batch", overrides_with = "batch")]
+    no_batch: bool,
+    /// Maximum number of scenes generated at once with `--batch`.
+    #[arg(
+        long,
+        value_name = "N",
+        default_value_t = DEFAULT_CONCURRENCY,
+        value_parser = clap::value_parser!(u16).range(1..)
+    )]
+    concurrency: u16,
+    /// Write the first prediction's decoded bytes to stdout instead of saving
+    /// files, for piping into other tools. Messages stay on stderr.
+    #[arg(
+        long = "stdout-image",
+        conflicts_with_all = ["batch", "contact_sheet"]
+    )]
+    stdout_image: bool,
+    /// Stop after printing the final prompt, even when a Gemini API key is
+    /// set. The place description is still fetched to enrich the prompt.
+    #[arg(long = "prompt-only", conflicts_with = "stdout_image")]
+    prompt_only: bool,
+    /// Also write each image's untouched base64 payload to `<stem>.b64`
+    /// next to the decoded file, for reproducing API responses.
+    #[arg(long = "save-raw", conflicts_with = "stdout_image")]
+    save_raw: bool,
+    /// After the background, generate a square (1:1) avatar focused on a
+    /// central motif of the scene, saved with an `-avatar` file name
+    /// component. This doubles the image requests.
+    #[arg(long = "also-avatar", conflicts_with = "stdout_image")]
+    also_avatar: bool,
+    /// Render the same prompt once per seed in `START..END` (both ends
+    /// included), with `seed<N>` in the file names, to compare seeds. Each
+    /// seed is a separate image request.
+    #[arg(
+        long = "seed-sweep",
+        value_name = "START..END",
+        conflicts_with_all = ["batch", "stdout_image", "prompt_only", "retry_failed"]
+    )]
+    seed_sweep: Option<SeedSweep>,
+    /// Most seeds `--seed-sweep` may request; larger sweeps are refused.
+    #[arg(
+        long = "seed-sweep-limit",
+        value_name = "N",
+        default_value_t = DEFAULT_SEED_SWEEP_LIMIT,
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    seed_sweep_limit: u32,
+    /// Ask Imagen to leave out its invisible SynthID watermark. Vertex AI
+    /// only applies a seed to unwatermarked images.
+    #[arg(long = "no-watermark")]
+    no_watermark: bool,
+    /// Print how many Gemini requests the run would send and their estimated
+    /// cost, then exit without calling the API. Prices can be overridden
+    /// under `[pricing]` in the configuration.
+    #[arg(long, conflicts_with_all = ["stdout_image", "prompt_only", "retry_failed"])]
+    estimate: bool,
+    /// First segment of saved file names, overriding the configured
+    /// `file_name_prefix`.
+    #[arg(long, value_name = "PREFIX", value_parser = parse_file_name_prefix)]
+    prefix: Option<String>,
+    /// How to report progress: `human` shows a spinner, `json` streams
+    /// newline-delimited JSON events to stderr instead.
+    #[arg(long, value_name = "FORMAT", default_value_t = ProgressFormat::Human)]
+    progress: ProgressFormat,
+    /// Spinner shown while an image generates: `ascii`, `dots`, or `none`.
+    /// Overrides the `spinner` setting.
+    #[arg(long, value_name = "STYLE")]
+    spinner: Option<SpinnerStyle>,
+    /// Whether Imagen may draw people: `dont_allow` (default), `allow_adult`,
+    /// or `allow_all`.
+    #[arg(long = "person-generation", value_name = "MODE")]
+    person_generation: Option<PersonGeneration>,
+    /// Safety threshold for every harm category in place descriptions:
+    /// `block_none`, `block_only_high`, `block_medium_and_above`,
+    /// `block_low_and_above`, or `off`. Overrides `gemini_api.safety_settings`.
+    #[arg(long = "safety-threshold", value_name = "THRESHOLD")]
+    safety_threshold: Option<SafetyThreshold>,
+    /// Cap the tokens Gemini may spend on each text response, keeping place
+    /// descriptions and prompts short. Overrides
+    /// `gemini_api.max_output_tokens`.
+    #[arg(
+        long = "max-output-tokens",
+        value_name = "N",
+        value_parser = clap::value_parser!(u32).range(1..)
+    )]
+    max_output_tokens: Option<u32>,
+    /// Fail when Gemini cannot be reached instead of printing the locally
+    /// built prompt.
+    #[arg(long = "require-online")]
+    require_online: bool,
+    /// Read the base prompt from a file instead of the configured
+    /// `prompt_template` or the built-in default.
+    #[arg(long = "prompt-file", value_name = "PATH", value_parser = read_prompt_file)]
+    base_prompt: Option<String>,
+    /// Also copy the final prompt to the system clipboard. Requires building
+    /// with `--features clipboard`.
+    #[arg(long)]
+    copy: bool,
+    /// Gemini API key for this run only. It takes precedence over the
+    /// environment variable and is never written to the configuration.
+    #[arg(long = "api-key", value_name = "KEY", global = true)]
+    api_key: Option<String>,
+    /// Store a Gemini API key in the configuration file and use it for this
+    /// and later runs, in place of the environment variable.
+    #[arg(long = "set-gemini-api-key", value_name = "KEY")]
+    set_gemini_api_key: Option<String>,
+    /// Reuse the scene flags of the previous run; flags given now override
+    /// individual values.
+    #[arg(long = "repeat-last")]
+    repeat_last: bool,
+    /// Render again only the batch scenes recorded in `failures.log` in the
+    /// image output directory, each with its original flags. Scenes that
+    /// succeed are cleared from the log. Retries render as a batch, so they
+    /// cannot write to stdout.
+    #[arg(
+        long = "retry-failed",
+        conflicts_with_all = ["location", "location_from_clipboard", "repeat_last", "stdout_image"]
+    )]
+    retry_failed: bool,
+    /// Ignore cached place descriptions and images and fetch fresh ones
+    /// from Gemini. The new results still replace the cached ones.
+    #[arg(long = "no-cache", global = true)]
+    no_cache: bool,
+    /// Reuse the images generated earlier for an identical prompt and
+    /// parameters instead of calling Imagen, like `cache.images` in the
+    /// configuration.
+    #[arg(long = "cache-images")]
+    cache_images: bool,
+    /// Skip the Gemini place description and send the prompt without it.
+    #[arg(long = "no-description", global = true)]
+    no_description: bool,
+    /// Print extra diagnostics, including the exact prompt sent to Imagen.
+    #[arg(short, long, global = true)]
+    verbose: bool,
+    /// Print each Gemini request (URL, headers, and JSON body) to stderr
+    /// before it is sent, with the API key redacted. Turns off the spinner
+    /// so it does not draw over the output.
+    #[arg(long = "dump-request", global = true)]
+    dump_request: bool,
+    /// Minimum severity of diagnostics written to stderr.
+    #[arg(
+        long = "log-level",
+        value_name = "LEVEL",
+        default_value_t = LogLevel::Info,
+        global = true
+    )]
+    log_level: LogLevel,
+    /// Aspect ratio of generated images (1:1, 3:4, 4:3, 9:16, or 16:9), or
+    /// `auto` to let Gemini pick 16:9 or 3:4 based on the place.
+    #[arg(long = "aspect-ratio", value_name = "RATIO")]
+    aspect_ratio: Option<AspectRatioArg>,
+    /// Upscale each generated image (x2 or x4). Increases cost and latency.
+    #[arg(long, value_name = "FACTOR")]
+    upscale: Option<UpscaleFactor>,
+    /// Burn the location, season, time of day, and weather into a corner of
+    /// each saved image. Requires building with `--features image`.
+    #[arg(long)]
+    label: bool,
+    /// Turn off a `--label` recorded by the previous run under
+    /// `--repeat-last`.
+    #[arg(long = "no-label", overrides_with = "label")]
+    no_label: bool,
+    /// Center-crop each saved image to this ratio (e.g. 16:9) so it has the
+    /// exact proportions downstream tools expect. Requires building with
+    /// `--features image`.
+    #[arg(long = "crop-to-aspect", value_name = "W:H")]
+    crop_to_aspect: Option<AspectRatio>,
+    /// Blur each saved image with this Gaussian sigma for a soft, bokeh-like
+    /// look. Requires building with `--features image`.
+    #[arg(long, value_name = "SIGMA", value_parser = parse_blur_sigma)]
+    blur: Option<f32>,
+    /// With --blur, also save the sharp original; the blurred copy gets a
+    /// `-blur` file name component.
+    #[arg(long = "keep-sharp", requires = "blur")]
+    keep_sharp: bool,
+    /// Compose all saved predictions into a single `<base>-sheet.png` grid.
+    /// Requires building with `--features image`.
+    #[arg(long = "contact-sheet")]
+    contact_sheet: bool,
+    /// Turn off a `--contact-sheet` recorded by the previous run under
+    /// `--repeat-last`.
+    #[arg(long = "no-contact-sheet", overrides_with = "contact_sheet")]
+    no_contact_sheet: bool,
+    /// Embed the prompt and scene parameters in each saved image (PNG text
+    /// chunks or JPEG EXIF) so they survive copying.
+    #[arg(long = "embed-metadata")]
+    embed_metadata: bool,
+    /// Turn off an `--embed-metadata` recorded by the previous run under
+    /// `--repeat-last`.
+    #[arg(long = "no-embed-metadata", overrides_with = "embed_metadata")]
+    no_embed_metadata: bool,
+    /// Re-encode saved images as png, jpg, or webp. Without it the bytes are
+    /// saved exactly as Imagen returned them. Requires building with
+    /// `--features image`.
+    #[arg(
+        long = "output-format",
+        value_name = "FORMAT",
+        conflicts_with = "stdout_image"
+    )]
+    output_format: Option<OutputFormatArg>,
+    /// JPEG quality from 1 to 100 for `--output-format jpg` (default 90).
+    #[arg(
+        long = "jpeg-quality",
+        value_name = "QUALITY",
+        requires = "output_format",
+        value_parser = clap::value_parser!(u8).range(1..=100)
+    )]
+    jpeg_quality: Option<u8>,
 }
 
-fn generate_image_with_progress(
-    api_key: &str,
-    prompt: &str,
-) -> Option<Result<PredictResponse, GeminiError>> {
-    let api_key = api_key.to_string();
-    let prompt = prompt.to_string();
-
-    let handle = thread::Builder::new()
-        .name("gemini-image-request".into())
-        .spawn(move || generate_image(&api_key, &prompt))
-        .expect("spawn gemini image request");
-
-    const SPINNER_FRAMES: &[&str] = &["|", "/", "-", "\\"];
-    let mut frame_index = 0;
-    let interval = Duration::from_millis(200);
-    let start = Instant::now();
-
-    eprint!("Generating image ");
-    let _ = io::stderr().flush();
-
-    while !handle.is_finished() {
-        eprint!("\rGenerating image {}", SPINNER_FRAMES[frame_index]);
-        let _ = io::stderr().flush();
-        frame_index = (frame_index + 1) % SPINNER_FRAMES.len();
-        thread::sleep(interval);
-    }
-
-    match handle.join() {
-        Ok(result) => {
-            eprintln!(
-                "\rGenerating image ... finished in {:.1}s",
-                start.elapsed().as_secs_f32()
-            );
-            Some(result)
+#[derive(Subcommand, Debug, Clone, PartialEq, Eq)]
+enum Command {
+    /// Fetch the place description and print it alongside the general
+    /// instructions and the assembled prompt, without generating images.
+    Preview,
+    /// Remove old generated images from the output directory. Only files
+    /// named like Mawaku predictions are considered.
+    Clean(clean::CleanArgs),
+    /// List the Imagen and Gemini models the API key can access, with their
+    /// supported generation methods.
+    Models {
+        /// Show every model, not only image- and text-capable ones.
+        #[arg(long)]
+        all: bool,
+    },
+    /// Manage short location names expanded by `--location`, stored in the
+    /// `[aliases]` table of the configuration.
+    #[command(subcommand)]
+    Alias(alias::AliasCommand),
+}
+
+/// Value of `--aspect-ratio`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum AspectRatioArg {
+    /// Classify the place as landscape or portrait before generating.
+    Auto,
+    Fixed(&'static str),
+}
+
+/// Value of `--output-format`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormatArg {
+    Png,
+    Jpeg,
+    Webp,
+}
+
+impl OutputFormatArg {
+    /// The encoding to save with; `jpeg_quality` only applies to JPEG.
+    fn image_format(self, jpeg_quality: Option<u8>) -> ImageFormat {
+        match self {
+            Self::Png => ImageFormat::Png,
+            Self::Jpeg => ImageFormat::Jpeg {
+                quality: jpeg_quality.unwrap_or(DEFAULT_JPEG_QUALITY),
+            },
+            Self::Webp => ImageFormat::Webp,
         }
-        Err(_) => {
-            eprintln!("\rGenerating image ... failed: worker panicked");
-            None
+    }
+}
+
+impl std::fmt::Display for OutputFormatArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Png => "png",
+            Self::Jpeg => "jpg",
+            Self::Webp => "webp",
+        })
+    }
+}
+
+impl std::str::FromStr for OutputFormatArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "png" => Ok(Self::Png),
+            "jpg" | "jpeg" => Ok(Self::Jpeg),
+            "webp" => Ok(Self::Webp),
+            other => Err(format!(
+                "unsupported output format `{other}` (expected png, jpg, or webp)"
+            )),
+        }
+    }
+}
+
+impl std::fmt::Display for AspectRatioArg {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Auto => f.write_str("auto"),
+            Self::Fixed(ratio) => f.write_str(ratio),
+        }
+    }
+}
+
+impl std::str::FromStr for AspectRatioArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("auto") {
+            return Ok(Self::Auto);
+        }
+        SUPPORTED_ASPECT_RATIOS
+            .iter()
+            .find(|ratio| **ratio == value)
+            .map(|ratio| Self::Fixed(ratio))
+            .ok_or_else(|| {
+                format!(
+                    "unsupported aspect ratio `{value}` (expected auto or one of {})",
+                    SUPPORTED_ASPECT_RATIOS.join(", ")
+                )
+            })
+    }
+}
+
+/// Use the clipboard text read for `--location-from-clipboard` as the only
+/// location.
+fn apply_clipboard_location(cli: &mut Cli, pasted: Result<String, String>) -> Result<(), String> {
+    let pasted =
+        pasted.map_err(|error| format!("cannot read a location from the clipboard ({error})."))?;
+    let location = trimmed_or_none(Some(&pasted))
+        .ok_or_else(|| "the clipboard holds no text to use as the location.".to_string())?;
+    cli.location = vec![location.to_string()];
+    Ok(())
+}
+
+/// Parse `--prefix` into the slug used in file names.
+fn parse_file_name_prefix(value: &str) -> Result<String, String> {
+    prefix_slug(value).ok_or_else(|| format!("prefix `{value}` has no letters or digits"))
+}
+
+/// Parse `--blur`, rejecting sigmas the image crate would refuse.
+fn parse_blur_sigma(value: &str) -> Result<f32, String> {
+    let sigma = value
+        .trim()
+        .parse::<f32>()
+        .map_err(|_| format!("invalid blur sigma `{value}`"))?;
+    mawaku_image::check_blur_sigma(sigma).map_err(|error| error.to_string())
+}
+
+/// Parse `--prompt-file` by reading the file, so an unreadable or empty file
+/// is reported before anything else runs.
+fn read_prompt_file(path: &str) -> Result<String, String> {
+    let contents = std::fs::read_to_string(path)
+        .map_err(|error| format!("cannot read prompt file `{path}` ({error})"))?;
+    trimmed_or_none(Some(&contents))
+        .map(str::to_string)
+        .ok_or_else(|| format!("prompt file `{path}` is empty"))
+}
+
+/// The art-direction section of the prompt. Templates with placeholders get
+/// every scene value substituted in place; otherwise only the location is
+/// appended, since the timing section lists the rest.
+fn build_general_instructions(context: &RunContext) -> String {
+    let instructions = fill_prompt_template(
+        &context.prompt_template,
+        &context.location,
+        context.season.as_deref(),
+        context.time_of_day.as_deref(),
+        context.weather.as_deref(),
+    )
+    .unwrap_or_else(|| {
+        craft_prompt_with_locations(
+            &context.prompt_template,
+            &location_refs(&context.locations),
+            None,
+            None,
+            None,
+            &[],
+        )
+    });
+    match context.theme {
+        Some(theme) if !instructions.is_empty() => format!("{instructions} {}", theme.directive()),
+        Some(theme) => theme.directive(),
+        None => instructions,
+    }
+}
+
+fn fetch_place_description(
+    text: &dyn TextGenerator,
+    context: &RunContext,
+    season: &str,
+) -> Result<PlaceDescription, GeminiError> {
+    let cache_key = cache::description_key(context);
+
+    if !context.refresh_cache
+        && let Some(cache) = context.description_cache.as_ref()
+        && let Some(description) = cache.get(&cache_key)
+    {
+        info!("Using cached place description.");
+        return Ok(description);
+    }
+
+    let described = text.describe_place(
+        &location_refs(&context.locations),
+        season,
+        context.time_of_day.as_deref(),
+        context.weather.as_deref(),
+    )?;
+
+    // A description cut short is used once but not cached.
+    if described.stopped_early() {
+        let reason = described.finish_reason.as_deref().unwrap_or_default();
+        warn!(
+            "Gemini stopped the place description early ({reason}); it may be truncated or incomplete."
+        );
+        warn_token_cap(context, reason);
+    } else if let Some(cache) = context.description_cache.as_ref()
+        && let Err(error) = cache.put(&cache_key, &described.description)
+    {
+        warn!("failed to cache place description ({error}).");
+    }
+
+    Ok(described.description)
+}
+
+/// Point at the configured cap when it is what cut a description short.
+fn warn_token_cap(context: &RunContext, finish_reason: &str) {
+    if finish_reason == MAX_TOKENS_FINISH_REASON
+        && let Some(max_output_tokens) = context.max_output_tokens
+    {
+        warn!(
+            "the description hit the {max_output_tokens}-token cap; raise --max-output-tokens or gemini_api.max_output_tokens."
+        );
+    }
+}
+
+/// The aspect ratio to request, or `None` to keep the client default.
+fn resolve_aspect_ratio(
+    text: &dyn TextGenerator,
+    context: &RunContext,
+    description: Option<&PlaceDescription>,
+) -> Option<String> {
+    match context.aspect_ratio? {
+        AspectRatioArg::Fixed(ratio) => Some(ratio.to_string()),
+        AspectRatioArg::Auto if context.locations.is_empty() => {
+            Some(DEFAULT_ASPECT_RATIO.to_string())
         }
+        AspectRatioArg::Auto => Some(classify_aspect_ratio(text, context, description)),
     }
 }
 
-fn build_structured_prompt(
-    general_instructions: &str,
+fn classify_aspect_ratio(
+    text: &dyn TextGenerator,
+    context: &RunContext,
     description: Option<&PlaceDescription>,
-    season: Option<&str>,
-    time_of_day: Option<&str>,
 ) -> String {
-    let mut sections = Vec::new();
-
-    let instructions = general_instructions.trim();
-    if !instructions.is_empty() {
-        sections.push(instructions.to_string());
+    let cache_key = cache::description_key(context);
+    if !context.refresh_cache
+        && let Some(cache) = context.description_cache.as_ref()
+        && let Some(orientation) = cache.orientation(&cache_key)
+    {
+        info!(
+            "Using cached {orientation} orientation ({}).",
+            orientation.aspect_ratio()
+        );
+        return orientation.aspect_ratio().to_string();
     }
 
-    let place_section = match description {
-        Some(details) => {
-            let ambiance =
-                trimmed_or_none(Some(details.ambiance.as_str())).unwrap_or("Unspecified");
-            let items = list_or_unspecified(&details.items);
-            let keywords = list_or_unspecified(&details.keywords);
-            format!(
-                "Complete place description:\nUse one or many of these details:\nAmbiance: {}\nItems: {}\nKeywords: {}",
-                ambiance, items, keywords
-            )
+    match text.classify_orientation(&location_refs(&context.locations), description) {
+        Ok(orientation) => {
+            info!(
+                "Gemini classified the place as {orientation} ({}).",
+                orientation.aspect_ratio()
+            );
+            if let Some(cache) = context.description_cache.as_ref()
+                && let Err(error) = cache.put_orientation(&cache_key, orientation)
+            {
+                warn!("failed to cache orientation ({error}).");
+            }
+            orientation.aspect_ratio().to_string()
         }
-        None => {
-            "Complete place description:\nUse one or many of these details:\nAmbiance: Unspecified\nItems: Unspecified\nKeywords: Unspecified"
-                .to_string()
+        Err(error) => {
+            warn!(
+                "failed to classify orientation via Gemini ({error}); using {DEFAULT_ASPECT_RATIO}."
+            );
+            DEFAULT_ASPECT_RATIO.to_string()
         }
-    };
-    sections.push(place_section);
+    }
+}
 
-    let timing_section = format!(
-        "Scene timing:\n{}\n{}",
-        format_context_line("Season", season),
-        format_context_line("Time of day", time_of_day),
-    );
-    sections.push(timing_section);
+fn upscale_predictions(
+    images: &dyn ImageGenerator,
+    response: &mut PredictResponse,
+    factor: UpscaleFactor,
+) {
+    for (index, prediction) in response.predictions.iter_mut().enumerate() {
+        let display_index = index + 1;
+        info!("Upscaling prediction #{display_index} ({factor})...");
+        match images.upscale(prediction, factor) {
+            Ok(Some(upscaled)) => match upscaled.predictions.into_iter().next() {
+                Some(upscaled_prediction) => *prediction = upscaled_prediction,
+                None => warn!(
+                    "upscaling prediction #{display_index} returned no image; keeping the original."
+                ),
+            },
+            Ok(None) => {}
+            Err(error) => warn!(
+                "failed to upscale prediction #{display_index} ({error}); keeping the original."
+            ),
+        }
+    }
+}
 
-    sections.join("\n\n")
+/// File names for `context`, using its resolved season, time of day,
+/// weather, and theme so configured defaults show up in the name.
+fn image_name_context_for(context: &RunContext, prefix: &str) -> ImageNameContext {
+    let locations = location_refs(&context.locations);
+    build_image_name_context(
+        prefix,
+        &locations,
+        context.season.as_deref(),
+        context.time_of_day.as_deref(),
+        context.weather.as_deref(),
+        context.theme.map(Theme::as_str),
+    )
 }
 
-fn build_image_name_context(cli: &Cli) -> ImageNameContext {
-    let mut builder = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX);
-    builder.push_component(Some(cli.location.as_str()));
-    builder.push_component(cli.season.as_deref());
-    builder.push_component(cli.time_of_day.as_deref());
-    builder.build()
+/// Outcome of a run, reported through the process exit code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum RunStatus {
+    /// Images were saved, or generation was skipped and only the prompt was
+    /// printed.
+    Success,
+    /// The Gemini request failed, no image could be saved, or `clean` could
+    /// not remove every file.
+    ApiFailure,
+    /// The configuration could not be loaded or the client could not be built.
+    ConfigFailure,
+    /// Some images were saved, but at least one prediction or batch location
+    /// failed.
+    PartialFailure,
 }
 
-fn main() {
-    let cli = Cli::parse();
-    let image_name_context = build_image_name_context(&cli);
+impl RunStatus {
+    fn code(self) -> u8 {
+        match self {
+            Self::Success => 0,
+            Self::ApiFailure => 1,
+            Self::ConfigFailure => 2,
+            Self::PartialFailure => 3,
+        }
+    }
+}
 
-    let context = run(cli);
+/// Record `cli` for `--repeat-last` when it describes a run worth repeating.
+/// A retry takes its scenes from the failures log, not the flags, and an
+/// estimate generates nothing.
+fn remember_run(cli: &Cli) {
+    if cli.command.is_none() && !cli.retry_failed && !cli.estimate {
+        last_run::remember(cli);
+    }
+}
+
+fn main() -> ExitCode {
+    let started = Instant::now();
+    let mut cli = Cli::parse();
+    logging::init(cli.log_level);
+    if let Some(Command::Clean(args)) = &cli.command {
+        return ExitCode::from(clean::run(args).code());
+    }
+    if let Some(Command::Alias(command)) = &cli.command {
+        return ExitCode::from(alias::run(command).code());
+    }
+    if cli.repeat_last {
+        last_run::preload(&mut cli);
+    }
+    if cli.location_from_clipboard
+        && let Err(error) = apply_clipboard_location(&mut cli, clipboard::paste())
+    {
+        error!("{error}");
+        return ExitCode::from(RunStatus::ConfigFailure.code());
+    }
+    if cli.interactive
+        && cli.command.is_none()
+        && let Err(error) = interactive::run(&mut cli)
+    {
+        error!("{error}");
+        return ExitCode::from(RunStatus::ConfigFailure.code());
+    }
+    if let Some(sweep) = cli.seed_sweep
+        && let Err(error) = sweep.check_limit(cli.seed_sweep_limit)
+    {
+        error!("{error}");
+        return ExitCode::from(RunStatus::ConfigFailure.code());
+    }
+    // Recorded before `run` expands aliases, so `--repeat-last` replays the
+    // alias as typed and picks up later edits to it.
+    remember_run(&cli);
+    let command = cli.command.clone();
+    let mut context = run(cli.clone());
+    // Names and failure records should use the expanded locations.
+    cli.location = context.locations.clone();
 
     for message in &context.infos {
-        eprintln!("{message}");
+        info!("{message}");
     }
+    progress::emit(
+        context.progress,
+        ProgressEvent::ConfigLoaded {
+            path: context
+                .config_path
+                .as_ref()
+                .map(|path| path.display().to_string()),
+            config_created: context.config_created,
+        },
+    );
 
     for warning in &context.warnings {
-        eprintln!("{warning}");
+        warn!("{warning}");
     }
 
-    let general_instructions = craft_prompt(DEFAULT_PROMPT, &context.location, None, None);
-    let mut prompt = build_structured_prompt(
-        general_instructions.as_str(),
-        None,
-        context.season.as_deref(),
-        context.time_of_day.as_deref(),
-    );
+    let failures_log = context.image_output_dir.as_deref().map(failures::log_path);
+    let batch_scenes: Vec<FailedScene> = if cli.retry_failed {
+        let Some(path) = failures_log.as_deref() else {
+            error!("--retry-failed needs an image output directory.");
+            return ExitCode::from(RunStatus::ConfigFailure.code());
+        };
+        match failures::load(path) {
+            Ok(scenes) if scenes.is_empty() => {
+                info!("No failed scenes recorded in {}.", path.display());
+                return ExitCode::from(RunStatus::Success.code());
+            }
+            Ok(scenes) => {
+                info!(
+                    "Retrying {} failed scene(s) from {}.",
+                    scenes.len(),
+                    path.display()
+                );
+                context.batch = true;
+                context.locations = scenes.iter().map(|scene| scene.location.clone()).collect();
+                scenes
+            }
+            Err(error) => {
+                error!("failed to read {} ({error}).", path.display());
+                return ExitCode::from(RunStatus::ConfigFailure.code());
+            }
+        }
+    } else if context.batch {
+        cli.location
+            .iter()
+            .map(|location| FailedScene::from_cli(&cli, location))
+            .collect()
+    } else {
+        Vec::new()
+    };
 
-    if context.config_ready
+    let scene_contexts = if context.batch {
+        batch_scenes
+            .iter()
+            .map(|scene| batch::recorded_scene_context(&context, scene))
+            .collect()
+    } else {
+        vec![context.clone()]
+    };
+    let prefix = context.file_name_prefix.as_str();
+    let scenes: Vec<(RunContext, ImageNameContext)> = scene_contexts
+        .into_iter()
+        .map(|scene| {
+            let image_name_context = image_name_context_for(&scene, prefix);
+            (scene, image_name_context)
+        })
+        .collect();
+
+    let mut status = if context.config_ready {
+        RunStatus::Success
+    } else {
+        RunStatus::ConfigFailure
+    };
+
+    if context.estimate {
+        let table = PriceTable::default().with_config(&context.pricing);
+        let scene_contexts: Vec<RunContext> =
+            scenes.iter().map(|(scene, _)| scene.clone()).collect();
+        let estimate = CostEstimate::new(&table, &client_options(&context), &scene_contexts);
+        for line in estimate.lines() {
+            println!("{line}");
+        }
+        return ExitCode::from(status.code());
+    }
+
+    let client = if context.config_ready
         && let Some(api_key) = context.gemini_api_key.as_deref()
     {
-        let season = context.season.as_deref().unwrap_or("any season");
-        match generate_place_description(&context.location, season, api_key) {
-            Ok(description) => {
-                eprintln!("Gemini place description: {}", description);
-                prompt = build_structured_prompt(
-                    general_instructions.as_str(),
-                    Some(&description),
-                    context.season.as_deref(),
-                    context.time_of_day.as_deref(),
-                );
-            }
+        match GeminiClient::with_options(api_key, client_options(&context)) {
+            Ok(client) => Some(client),
             Err(error) => {
-                eprintln!("Warning: failed to generate place description via Gemini ({error}).");
+                warn!("failed to initialize Gemini client ({error}).");
+                status = RunStatus::ConfigFailure;
+                None
             }
         }
-        match generate_image_with_progress(api_key, &prompt) {
-            Some(Ok(response)) => {
-                eprintln!(
-                    "Gemini generated {} prediction(s).",
-                    response.predictions.len()
+    } else {
+        None
+    };
+
+    if context.seed_sweep.is_some()
+        && let Some(client) = client.as_ref()
+        && !client.honors_seed()
+    {
+        error!(
+            "--seed-sweep needs an Imagen endpoint that honors `seed`, which the Gemini API does not document; set {BASE_URL_ENV_VAR} to a compatible endpoint to sweep seeds."
+        );
+        return ExitCode::from(RunStatus::ConfigFailure.code());
+    }
+
+    if let Some(Command::Models { all }) = &command {
+        let Some(client) = client.as_ref() else {
+            error!("listing models requires a configured Gemini API key.");
+            return ExitCode::from(RunStatus::ConfigFailure.code());
+        };
+        return match client.list_models() {
+            Ok(models) => {
+                for line in model_lines(&models, *all) {
+                    println!("{line}");
+                }
+                ExitCode::from(status.code())
+            }
+            Err(error) => {
+                error!("failed to list Gemini models ({error}).");
+                ExitCode::from(RunStatus::ApiFailure.code())
+            }
+        };
+    }
+
+    if command == Some(Command::Preview) {
+        let text = client.as_ref().map(|client| client as &dyn TextGenerator);
+        let previews: Vec<String> = scenes
+            .iter()
+            .map(|(scene, _)| preview_scene(text, scene))
+            .collect();
+        println!("{}", previews.join("\n\n"));
+        return ExitCode::from(status.code());
+    }
+
+    let mut summary = SaveSummary::default();
+    let prompts: Vec<String> = match client.as_ref() {
+        Some(client) if context.prompt_only => scenes
+            .iter()
+            .map(|(scene, _)| {
+                build_scene_prompt(
+                    scene,
+                    describe_scene(client, scene).as_ref(),
+                    known_aspect_ratio(scene),
+                )
+            })
+            .collect(),
+        Some(client) if context.batch => {
+            let outcomes = batch::render(client, client, &scenes, context.concurrency);
+            for ((scene, _), outcome) in scenes.iter().zip(&outcomes) {
+                info!("{}", batch::format_line(&scene.location, outcome.as_ref()));
+            }
+            if let Some(path) = failures_log.as_deref() {
+                batch::record_failures(path, &batch_scenes, &outcomes);
+            }
+            if let Some(dir) = context.image_output_dir.as_deref() {
+                // Record what each scene was rendered with, not just its flags.
+                let rendered: Vec<FailedScene> = batch_scenes
+                    .iter()
+                    .zip(&scenes)
+                    .map(|(scene, (scene_context, _))| scene.resolved(scene_context))
+                    .collect();
+                let manifest = Manifest::new(
+                    client.options(),
+                    &context,
+                    &rendered,
+                    &outcomes,
+                    format_iso8601(unix_timestamp_secs()),
                 );
+                match manifest::write(dir, &manifest) {
+                    Ok(path) => info!("Wrote the run manifest to {}", path.display()),
+                    Err(error) => warn!("failed to write the run manifest ({error})."),
+                }
+            }
+            summary = outcomes
+                .iter()
+                .flatten()
+                .map(SceneOutcome::summary)
+                .fold(SaveSummary::default(), SaveSummary::combine);
+            let any_scene_empty = outcomes
+                .iter()
+                .any(|outcome| outcome.as_ref().is_none_or(|o| o.saved_paths().is_empty()));
+            let all_offline = outcomes
+                .iter()
+                .all(|outcome| outcome.as_ref().is_some_and(|o| o.offline));
+            status = if all_offline {
+                RunStatus::Success
+            } else {
+                summary.status(any_scene_empty)
+            };
+            scenes
+                .iter()
+                .zip(outcomes)
+                .map(|((scene, _), outcome)| {
+                    outcome.map_or_else(
+                        || build_scene_prompt(scene, None, known_aspect_ratio(scene)),
+                        |o| o.prompt,
+                    )
+                })
+                .collect()
+        }
+        Some(client) if let Some(sweep) = context.seed_sweep => {
+            let (scene, image_name_context) = &scenes[0];
+            let (prompt, outcomes) =
+                render_seed_sweep(client, client, scene, image_name_context, sweep);
+            summary = outcomes
+                .iter()
+                .map(SceneOutcome::summary)
+                .fold(SaveSummary::default(), SaveSummary::combine);
+            status = if outcomes.iter().any(|outcome| outcome.offline) {
+                RunStatus::Success
+            } else {
+                summary.status(false)
+            };
+            vec![prompt]
+        }
+        Some(client) => {
+            let (scene, image_name_context) = &scenes[0];
+            let outcome = render_scene(client, client, scene, image_name_context, true);
+            summary = outcome.summary();
+            status = if outcome.offline {
+                RunStatus::Success
+            } else {
+                summary.status(false)
+            };
+            vec![outcome.prompt]
+        }
+        None => scenes
+            .iter()
+            .map(|(scene, _)| build_scene_prompt(scene, None, known_aspect_ratio(scene)))
+            .collect(),
+    };
+
+    let prompt_text = prompts.join("\n\n");
+    // Keep stdout clean for the image bytes in `--stdout-image` mode.
+    if context.stdout_image {
+        info!("Prompt:\n{prompt_text}");
+    } else {
+        println!("{prompt_text}");
+    }
+    if context.copy_prompt {
+        match clipboard::copy(&prompt_text) {
+            Ok(()) => info!("Copied the prompt to the clipboard."),
+            Err(error) => warn!("failed to copy the prompt to the clipboard ({error})."),
+        }
+    }
+    let output_dir = if context.stdout_image {
+        None
+    } else {
+        context.image_output_dir.as_deref()
+    };
+    info!(
+        "{}",
+        format_run_summary(
+            started.elapsed(),
+            summary,
+            output_dir,
+            logging::warning_count(),
+        )
+    );
+    progress::emit(
+        context.progress,
+        ProgressEvent::Done {
+            requested: summary.requested,
+            saved: summary.saved,
+            failed: summary.failed(),
+            exit_code: status.code(),
+        },
+    );
+    ExitCode::from(status.code())
+}
+
+/// Gemini client settings for the run.
+fn client_options(context: &RunContext) -> GeminiClientOptions {
+    let defaults = GeminiClientOptions::default();
+    GeminiClientOptions {
+        image_model: context.image_model.clone().unwrap_or(defaults.image_model),
+        text_model: context.text_model.clone().unwrap_or(defaults.text_model),
+        negative_prompt: context.negative_prompt.clone(),
+        person_generation: context.person_generation.or(defaults.person_generation),
+        disable_watermark: context.disable_watermark,
+        safety_settings: context.safety_settings.clone(),
+        max_output_tokens: context.max_output_tokens,
+        proxy: context.proxy.clone(),
+        extra_headers: context.extra_headers.clone(),
+        requests_per_minute: context.requests_per_minute,
+        user_agent: USER_AGENT.to_string(),
+        dump_requests: context
+            .dump_request
+            .then(|| RequestDumper::new(|request| eprintln!("{request}"))),
+        ..defaults
+    }
+}
+
+/// The prompt for a scene, with the place description section filled in
+/// when one is available.
+fn build_scene_prompt(
+    context: &RunContext,
+    description: Option<&PlaceDescription>,
+    aspect_ratio: Option<&str>,
+) -> String {
+    let general_instructions = build_general_instructions(context);
+    let season_prompt = season_prompt(context.season.as_deref(), context.season_fragment);
+    build_structured_prompt(
+        general_instructions.as_str(),
+        description,
+        season_prompt.as_deref(),
+        context.time_of_day.as_deref(),
+        context.weather.as_deref(),
+        aspect_ratio,
+    )
+}
+
+/// The aspect ratio known without asking Gemini: the fixed ratio, or the
+/// client default when none was chosen. `None` for an unresolved `auto`.
+fn known_aspect_ratio(context: &RunContext) -> Option<&'static str> {
+    match context.aspect_ratio {
+        None => Some(DEFAULT_ASPECT_RATIO),
+        Some(AspectRatioArg::Fixed(ratio)) => Some(ratio),
+        Some(AspectRatioArg::Auto) => None,
+    }
+}
+
+/// One `name  methods` line per model, aligned on the longest name. Unless
+/// `all` is set, only models Mawaku can generate images or text with are kept.
+fn model_lines(models: &[ModelInfo], all: bool) -> Vec<String> {
+    let shown: Vec<&ModelInfo> = models
+        .iter()
+        .filter(|model| all || model.is_image_or_text_capable())
+        .collect();
+    let width = shown
+        .iter()
+        .map(|model| model.id().len())
+        .max()
+        .unwrap_or(0);
+    shown
+        .iter()
+        .map(|model| {
+            format!(
+                "{:<width$}  {}",
+                model.id(),
+                model.supported_generation_methods.join(", ")
+            )
+        })
+        .collect()
+}
+
+/// Labeled sections showing how the place description feeds the prompt,
+/// for the `preview` subcommand. The description is fetched only when a
+/// text backend is available.
+fn preview_scene(text: Option<&dyn TextGenerator>, context: &RunContext) -> String {
+    let description = text.and_then(|text| describe_scene(text, context));
+    let description_section = match description.as_ref() {
+        Some(description) => serde_json::to_string_pretty(description)
+            .unwrap_or_else(|error| format!("(failed to format description: {error})")),
+        None => "(none)".to_string(),
+    };
+    let mut sections = Vec::new();
+    if context.batch {
+        sections.push(format!("=== {} ===", context.location));
+    }
+    sections.push(format!("--- Place description ---\n{description_section}"));
+    sections.push(format!(
+        "--- General instructions ---\n{}",
+        build_general_instructions(context)
+    ));
+    sections.push(format!(
+        "--- Structured prompt ---\n{}",
+        build_scene_prompt(context, description.as_ref(), known_aspect_ratio(context))
+    ));
+    sections.join("\n\n")
+}
+
+/// The Gemini place description for a scene, or `None` when there is no
+/// location, it was skipped, or the request failed.
+fn describe_scene(text: &dyn TextGenerator, context: &RunContext) -> Option<PlaceDescription> {
+    if context.location.is_empty() || context.skip_description {
+        return None;
+    }
+    let season = context.season.as_deref().unwrap_or("any season");
+    match fetch_place_description(text, context, season) {
+        Ok(description) => {
+            info!("Gemini place description: {}", description);
+            Some(description)
+        }
+        Err(error) => {
+            warn!("failed to generate place description via Gemini ({error}).");
+            if let GeminiError::StoppedEarly { finish_reason, .. } = &error {
+                warn_token_cap(context, finish_reason);
+            }
+            None
+        }
+    }
+}
+
+/// What [`render_scene`] produced for one scene.
+#[derive(Debug)]
+struct SceneOutcome {
+    prompt: String,
+    /// One entry per prediction returned by Gemini, avatars included, plus
+    /// a failed entry when the avatar request itself failed.
+    predictions: Vec<PredictionOutcome>,
+    /// Whether an image was written to stdout (`--stdout-image`).
+    streamed: bool,
+    /// Whether generation was skipped because Gemini could not be reached.
+    offline: bool,
+}
+
+/// The prompt for a scene and what went into it.
+struct ScenePrompt {
+    description: Option<PlaceDescription>,
+    aspect_ratio: Option<String>,
+    prompt: String,
+}
+
+/// Describe the place and build the prompt sent to Imagen.
+fn prepare_scene(text: &dyn TextGenerator, context: &RunContext) -> ScenePrompt {
+    let description = describe_scene(text, context);
+    progress::emit(
+        context.progress,
+        ProgressEvent::DescriptionDone {
+            location: context.location.clone(),
+            described: description.is_some(),
+        },
+    );
+    let aspect_ratio = resolve_aspect_ratio(text, context, description.as_ref());
+    let prompt = build_scene_prompt(
+        context,
+        description.as_ref(),
+        Some(aspect_ratio.as_deref().unwrap_or(DEFAULT_ASPECT_RATIO)),
+    );
+    ScenePrompt {
+        description,
+        aspect_ratio,
+        prompt,
+    }
+}
+
+/// Describe the place, generate predictions, and save them. Failures are
+/// logged; the outcome lists whatever was saved.
+fn render_scene(
+    images: &dyn ImageGenerator,
+    text: &dyn TextGenerator,
+    context: &RunContext,
+    image_name_context: &ImageNameContext,
+    show_progress: bool,
+) -> SceneOutcome {
+    let scene_prompt = prepare_scene(text, context);
+    render_prompt(
+        images,
+        context,
+        image_name_context,
+        scene_prompt,
+        show_progress,
+    )
+}
+
+/// Render the scene once per seed of `sweep`, reusing one place
+/// description and prompt so only the seed differs. Stops early when Gemini
+/// cannot be reached. Returns the prompt and one outcome per seed tried.
+fn render_seed_sweep(
+    images: &dyn ImageGenerator,
+    text: &dyn TextGenerator,
+    context: &RunContext,
+    image_name_context: &ImageNameContext,
+    sweep: SeedSweep,
+) -> (String, Vec<SceneOutcome>) {
+    let scene_prompt = prepare_scene(text, context);
+    info!(
+        "Sweeping {} seed(s) ({sweep}) with the same prompt.",
+        sweep.len()
+    );
+    let mut outcomes = Vec::new();
+    for seed in sweep.seeds() {
+        info!("Rendering seed {seed}...");
+        let seeded = RunContext {
+            seed: Some(seed),
+            ..context.clone()
+        };
+        let outcome = render_prompt(
+            images,
+            &seeded,
+            &image_name_context.with_component(&format!("seed{seed}")),
+            ScenePrompt {
+                description: scene_prompt.description.clone(),
+                aspect_ratio: scene_prompt.aspect_ratio.clone(),
+                prompt: scene_prompt.prompt.clone(),
+            },
+            true,
+        );
+        let offline = outcome.offline;
+        outcomes.push(outcome);
+        if offline {
+            break;
+        }
+    }
+    (scene_prompt.prompt, outcomes)
+}
+
+/// Generate predictions for a prepared prompt and save them.
+fn render_prompt(
+    images: &dyn ImageGenerator,
+    context: &RunContext,
+    image_name_context: &ImageNameContext,
+    scene_prompt: ScenePrompt,
+    show_progress: bool,
+) -> SceneOutcome {
+    let ScenePrompt {
+        description,
+        aspect_ratio,
+        prompt,
+    } = scene_prompt;
+    progress::emit(
+        context.progress,
+        ProgressEvent::ImageStart {
+            location: context.location.clone(),
+        },
+    );
+    let result = fetch_images(
+        images,
+        context,
+        &prompt,
+        aspect_ratio.as_deref(),
+        show_progress,
+    );
+    let mut predictions = Vec::new();
+    let mut offline = false;
+    match result {
+        Some(Ok(mut response)) => {
+            if context.verbose {
+                info!("Prompt sent to Imagen:\n{}", response.echoed_prompt);
+            }
+
+            if let Some(factor) = context.upscale {
+                upscale_predictions(images, &mut response, factor);
+            }
+
+            if context.stdout_image {
+                return SceneOutcome {
+                    streamed: stream_first_image(&response),
+                    prompt,
+                    predictions,
+                    offline: false,
+                };
+            }
+
+            predictions = save_predictions(&response, context, image_name_context, &prompt);
+            // One tile per prediction: `--keep-sharp` originals stay off the sheet.
+            let saved_paths = saved_paths(&predictions);
+
+            if context.contact_sheet && !saved_paths.is_empty() {
+                let sheet_stem = format!("{}-sheet", image_name_context.base());
+                // Keep the sheet next to the images it summarizes.
+                let sheet_dir = saved_paths[0].parent();
+                match save_contact_sheet(&saved_paths, &sheet_stem, sheet_dir) {
+                    Ok(path) => info!("Saved contact sheet to {}", path.display()),
+                    Err(error) => {
+                        warn!("failed to save contact sheet ({error}).")
+                    }
+                }
+            }
+
+            if context.also_avatar {
+                predictions.extend(render_avatar(
+                    images,
+                    context,
+                    description.as_ref(),
+                    image_name_context,
+                    show_progress,
+                ));
+            }
+        }
+        Some(Err(error)) if error.is_offline() && !context.require_online => {
+            report_image_failed(context, error.to_string());
+            warn!(
+                "cannot reach Gemini ({error}); skipped image generation and printing the locally built prompt instead."
+            );
+            offline = true;
+        }
+        Some(Err(error)) => {
+            report_image_failed(context, error.to_string());
+            warn!("failed to generate image via Gemini ({error}).");
+        }
+        None => {
+            report_image_failed(context, "worker panicked".to_string());
+            warn!("image generation request ended unexpectedly.");
+        }
+    }
+
+    SceneOutcome {
+        prompt,
+        predictions,
+        streamed: false,
+        offline,
+    }
+}
+
+/// The images for `prompt`: reused from the image cache when it holds an
+/// entry for the same prompt and parameters, otherwise requested from Imagen
+/// and cached. `None` means the request ended unexpectedly.
+fn fetch_images(
+    images: &dyn ImageGenerator,
+    context: &RunContext,
+    prompt: &str,
+    aspect_ratio: Option<&str>,
+    show_progress: bool,
+) -> Option<Result<PredictResponse, GeminiError>> {
+    let image_cache_key = context
+        .image_cache
+        .as_ref()
+        .map(|_| cache::image_key(context, prompt, aspect_ratio));
+    let cached = match (context.image_cache.as_ref(), image_cache_key.as_deref()) {
+        (Some(cache), Some(key)) if !context.refresh_cache => cache.get(key),
+        _ => None,
+    };
+    if let Some(mut response) = cached {
+        info!(
+            "Reusing {} cached image(s) for an identical prompt; Imagen was not called.",
+            response.predictions.len()
+        );
+        response.echoed_prompt = prompt.to_string();
+        return Some(Ok(response));
+    }
+
+    // The spinner and the JSON event stream share stderr, so show only one.
+    let result = if show_progress && context.progress == ProgressFormat::Human {
+        retry::generate_with_progress(images, prompt, aspect_ratio, context.seed, context.spinner)
+    } else {
+        Some(retry::request_images(
+            images,
+            prompt,
+            aspect_ratio,
+            context.seed,
+        ))
+    };
+    if let Some(Ok(response)) = &result {
+        info!(
+            "Gemini generated {} prediction(s).",
+            response.predictions.len()
+        );
+        if let (Some(cache), Some(key)) = (context.image_cache.as_ref(), image_cache_key.as_deref())
+            && let Err(error) = cache.put(key, response)
+        {
+            warn!("failed to cache the generated images ({error}).");
+        }
+    }
+    result
+}
+
+/// Generate and save the square avatar for `--also-avatar`, named like the
+/// scene's images plus an `avatar` component. A failed request is recorded
+/// as one failed prediction so the run reports it.
+fn render_avatar(
+    images: &dyn ImageGenerator,
+    context: &RunContext,
+    description: Option<&PlaceDescription>,
+    image_name_context: &ImageNameContext,
+    show_progress: bool,
+) -> Vec<PredictionOutcome> {
+    let prompt = build_avatar_prompt(&build_scene_prompt(
+        context,
+        description,
+        Some(AVATAR_ASPECT_RATIO),
+    ));
+    info!("Generating the square avatar ({AVATAR_ASPECT_RATIO}).");
+    match fetch_images(
+        images,
+        context,
+        &prompt,
+        Some(AVATAR_ASPECT_RATIO),
+        show_progress,
+    ) {
+        Some(Ok(mut response)) => {
+            if let Some(factor) = context.upscale {
+                upscale_predictions(images, &mut response, factor);
+            }
+            let avatar_name_context = image_name_context.with_component(AVATAR_FILE_NAME_COMPONENT);
+            save_predictions(&response, context, &avatar_name_context, &prompt)
+        }
+        Some(Err(error)) => {
+            report_image_failed(context, error.to_string());
+            warn!("failed to generate the avatar via Gemini ({error}).");
+            vec![PredictionOutcome::Failed(error.to_string())]
+        }
+        None => {
+            report_image_failed(context, "worker panicked".to_string());
+            warn!("avatar generation request ended unexpectedly.");
+            vec![PredictionOutcome::Failed("worker panicked".to_string())]
+        }
+    }
+}
+
+impl SceneOutcome {
+    /// Paths of the predictions that were saved, in order.
+    fn saved_paths(&self) -> Vec<PathBuf> {
+        saved_paths(&self.predictions)
+    }
+
+    /// Every file written for the saved predictions, including sharp
+    /// originals kept by `--keep-sharp`.
+    fn saved_files(&self) -> Vec<PathBuf> {
+        saved_files(&self.predictions)
+    }
 
-                for (index, prediction) in response.predictions.iter().enumerate() {
-                    let display_index = index + 1;
-                    match prediction.bytes_base64_encoded.as_deref() {
-                        Some(encoded) => {
-                            let file_stem = image_name_context.file_stem(display_index);
-                            let output_dir = context.image_output_dir.as_deref();
-                            let options = SaveImageOptions {
-                                file_stem: Some(file_stem.as_str()),
-                                mime_type: prediction.mime_type.as_deref(),
-                                output_dir,
-                            };
-
-                            match save_base64_image(encoded, options) {
-                                Ok(path) => {
-                                    eprintln!(
-                                        "Saved prediction #{display_index} to {}",
-                                        path.display()
-                                    );
-                                }
-                                Err(error) => {
-                                    eprintln!(
-                                        "Warning: failed to save prediction #{display_index} ({error})."
-                                    );
-                                }
+    fn summary(&self) -> SaveSummary {
+        if self.streamed {
+            return SaveSummary {
+                requested: 1,
+                saved: 1,
+            };
+        }
+        SaveSummary {
+            requested: self.predictions.len(),
+            saved: self.saved_paths().len(),
+        }
+    }
+}
+
+/// How many predictions a run received and how many of them were saved.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct SaveSummary {
+    requested: usize,
+    saved: usize,
+}
+
+impl SaveSummary {
+    fn failed(self) -> usize {
+        self.requested - self.saved
+    }
+
+    fn combine(self, other: Self) -> Self {
+        Self {
+            requested: self.requested + other.requested,
+            saved: self.saved + other.saved,
+        }
+    }
+
+    /// Nothing saved is a failure; some but not all is a partial failure.
+    /// `other_failures` marks losses the counts cannot show, such as a batch
+    /// location whose request failed outright.
+    fn status(self, other_failures: bool) -> RunStatus {
+        if self.saved == 0 {
+            RunStatus::ApiFailure
+        } else if self.failed() > 0 || other_failures {
+            RunStatus::PartialFailure
+        } else {
+            RunStatus::Success
+        }
+    }
+}
+
+fn saved_paths(predictions: &[PredictionOutcome]) -> Vec<PathBuf> {
+    predictions
+        .iter()
+        .filter_map(|outcome| match outcome {
+            PredictionOutcome::Saved { path, .. } => Some(path.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn saved_files(predictions: &[PredictionOutcome]) -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    for outcome in predictions {
+        if let PredictionOutcome::Saved { path, sharp, .. } = outcome {
+            files.push(path.clone());
+            files.extend(sharp.clone());
+        }
+    }
+    files
+}
+
+/// What happened to one prediction in [`save_predictions`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum PredictionOutcome {
+    Saved {
+        path: PathBuf,
+        dimensions: Option<ImageDimensions>,
+        /// The unblurred original saved by `--keep-sharp`.
+        sharp: Option<PathBuf>,
+    },
+    /// The prediction carried no image bytes.
+    MissingBytes,
+    /// Every candidate file name was already taken.
+    NoFileName,
+    /// Writing the file failed; holds the error message.
+    Failed(String),
+}
+
+/// A prediction in [`save_predictions`] once its file name is settled.
+enum SaveJob<'a> {
+    Save {
+        file_stem: String,
+        encoded: &'a str,
+        mime_type: Option<&'a str>,
+    },
+    MissingBytes,
+    NoFileName,
+}
+
+/// Write every prediction in `response` to the output directory, one
+/// outcome per prediction in order. Makes no network calls.
+///
+/// File names are picked up front, then up to [`MAX_CONCURRENT_SAVES`]
+/// predictions are decoded and written at once. Results are reported in
+/// prediction order.
+fn save_predictions(
+    response: &PredictResponse,
+    context: &RunContext,
+    image_name_context: &ImageNameContext,
+    prompt: &str,
+) -> Vec<PredictionOutcome> {
+    // Saved images land in today's subdirectory when organizing
+    // by date, so check that directory for name collisions.
+    let mut taken_stems = context
+        .image_output_dir
+        .as_deref()
+        .map(|dir| {
+            if context.organize_by_date {
+                existing_file_stems(&dir.join(today_partition()))
+            } else {
+                existing_file_stems(dir)
+            }
+        })
+        .unwrap_or_default();
+
+    // With `--keep-sharp`, the blurred copy is named with a `blur`
+    // component and the original keeps the plain name.
+    let blur_name_context = (context.blur.is_some() && context.keep_sharp)
+        .then(|| image_name_context.with_component(BLUR_FILE_NAME_COMPONENT));
+    let mut sharp_stems = HashMap::new();
+    let jobs: Vec<SaveJob> = response
+        .predictions
+        .iter()
+        .enumerate()
+        .map(|(index, prediction)| {
+            let Some(encoded) = prediction.bytes_base64_encoded.as_deref() else {
+                return SaveJob::MissingBytes;
+            };
+            let Some(file_stem) = image_name_context.file_stem_avoiding(index + 1, &taken_stems)
+            else {
+                return SaveJob::NoFileName;
+            };
+            taken_stems.insert(file_stem.clone());
+            let file_stem = match &blur_name_context {
+                Some(blur_name_context) => {
+                    let Some(blurred_stem) =
+                        blur_name_context.file_stem_avoiding(index + 1, &taken_stems)
+                    else {
+                        return SaveJob::NoFileName;
+                    };
+                    taken_stems.insert(blurred_stem.clone());
+                    sharp_stems.insert(blurred_stem.clone(), file_stem);
+                    blurred_stem
+                }
+                None => file_stem,
+            };
+            SaveJob::Save {
+                file_stem,
+                encoded,
+                mime_type: prediction.mime_type.as_deref(),
+            }
+        })
+        .collect();
+
+    let metadata = if context.embed_metadata {
+        build_metadata(context, prompt)
+    } else {
+        Vec::new()
+    };
+    let results = save_concurrently(
+        &jobs,
+        MAX_CONCURRENT_SAVES,
+        |file_stem, encoded, mime_type| -> Result<_, ImageSaveError> {
+            let options = |file_stem, blur, save_raw| SaveImageOptions {
+                file_stem: Some(file_stem),
+                mime_type,
+                output_dir: context.image_output_dir.as_deref(),
+                caption: context.caption.as_deref(),
+                crop_to_aspect: context.crop_to_aspect,
+                blur,
+                target_format: context.target_format,
+                metadata: &metadata,
+                organize_by_date: context.organize_by_date,
+                save_raw,
+                ..SaveImageOptions::default()
+            };
+            // The raw payload is the sharp original, so it is written once,
+            // next to the sharp copy when there is one.
+            let sharp = sharp_stems.get(file_stem).map(|sharp_stem| {
+                save_base64_image(encoded, options(sharp_stem, None, context.save_raw))
+            });
+            let save_raw = context.save_raw && sharp.is_none();
+            let saved = save_base64_image(encoded, options(file_stem, context.blur, save_raw))?;
+            Ok((saved, sharp))
+        },
+    );
+
+    jobs.iter()
+        .zip(results)
+        .enumerate()
+        .map(|(index, (job, result))| {
+            let display_index = index + 1;
+            match (job, result) {
+                (SaveJob::MissingBytes, _) => {
+                    warn!("prediction #{display_index} did not include encoded image bytes.");
+                    report_image_failed(context, "no encoded image bytes".to_string());
+                    PredictionOutcome::MissingBytes
+                }
+                (SaveJob::NoFileName, _) => {
+                    warn!("could not find an unused file name for prediction #{display_index}.");
+                    report_image_failed(context, "no unused file name".to_string());
+                    PredictionOutcome::NoFileName
+                }
+                (SaveJob::Save { .. }, Some(Ok((saved, sharp)))) => {
+                    let sharp = match sharp {
+                        Some(Ok(sharp)) => {
+                            if let Some(raw_path) = &sharp.raw_path {
+                                info!(
+                                    "Saved raw payload of prediction #{display_index} to {}",
+                                    raw_path.display()
+                                );
                             }
+                            info!(
+                                "Saved the sharp original of prediction #{display_index} to {}",
+                                sharp.path.display()
+                            );
+                            Some(sharp.path)
                         }
-                        None => {
-                            eprintln!(
-                                "Warning: prediction #{display_index} did not include encoded image bytes."
+                        Some(Err(error)) => {
+                            warn!(
+                                "failed to save the sharp original of prediction #{display_index} ({error}); the blurred copy was kept."
                             );
+                            None
                         }
-                    }
+                        None => None,
+                    };
+                    report_saved(context, display_index, saved, sharp)
+                }
+                (SaveJob::Save { .. }, Some(Err(error))) => {
+                    warn!("failed to save prediction #{display_index} ({error}).");
+                    report_image_failed(context, error.to_string());
+                    PredictionOutcome::Failed(error.to_string())
+                }
+                (SaveJob::Save { .. }, None) => {
+                    warn!("failed to save prediction #{display_index} (save worker panicked).");
+                    report_image_failed(context, "save worker panicked".to_string());
+                    PredictionOutcome::Failed("save worker panicked".to_string())
                 }
             }
-            Some(Err(error)) => {
-                eprintln!("Warning: failed to generate image via Gemini ({error}).");
-            }
-            None => {
-                eprintln!("Warning: image generation request ended unexpectedly.");
+        })
+        .collect()
+}
+
+/// Run `save` for every [`SaveJob::Save`] in `jobs` on at most `concurrency`
+/// threads. Results line up with `jobs`; entries are `None` for jobs with
+/// nothing to save or whose worker panicked.
+fn save_concurrently<R, F>(jobs: &[SaveJob], concurrency: usize, save: F) -> Vec<Option<R>>
+where
+    R: Send,
+    F: Fn(&str, &str, Option<&str>) -> R + Sync,
+{
+    let next = AtomicUsize::new(0);
+    let pending = jobs
+        .iter()
+        .filter(|job| matches!(job, SaveJob::Save { .. }))
+        .count();
+    let workers = concurrency.clamp(1, pending.max(1));
+    let mut results: Vec<Option<R>> = jobs.iter().map(|_| None).collect();
+
+    thread::scope(|scope| {
+        let handles: Vec<_> = (0..workers)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut finished = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, Ordering::Relaxed);
+                        let Some(job) = jobs.get(index) else {
+                            break;
+                        };
+                        if let SaveJob::Save {
+                            file_stem,
+                            encoded,
+                            mime_type,
+                        } = job
+                        {
+                            finished.push((index, save(file_stem, encoded, *mime_type)));
+                        }
+                    }
+                    finished
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            match handle.join() {
+                Ok(finished) => {
+                    for (index, result) in finished {
+                        results[index] = Some(result);
+                    }
+                }
+                Err(_) => error!("a save worker panicked; its predictions were not saved."),
             }
         }
+    });
+
+    results
+}
+
+/// Log a saved prediction and announce it on the progress stream.
+fn report_saved(
+    context: &RunContext,
+    display_index: usize,
+    saved: SavedImage,
+    sharp: Option<PathBuf>,
+) -> PredictionOutcome {
+    if let Some(raw_path) = &saved.raw_path {
+        info!(
+            "Saved raw payload of prediction #{display_index} to {}",
+            raw_path.display()
+        );
+    }
+    match saved.dimensions {
+        Some(dimensions) => info!(
+            "Saved prediction #{display_index} ({dimensions}) to {}",
+            saved.path.display()
+        ),
+        None => info!(
+            "Saved prediction #{display_index} to {}",
+            saved.path.display()
+        ),
+    }
+    if saved.metadata_skipped {
+        warn!("prediction #{display_index} is not a PNG or JPEG image; saved it without metadata.");
+    }
+    if let Some(min_bytes) = context.min_image_bytes
+        && let Some(issue) = saved.check(min_bytes)
+    {
+        warn!("prediction #{display_index} may be a failed generation ({issue}).");
+    }
+    progress::emit(
+        context.progress,
+        ProgressEvent::ImageSaved {
+            location: context.location.clone(),
+            path: saved.path.display().to_string(),
+        },
+    );
+    PredictionOutcome::Saved {
+        path: saved.path,
+        dimensions: saved.dimensions,
+        sharp,
+    }
+}
+
+fn report_image_failed(context: &RunContext, error: String) {
+    progress::emit(
+        context.progress,
+        ProgressEvent::ImageFailed {
+            location: context.location.clone(),
+            error,
+        },
+    );
+}
+
+/// Write the first decodable prediction to stdout, logging the outcome.
+fn stream_first_image(response: &PredictResponse) -> bool {
+    match write_first_image(&mut io::stdout().lock(), response) {
+        Ok(Some(len)) => {
+            info!("Wrote {len} bytes of image data to stdout.");
+            true
+        }
+        Ok(None) => {
+            warn!("no prediction could be decoded; nothing was written to stdout.");
+            false
+        }
+        Err(error) => {
+            warn!("failed to write image to stdout ({error}).");
+            false
+        }
+    }
+}
+
+/// Write the first decodable prediction to `out` and flush it. Returns the
+/// number of bytes written, or `None` when no prediction could be decoded.
+fn write_first_image(
+    out: &mut impl Write,
+    response: &PredictResponse,
+) -> io::Result<Option<usize>> {
+    let decoded = response.decode_images();
+    let Some(image) = decoded.images.first() else {
+        for warning in &decoded.warnings {
+            warn!("{warning}.");
+        }
+        return Ok(None);
+    };
+    if response.predictions.len() > 1 {
+        warn!(
+            "Gemini returned {} predictions; writing only the first to stdout.",
+            response.predictions.len()
+        );
     }
 
-    println!("{prompt}");
+    out.write_all(&image.bytes)?;
+    out.flush()?;
+    Ok(Some(image.bytes.len()))
 }
 
-#[derive(Debug, Default)]
+fn format_run_summary(
+    elapsed: Duration,
+    counts: SaveSummary,
+    output_dir: Option<&Path>,
+    warnings: usize,
+) -> String {
+    let mut summary = format!(
+        "Done in {:.1}s: {} requested, {} saved",
+        elapsed.as_secs_f32(),
+        counts.requested,
+        counts.saved
+    );
+    if let Some(dir) = output_dir {
+        summary.push_str(&format!(" to {}", dir.display()));
+    }
+    summary.push_str(&format!(", {} failed", counts.failed()));
+    let warning_label = if warnings == 1 { "warning" } else { "warnings" };
+    summary.push_str(&format!(" ({warnings} {warning_label})"));
+    summary
+}
+
+#[derive(Debug, Clone, Default)]
 struct RunContext {
     #[cfg_attr(not(test), allow(dead_code))]
     prompt: String,
+    prompt_template: String,
+    /// Every location joined for display, cache keys, and captions.
     location: String,
+    locations: Vec<String>,
     infos: Vec<String>,
     warnings: Vec<String>,
     gemini_api_key: Option<String>,
     config_ready: bool,
     image_output_dir: Option<PathBuf>,
+    organize_by_date: bool,
     season: Option<String>,
     time_of_day: Option<String>,
+    weather: Option<String>,
+    theme: Option<Theme>,
+    description_cache: Option<PlaceDescriptionCache>,
+    /// Set when image caching is enabled.
+    image_cache: Option<ImageCache>,
+    refresh_cache: bool,
+    skip_description: bool,
+    stdout_image: bool,
+    prompt_only: bool,
+    save_raw: bool,
+    also_avatar: bool,
+    seed_sweep: Option<SeedSweep>,
+    /// Imagen seed for the current request; set per seed by `--seed-sweep`.
+    seed: Option<u32>,
+    disable_watermark: bool,
+    estimate: bool,
+    /// Price overrides for `--estimate`.
+    pricing: PricingConfig,
+    /// Slugified first segment of saved file names.
+    file_name_prefix: String,
+    /// Whether this run created the configuration file.
+    config_created: bool,
+    config_path: Option<PathBuf>,
+    progress: ProgressFormat,
+    spinner: SpinnerStyle,
+    /// Overrides the client's `personGeneration` default when set.
+    person_generation: Option<PersonGeneration>,
+    /// Safety filters sent with text requests.
+    safety_settings: Vec<SafetySetting>,
+    /// Treat an unreachable Gemini API as a failure rather than falling
+    /// back to printing the prompt.
+    require_online: bool,
+    copy_prompt: bool,
+    batch: bool,
+    concurrency: usize,
+    verbose: bool,
+    dump_request: bool,
+    proxy: Option<String>,
+    extra_headers: HashMap<String, String>,
+    /// Gemini requests allowed per minute; `None` is unlimited.
+    requests_per_minute: Option<NonZeroU32>,
+    /// Token cap for place descriptions; `None` keeps the model's limit.
+    max_output_tokens: Option<u32>,
+    image_model: Option<String>,
+    text_model: Option<String>,
+    aspect_ratio: Option<AspectRatioArg>,
+    negative_prompt: Option<String>,
+    /// Byte threshold for flagging suspiciously small images; `None` skips
+    /// the check.
+    min_image_bytes: Option<usize>,
+    upscale: Option<UpscaleFactor>,
+    caption: Option<String>,
+    crop_to_aspect: Option<AspectRatio>,
+    blur: Option<f32>,
+    keep_sharp: bool,
+    contact_sheet: bool,
+    embed_metadata: bool,
+    /// Encoding saved images are converted to; `None` keeps Imagen's bytes.
+    target_format: Option<ImageFormat>,
+    season_fragment: Option<&'static str>,
 }
 
 fn run(cli: Cli) -> RunContext {
     let Cli {
-        location,
+        command: _,
+        location: locations,
+        location_from_clipboard: _,
+        interactive: _,
         season,
+        season_preset,
         time_of_day,
+        weather,
+        theme,
+        batch,
+        no_batch: _,
+        concurrency,
+        stdout_image,
+        prompt_only,
+        save_raw,
+        also_avatar,
+        seed_sweep,
+        seed_sweep_limit: _,
+        no_watermark,
+        estimate,
+        prefix,
+        progress,
+        spinner,
+        person_generation,
+        safety_threshold,
+        max_output_tokens,
+        require_online,
+        base_prompt,
+        copy,
+        api_key,
+        set_gemini_api_key,
+        repeat_last: _,
+        retry_failed: _,
+        no_cache,
+        cache_images,
+        no_description,
+        verbose,
+        dump_request,
+        log_level: _,
+        aspect_ratio,
+        upscale,
+        label,
+        no_label: _,
+        crop_to_aspect,
+        blur,
+        keep_sharp,
+        contact_sheet,
+        no_contact_sheet: _,
+        embed_metadata,
+        no_embed_metadata: _,
+        output_format,
+        jpeg_quality,
     } = cli;
 
     let mut infos = Vec::new();
     let mut warnings = Vec::new();
 
-    match load_or_init() {
+    let loaded = load_or_init();
+    let locations = match &loaded {
+        Ok(outcome) => alias::expand(&outcome.config, locations, &mut infos),
+        Err(_) => locations,
+    };
+    let location = join_locations(&location_refs(&locations));
+    // Blank flags count as unspecified, so the configured defaults apply.
+    let config_defaults = loaded.as_ref().ok().map(|outcome| &outcome.config);
+    let season = trimmed_or_none(season.as_deref())
+        .map(str::to_string)
+        .or_else(|| season_preset.map(|preset| preset.as_str().to_string()))
+        .or_else(|| {
+            config_defaults
+                .and_then(Config::default_season)
+                .map(str::to_string)
+        });
+    let time_of_day = trimmed_or_none(time_of_day.as_deref())
+        .map(str::to_string)
+        .or_else(|| {
+            config_defaults
+                .and_then(Config::default_time_of_day)
+                .map(str::to_string)
+        });
+    let season_fragment = season_preset.map(SeasonPreset::fragment);
+    let season_for_prompt = season_prompt(season.as_deref(), season_fragment);
+
+    if let Some(factor) = upscale {
+        warnings.push(format!(
+            "upscaling ({factor}) sends an extra request per image and increases cost and latency."
+        ));
+    }
+
+    let caption = if !label {
+        None
+    } else if cfg!(feature = "image") {
+        Some(build_caption(
+            &location,
+            season.as_deref(),
+            time_of_day.as_deref(),
+            weather.as_deref(),
+        ))
+    } else {
+        warnings.push(
+            "--label requires building Mawaku with `--features image`; saving images without a caption."
+                .to_string(),
+        );
+        None
+    };
+
+    let crop_to_aspect = if crop_to_aspect.is_some() && !cfg!(feature = "image") {
+        warnings.push(
+            "--crop-to-aspect requires building Mawaku with `--features image`; saving images uncropped."
+                .to_string(),
+        );
+        None
+    } else {
+        crop_to_aspect
+    };
+
+    let (blur, keep_sharp) = if blur.is_some() && !cfg!(feature = "image") {
+        warnings.push(
+            "--blur requires building Mawaku with `--features image`; saving images without blurring."
+                .to_string(),
+        );
+        (None, false)
+    } else {
+        (blur, keep_sharp)
+    };
+
+    let target_format = match output_format {
+        None => None,
+        Some(_) if !cfg!(feature = "image") => {
+            warnings.push(
+                "--output-format requires building Mawaku with `--features image`; saving images as returned."
+                    .to_string(),
+            );
+            None
+        }
+        Some(format) => {
+            if jpeg_quality.is_some() && format != OutputFormatArg::Jpeg {
+                warnings.push(format!(
+                    "--jpeg-quality only applies to --output-format jpg; ignoring it for {format}."
+                ));
+            }
+            Some(format.image_format(jpeg_quality))
+        }
+    };
+    let embed_metadata = if embed_metadata && target_format == Some(ImageFormat::Webp) {
+        warnings.push(
+            "--embed-metadata supports only PNG and JPEG images; saving WebP images without metadata."
+                .to_string(),
+        );
+        false
+    } else {
+        embed_metadata
+    };
+
+    let contact_sheet = if contact_sheet && !cfg!(feature = "image") {
+        warnings.push(
+            "--contact-sheet requires building Mawaku with `--features image`; skipping the contact sheet."
+                .to_string(),
+        );
+        false
+    } else {
+        contact_sheet
+    };
+
+    let copy = if copy && !cfg!(feature = "clipboard") {
+        warnings.push(
+            "--copy requires building Mawaku with `--features clipboard`; the prompt is only printed."
+                .to_string(),
+        );
+        false
+    } else {
+        copy
+    };
+
+    let api_key = trimmed_or_none(api_key.as_deref()).map(str::to_string);
+    if api_key.is_some() {
+        warnings.push(
+            "--api-key is visible to other users in process listings; prefer the environment variable outside one-off runs."
+                .to_string(),
+        );
+    }
+
+    let (config, config_ready, config_created, config_path) = match loaded {
         Ok(outcome) => {
             if outcome.created {
                 infos.push(format!(
@@ -258,72 +2123,228 @@ fn run(cli: Cli) -> RunContext {
                     outcome.path.display()
                 ));
             }
+            warnings.extend(outcome.warnings.iter().cloned());
 
-            let config = outcome.config;
-
-            let (gemini_api_key, warning) = resolve_gemini_api_key(&config);
-            if let Some(message) = warning {
-                warnings.push(message);
-            }
-
-            let prompt_value = craft_prompt(
-                DEFAULT_PROMPT,
-                &location,
-                season.as_deref(),
-                time_of_day.as_deref(),
-            );
-            let gemini_api_key = gemini_api_key.clone();
-            let image_output_dir = Some(PathBuf::from(&config.image_output_dir));
-
-            RunContext {
-                prompt: prompt_value,
-                location: location.to_string(),
-                infos,
-                warnings,
-                gemini_api_key,
-                config_ready: true,
-                image_output_dir,
-                season: season.clone(),
-                time_of_day: time_of_day.clone(),
+            let mut config = outcome.config;
+            if let Some(key) = trimmed_or_none(set_gemini_api_key.as_deref()) {
+                match update(&outcome.path, |stored| {
+                    stored.gemini_api.api_key = Some(key.to_string());
+                }) {
+                    Ok(()) => {
+                        config.gemini_api.api_key = Some(key.to_string());
+                        infos.push(format!(
+                            "Updated Gemini API key in {}",
+                            outcome.path.display()
+                        ));
+                    }
+                    Err(error) => {
+                        warnings.push(format!("failed to store the Gemini API key ({error})."))
+                    }
+                }
             }
+            (config, true, outcome.created, Some(outcome.path))
         }
         Err(error) => {
             warnings.push(format!(
-                "Warning: failed to load Mawaku configuration ({error}). Falling back to defaults."
+                "failed to load Mawaku configuration ({error}). Falling back to defaults."
             ));
+            if set_gemini_api_key.is_some() {
+                warnings.push(
+                    "the Gemini API key was not stored because the configuration could not be loaded."
+                        .to_string(),
+                );
+            }
+            (Config::default(), false, false, None)
+        }
+    };
 
-            let config = Config::default();
+    let (gemini_api_key, warning) = resolve_gemini_api_key(&config, api_key.as_deref());
+    if let Some(message) = warning {
+        warnings.push(message);
+    }
 
-            let (gemini_api_key, warning) = resolve_gemini_api_key(&config);
-            if let Some(message) = warning {
-                warnings.push(message);
-            }
+    let prompt_template = base_prompt.unwrap_or_else(|| config.prompt_template().to_string());
+    let prompt = craft_prompt_with_locations(
+        &prompt_template,
+        &location_refs(&locations),
+        season_for_prompt.as_deref(),
+        time_of_day.as_deref(),
+        weather.as_deref(),
+        &[],
+    );
 
-            let prompt_value = craft_prompt(
-                DEFAULT_PROMPT,
-                &location,
-                season.as_deref(),
-                time_of_day.as_deref(),
-            );
-            let gemini_api_key = gemini_api_key.clone();
-            let image_output_dir = Some(PathBuf::from(&config.image_output_dir));
-
-            RunContext {
-                prompt: prompt_value,
-                location: location.to_string(),
-                infos,
-                warnings,
-                gemini_api_key,
-                config_ready: false,
-                image_output_dir,
-                season: season.clone(),
-                time_of_day: time_of_day.clone(),
-            }
+    RunContext {
+        prompt,
+        prompt_template,
+        location,
+        locations,
+        infos,
+        warnings,
+        gemini_api_key,
+        config_ready,
+        image_output_dir: Some(PathBuf::from(&config.image_output_dir)),
+        organize_by_date: config.organize_by_date,
+        season,
+        time_of_day,
+        weather,
+        theme,
+        description_cache: cache::description_cache(&config),
+        image_cache: cache::image_cache(&config, cache_images),
+        refresh_cache: no_cache,
+        skip_description: no_description,
+        stdout_image,
+        prompt_only,
+        save_raw,
+        also_avatar,
+        seed_sweep,
+        seed: None,
+        disable_watermark: no_watermark,
+        estimate,
+        pricing: config.pricing.clone(),
+        file_name_prefix: prefix.unwrap_or_else(|| config.file_name_prefix()),
+        config_created,
+        config_path,
+        progress,
+        spinner: if dump_request {
+            SpinnerStyle::None
+        } else {
+            spinner
+                .or_else(|| config_spinner(&config))
+                .unwrap_or_default()
+        },
+        person_generation,
+        safety_settings: safety_threshold
+            .map(SafetySetting::all)
+            .unwrap_or_else(|| config_safety_settings(&config)),
+        require_online,
+        copy_prompt: copy,
+        batch,
+        concurrency: usize::from(concurrency),
+        verbose,
+        dump_request,
+        proxy: config.gemini_api.proxy.clone(),
+        extra_headers: config
+            .gemini_api
+            .extra_headers
+            .clone()
+            .into_iter()
+            .collect(),
+        requests_per_minute: config
+            .gemini_api
+            .requests_per_minute
+            .and_then(NonZeroU32::new),
+        max_output_tokens: max_output_tokens.or_else(|| config_max_output_tokens(&config)),
+        image_model: config.gemini_api.image_model.clone(),
+        text_model: config.gemini_api.text_model.clone(),
+        aspect_ratio: aspect_ratio.or_else(|| config_aspect_ratio(&config)),
+        negative_prompt: config.image.negative_prompt(),
+        min_image_bytes: config.image.payload_check_threshold(),
+        upscale,
+        caption,
+        crop_to_aspect,
+        blur,
+        keep_sharp,
+        contact_sheet,
+        embed_metadata,
+        target_format,
+        season_fragment,
+    }
+}
+
+fn location_refs(locations: &[String]) -> Vec<&str> {
+    locations.iter().map(String::as_str).collect()
+}
+
+/// The season text used in prompts: the season itself, followed by the
+/// curated fragment when a preset was chosen.
+fn season_prompt(season: Option<&str>, fragment: Option<&str>) -> Option<String> {
+    let season = trimmed_or_none(season)?;
+    match fragment {
+        Some(fragment) => Some(format!("{season}, {fragment}")),
+        None => Some(season.to_string()),
+    }
+}
+
+fn build_caption(
+    location: &str,
+    season: Option<&str>,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+) -> String {
+    [Some(location), season, time_of_day, weather]
+        .into_iter()
+        .filter_map(trimmed_or_none)
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+fn build_metadata<'a>(context: &'a RunContext, prompt: &'a str) -> Vec<(&'a str, &'a str)> {
+    let mut entries = vec![("Software", "Mawaku"), ("Prompt", prompt)];
+    let optional = [
+        ("Location", Some(context.location.as_str())),
+        ("Season", context.season.as_deref()),
+        ("Time of day", context.time_of_day.as_deref()),
+        ("Weather", context.weather.as_deref()),
+    ];
+    for (key, value) in optional {
+        if let Some(value) = trimmed_or_none(value) {
+            entries.push((key, value));
+        }
+    }
+    entries
+}
+
+/// The `gemini_api.aspect_ratio` default, used when `--aspect-ratio` is not
+/// given. `load_or_init` has already rejected unsupported values.
+fn config_aspect_ratio(config: &Config) -> Option<AspectRatioArg> {
+    config
+        .gemini_api
+        .aspect_ratio
+        .as_deref()
+        .and_then(|ratio| ratio.parse().ok())
+}
+
+/// The `gemini_api.max_output_tokens` cap, used when `--max-output-tokens`
+/// is not given. `0` means no cap.
+fn config_max_output_tokens(config: &Config) -> Option<u32> {
+    config
+        .gemini_api
+        .max_output_tokens
+        .filter(|&max_output_tokens| max_output_tokens > 0)
+}
+
+fn config_spinner(config: &Config) -> Option<SpinnerStyle> {
+    config
+        .spinner
+        .as_deref()
+        .and_then(|style| style.parse().ok())
+}
+
+/// The default threshold for every harm category, overridden by entries of
+/// `gemini_api.safety_settings`.
+fn config_safety_settings(config: &Config) -> Vec<SafetySetting> {
+    let mut settings = SafetySetting::all(SafetyThreshold::default());
+    for (category, threshold) in &config.gemini_api.safety_settings {
+        if let (Ok(category), Ok(threshold)) = (
+            category.parse::<HarmCategory>(),
+            threshold.parse::<SafetyThreshold>(),
+        ) && let Some(setting) = settings
+            .iter_mut()
+            .find(|setting| setting.category == category)
+        {
+            setting.threshold = threshold;
         }
     }
+    settings
 }
 
-fn resolve_gemini_api_key(config: &Config) -> (Option<String>, Option<String>) {
+/// The API key from `--api-key` when given, then the key stored in the
+/// configuration, then the configured environment variable, with a warning
+/// when none is set.
+fn resolve_gemini_api_key(config: &Config, flag: Option<&str>) -> (Option<String>, Option<String>) {
+    if let Some(key) = flag.or_else(|| config.gemini_api.stored_api_key()) {
+        return (Some(key.to_string()), None);
+    }
     let env_var = config.gemini_api.api_key_env_var();
     match env::var(env_var) {
         Ok(value) if !value.trim().is_empty() => (Some(value), None),
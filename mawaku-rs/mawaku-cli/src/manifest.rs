@@ -0,0 +1,143 @@
+use crate::failures::FailedScene;
+use crate::{PredictionOutcome, RunContext, SceneOutcome};
+use mawaku_gemini::GeminiClientOptions;
+use serde::Serialize;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// File in the image output directory indexing everything the last batch
+/// produced.
+pub(crate) const MANIFEST_FILE_NAME: &str = "manifest.json";
+
+/// A summary of one batch run: what was requested, with which settings, and
+/// what came out of each scene.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct Manifest {
+    pub(crate) mawaku_version: String,
+    /// When the manifest was written, in ISO 8601 UTC.
+    pub(crate) created_at: String,
+    pub(crate) parameters: RunParameters,
+    pub(crate) scenes: Vec<ManifestScene>,
+}
+
+/// Request settings shared by every scene of the run.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct RunParameters {
+    pub(crate) image_model: String,
+    pub(crate) text_model: String,
+    pub(crate) sample_count: u32,
+    /// The requested ratio: fixed, `auto`, or the client default.
+    pub(crate) aspect_ratio: Option<String>,
+    pub(crate) negative_prompt: Option<String>,
+    pub(crate) person_generation: Option<String>,
+    pub(crate) upscale: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub(crate) enum SceneStatus {
+    /// Every prediction was saved.
+    Success,
+    /// Some predictions were saved, others were not.
+    Partial,
+    /// Nothing was saved.
+    Failed,
+    /// Gemini could not be reached, so generation was skipped.
+    Offline,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub(crate) struct ManifestScene {
+    #[serde(flatten)]
+    pub(crate) scene: FailedScene,
+    pub(crate) status: SceneStatus,
+    /// The prompt sent to Imagen; `None` when the scene ended unexpectedly.
+    pub(crate) prompt: Option<String>,
+    pub(crate) files: Vec<PathBuf>,
+    /// Predictions returned but not saved.
+    pub(crate) failed_predictions: usize,
+}
+
+impl ManifestScene {
+    pub(crate) fn new(scene: &FailedScene, outcome: Option<&SceneOutcome>) -> Self {
+        let Some(outcome) = outcome else {
+            return Self {
+                scene: scene.clone(),
+                status: SceneStatus::Failed,
+                prompt: None,
+                files: Vec::new(),
+                failed_predictions: 0,
+            };
+        };
+        let files = outcome.saved_files();
+        let failed_predictions = outcome
+            .predictions
+            .iter()
+            .filter(|prediction| !matches!(prediction, PredictionOutcome::Saved { .. }))
+            .count();
+        let status = if outcome.offline {
+            SceneStatus::Offline
+        } else if files.is_empty() {
+            SceneStatus::Failed
+        } else if failed_predictions > 0 {
+            SceneStatus::Partial
+        } else {
+            SceneStatus::Success
+        };
+        Self {
+            scene: scene.clone(),
+            status,
+            prompt: Some(outcome.prompt.clone()),
+            files,
+            failed_predictions,
+        }
+    }
+}
+
+impl Manifest {
+    /// The manifest for `scenes`, paired in order with their outcomes.
+    pub(crate) fn new(
+        options: &GeminiClientOptions,
+        context: &RunContext,
+        scenes: &[FailedScene],
+        outcomes: &[Option<SceneOutcome>],
+        created_at: String,
+    ) -> Self {
+        let parameters = RunParameters {
+            image_model: options.image_model.clone(),
+            text_model: options.text_model.clone(),
+            sample_count: options.sample_count,
+            aspect_ratio: context
+                .aspect_ratio
+                .map(|ratio| ratio.to_string())
+                .or_else(|| options.aspect_ratio.clone()),
+            negative_prompt: options.negative_prompt.clone(),
+            person_generation: options
+                .person_generation
+                .map(|setting| setting.as_str().to_string()),
+            upscale: context.upscale.map(|factor| factor.to_string()),
+        };
+        Self {
+            mawaku_version: env!("CARGO_PKG_VERSION").to_string(),
+            created_at,
+            parameters,
+            scenes: scenes
+                .iter()
+                .zip(outcomes)
+                .map(|(scene, outcome)| ManifestScene::new(scene, outcome.as_ref()))
+                .collect(),
+        }
+    }
+}
+
+/// Write `manifest` as pretty-printed JSON to [`MANIFEST_FILE_NAME`] in `dir`,
+/// replacing the previous run's manifest.
+pub(crate) fn write(dir: &Path, manifest: &Manifest) -> io::Result<PathBuf> {
+    fs::create_dir_all(dir)?;
+    let path = dir.join(MANIFEST_FILE_NAME);
+    let mut contents = serde_json::to_string_pretty(manifest)?;
+    contents.push('\n');
+    fs::write(&path, contents)?;
+    Ok(path)
+}
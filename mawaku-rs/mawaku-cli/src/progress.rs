@@ -0,0 +1,148 @@
+use mawaku_config::SUPPORTED_SPINNER_STYLES;
+use serde::Serialize;
+use std::io::{self, Write};
+
+/// How progress is shown, chosen with `--progress`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ProgressFormat {
+    /// A spinner while the image is generating.
+    #[default]
+    Human,
+    /// Newline-delimited JSON events on stderr, without the spinner.
+    Json,
+}
+
+impl ProgressFormat {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Self::Human => "human",
+            Self::Json => "json",
+        }
+    }
+}
+
+impl std::fmt::Display for ProgressFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for ProgressFormat {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            other => Err(format!(
+                "unsupported progress format `{other}` (expected human or json)"
+            )),
+        }
+    }
+}
+
+/// Animation drawn while an image generates, chosen with `--spinner` or the
+/// `spinner` setting.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum SpinnerStyle {
+    #[default]
+    Ascii,
+    /// Braille dots, for terminals with good Unicode fonts.
+    Dots,
+    /// No animation; only the start and finish lines are logged.
+    None,
+}
+
+impl SpinnerStyle {
+    /// Every style, in the order of [`SUPPORTED_SPINNER_STYLES`].
+    pub(crate) const ALL: [Self; 3] = [Self::Ascii, Self::Dots, Self::None];
+
+    pub(crate) fn as_str(self) -> &'static str {
+        SUPPORTED_SPINNER_STYLES[self as usize]
+    }
+
+    /// The animation frames, empty for [`SpinnerStyle::None`].
+    pub(crate) fn frames(self) -> &'static [&'static str] {
+        match self {
+            Self::Ascii => &["|", "/", "-", "\\"],
+            Self::Dots => &["⠋", "⠙", "⠹", "⠸", "⠼", "⠴", "⠦", "⠧", "⠇", "⠏"],
+            Self::None => &[],
+        }
+    }
+}
+
+impl std::fmt::Display for SpinnerStyle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for SpinnerStyle {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_ascii_lowercase();
+        Self::ALL
+            .into_iter()
+            .find(|style| style.as_str() == value)
+            .ok_or_else(|| {
+                format!(
+                    "unsupported spinner style `{value}` (expected one of {})",
+                    SUPPORTED_SPINNER_STYLES.join(", ")
+                )
+            })
+    }
+}
+
+const _: () = assert!(SpinnerStyle::ALL.len() == SUPPORTED_SPINNER_STYLES.len());
+
+/// A stage boundary reported by `--progress json`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub(crate) enum ProgressEvent {
+    /// The configuration was read; `config_created` is true on the very
+    /// first run, when Mawaku wrote a fresh file at `path`. `path` is `None`
+    /// when loading failed and defaults are used.
+    ConfigLoaded {
+        path: Option<String>,
+        config_created: bool,
+    },
+    /// The place description step finished; `described` is false when it
+    /// was skipped or failed.
+    DescriptionDone {
+        location: String,
+        described: bool,
+    },
+    ImageStart {
+        location: String,
+    },
+    ImageSaved {
+        location: String,
+        path: String,
+    },
+    ImageFailed {
+        location: String,
+        error: String,
+    },
+    Done {
+        requested: usize,
+        saved: usize,
+        failed: usize,
+        exit_code: u8,
+    },
+}
+
+impl ProgressEvent {
+    /// The event as a single JSON line, without the trailing newline.
+    pub(crate) fn to_line(&self) -> String {
+        serde_json::to_string(self).expect("progress events always serialize")
+    }
+}
+
+/// Write `event` to stderr when `format` is [`ProgressFormat::Json`].
+pub(crate) fn emit(format: ProgressFormat, event: ProgressEvent) {
+    if format == ProgressFormat::Json {
+        let mut stderr = io::stderr().lock();
+        let _ = writeln!(stderr, "{}", event.to_line());
+    }
+}
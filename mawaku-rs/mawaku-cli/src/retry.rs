@@ -0,0 +1,143 @@
+use crate::progress::SpinnerStyle;
+use log::{Level, error, info, log_enabled};
+use mawaku_gemini::{GeminiError, ImageGenerator, PredictResponse, RetryAttempt};
+use std::io::{self, Write};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// The spinner text; `retry` is the current retry and the maximum, once the
+/// first attempt has failed.
+pub(crate) fn spinner_line(frame: &str, elapsed: Duration, retry: Option<(u32, u32)>) -> String {
+    let label = match retry {
+        Some((retry, max_retries)) => format!("Generating image (retry {retry}/{max_retries})"),
+        None => "Generating image".to_string(),
+    };
+    format!("{label} {frame} {:.1}s", elapsed.as_secs_f32())
+}
+
+/// Generate without a spinner, logging each retry instead.
+pub(crate) fn request_images(
+    images: &dyn ImageGenerator,
+    prompt: &str,
+    aspect_ratio: Option<&str>,
+    seed: Option<u32>,
+) -> Result<PredictResponse, GeminiError> {
+    generate_images(images, prompt, aspect_ratio, seed, &|attempt| {
+        info!(
+            "Retrying image request ({}/{}) in {:.1}s.",
+            attempt.retry,
+            attempt.max_retries,
+            attempt.delay.as_secs_f32()
+        );
+    })
+}
+
+/// Generate with `seed` when one is given, otherwise let Imagen pick.
+fn generate_images(
+    images: &dyn ImageGenerator,
+    prompt: &str,
+    aspect_ratio: Option<&str>,
+    seed: Option<u32>,
+    on_retry: &dyn Fn(RetryAttempt),
+) -> Result<PredictResponse, GeminiError> {
+    match seed {
+        Some(seed) => images.generate_seeded(prompt, aspect_ratio, seed, on_retry),
+        None => images.generate_reporting_retries(prompt, aspect_ratio, on_retry),
+    }
+}
+
+/// Retry progress shared between the request thread and the spinner.
+#[derive(Default)]
+struct RetryProgress {
+    retry: AtomicU32,
+    max_retries: AtomicU32,
+}
+
+impl RetryProgress {
+    fn record(&self, attempt: RetryAttempt) {
+        self.max_retries
+            .store(attempt.max_retries, Ordering::Relaxed);
+        self.retry.store(attempt.retry, Ordering::Relaxed);
+    }
+
+    fn current(&self) -> Option<(u32, u32)> {
+        match self.retry.load(Ordering::Relaxed) {
+            0 => None,
+            retry => Some((retry, self.max_retries.load(Ordering::Relaxed))),
+        }
+    }
+}
+
+pub(crate) fn generate_with_progress(
+    images: &dyn ImageGenerator,
+    prompt: &str,
+    aspect_ratio: Option<&str>,
+    seed: Option<u32>,
+    spinner: SpinnerStyle,
+) -> Option<Result<PredictResponse, GeminiError>> {
+    let progress = RetryProgress::default();
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .name("gemini-image-request".into())
+            .spawn_scoped(scope, || {
+                generate_images(images, prompt, aspect_ratio, seed, &|attempt| {
+                    progress.record(attempt)
+                })
+            })
+            .expect("spawn gemini image request");
+        wait_with_spinner(handle, &progress, spinner.frames())
+    })
+}
+
+/// Draw the spinner until the image request on `handle` finishes.
+fn wait_with_spinner(
+    handle: thread::ScopedJoinHandle<'_, Result<PredictResponse, GeminiError>>,
+    progress: &RetryProgress,
+    frames: &[&str],
+) -> Option<Result<PredictResponse, GeminiError>> {
+    let mut frame_index = 0;
+    let interval = Duration::from_millis(200);
+    let start = Instant::now();
+
+    // The spinner is drawn straight to stderr, so only show it when
+    // info-level output is enabled and a style with frames is selected.
+    let show_spinner = log_enabled!(Level::Info) && !frames.is_empty();
+    if show_spinner {
+        eprint!("Generating image ");
+        let _ = io::stderr().flush();
+    }
+
+    let mut line_len: usize = 0;
+    while !handle.is_finished() {
+        if show_spinner {
+            let line = spinner_line(frames[frame_index], start.elapsed(), progress.current());
+            // Pad so a shorter line fully covers the previous one.
+            let width = line.chars().count();
+            let padding = " ".repeat(line_len.saturating_sub(width));
+            line_len = width;
+            eprint!("\r{line}{padding}");
+            let _ = io::stderr().flush();
+            frame_index = (frame_index + 1) % frames.len();
+        }
+        thread::sleep(interval);
+    }
+
+    if show_spinner {
+        // Blank the spinner line so the log line that follows starts clean.
+        eprint!("\r{}\r", " ".repeat(line_len));
+    }
+    match handle.join() {
+        Ok(result) => {
+            info!(
+                "Generating image ... finished in {:.1}s",
+                start.elapsed().as_secs_f32()
+            );
+            Some(result)
+        }
+        Err(_) => {
+            error!("Generating image ... failed: worker panicked");
+            None
+        }
+    }
+}
@@ -0,0 +1,60 @@
+use std::fmt;
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+/// Largest `--seed-sweep` run without raising `--seed-sweep-limit`.
+pub(crate) const DEFAULT_SEED_SWEEP_LIMIT: u32 = 5;
+
+/// An inclusive range of Imagen seeds given as `START..END`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct SeedSweep {
+    start: u32,
+    end: u32,
+}
+
+impl SeedSweep {
+    pub(crate) fn seeds(self) -> RangeInclusive<u32> {
+        self.start..=self.end
+    }
+
+    /// Number of seeds, and so of image requests, in the sweep.
+    pub(crate) fn len(self) -> u64 {
+        u64::from(self.end - self.start) + 1
+    }
+
+    /// Refuse sweeps larger than `limit`, so a typo in the range cannot
+    /// quietly fire dozens of paid requests.
+    pub(crate) fn check_limit(self, limit: u32) -> Result<(), String> {
+        if self.len() <= u64::from(limit) {
+            return Ok(());
+        }
+        Err(format!(
+            "--seed-sweep {self} would send {} image requests, more than the limit of {limit}; pass --seed-sweep-limit {} to confirm.",
+            self.len(),
+            self.len()
+        ))
+    }
+}
+
+impl fmt::Display for SeedSweep {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}..{}", self.start, self.end)
+    }
+}
+
+impl FromStr for SeedSweep {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || format!("invalid seed range `{value}` (expected START..END, e.g. 1..5)");
+        let (start, end) = value.trim().split_once("..").ok_or_else(invalid)?;
+        let start = start.trim().parse::<u32>().map_err(|_| invalid())?;
+        let end = end.trim().parse::<u32>().map_err(|_| invalid())?;
+        if start > end {
+            return Err(format!(
+                "seed range `{value}` is empty (START must not exceed END)"
+            ));
+        }
+        Ok(Self { start, end })
+    }
+}
@@ -1,12 +1,19 @@
 use super::*;
 use mawaku_config::{DEFAULT_GEMINI_API_KEY_ENV_VAR, DEFAULT_PROMPT};
-use mawaku_gemini::craft_prompt;
-use mawaku_utils::{COMPONENT_MAX_LEN, DEFAULT_RANDOM_SUFFIX_LENGTH, component_token};
+use mawaku_gemini::{
+    AVATAR_DIRECTIVE, DESCRIPTION_INPUT_TOKENS_ESTIMATE, DESCRIPTION_OUTPUT_TOKENS_ESTIMATE,
+    DescribedPlace, NORMAL_FINISH_REASON, RetryAttempt, craft_prompt, estimate_cost,
+};
+use mawaku_utils::{
+    COMPONENT_MAX_LEN, DEFAULT_FILE_NAME_PREFIX, DEFAULT_RANDOM_SUFFIX_LENGTH,
+    DEFAULT_SUFFIX_ALPHABET, component_token,
+};
 use std::ffi::{OsStr, OsString};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Mutex;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::SystemTime;
 use toml::Value;
 
 static TEST_MUTEX: Mutex<()> = Mutex::new(());
@@ -94,12 +101,13 @@ fn remove_env(key: &str) {
 fn run_warns_when_gemini_key_missing() {
     with_isolated_home(|home| {
         let context = run(Cli {
-            location: "Hakone, Japan".to_string(),
+            location: vec!["Hakone, Japan".to_string()],
             season: None,
             time_of_day: None,
+            ..Cli::default()
         });
 
-        let expected_prompt = craft_prompt(DEFAULT_PROMPT, "Hakone, Japan", None, None);
+        let expected_prompt = craft_prompt(DEFAULT_PROMPT, "Hakone, Japan", None, None, None);
         assert_eq!(context.prompt, expected_prompt);
         assert!(context.config_ready);
         assert!(context.gemini_api_key.is_none());
@@ -142,9 +150,10 @@ fn run_uses_custom_env_var_from_config() {
         let secret = OsString::from("secret-key");
         set_env(env_var, secret.as_os_str());
         let context = run(Cli {
-            location: "Hakone, Japan".to_string(),
+            location: vec!["Hakone, Japan".to_string()],
             season: None,
             time_of_day: None,
+            ..Cli::default()
         });
 
         assert!(context.config_ready);
@@ -172,9 +181,10 @@ fn run_uses_custom_env_var_from_config() {
         fs::write(&config_path, serialized).expect("write updated config");
 
         let second_run = run(Cli {
-            location: "Hakone, Japan".to_string(),
+            location: vec!["Hakone, Japan".to_string()],
             season: None,
             time_of_day: None,
+            ..Cli::default()
         });
 
         assert!(second_run.config_ready);
@@ -185,7 +195,7 @@ fn run_uses_custom_env_var_from_config() {
                 .iter()
                 .any(|warning| warning.contains("Gemini API key environment"))
         );
-        let expected_prompt = craft_prompt(DEFAULT_PROMPT, "Hakone, Japan", None, None);
+        let expected_prompt = craft_prompt(DEFAULT_PROMPT, "Hakone, Japan", None, None, None);
         assert_eq!(second_run.prompt, expected_prompt);
         assert_eq!(
             second_run.image_output_dir.as_deref(),
@@ -197,95 +207,2711 @@ fn run_uses_custom_env_var_from_config() {
 }
 
 #[test]
-fn image_name_context_builds_unique_file_stem() {
-    let cli = Cli {
-        location: "Hakone, Japan".to_string(),
-        season: Some("Spring".to_string()),
-        time_of_day: Some("Dusk".to_string()),
-    };
+fn run_configures_description_cache_from_config() {
+    with_isolated_home(|home| {
+        let context = run(Cli {
+            location: vec!["Hakone, Japan".to_string()],
+            no_cache: true,
+            ..Cli::default()
+        });
 
-    let context = build_image_name_context(&cli);
-    let stem = context.file_stem(1);
+        let cache = context
+            .description_cache
+            .as_ref()
+            .expect("description cache enabled by default");
+        assert_eq!(cache.dir(), home.join(".mawaku").join("cache").as_path());
+        assert!(context.refresh_cache);
+    });
+}
+
+#[test]
+fn run_skips_place_description_when_requested() {
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Hakone, Japan".to_string()],
+            no_description: true,
+            ..Cli::default()
+        });
+        assert!(context.skip_description);
 
-    assert!(stem.starts_with("mawaku-hakone-jap-spring-dusk-p1-"));
+        let prompt = build_scene_prompt(&context, None, None);
+        assert!(prompt.contains("Ambiance: Unspecified"));
+    });
+}
 
-    let (_, suffix) = stem
-        .rsplit_once('-')
-        .expect("file stem includes random suffix separator");
-    assert_eq!(suffix.len(), DEFAULT_RANDOM_SUFFIX_LENGTH);
+#[test]
+fn run_warns_about_upscale_cost() {
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Hakone, Japan".to_string()],
+            upscale: Some(UpscaleFactor::X2),
+            ..Cli::default()
+        });
+
+        assert_eq!(context.upscale, Some(UpscaleFactor::X2));
+        assert!(
+            context
+                .warnings
+                .iter()
+                .any(|warning| warning.contains("increases cost and latency"))
+        );
+    });
+}
+
+#[test]
+fn run_uses_prompt_template_from_config() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "prompt_template = \"Moody loft with exposed brick.\"\n",
+        )
+        .expect("write config");
+
+        let context = run(Cli {
+            location: vec!["Hakone, Japan".to_string()],
+            ..Cli::default()
+        });
 
-    let mut chars: Vec<char> = suffix.chars().collect();
-    chars.sort_unstable();
-    chars.dedup();
-    assert_eq!(chars.len(), DEFAULT_RANDOM_SUFFIX_LENGTH);
+        assert_eq!(context.prompt_template, "Moody loft with exposed brick.");
+        assert!(context.prompt.starts_with("Moody loft with exposed brick."));
+        assert!(!context.prompt.contains(DEFAULT_PROMPT));
+    });
 }
 
 #[test]
-fn image_name_context_truncates_long_components() {
-    let cli = Cli {
-        location: "Extremely Long Location Name That Keeps Going".to_string(),
-        season: Some("Supercalifragilisticexpialidocious".to_string()),
-        time_of_day: Some("Midnight Sun Time".to_string()),
+fn run_expands_location_aliases_from_config() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[aliases]\nhakone = \"Hakone, Japan, near the hot springs\"\n",
+        )
+        .expect("write config");
+
+        let context = run(Cli {
+            location: vec!["Hakone".to_string(), "Kyoto, Japan".to_string()],
+            ..Cli::default()
+        });
+
+        assert_eq!(
+            context.locations,
+            vec![
+                "Hakone, Japan, near the hot springs".to_string(),
+                "Kyoto, Japan".to_string(),
+            ]
+        );
+        assert!(context.prompt.contains("near the hot springs"));
+        assert!(
+            context
+                .infos
+                .iter()
+                .any(|message| message.contains("alias `Hakone`"))
+        );
+    });
+}
+
+#[test]
+fn alias_subcommand_edits_aliases_ignoring_case() {
+    let cli = Cli::try_parse_from(["mawaku", "alias", "add", "hakone", "Hakone, Japan"])
+        .expect("parse alias add");
+    assert_eq!(
+        cli.command,
+        Some(Command::Alias(alias::AliasCommand::Add {
+            name: "hakone".to_string(),
+            location: "Hakone, Japan".to_string(),
+        }))
+    );
+
+    let mut config = Config::default();
+    alias::add_alias(&mut config, "Hakone", "Hakone, Japan");
+    alias::add_alias(&mut config, "hakone", "Hakone, Japan, near the hot springs");
+    assert_eq!(
+        alias::alias_lines(&config),
+        vec!["hakone = Hakone, Japan, near the hot springs".to_string()]
+    );
+
+    assert!(alias::remove_alias(&mut config, "HAKONE"));
+    assert!(!alias::remove_alias(&mut config, "hakone"));
+    assert!(config.aliases.is_empty());
+}
+
+#[test]
+fn run_uses_gemini_settings_from_config_unless_overridden() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[gemini_api]\nimage_model = \"imagen-4.0-generate-001\"\naspect_ratio = \"3:4\"\nrequests_per_minute = 20\nmax_output_tokens = 300\n",
+        )
+        .expect("write config");
+
+        let context = run(Cli::default());
+        assert!(context.config_ready);
+        assert_eq!(
+            context.image_model.as_deref(),
+            Some("imagen-4.0-generate-001")
+        );
+        assert_eq!(context.aspect_ratio, Some(AspectRatioArg::Fixed("3:4")));
+        assert_eq!(context.requests_per_minute, NonZeroU32::new(20));
+        assert_eq!(context.max_output_tokens, Some(300));
+
+        let context = run(Cli {
+            aspect_ratio: Some(AspectRatioArg::Auto),
+            max_output_tokens: Some(120),
+            ..Cli::default()
+        });
+        assert_eq!(context.aspect_ratio, Some(AspectRatioArg::Auto));
+        assert_eq!(context.max_output_tokens, Some(120));
+    });
+
+    assert!(Cli::try_parse_from(["mawaku", "--max-output-tokens", "0"]).is_err());
+}
+
+#[test]
+fn run_joins_negative_keywords_from_config() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[image]\nnegative_keywords = [\"logos\", \"text\", \"watermarks\"]\n",
+        )
+        .expect("write config");
+
+        let context = run(Cli::default());
+        assert_eq!(
+            context.negative_prompt.as_deref(),
+            Some("logos, text, watermarks")
+        );
+    });
+}
+
+#[test]
+fn run_falls_back_to_configured_season_and_time_of_day() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "default_season = \"autumn\"\ndefault_time_of_day = \"evening\"\n",
+        )
+        .expect("write config");
+
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            ..Cli::default()
+        });
+        assert_eq!(context.season.as_deref(), Some("autumn"));
+        assert_eq!(context.time_of_day.as_deref(), Some("evening"));
+        assert!(context.prompt.contains("evening"));
+
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            season: Some("  ".to_string()),
+            time_of_day: Some("dawn".to_string()),
+            ..Cli::default()
+        });
+        assert_eq!(context.season.as_deref(), Some("autumn"));
+        assert_eq!(context.time_of_day.as_deref(), Some("dawn"));
+
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            season_preset: Some(SeasonPreset::Winter),
+            ..Cli::default()
+        });
+        assert_eq!(context.season.as_deref(), Some("winter"));
+    });
+}
+
+#[test]
+fn run_reads_payload_check_settings() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let path = config_dir.join("config.toml");
+
+        fs::write(&path, "[image]\nmin_image_bytes = 2048\n").expect("write config");
+        assert_eq!(run(Cli::default()).min_image_bytes, Some(2048));
+
+        fs::write(&path, "[image]\ncheck_payloads = false\n").expect("write config");
+        assert_eq!(run(Cli::default()).min_image_bytes, None);
+    });
+}
+
+#[test]
+fn run_reports_invalid_config_values() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[gemini_api]\ntext_model = \"gpt-4o\"\n",
+        )
+        .expect("write config");
+
+        let context = run(Cli::default());
+        assert!(!context.config_ready);
+        assert!(
+            context
+                .warnings
+                .iter()
+                .any(|warning| warning.contains("gemini_api.text_model `gpt-4o`"))
+        );
+    });
+}
+
+#[test]
+fn build_general_instructions_fills_template_placeholders() {
+    let context = RunContext {
+        prompt_template: "Loft in {location} on a {season} {time_of_day}.".to_string(),
+        location: "Hakone".to_string(),
+        locations: vec!["Hakone".to_string()],
+        season: Some("spring".to_string()),
+        time_of_day: Some("evening".to_string()),
+        ..RunContext::default()
     };
+    assert_eq!(
+        build_general_instructions(&context),
+        "Loft in Hakone on a spring evening."
+    );
 
-    let context = build_image_name_context(&cli);
-    let stem = context.file_stem(2);
-    let pattern = format!("-p{}-", 2);
-    let (base, _) = stem
-        .split_once(&pattern)
-        .expect("file stem includes prediction index separator");
+    let context = RunContext {
+        prompt_template: "Plain loft.".to_string(),
+        ..context
+    };
+    assert_eq!(
+        build_general_instructions(&context),
+        craft_prompt("Plain loft.", "Hakone", None, None, None)
+    );
+}
 
-    assert!(stem.starts_with("mawaku-extremely-supercalif-midnight-s-p2-"));
-    assert_eq!(base, "mawaku-extremely-supercalif-midnight-s");
+#[test]
+fn build_metadata_lists_prompt_and_present_fields() {
+    let context = RunContext {
+        location: "Hakone".to_string(),
+        season: Some("spring".to_string()),
+        weather: Some(" ".to_string()),
+        ..RunContext::default()
+    };
 
-    let location_component =
-        component_token(&cli.location).expect("location component slug exists");
-    assert_eq!(location_component, "extremely");
+    let metadata = build_metadata(&context, "A cozy loft");
+    assert_eq!(
+        metadata,
+        vec![
+            ("Software", "Mawaku"),
+            ("Prompt", "A cozy loft"),
+            ("Location", "Hakone"),
+            ("Season", "spring"),
+        ]
+    );
+}
 
-    let season_component =
-        component_token(cli.season.as_deref().unwrap()).expect("season component slug exists");
-    assert_eq!(season_component.len(), COMPONENT_MAX_LEN);
-    assert_eq!(season_component, "supercalif");
+#[test]
+fn build_caption_joins_present_fields() {
+    let caption = build_caption("Hakone, Japan", Some("spring"), Some("  "), Some("rain"));
+    assert_eq!(caption, "Hakone, Japan | spring | rain");
+}
 
-    let time_component =
-        component_token(cli.time_of_day.as_deref().unwrap()).expect("time component slug exists");
-    assert_eq!(time_component.len(), COMPONENT_MAX_LEN);
-    assert_eq!(time_component, "midnight-s");
+#[test]
+fn run_sets_caption_only_when_label_is_supported() {
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            season: Some("spring".to_string()),
+            label: true,
+            ..Cli::default()
+        });
+
+        if cfg!(feature = "image") {
+            assert_eq!(context.caption.as_deref(), Some("Hakone | spring"));
+        } else {
+            assert!(context.caption.is_none());
+            assert!(
+                context
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains("--features image"))
+            );
+        }
+    });
+}
+
+#[test]
+fn run_enables_contact_sheet_only_when_supported() {
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            contact_sheet: true,
+            ..Cli::default()
+        });
+
+        assert_eq!(context.contact_sheet, cfg!(feature = "image"));
+        if !cfg!(feature = "image") {
+            assert!(
+                context
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains("--contact-sheet"))
+            );
+        }
+    });
+}
+
+#[test]
+fn crop_to_aspect_is_validated_when_parsing() {
+    let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone", "--crop-to-aspect", "16:9"])
+        .expect("parse crop ratio");
+    assert_eq!(
+        cli.crop_to_aspect,
+        Some(AspectRatio {
+            width: 16,
+            height: 9
+        })
+    );
+    for ratio in ["16x9", "0:1", "wide"] {
+        assert!(
+            Cli::try_parse_from(["mawaku", "--location", "Hakone", "--crop-to-aspect", ratio])
+                .is_err(),
+            "expected --crop-to-aspect {ratio} to be rejected"
+        );
+    }
+
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            crop_to_aspect: cli.crop_to_aspect,
+            ..Cli::default()
+        });
+        if cfg!(feature = "image") {
+            assert_eq!(context.crop_to_aspect, cli.crop_to_aspect);
+        } else {
+            assert_eq!(context.crop_to_aspect, None);
+            assert!(
+                context
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains("--crop-to-aspect"))
+            );
+        }
+    });
+}
+
+#[test]
+fn output_format_maps_to_the_target_format() {
+    let cli = Cli::try_parse_from([
+        "mawaku",
+        "--location",
+        "Hakone",
+        "--output-format",
+        "JPEG",
+        "--jpeg-quality",
+        "70",
+    ])
+    .expect("parse output format");
+    assert_eq!(cli.output_format, Some(OutputFormatArg::Jpeg));
+    assert_eq!(cli.jpeg_quality, Some(70));
+    assert!(Cli::try_parse_from(["mawaku", "--output-format", "gif"]).is_err());
+    assert!(Cli::try_parse_from(["mawaku", "--jpeg-quality", "70"]).is_err());
+    assert!(
+        Cli::try_parse_from(["mawaku", "--output-format", "jpg", "--jpeg-quality", "0"]).is_err()
+    );
+    assert!(Cli::try_parse_from(["mawaku", "--output-format", "png", "--stdout-image"]).is_err());
+    assert_eq!(
+        OutputFormatArg::Jpeg.image_format(None),
+        ImageFormat::Jpeg {
+            quality: DEFAULT_JPEG_QUALITY
+        }
+    );
+
+    with_isolated_home(|_| {
+        let context = run(cli);
+        if cfg!(feature = "image") {
+            assert_eq!(
+                context.target_format,
+                Some(ImageFormat::Jpeg { quality: 70 })
+            );
+        } else {
+            assert_eq!(context.target_format, None);
+            assert!(
+                context
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains("--output-format"))
+            );
+        }
+
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            ..Cli::default()
+        });
+        assert_eq!(context.target_format, None);
+    });
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn output_format_changes_the_extension_but_keeps_the_stem() {
+    with_isolated_home(|home| {
+        let output_dir = home.join("outputs");
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(output_dir.clone()),
+            target_format: Some(ImageFormat::Jpeg { quality: 80 }),
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let response: PredictResponse = serde_json::from_str(
+            r#"{"predictions": [{
+                "bytesBase64Encoded": "iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEElEQVR4nGP4z8AARAwQCgAf7gP9i18U1AAAAABJRU5ErkJggg==",
+                "mimeType": "image/png"
+            }]}"#,
+        )
+        .expect("parse response");
+
+        let outcomes = save_predictions(&response, &context, &image_name_context, "prompt");
+
+        let PredictionOutcome::Saved { path, .. } = &outcomes[0] else {
+            panic!("expected the prediction to be saved, got {:?}", outcomes[0]);
+        };
+        let name = path.file_name().and_then(OsStr::to_str).expect("file name");
+        assert!(name.starts_with("mawaku-hakone-p1-"), "{name}");
+        assert_eq!(path.extension(), Some(OsStr::new("jpg")));
+        assert!(
+            fs::read(path)
+                .expect("read saved image")
+                .starts_with(&[0xFF, 0xD8])
+        );
+    });
 }
 
 #[test]
-fn build_structured_prompt_formats_sections() {
-    let description = PlaceDescription {
-        ambiance: "Warm mountain inn with panoramic views".to_string(),
-        items: vec!["tatami mats".to_string(), "shoji screens".to_string()],
-        keywords: vec!["serene".to_string(), "onsen".to_string()],
+fn blur_sigma_is_validated_when_parsing() {
+    let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone", "--blur", "2.5"])
+        .expect("parse blur");
+    assert_eq!(cli.blur, Some(2.5));
+    assert!(!cli.keep_sharp);
+    for sigma in ["0", "-1", "NaN", "500", "soft"] {
+        assert!(
+            Cli::try_parse_from(["mawaku", "--location", "Hakone", "--blur", sigma]).is_err(),
+            "expected --blur {sigma} to be rejected"
+        );
+    }
+    assert!(Cli::try_parse_from(["mawaku", "--location", "Hakone", "--keep-sharp"]).is_err());
+}
+
+#[test]
+fn run_enables_blur_only_when_supported() {
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            blur: Some(3.0),
+            keep_sharp: true,
+            ..Cli::default()
+        });
+
+        if cfg!(feature = "image") {
+            assert_eq!(context.blur, Some(3.0));
+            assert!(context.keep_sharp);
+        } else {
+            assert_eq!(context.blur, None);
+            assert!(!context.keep_sharp);
+            assert!(
+                context
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains("--blur"))
+            );
+        }
+    });
+}
+
+#[test]
+fn run_enables_copy_only_when_supported() {
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Hakone".to_string()],
+            copy: true,
+            ..Cli::default()
+        });
+
+        assert_eq!(context.copy_prompt, cfg!(feature = "clipboard"));
+        if !cfg!(feature = "clipboard") {
+            assert!(
+                context
+                    .warnings
+                    .iter()
+                    .any(|warning| warning.contains("--copy"))
+            );
+        }
+    });
+}
+
+#[test]
+fn run_expands_season_preset_into_prompt() {
+    with_isolated_home(|_| {
+        let context = run(Cli {
+            location: vec!["Kyoto".to_string()],
+            season_preset: Some(SeasonPreset::Autumn),
+            ..Cli::default()
+        });
+
+        assert_eq!(context.season.as_deref(), Some("autumn"));
+        assert!(
+            context.prompt.contains(
+                "It is autumn, warm amber light, fallen leaves visible through the window."
+            )
+        );
+    });
+}
+
+#[test]
+fn run_blends_repeated_locations() {
+    with_isolated_home(|_| {
+        let cli = Cli::try_parse_from([
+            "mawaku",
+            "--location",
+            "Kyoto temple",
+            "--location",
+            "Scandinavian cabin",
+        ])
+        .expect("parse repeated locations");
+        let context = run(cli);
+        let name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+
+        assert_eq!(context.location, "Kyoto temple and Scandinavian cabin");
+        assert!(context.prompt.contains(
+            "Create a scene that harmoniously blends Kyoto temple and Scandinavian cabin"
+        ));
+        assert_eq!(name_context.base(), "mawaku-kyoto_temp-scandinavi");
+    });
+}
+
+#[test]
+fn run_without_location_builds_abstract_scene() {
+    with_isolated_home(|_| {
+        let cli =
+            Cli::try_parse_from(["mawaku", "--season", "winter"]).expect("location is optional");
+        let context = run(cli);
+        let name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+
+        assert!(context.location.is_empty());
+        assert!(!context.prompt.contains("Set the scene in"));
+        assert!(context.prompt.contains("It is winter."));
+        assert_eq!(name_context.base(), "mawaku-abstract-winter");
+    });
+}
+
+#[test]
+fn run_status_maps_to_distinct_exit_codes() {
+    assert_eq!(RunStatus::Success.code(), 0);
+    assert_eq!(RunStatus::ApiFailure.code(), 1);
+    assert_eq!(RunStatus::ConfigFailure.code(), 2);
+}
+
+#[test]
+fn cli_parses_log_level_with_info_default() {
+    let cli = Cli::try_parse_from(["mawaku"]).expect("parse defaults");
+    assert_eq!(cli.log_level, LogLevel::Info);
+
+    let cli = Cli::try_parse_from(["mawaku", "--log-level", "DEBUG"]).expect("parse log level");
+    assert_eq!(cli.log_level, LogLevel::Debug);
+    assert_eq!(cli.log_level.filter(), log::LevelFilter::Debug);
+
+    assert!(Cli::try_parse_from(["mawaku", "--log-level", "trace"]).is_err());
+}
+
+#[test]
+fn log_records_include_timestamp_and_level() {
+    let line = logging::format_record(
+        &log::Record::builder()
+            .args(format_args!("Saved prediction #1"))
+            .level(log::Level::Warn)
+            .build(),
+    );
+
+    let (timestamp, rest) = line.split_once(' ').expect("timestamp separator");
+    assert_eq!(timestamp.len(), "1970-01-01T00:00:00Z".len());
+    assert!(timestamp.ends_with('Z'));
+    assert_eq!(rest, "WARN  Saved prediction #1");
+}
+
+#[test]
+fn run_summary_reports_elapsed_time_counts_and_directory() {
+    let counts = SaveSummary {
+        requested: 2,
+        saved: 1,
     };
+    let summary = format_run_summary(
+        Duration::from_millis(12_400),
+        counts,
+        Some(Path::new("/tmp/out")),
+        1,
+    );
+    assert_eq!(
+        summary,
+        "Done in 12.4s: 2 requested, 1 saved to /tmp/out, 1 failed (1 warning)"
+    );
 
-    let prompt = build_structured_prompt(
-        "Follow the art direction guidelines.",
-        Some(&description),
-        Some("spring"),
-        Some("dusk"),
+    let counts = SaveSummary {
+        requested: 1,
+        saved: 1,
+    };
+    let summary = format_run_summary(Duration::from_millis(50), counts, None, 0);
+    assert_eq!(
+        summary,
+        "Done in 0.1s: 1 requested, 1 saved, 0 failed (0 warnings)"
     );
+}
+
+#[test]
+fn save_summary_distinguishes_full_partial_and_failed_runs() {
+    let summary = |requested, saved| SaveSummary { requested, saved };
+    assert_eq!(summary(3, 3).status(false), RunStatus::Success);
+    assert_eq!(summary(3, 1).status(false), RunStatus::PartialFailure);
+    assert_eq!(summary(3, 3).status(true), RunStatus::PartialFailure);
+    assert_eq!(summary(3, 0).status(false), RunStatus::ApiFailure);
+    assert_eq!(summary(0, 0).status(false), RunStatus::ApiFailure);
+    assert_eq!(summary(2, 1).combine(summary(3, 3)), summary(5, 4));
+    assert_eq!(RunStatus::PartialFailure.code(), 3);
+}
 
-    assert!(prompt.contains("Follow the art direction guidelines."));
-    assert!(prompt.contains("Complete place description:"));
-    assert!(prompt.contains("Ambiance: Warm mountain inn with panoramic views"));
-    assert!(prompt.contains("Items: tatami mats, shoji screens"));
-    assert!(prompt.contains("Keywords: serene, onsen"));
-    assert!(prompt.contains("Scene timing:"));
-    assert!(prompt.contains("Season: spring"));
-    assert!(prompt.contains("Time of day: dusk"));
+#[test]
+fn cli_rejects_season_with_season_preset() {
+    let result = Cli::try_parse_from([
+        "mawaku",
+        "--location",
+        "Kyoto",
+        "--season",
+        "autumn",
+        "--season-preset",
+        "winter",
+    ]);
+    assert!(result.is_err());
+}
+
+#[test]
+fn cli_parses_upscale_factor() {
+    let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone", "--upscale", "x4"])
+        .expect("parse upscale flag");
+    assert_eq!(cli.upscale, Some(UpscaleFactor::X4));
+    assert!(Cli::try_parse_from(["mawaku", "--location", "Hakone", "--upscale", "x3"]).is_err());
+}
+
+#[test]
+fn cli_parses_aspect_ratio() {
+    let cli = Cli::try_parse_from(["mawaku", "--aspect-ratio", "AUTO"]).expect("parse auto");
+    assert_eq!(cli.aspect_ratio, Some(AspectRatioArg::Auto));
+    let cli = Cli::try_parse_from(["mawaku", "--aspect-ratio", "3:4"]).expect("parse ratio");
+    assert_eq!(cli.aspect_ratio, Some(AspectRatioArg::Fixed("3:4")));
+    assert!(Cli::try_parse_from(["mawaku", "--aspect-ratio", "2:1"]).is_err());
+}
+
+#[test]
+fn image_name_context_builds_unique_file_stem() {
+    let scene = RunContext {
+        locations: vec!["Hakone, Japan".to_string()],
+        season: Some("Spring".to_string()),
+        time_of_day: Some("Dusk".to_string()),
+        weather: Some("Rain".to_string()),
+        ..RunContext::default()
+    };
+
+    let context = image_name_context_for(&scene, DEFAULT_FILE_NAME_PREFIX);
+    let stem = context.file_stem(1);
+
+    assert!(stem.starts_with("mawaku-hakone_jap-spring-dusk-rain-p1-"));
+    assert_eq!(context.base(), "mawaku-hakone_jap-spring-dusk-rain");
+
+    let (_, suffix) = stem
+        .rsplit_once('-')
+        .expect("file stem includes random suffix separator");
+    assert_eq!(suffix.len(), DEFAULT_RANDOM_SUFFIX_LENGTH);
+    assert!(
+        suffix
+            .bytes()
+            .all(|byte| DEFAULT_SUFFIX_ALPHABET.contains(&byte))
+    );
+}
+
+#[test]
+fn image_name_context_truncates_long_components() {
+    let scene = RunContext {
+        locations: vec!["Extremely Long Location Name That Keeps Going".to_string()],
+        season: Some("Supercalifragilisticexpialidocious".to_string()),
+        time_of_day: Some("Midnight Sun Time".to_string()),
+        ..RunContext::default()
+    };
+
+    let context = image_name_context_for(&scene, DEFAULT_FILE_NAME_PREFIX);
+    let stem = context.file_stem(2);
+    let pattern = format!("-p{}-", 2);
+    let (base, _) = stem
+        .split_once(&pattern)
+        .expect("file stem includes prediction index separator");
+
+    assert!(stem.starts_with("mawaku-extremely-supercalif-midnight_s-p2-"));
+    assert_eq!(base, "mawaku-extremely-supercalif-midnight_s");
+
+    let location_component =
+        component_token(&scene.locations[0]).expect("location component slug exists");
+    assert_eq!(location_component, "extremely");
+
+    let season_component =
+        component_token(scene.season.as_deref().unwrap()).expect("season component slug exists");
+    assert_eq!(season_component.len(), COMPONENT_MAX_LEN);
+    assert_eq!(season_component, "supercalif");
+
+    let time_component =
+        component_token(scene.time_of_day.as_deref().unwrap()).expect("time component slug exists");
+    assert_eq!(time_component.len(), COMPONENT_MAX_LEN);
+    assert_eq!(time_component, "midnight_s");
+}
+
+#[test]
+fn cli_parses_batch_concurrency() {
+    let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone", "--batch"])
+        .expect("parse batch flag");
+    assert!(cli.batch);
+    assert_eq!(cli.concurrency, DEFAULT_CONCURRENCY);
+
+    let cli = Cli::try_parse_from(["mawaku", "--location", "A", "--concurrency", "4"])
+        .expect("parse concurrency");
+    assert_eq!(cli.concurrency, 4);
+
+    assert!(Cli::try_parse_from(["mawaku", "--location", "A", "--concurrency", "0"]).is_err());
+    assert!(Cli::try_parse_from(["mawaku", "--batch"]).is_err());
+}
+
+#[test]
+fn scene_context_narrows_to_one_location() {
+    let context = RunContext {
+        location: "Kyoto and Oslo".to_string(),
+        locations: vec!["Kyoto".to_string(), "Oslo".to_string()],
+        season: Some("winter".to_string()),
+        caption: Some("Kyoto and Oslo | winter".to_string()),
+        batch: true,
+        ..RunContext::default()
+    };
+
+    let scene = batch::scene_context(&context, "Oslo");
+    assert_eq!(scene.location, "Oslo");
+    assert_eq!(scene.locations, vec!["Oslo".to_string()]);
+    assert_eq!(scene.caption.as_deref(), Some("Oslo | winter"));
+    assert_eq!(scene.season.as_deref(), Some("winter"));
+
+    let uncaptioned = batch::scene_context(
+        &RunContext {
+            caption: None,
+            ..context
+        },
+        "Kyoto",
+    );
+    assert!(uncaptioned.caption.is_none());
+}
+
+#[test]
+fn batch_line_reports_saved_images_or_failure() {
+    let outcome = SceneOutcome {
+        prompt: String::new(),
+        predictions: vec![
+            PredictionOutcome::Saved {
+                path: PathBuf::from("a.png"),
+                dimensions: None,
+                sharp: None,
+            },
+            PredictionOutcome::MissingBytes,
+            PredictionOutcome::Saved {
+                path: PathBuf::from("b.png"),
+                dimensions: None,
+                sharp: None,
+            },
+        ],
+        streamed: false,
+        offline: false,
+    };
+    assert_eq!(
+        batch::format_line("Hakone", Some(&outcome)),
+        "Hakone: 2 images saved"
+    );
+
+    let empty = SceneOutcome {
+        prompt: String::new(),
+        predictions: vec![PredictionOutcome::Failed("disk full".to_string())],
+        streamed: false,
+        offline: false,
+    };
+    assert_eq!(batch::format_line("Oslo", Some(&empty)), "Oslo: failed");
+    let offline = SceneOutcome {
+        predictions: Vec::new(),
+        offline: true,
+        ..empty
+    };
+    assert_eq!(
+        batch::format_line("Oslo", Some(&offline)),
+        "Oslo: skipped (offline)"
+    );
+    assert_eq!(batch::format_line("Lima", None), "Lima: failed");
+}
+
+#[test]
+fn last_run_fills_only_unset_flags() {
+    let previous = Cli {
+        location: vec!["Hakone".to_string()],
+        season_preset: Some(SeasonPreset::Autumn),
+        time_of_day: Some("dusk".to_string()),
+        weather: Some("rain".to_string()),
+        aspect_ratio: Some(AspectRatioArg::Auto),
+        upscale: Some(UpscaleFactor::X2),
+        label: true,
+        ..Cli::default()
+    };
+    let mut cli = Cli {
+        season: Some("winter".to_string()),
+        weather: Some("snow".to_string()),
+        repeat_last: true,
+        ..Cli::default()
+    };
+
+    last_run::LastRun::from_cli(&previous).apply_to(&mut cli);
+
+    assert_eq!(cli.location, vec!["Hakone".to_string()]);
+    assert_eq!(cli.season.as_deref(), Some("winter"));
+    assert_eq!(cli.season_preset, None);
+    assert_eq!(cli.time_of_day.as_deref(), Some("dusk"));
+    assert_eq!(cli.weather.as_deref(), Some("snow"));
+    assert_eq!(cli.aspect_ratio, Some(AspectRatioArg::Auto));
+    assert_eq!(cli.upscale, Some(UpscaleFactor::X2));
+    assert!(cli.label);
+    assert!(!cli.contact_sheet);
+}
+
+#[test]
+fn repeat_last_keeps_a_recorded_switch_on() {
+    with_isolated_home(|_| {
+        last_run::remember(&Cli {
+            location: vec!["Hakone".to_string()],
+            label: true,
+            ..Cli::default()
+        });
+
+        let mut cli = Cli::try_parse_from(["mawaku", "--repeat-last"]).expect("parse repeat last");
+        last_run::preload(&mut cli);
+        assert!(cli.label);
+
+        // `--no-label` turns the recorded switch off, and the run records
+        // it off for the next `--repeat-last`.
+        let mut cli =
+            Cli::try_parse_from(["mawaku", "--repeat-last", "--no-label"]).expect("parse no label");
+        last_run::preload(&mut cli);
+        assert!(!cli.label);
+        assert_eq!(cli.location, vec!["Hakone".to_string()]);
+        last_run::remember(&cli);
+        let mut cli = Cli::try_parse_from(["mawaku", "--repeat-last"]).expect("parse repeat last");
+        last_run::preload(&mut cli);
+        assert!(!cli.label);
+    });
+}
+
+#[test]
+fn repeat_last_replays_the_alias_as_typed() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "[aliases]\nhakone = \"Hakone, Japan\"\n",
+        )
+        .expect("write config");
+
+        let cli = Cli::try_parse_from(["mawaku", "--location", "hakone"]).expect("parse run");
+        remember_run(&cli);
+        let context = run(cli);
+        assert_eq!(context.locations, vec!["Hakone, Japan".to_string()]);
+
+        let mut cli = Cli::try_parse_from(["mawaku", "--repeat-last"]).expect("parse repeat last");
+        last_run::preload(&mut cli);
+        assert_eq!(cli.location, vec!["hakone".to_string()]);
+    });
+}
+
+#[test]
+fn no_flags_turn_off_every_recorded_switch() {
+    with_isolated_home(|_| {
+        last_run::remember(&Cli {
+            location: vec!["Hakone".to_string(), "Oslo".to_string()],
+            label: true,
+            contact_sheet: true,
+            embed_metadata: true,
+            batch: true,
+            ..Cli::default()
+        });
+
+        let mut cli = Cli::try_parse_from([
+            "mawaku",
+            "--repeat-last",
+            "--no-label",
+            "--no-contact-sheet",
+            "--no-embed-metadata",
+            "--no-batch",
+        ])
+        .expect("parse no flags");
+        last_run::preload(&mut cli);
+        assert!(!cli.label && !cli.contact_sheet && !cli.embed_metadata && !cli.batch);
+
+        // The later flag wins when both forms are given.
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Oslo", "--label", "--no-label"])
+            .expect("parse label then no label");
+        assert!(!cli.label);
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Oslo", "--no-label", "--label"])
+            .expect("parse no label then label");
+        assert!(cli.label && !cli.no_label);
+    });
+}
+
+#[test]
+fn repeat_last_batches_the_recorded_locations() {
+    with_isolated_home(|_| {
+        last_run::remember(&Cli {
+            location: vec!["Hakone".to_string(), "Oslo".to_string()],
+            ..Cli::default()
+        });
+
+        let mut cli = Cli::try_parse_from(["mawaku", "--repeat-last", "--batch"])
+            .expect("parse repeat last with batch");
+        last_run::preload(&mut cli);
+        assert!(cli.batch);
+        assert_eq!(cli.location, vec!["Hakone".to_string(), "Oslo".to_string()]);
+    });
+}
+
+#[test]
+fn estimates_are_not_recorded_for_repeat_last() {
+    with_isolated_home(|home| {
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone", "--estimate"])
+            .expect("parse estimate");
+        remember_run(&cli);
+        assert!(!home.join(".mawaku").join("last_run.json").exists());
+
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone"]).expect("parse run");
+        remember_run(&cli);
+        assert!(home.join(".mawaku").join("last_run.json").exists());
+    });
+}
+
+#[test]
+fn last_run_round_trips_through_disk_and_ignores_bad_values() {
+    with_isolated_home(|home| {
+        let path = home.join(".mawaku").join("last_run.json");
+        let stored = last_run::LastRun {
+            location: vec!["Lisbon".to_string()],
+            season_preset: Some("fall".to_string()),
+            aspect_ratio: Some("7:5".to_string()),
+            ..last_run::LastRun::default()
+        };
+        last_run::save(&path, &stored).expect("save last run");
+
+        let loaded = last_run::load(&path).expect("load last run");
+        assert_eq!(loaded, stored);
+
+        let mut cli = Cli::default();
+        loaded.apply_to(&mut cli);
+        assert_eq!(cli.location, vec!["Lisbon".to_string()]);
+        assert_eq!(cli.season_preset, Some(SeasonPreset::Autumn));
+        assert_eq!(cli.aspect_ratio, None);
+    });
+}
+
+#[test]
+fn write_first_image_emits_only_the_first_decodable_prediction() {
+    let response: PredictResponse = serde_json::from_str(
+        r#"{"predictions": [
+            {"mimeType": "image/png"},
+            {"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"},
+            {"bytesBase64Encoded": "d29ybGQ=", "mimeType": "image/png"}
+        ]}"#,
+    )
+    .expect("parse response");
+
+    let mut out = Vec::new();
+    let written = write_first_image(&mut out, &response).expect("write image");
+    assert_eq!(written, Some(5));
+    assert_eq!(out, b"hello");
+
+    let empty: PredictResponse =
+        serde_json::from_str(r#"{"predictions": []}"#).expect("parse empty response");
+    let mut out = Vec::new();
+    assert_eq!(
+        write_first_image(&mut out, &empty).expect("write nothing"),
+        None
+    );
+    assert!(out.is_empty());
+}
+
+#[test]
+fn cli_rejects_stdout_image_with_file_outputs() {
+    let cli = Cli::try_parse_from(["mawaku", "--location", "Oslo", "--stdout-image"])
+        .expect("parse stdout-image");
+    assert!(cli.stdout_image);
+    assert!(
+        Cli::try_parse_from(["mawaku", "--location", "Oslo", "--stdout-image", "--batch"]).is_err()
+    );
+    assert!(Cli::try_parse_from(["mawaku", "--stdout-image", "--contact-sheet"]).is_err());
+}
+
+#[test]
+fn prompt_only_keeps_the_api_key_but_conflicts_with_stdout_image() {
+    with_isolated_home(|_| {
+        set_env(DEFAULT_GEMINI_API_KEY_ENV_VAR, OsStr::new("test-key"));
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Oslo", "--prompt-only"])
+            .expect("parse prompt-only");
+        let context = run(cli);
+
+        assert!(context.prompt_only);
+        assert_eq!(context.gemini_api_key.as_deref(), Some("test-key"));
+    });
+    assert!(Cli::try_parse_from(["mawaku", "--prompt-only", "--stdout-image"]).is_err());
+}
+
+#[test]
+fn spinner_line_shows_running_elapsed_time() {
+    assert_eq!(
+        retry::spinner_line("/", Duration::from_millis(4_230), None),
+        "Generating image / 4.2s"
+    );
+    assert_eq!(
+        retry::spinner_line("|", Duration::ZERO, None),
+        "Generating image | 0.0s"
+    );
+    assert_eq!(
+        retry::spinner_line("\\", Duration::from_secs(31), Some((2, 3))),
+        "Generating image (retry 2/3) \\ 31.0s"
+    );
+}
+
+#[test]
+fn save_predictions_writes_files_and_reports_each_prediction() {
+    with_isolated_home(|home| {
+        let output_dir = home.join("outputs");
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(output_dir.clone()),
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let response: PredictResponse = serde_json::from_str(
+            r#"{"predictions": [
+                {"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"},
+                {"mimeType": "image/png"},
+                {"bytesBase64Encoded": "not base64!", "mimeType": "image/png"}
+            ]}"#,
+        )
+        .expect("parse response");
+
+        let outcomes = save_predictions(&response, &context, &image_name_context, "prompt");
+
+        assert_eq!(outcomes.len(), 3);
+        let PredictionOutcome::Saved { path, .. } = &outcomes[0] else {
+            panic!(
+                "expected first prediction to be saved, got {:?}",
+                outcomes[0]
+            );
+        };
+        assert_eq!(path.parent(), Some(output_dir.as_path()));
+        assert!(
+            path.file_name()
+                .and_then(OsStr::to_str)
+                .is_some_and(|name| name.starts_with("mawaku-hakone-p1-") && name.ends_with(".png"))
+        );
+        assert_eq!(fs::read(path).expect("read saved image"), b"hello");
+        assert_eq!(outcomes[1], PredictionOutcome::MissingBytes);
+        assert!(matches!(outcomes[2], PredictionOutcome::Failed(_)));
+        assert_eq!(saved_paths(&outcomes), vec![path.clone()]);
+    });
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn keep_sharp_saves_the_original_next_to_the_blurred_copy() {
+    with_isolated_home(|home| {
+        let output_dir = home.join("outputs");
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(output_dir.clone()),
+            blur: Some(1.0),
+            keep_sharp: true,
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let response: PredictResponse = serde_json::from_str(
+            r#"{"predictions": [{
+                "bytesBase64Encoded": "iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEElEQVR4nGP4z8AARAwQCgAf7gP9i18U1AAAAABJRU5ErkJggg==",
+                "mimeType": "image/png"
+            }]}"#,
+        )
+        .expect("parse response");
+
+        let outcomes = save_predictions(&response, &context, &image_name_context, "prompt");
+
+        let PredictionOutcome::Saved { path, .. } = &outcomes[0] else {
+            panic!("expected the prediction to be saved, got {:?}", outcomes[0]);
+        };
+        let name = path.file_name().and_then(OsStr::to_str).expect("file name");
+        assert!(name.starts_with("mawaku-hakone-blur-p1-"), "{name}");
+        let mut names: Vec<String> = fs::read_dir(&output_dir)
+            .expect("list outputs")
+            .map(|entry| {
+                entry
+                    .expect("dir entry")
+                    .file_name()
+                    .to_string_lossy()
+                    .into_owned()
+            })
+            .collect();
+        names.sort();
+        assert_eq!(names.len(), 2);
+        assert!(
+            names
+                .iter()
+                .any(|name| name.starts_with("mawaku-hakone-p1-"))
+        );
+    });
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn keep_sharp_records_the_original_and_writes_the_raw_payload_once() {
+    with_isolated_home(|home| {
+        let output_dir = home.join("outputs");
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(output_dir.clone()),
+            blur: Some(1.0),
+            keep_sharp: true,
+            save_raw: true,
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let response: PredictResponse = serde_json::from_str(
+            r#"{"predictions": [{
+                "bytesBase64Encoded": "iVBORw0KGgoAAAANSUhEUgAAAAIAAAACCAIAAAD91JpzAAAAEElEQVR4nGP4z8AARAwQCgAf7gP9i18U1AAAAABJRU5ErkJggg==",
+                "mimeType": "image/png"
+            }]}"#,
+        )
+        .expect("parse response");
+
+        let outcomes = save_predictions(&response, &context, &image_name_context, "prompt");
+
+        let PredictionOutcome::Saved { path, sharp, .. } = &outcomes[0] else {
+            panic!("expected the prediction to be saved, got {:?}", outcomes[0]);
+        };
+        let sharp = sharp.as_ref().expect("sharp original recorded");
+        let sharp_name = sharp
+            .file_name()
+            .and_then(OsStr::to_str)
+            .expect("file name");
+        assert!(sharp_name.starts_with("mawaku-hakone-p1-"), "{sharp_name}");
+        assert_eq!(saved_files(&outcomes), vec![path.clone(), sharp.clone()]);
+        assert_eq!(saved_paths(&outcomes), vec![path.clone()]);
+
+        let raw: Vec<PathBuf> = fs::read_dir(&output_dir)
+            .expect("list outputs")
+            .map(|entry| entry.expect("dir entry").path())
+            .filter(|path| path.extension() == Some(OsStr::new("b64")))
+            .collect();
+        assert_eq!(raw, vec![sharp.with_extension("b64")]);
+    });
+}
+
+#[test]
+fn save_concurrently_caps_workers_and_keeps_job_order() {
+    let jobs: Vec<SaveJob> = (0..6)
+        .map(|index| {
+            if index == 2 {
+                SaveJob::MissingBytes
+            } else {
+                SaveJob::Save {
+                    file_stem: format!("stem-{index}"),
+                    encoded: "aGVsbG8=",
+                    mime_type: None,
+                }
+            }
+        })
+        .collect();
+    let running = AtomicUsize::new(0);
+    let peak = AtomicUsize::new(0);
+
+    let results = save_concurrently(&jobs, 2, |file_stem, _, _| {
+        let now = running.fetch_add(1, Ordering::SeqCst) + 1;
+        peak.fetch_max(now, Ordering::SeqCst);
+        std::thread::sleep(std::time::Duration::from_millis(10));
+        running.fetch_sub(1, Ordering::SeqCst);
+        if file_stem == "stem-4" {
+            Err(ImageSaveError::EmptyPayload)
+        } else {
+            Ok(SavedImage {
+                path: PathBuf::from(format!("{file_stem}.png")),
+                dimensions: None,
+                byte_len: 5,
+                raw_path: None,
+                metadata_skipped: false,
+            })
+        }
+    });
+
+    assert!(peak.load(Ordering::SeqCst) <= 2);
+    assert_eq!(results.len(), jobs.len());
+    assert!(results[2].is_none());
+    assert!(matches!(
+        results[4],
+        Some(Err(ImageSaveError::EmptyPayload))
+    ));
+    for index in [0, 1, 3, 5] {
+        let Some(Ok(saved)) = &results[index] else {
+            panic!("expected job {index} to be saved, got {:?}", results[index]);
+        };
+        assert_eq!(saved.path, PathBuf::from(format!("stem-{index}.png")));
+    }
+}
+
+#[test]
+fn save_raw_keeps_payloads_even_when_decoding_fails() {
+    with_isolated_home(|home| {
+        let output_dir = home.join("outputs");
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone", "--save-raw"])
+            .expect("parse save raw");
+        assert!(cli.save_raw);
+        assert!(Cli::try_parse_from(["mawaku", "--save-raw", "--stdout-image"]).is_err());
+
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(output_dir.clone()),
+            save_raw: true,
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let response: PredictResponse = serde_json::from_str(
+            r#"{"predictions": [
+                {"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"},
+                {"bytesBase64Encoded": "not base64!", "mimeType": "image/png"}
+            ]}"#,
+        )
+        .expect("parse response");
+
+        let outcomes = save_predictions(&response, &context, &image_name_context, "prompt");
+
+        let PredictionOutcome::Saved { path, .. } = &outcomes[0] else {
+            panic!(
+                "expected first prediction to be saved, got {:?}",
+                outcomes[0]
+            );
+        };
+        assert_eq!(
+            fs::read_to_string(path.with_extension("b64")).expect("read raw payload"),
+            "aGVsbG8="
+        );
+        assert!(matches!(outcomes[1], PredictionOutcome::Failed(_)));
+        let raw_payloads: Vec<String> = fs::read_dir(&output_dir)
+            .expect("list outputs")
+            .filter_map(|entry| {
+                let path = entry.expect("dir entry").path();
+                (path.extension() == Some(OsStr::new("b64")))
+                    .then(|| fs::read_to_string(path).expect("read raw payload"))
+            })
+            .collect();
+        assert_eq!(raw_payloads.len(), 2);
+        assert!(raw_payloads.contains(&"not base64!".to_string()));
+    });
+}
+
+/// Canned Gemini backend: every image request returns `predictions`, and
+/// place descriptions and orientations are fixed.
+struct FakeGemini {
+    predictions: &'static str,
+    image_prompts: Mutex<Vec<(String, Option<String>)>>,
+    seeds: Mutex<Vec<u32>>,
+    finish_reason: Option<&'static str>,
+}
+
+impl FakeGemini {
+    fn new(predictions: &'static str) -> Self {
+        Self {
+            predictions,
+            image_prompts: Mutex::new(Vec::new()),
+            seeds: Mutex::new(Vec::new()),
+            finish_reason: Some(NORMAL_FINISH_REASON),
+        }
+    }
+}
+
+impl ImageGenerator for FakeGemini {
+    fn generate(&self, prompt: &str) -> Result<PredictResponse, GeminiError> {
+        self.image_prompts
+            .lock()
+            .unwrap()
+            .push((prompt.to_string(), None));
+        Ok(serde_json::from_str(self.predictions)?)
+    }
+
+    fn generate_with_aspect_ratio(
+        &self,
+        prompt: &str,
+        aspect_ratio: &str,
+    ) -> Result<PredictResponse, GeminiError> {
+        self.image_prompts
+            .lock()
+            .unwrap()
+            .push((prompt.to_string(), Some(aspect_ratio.to_string())));
+        Ok(serde_json::from_str(self.predictions)?)
+    }
+
+    fn generate_seeded(
+        &self,
+        prompt: &str,
+        aspect_ratio: Option<&str>,
+        seed: u32,
+        on_retry: &dyn Fn(RetryAttempt),
+    ) -> Result<PredictResponse, GeminiError> {
+        self.seeds.lock().unwrap().push(seed);
+        self.generate_reporting_retries(prompt, aspect_ratio, on_retry)
+    }
+}
+
+impl TextGenerator for FakeGemini {
+    fn describe_place(
+        &self,
+        _locations: &[&str],
+        _season: &str,
+        _time_of_day: Option<&str>,
+        _weather: Option<&str>,
+    ) -> Result<DescribedPlace, GeminiError> {
+        Ok(DescribedPlace {
+            description: PlaceDescription {
+                ambiance: "Steam rising from the onsen".to_string(),
+                items: vec!["lantern".to_string()],
+                keywords: vec!["calm".to_string()],
+            },
+            finish_reason: self.finish_reason.map(str::to_string),
+        })
+    }
+
+    fn classify_orientation(
+        &self,
+        _locations: &[&str],
+        _description: Option<&PlaceDescription>,
+    ) -> Result<mawaku_gemini::Orientation, GeminiError> {
+        Ok(mawaku_gemini::Orientation::Portrait)
+    }
+}
+
+#[test]
+fn descriptions_that_stopped_early_are_used_but_not_cached() {
+    with_isolated_home(|home| {
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            description_cache: Some(PlaceDescriptionCache::new(
+                home.join("cache"),
+                Duration::from_secs(3_600),
+            )),
+            ..RunContext::default()
+        };
+        let key = cache::description_key(&context);
+        let truncated = FakeGemini {
+            finish_reason: Some(MAX_TOKENS_FINISH_REASON),
+            ..FakeGemini::new("{}")
+        };
+
+        let description =
+            fetch_place_description(&truncated, &context, "spring").expect("describe place");
+        assert_eq!(description.ambiance, "Steam rising from the onsen");
+        let cache = context.description_cache.as_ref().expect("cache");
+        assert!(cache.get(&key).is_none());
+
+        fetch_place_description(&FakeGemini::new("{}"), &context, "spring")
+            .expect("describe place");
+        assert!(cache.get(&key).is_some());
+    });
+}
+
+#[test]
+fn render_scene_saves_canned_predictions_without_network() {
+    with_isolated_home(|home| {
+        let output_dir = home.join("outputs");
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(output_dir.clone()),
+            aspect_ratio: Some(AspectRatioArg::Auto),
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let fake = FakeGemini::new(
+            r#"{"predictions": [
+                {"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"},
+                {"bytesBase64Encoded": "d29ybGQ=", "mimeType": "image/jpeg"}
+            ]}"#,
+        );
+
+        let outcome = render_scene(&fake, &fake, &context, &image_name_context, false);
+
+        assert!(
+            outcome
+                .prompt
+                .contains("Ambiance: Steam rising from the onsen")
+        );
+        let paths = outcome.saved_paths();
+        assert_eq!(paths.len(), 2);
+        assert!(
+            paths
+                .iter()
+                .all(|path| path.parent() == Some(output_dir.as_path()))
+        );
+        assert_eq!(fs::read(&paths[0]).expect("read first image"), b"hello");
+        assert_eq!(paths[1].extension().and_then(OsStr::to_str), Some("jpg"));
+
+        let requests = fake.image_prompts.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        assert_eq!(requests[0].0, outcome.prompt);
+        assert_eq!(requests[0].1.as_deref(), Some("3:4"));
+        assert!(
+            outcome
+                .prompt
+                .ends_with("Compose this as a tall vertical image.")
+        );
+    });
+}
+
+#[test]
+fn preview_subcommand_accepts_scene_flags_after_it() {
+    let cli = Cli::try_parse_from([
+        "mawaku",
+        "preview",
+        "--location",
+        "Hakone",
+        "--time-of-day",
+        "dusk",
+    ])
+    .expect("parse preview");
+
+    assert_eq!(cli.command, Some(Command::Preview));
+    assert_eq!(cli.location, vec!["Hakone".to_string()]);
+    assert_eq!(cli.time_of_day.as_deref(), Some("dusk"));
+}
+
+#[test]
+fn preview_scene_shows_description_instructions_and_prompt() {
+    with_isolated_home(|_| {
+        let context = RunContext {
+            prompt_template: "Render a calm office.".to_string(),
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            ..RunContext::default()
+        };
+        let fake = FakeGemini::new(r#"{"predictions": []}"#);
+
+        let preview = preview_scene(Some(&fake), &context);
+
+        let description_at = preview.find("--- Place description ---").unwrap();
+        let instructions_at = preview.find("--- General instructions ---").unwrap();
+        let prompt_at = preview.find("--- Structured prompt ---").unwrap();
+        assert!(description_at < instructions_at && instructions_at < prompt_at);
+        assert!(preview.contains(r#""ambiance": "Steam rising from the onsen""#));
+        assert!(preview[prompt_at..].contains("Ambiance: Steam rising from the onsen"));
+        assert!(fake.image_prompts.lock().unwrap().is_empty());
+    });
+}
+
+#[test]
+fn preview_scene_without_backend_leaves_description_empty() {
+    let context = RunContext {
+        location: "Hakone".to_string(),
+        locations: vec!["Hakone".to_string()],
+        ..RunContext::default()
+    };
+
+    let preview = preview_scene(None, &context);
+
+    assert!(preview.contains("--- Place description ---\n(none)"));
+    assert!(preview.contains("Ambiance: Unspecified"));
+}
+
+#[test]
+fn clean_parses_ages_and_requires_a_criterion() {
+    assert_eq!(
+        clean::parse_age("30d"),
+        Ok(Duration::from_secs(30 * 86_400))
+    );
+    assert_eq!(clean::parse_age("12h"), Ok(Duration::from_secs(12 * 3_600)));
+    assert_eq!(clean::parse_age("2w"), Ok(Duration::from_secs(14 * 86_400)));
+    assert!(clean::parse_age("30").is_err());
+    assert!(clean::parse_age("d").is_err());
+
+    let cli =
+        Cli::try_parse_from(["mawaku", "clean", "--keep", "5", "--dry-run"]).expect("parse clean");
+    let Some(Command::Clean(args)) = cli.command else {
+        panic!("expected the clean subcommand");
+    };
+    assert_eq!(args.keep, Some(5));
+    assert!(args.dry_run);
+    assert!(Cli::try_parse_from(["mawaku", "clean"]).is_err());
+}
+
+#[test]
+fn clean_only_selects_mawaku_images() {
+    let dir = create_unique_home();
+    let dated = dir.join("2024").join("05").join("01");
+    fs::create_dir_all(&dated).expect("create date partition");
+    fs::create_dir_all(dir.join("exports")).expect("create unrelated dir");
+    for path in [
+        dir.join("mawaku-hakone-p1-AB3KZ.png"),
+        dated.join("mawaku-oslo-p2-20240501-101500-QW7ER.jpg"),
+        dir.join("mawaku-notes.png"),
+        dir.join("mawaku-hakone-p1-AB3KZ.txt"),
+        dir.join("holiday-p1-AB3KZ.png"),
+        dir.join("clientwork-oslo-p1-CW4RK.png"),
+        dir.join("exports").join("mawaku-kyoto-p1-ZZ9ZZ.png"),
+    ] {
+        fs::write(&path, b"image").expect("write file");
+    }
+
+    let mut found: Vec<PathBuf> = clean::generated_images(&dir, DEFAULT_FILE_NAME_PREFIX)
+        .expect("scan directory")
+        .into_iter()
+        .map(|image| image.path)
+        .collect();
+    found.sort();
+    assert_eq!(
+        found,
+        vec![
+            dated.join("mawaku-oslo-p2-20240501-101500-QW7ER.jpg"),
+            dir.join("mawaku-hakone-p1-AB3KZ.png"),
+        ]
+    );
+    let found: Vec<PathBuf> = clean::generated_images(&dir, "clientwork")
+        .expect("scan directory")
+        .into_iter()
+        .map(|image| image.path)
+        .collect();
+    assert_eq!(found, vec![dir.join("clientwork-oslo-p1-CW4RK.png")]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn clean_leaves_user_files_with_generated_looking_names_alone() {
+    let dir = create_unique_home();
+    for name in [
+        "mawaku-trip-p1-final.png",
+        "mawaku-trip-p1-AB3K.png",
+        "mawaku-trip-p1-AB3KZ9.png",
+        "mawaku-trip-p1-AB3KZ.png",
+    ] {
+        fs::write(dir.join(name), b"image").expect("write file");
+    }
+
+    let found: Vec<PathBuf> = clean::generated_images(&dir, DEFAULT_FILE_NAME_PREFIX)
+        .expect("scan directory")
+        .into_iter()
+        .map(|image| image.path)
+        .collect();
+    assert_eq!(found, vec![dir.join("mawaku-trip-p1-AB3KZ.png")]);
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn clean_leaves_images_of_sibling_prefixes_alone() {
+    // Prefixes are single words, so `client work` cannot produce names that
+    // start with the `client` prefix.
+    assert_eq!(
+        parse_file_name_prefix("client-work").as_deref(),
+        Ok("clientwork")
+    );
+
+    let dir = create_unique_home();
+    for name in [
+        "client-oslo-p1-AB3KZ.png",
+        "clientwork-oslo-p1-CW4RK.png",
+        "mawakustudio-oslo-p1-ZZ9ZZ.png",
+    ] {
+        fs::write(dir.join(name), b"image").expect("write file");
+    }
+
+    let selected = clean::select_for_removal(
+        clean::generated_images(&dir, "client").expect("scan directory"),
+        None,
+        Some(0),
+        SystemTime::now(),
+    );
+    let removed: Vec<PathBuf> = selected.into_iter().map(|image| image.path).collect();
+    assert_eq!(removed, vec![dir.join("client-oslo-p1-AB3KZ.png")]);
+    assert!(
+        clean::generated_images(&dir, DEFAULT_FILE_NAME_PREFIX)
+            .expect("scan directory")
+            .is_empty()
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn clean_removes_raw_payloads_with_their_images() {
+    let dir = create_unique_home();
+    for name in [
+        "mawaku-hakone-p1-AB3KZ.png",
+        "mawaku-hakone-p1-AB3KZ.b64",
+        "mawaku-oslo-p1-QW7ER.b64",
+        "notes.b64",
+    ] {
+        fs::write(dir.join(name), b"image").expect("write file");
+    }
+
+    let mut found: Vec<(PathBuf, Option<PathBuf>)> =
+        clean::generated_images(&dir, DEFAULT_FILE_NAME_PREFIX)
+            .expect("scan directory")
+            .into_iter()
+            .map(|image| (image.path, image.raw_path))
+            .collect();
+    found.sort();
+    assert_eq!(
+        found,
+        vec![
+            (
+                dir.join("mawaku-hakone-p1-AB3KZ.png"),
+                Some(dir.join("mawaku-hakone-p1-AB3KZ.b64")),
+            ),
+            (dir.join("mawaku-oslo-p1-QW7ER.b64"), None),
+        ]
+    );
+
+    let _ = fs::remove_dir_all(&dir);
+}
+
+#[test]
+fn clean_selects_by_age_and_keep_count() {
+    let now = SystemTime::UNIX_EPOCH + Duration::from_secs(100 * 86_400);
+    let image = |name: &str, age_days: u64| clean::GeneratedImage {
+        path: PathBuf::from(name),
+        modified: now - Duration::from_secs(age_days * 86_400),
+        raw_path: None,
+    };
+    let images = vec![
+        image("a", 1),
+        image("b", 40),
+        image("c", 10),
+        image("d", 60),
+    ];
+    let names = |selected: Vec<clean::GeneratedImage>| -> Vec<String> {
+        selected
+            .into_iter()
+            .map(|image| image.path.display().to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        names(clean::select_for_removal(
+            images.clone(),
+            Some(Duration::from_secs(30 * 86_400)),
+            None,
+            now
+        )),
+        ["b", "d"]
+    );
+    assert_eq!(
+        names(clean::select_for_removal(
+            images.clone(),
+            None,
+            Some(1),
+            now
+        )),
+        ["c", "b", "d"]
+    );
+    assert_eq!(
+        names(clean::select_for_removal(
+            images,
+            Some(Duration::from_secs(50 * 86_400)),
+            Some(1),
+            now
+        )),
+        ["d"]
+    );
+}
+
+#[test]
+fn models_subcommand_lists_capable_models_unless_all() {
+    let cli = Cli::try_parse_from(["mawaku", "models", "--all"]).expect("parse models");
+    assert_eq!(cli.command, Some(Command::Models { all: true }));
+    let cli = Cli::try_parse_from(["mawaku", "models"]).expect("parse models");
+    assert_eq!(cli.command, Some(Command::Models { all: false }));
+
+    let model = |name: &str, methods: &[&str]| ModelInfo {
+        name: format!("models/{name}"),
+        display_name: String::new(),
+        supported_generation_methods: methods.iter().map(|method| method.to_string()).collect(),
+    };
+    let models = [
+        model("imagen-4.0-generate-001", &["predict"]),
+        model("gemini-2.5-flash", &["generateContent", "countTokens"]),
+        model("text-embedding-004", &["embedContent"]),
+    ];
+
+    assert_eq!(
+        model_lines(&models, false),
+        [
+            "imagen-4.0-generate-001  predict",
+            "gemini-2.5-flash         generateContent, countTokens",
+        ]
+    );
+    assert_eq!(model_lines(&models, true).len(), 3);
+    assert!(model_lines(&[], false).is_empty());
+}
+
+#[test]
+fn prompt_file_replaces_the_configured_template() {
+    with_isolated_home(|home| {
+        let prompt_path = home.join("prompt.txt");
+        fs::write(&prompt_path, "\n  A quiet reading nook  \n").expect("write prompt file");
+        let cli = Cli::try_parse_from([
+            "mawaku".as_ref(),
+            "--location".as_ref(),
+            "Kyoto".as_ref(),
+            "--prompt-file".as_ref(),
+            prompt_path.as_os_str(),
+        ])
+        .expect("parse prompt file");
+        assert_eq!(cli.base_prompt.as_deref(), Some("A quiet reading nook"));
+
+        let context = run(cli);
+        assert_eq!(context.prompt_template, "A quiet reading nook");
+        assert!(context.prompt.starts_with("A quiet reading nook"));
+
+        let context = run(Cli {
+            location: vec!["Kyoto".to_string()],
+            ..Cli::default()
+        });
+        assert_eq!(context.prompt_template, DEFAULT_PROMPT);
+
+        let empty_path = home.join("empty.txt");
+        fs::write(&empty_path, "  \n").expect("write empty prompt file");
+        for path in [empty_path, home.join("missing.txt")] {
+            let result = Cli::try_parse_from([
+                "mawaku".as_ref(),
+                "--prompt-file".as_ref(),
+                path.as_os_str(),
+            ]);
+            assert!(result.is_err(), "{} should be rejected", path.display());
+        }
+    });
+}
+
+#[test]
+fn api_key_flag_wins_without_being_persisted() {
+    with_isolated_home(|home| {
+        set_env(DEFAULT_GEMINI_API_KEY_ENV_VAR, OsStr::new("env-key"));
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Kyoto", "--api-key", "flag-key"])
+            .expect("parse api key");
+        let context = run(cli);
+
+        assert_eq!(context.gemini_api_key.as_deref(), Some("flag-key"));
+        assert!(
+            context
+                .warnings
+                .iter()
+                .any(|warning| warning.contains("process listings"))
+        );
+        let contents =
+            fs::read_to_string(home.join(".mawaku").join("config.toml")).expect("config written");
+        assert!(!contents.contains("flag-key"));
+
+        let context = run(Cli {
+            location: vec!["Kyoto".to_string()],
+            ..Cli::default()
+        });
+        assert_eq!(context.gemini_api_key.as_deref(), Some("env-key"));
+        remove_env(DEFAULT_GEMINI_API_KEY_ENV_VAR);
+    });
+}
+
+#[test]
+fn set_gemini_api_key_persists_and_is_preferred() {
+    with_isolated_home(|home| {
+        set_env(DEFAULT_GEMINI_API_KEY_ENV_VAR, OsStr::new("env-key"));
+        let context = run(Cli {
+            location: vec!["Kyoto".to_string()],
+            set_gemini_api_key: Some("stored-key".to_string()),
+            ..Cli::default()
+        });
+        assert_eq!(context.gemini_api_key.as_deref(), Some("stored-key"));
+        assert!(
+            context
+                .infos
+                .iter()
+                .any(|info| info.starts_with("Updated Gemini API key in"))
+        );
+        let contents =
+            fs::read_to_string(home.join(".mawaku").join("config.toml")).expect("config written");
+        assert!(contents.contains("api_key = \"stored-key\""));
+
+        let context = run(Cli {
+            location: vec!["Kyoto".to_string()],
+            ..Cli::default()
+        });
+        assert_eq!(context.gemini_api_key.as_deref(), Some("stored-key"));
+
+        let context = run(Cli {
+            location: vec!["Kyoto".to_string()],
+            api_key: Some("flag-key".to_string()),
+            ..Cli::default()
+        });
+        assert_eq!(context.gemini_api_key.as_deref(), Some("flag-key"));
+        remove_env(DEFAULT_GEMINI_API_KEY_ENV_VAR);
+    });
+}
+
+#[test]
+fn clipboard_text_becomes_the_trimmed_location() {
+    let mut cli = Cli::try_parse_from(["mawaku", "--location-from-clipboard"])
+        .expect("parse location from clipboard");
+    apply_clipboard_location(&mut cli, Ok("  Hakone, Japan\n".to_string()))
+        .expect("use clipboard text");
+    assert_eq!(cli.location, vec!["Hakone, Japan".to_string()]);
+
+    let error = apply_clipboard_location(&mut cli, Ok(" \n".to_string())).expect_err("empty");
+    assert!(error.contains("no text"));
+    let error =
+        apply_clipboard_location(&mut cli, Err("no display".to_string())).expect_err("unavailable");
+    assert!(error.contains("no display"));
+
+    assert!(
+        Cli::try_parse_from(["mawaku", "--location", "Kyoto", "--location-from-clipboard"])
+            .is_err()
+    );
+}
+
+/// A client whose proxy is a local port that was just closed, so every
+/// request fails to connect.
+fn unreachable_gemini() -> GeminiClient {
+    let address = std::net::TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("reserve a closed port");
+    let options = GeminiClientOptions {
+        proxy: Some(format!("http://{address}")),
+        max_retries: 0,
+        ..GeminiClientOptions::default()
+    };
+    GeminiClient::with_options("secret", options).expect("client")
+}
+
+#[test]
+fn unreachable_gemini_falls_back_to_the_prompt_unless_online_is_required() {
+    let images = unreachable_gemini();
+    let context = RunContext {
+        location: "Hakone".to_string(),
+        locations: vec!["Hakone".to_string()],
+        ..RunContext::default()
+    };
+    let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+    let text = FakeGemini::new(r#"{"predictions": []}"#);
+
+    let outcome = render_scene(&images, &text, &context, &image_name_context, false);
+    assert!(outcome.offline);
+    assert!(outcome.prompt.contains("Hakone"));
+
+    let context = RunContext {
+        require_online: true,
+        ..context
+    };
+    let outcome = render_scene(&images, &text, &context, &image_name_context, false);
+    assert!(!outcome.offline);
+    assert!(
+        Cli::try_parse_from(["mawaku", "--require-online"])
+            .expect("parse require online")
+            .require_online
+    );
+}
+
+#[test]
+fn prefix_flag_overrides_configured_file_name_prefix() {
+    with_isolated_home(|_home| {
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Oslo", "--prefix", "Client Work"])
+            .expect("parse prefix");
+        assert_eq!(cli.prefix.as_deref(), Some("clientwork"));
+        let context = run(cli.clone());
+        assert_eq!(context.file_name_prefix, "clientwork");
+        let name_context = image_name_context_for(&context, &context.file_name_prefix);
+        assert!(name_context.file_stem(1).starts_with("clientwork-oslo-p1-"));
+
+        let context = run(Cli {
+            location: vec!["Oslo".to_string()],
+            ..Cli::default()
+        });
+        assert_eq!(context.file_name_prefix, DEFAULT_FILE_NAME_PREFIX);
+        assert!(Cli::try_parse_from(["mawaku", "--prefix", "***"]).is_err());
+    });
+}
+
+#[test]
+fn progress_events_serialize_as_tagged_json_lines() {
+    let cli = Cli::try_parse_from(["mawaku", "--progress", "json"]).expect("parse progress");
+    assert_eq!(cli.progress, ProgressFormat::Json);
+    assert_eq!(Cli::default().progress, ProgressFormat::Human);
+    assert!(Cli::try_parse_from(["mawaku", "--progress", "xml"]).is_err());
+
+    assert_eq!(
+        ProgressEvent::ImageStart {
+            location: "Kyoto".to_string()
+        }
+        .to_line(),
+        r#"{"event":"image_start","location":"Kyoto"}"#
+    );
+    assert_eq!(
+        ProgressEvent::ImageSaved {
+            location: "Kyoto".to_string(),
+            path: "/tmp/out/mawaku-kyoto-p1-AB3KZ.png".to_string(),
+        }
+        .to_line(),
+        r#"{"event":"image_saved","location":"Kyoto","path":"/tmp/out/mawaku-kyoto-p1-AB3KZ.png"}"#
+    );
+    assert_eq!(
+        ProgressEvent::Done {
+            requested: 2,
+            saved: 1,
+            failed: 1,
+            exit_code: 3,
+        }
+        .to_line(),
+        r#"{"event":"done","requested":2,"saved":1,"failed":1,"exit_code":3}"#
+    );
+}
+
+#[test]
+fn spinner_style_comes_from_flag_then_config() {
+    assert_eq!(SpinnerStyle::Ascii.frames(), ["|", "/", "-", "\\"]);
+    assert_eq!(SpinnerStyle::Dots.frames().len(), 10);
+    assert!(SpinnerStyle::None.frames().is_empty());
+    assert!("sparkles".parse::<SpinnerStyle>().is_err());
+
+    with_isolated_home(|home| {
+        let context = run(Cli::default());
+        assert_eq!(context.spinner, SpinnerStyle::Ascii);
+
+        let config_path = home.join(".mawaku").join("config.toml");
+        let contents = fs::read_to_string(&config_path).expect("config written");
+        fs::write(&config_path, format!("spinner = \"dots\"\n{contents}"))
+            .expect("write spinner setting");
+        let context = run(Cli::default());
+        assert_eq!(context.spinner, SpinnerStyle::Dots);
+
+        let cli = Cli::try_parse_from(["mawaku", "--spinner", "none"]).expect("parse spinner");
+        assert_eq!(run(cli).spinner, SpinnerStyle::None);
+    });
+}
+
+#[test]
+fn client_identifies_as_the_cli_version() {
+    assert_eq!(
+        client_options(&RunContext::default()).user_agent,
+        format!("mawaku-cli/{}", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn dump_request_reaches_the_client_and_silences_the_spinner() {
+    with_isolated_home(|_| {
+        let context = run(Cli::default());
+        assert!(client_options(&context).dump_requests.is_none());
+
+        let cli = Cli::try_parse_from(["mawaku", "--location", "Hakone", "--dump-request"])
+            .expect("parse dump request");
+        let context = run(cli);
+        assert!(client_options(&context).dump_requests.is_some());
+        assert_eq!(context.spinner, SpinnerStyle::None);
+    });
+}
+
+#[test]
+fn watermark_is_kept_unless_no_watermark_is_passed() {
+    with_isolated_home(|_| {
+        let cli = Cli::try_parse_from(["mawaku", "--seed-sweep", "1..2"]).expect("parse sweep");
+        assert!(!client_options(&run(cli)).disable_watermark);
+
+        let cli = Cli::try_parse_from(["mawaku", "--no-watermark"]).expect("parse no watermark");
+        assert!(client_options(&run(cli)).disable_watermark);
+    });
+}
+
+#[test]
+fn first_run_is_reported_as_config_created() {
+    with_isolated_home(|home| {
+        let first = run(Cli::default());
+        assert!(first.config_created);
+        let expected_path = home.join(".mawaku").join("config.toml");
+        assert_eq!(first.config_path.as_deref(), Some(expected_path.as_path()));
+
+        let second = run(Cli::default());
+        assert!(!second.config_created);
+        assert_eq!(
+            ProgressEvent::ConfigLoaded {
+                path: Some("/home/me/.mawaku/config.toml".to_string()),
+                config_created: true,
+            }
+            .to_line(),
+            r#"{"event":"config_loaded","path":"/home/me/.mawaku/config.toml","config_created":true}"#
+        );
+    });
+}
+
+#[test]
+fn person_generation_flag_overrides_the_default() {
+    let cli = Cli::try_parse_from(["mawaku", "--person-generation", "allow_adult"])
+        .expect("parse person generation");
+    assert_eq!(cli.person_generation, Some(PersonGeneration::AllowAdult));
+    assert!(Cli::try_parse_from(["mawaku", "--person-generation", "crowds"]).is_err());
+
+    with_isolated_home(|_home| {
+        assert_eq!(
+            run(cli).person_generation,
+            Some(PersonGeneration::AllowAdult)
+        );
+        assert_eq!(run(Cli::default()).person_generation, None);
+    });
+}
+
+#[test]
+fn safety_threshold_comes_from_flag_then_config() {
+    with_isolated_home(|home| {
+        let context = run(Cli::default());
+        assert_eq!(
+            context.safety_settings,
+            SafetySetting::all(SafetyThreshold::BlockOnlyHigh)
+        );
+
+        let config_path = home.join(".mawaku").join("config.toml");
+        let contents = fs::read_to_string(&config_path).expect("config written");
+        fs::write(
+            &config_path,
+            format!("{contents}\n[gemini_api.safety_settings]\ndangerous_content = \"block_medium_and_above\"\n"),
+        )
+        .expect("write safety settings");
+        let settings = run(Cli::default()).safety_settings;
+        let dangerous = settings
+            .iter()
+            .find(|setting| setting.category == HarmCategory::DangerousContent)
+            .expect("dangerous content setting");
+        assert_eq!(dangerous.threshold, SafetyThreshold::BlockMediumAndAbove);
+        assert!(
+            settings
+                .iter()
+                .filter(|setting| setting.category != HarmCategory::DangerousContent)
+                .all(|setting| setting.threshold == SafetyThreshold::BlockOnlyHigh)
+        );
+
+        let cli = Cli::try_parse_from(["mawaku", "--safety-threshold", "block_none"])
+            .expect("parse safety threshold");
+        assert_eq!(
+            run(cli).safety_settings,
+            SafetySetting::all(SafetyThreshold::BlockNone)
+        );
+    });
+    assert!(Cli::try_parse_from(["mawaku", "--safety-threshold", "lenient"]).is_err());
+}
+
+#[test]
+fn failures_log_round_trips_scene_flags_and_clears_successes() {
+    with_isolated_home(|home| {
+        let path = failures::log_path(&home.join("outputs"));
+        let cli = Cli::try_parse_from([
+            "mawaku",
+            "--location",
+            "Kyoto",
+            "--location",
+            "Oslo",
+            "--batch",
+            "--season-preset",
+            "autumn",
+            "--time-of-day",
+            "dusk",
+        ])
+        .expect("parse batch");
+        let kyoto = FailedScene::from_cli(&cli, "Kyoto");
+        let oslo = FailedScene::from_cli(&cli, "Oslo");
+        assert_eq!(failures::load(&path).expect("missing log"), Vec::new());
+
+        failures::record(
+            &path,
+            &[kyoto.clone(), oslo.clone()],
+            &[kyoto.clone(), oslo.clone()],
+        )
+        .expect("record failures");
+        let loaded = failures::load(&path).expect("load failures");
+        assert_eq!(loaded, vec![kyoto.clone(), oslo.clone()]);
+        assert_eq!(loaded[0].season_preset(), Some(SeasonPreset::Autumn));
+        assert_eq!(loaded[0].time_of_day.as_deref(), Some("dusk"));
+
+        let retried = batch::recorded_scene_context(&RunContext::default(), &loaded[1]);
+        assert_eq!(retried.locations, vec!["Oslo".to_string()]);
+        assert_eq!(retried.season.as_deref(), Some("autumn"));
+
+        failures::record(&path, std::slice::from_ref(&oslo), &[]).expect("clear oslo");
+        assert_eq!(
+            failures::load(&path).expect("load failures"),
+            vec![kyoto.clone()]
+        );
+
+        failures::record(&path, &[kyoto], &[]).expect("clear kyoto");
+        assert!(!path.exists());
+    });
+}
+
+#[test]
+fn batch_scenes_use_their_recorded_flags() {
+    let context = RunContext {
+        season: Some("summer".to_string()),
+        time_of_day: Some("noon".to_string()),
+        batch: true,
+        ..RunContext::default()
+    };
+    let scene = FailedScene {
+        location: "Kyoto".to_string(),
+        season_preset: Some("winter".to_string()),
+        weather: Some("snow".to_string()),
+        ..FailedScene::default()
+    };
+
+    let scene_context = batch::recorded_scene_context(&context, &scene);
+    assert_eq!(scene_context.location, "Kyoto");
+    assert_eq!(scene_context.season.as_deref(), Some("winter"));
+    assert_eq!(
+        scene_context.season_fragment,
+        Some(SeasonPreset::Winter.fragment())
+    );
+    assert_eq!(scene_context.time_of_day.as_deref(), Some("noon"));
+    assert_eq!(scene_context.weather.as_deref(), Some("snow"));
+}
+
+#[test]
+fn file_names_and_manifest_use_configured_season_and_time_of_day() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "default_season = \"autumn\"\ndefault_time_of_day = \"evening\"\n",
+        )
+        .expect("write config");
+
+        let cli = Cli {
+            location: vec!["Hakone".to_string()],
+            ..Cli::default()
+        };
+        let context = run(cli.clone());
+        let name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        assert_eq!(name_context.base(), "mawaku-hakone-autumn-evening");
+        assert!(
+            name_context
+                .file_stem(1)
+                .starts_with("mawaku-hakone-autumn-evening-p1-")
+        );
+
+        let scene = FailedScene::from_cli(&cli, "Hakone");
+        assert!(scene.season.is_none());
+        let rendered = scene.resolved(&batch::recorded_scene_context(&context, &scene));
+        assert_eq!(rendered.season.as_deref(), Some("autumn"));
+        assert_eq!(rendered.time_of_day.as_deref(), Some("evening"));
+    });
+}
+
+#[test]
+fn batch_scenes_fall_back_to_configured_season_and_time_of_day() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "default_season = \"autumn\"\ndefault_time_of_day = \"evening\"\n",
+        )
+        .expect("write config");
+
+        let context = run(Cli {
+            location: vec!["Kyoto".to_string(), "Nara".to_string()],
+            batch: true,
+            ..Cli::default()
+        });
+        let recorded = FailedScene {
+            location: "Kyoto".to_string(),
+            ..FailedScene::default()
+        };
+        let scene_context = batch::recorded_scene_context(&context, &recorded);
+        assert_eq!(scene_context.season.as_deref(), Some("autumn"));
+        assert_eq!(scene_context.time_of_day.as_deref(), Some("evening"));
+
+        let flagged = FailedScene {
+            location: "Nara".to_string(),
+            season: Some("spring".to_string()),
+            time_of_day: Some("dawn".to_string()),
+            ..FailedScene::default()
+        };
+        let scene_context = batch::recorded_scene_context(&context, &flagged);
+        assert_eq!(scene_context.season.as_deref(), Some("spring"));
+        assert_eq!(scene_context.time_of_day.as_deref(), Some("dawn"));
+    });
+}
+
+#[test]
+fn batch_failures_are_recorded_for_retry() {
+    with_isolated_home(|home| {
+        let path = failures::log_path(home);
+        let scenes = vec![
+            FailedScene {
+                location: "Kyoto".to_string(),
+                ..FailedScene::default()
+            },
+            FailedScene {
+                location: "Oslo".to_string(),
+                ..FailedScene::default()
+            },
+        ];
+        let saved = SceneOutcome {
+            prompt: String::new(),
+            predictions: vec![PredictionOutcome::Saved {
+                path: PathBuf::from("a.png"),
+                dimensions: None,
+                sharp: None,
+            }],
+            streamed: false,
+            offline: false,
+        };
+
+        batch::record_failures(&path, &scenes, &[Some(saved), None]);
+        assert_eq!(
+            failures::load(&path).expect("load failures"),
+            vec![scenes[1].clone()]
+        );
+    });
+
+    let cli = Cli::try_parse_from(["mawaku", "--retry-failed"]).expect("parse retry");
+    assert!(cli.retry_failed);
+    assert!(Cli::try_parse_from(["mawaku", "--retry-failed", "--location", "Oslo"]).is_err());
+    assert!(Cli::try_parse_from(["mawaku", "--retry-failed", "--stdout-image"]).is_err());
+    assert!(Cli::try_parse_from(["mawaku", "--retry-failed", "--contact-sheet"]).is_ok());
+}
+
+#[test]
+fn batch_manifest_lists_each_scene_with_its_files_and_status() {
+    with_isolated_home(|home| {
+        let scenes = vec![
+            FailedScene {
+                location: "Kyoto".to_string(),
+                season: Some("autumn".to_string()),
+                ..FailedScene::default()
+            },
+            FailedScene {
+                location: "Oslo".to_string(),
+                ..FailedScene::default()
+            },
+        ];
+        let partial = SceneOutcome {
+            prompt: "Kyoto prompt".to_string(),
+            predictions: vec![
+                PredictionOutcome::Saved {
+                    path: PathBuf::from("kyoto-p1.png"),
+                    dimensions: None,
+                    sharp: None,
+                },
+                PredictionOutcome::MissingBytes,
+            ],
+            streamed: false,
+            offline: false,
+        };
+        let context = RunContext {
+            aspect_ratio: Some(AspectRatioArg::Auto),
+            ..RunContext::default()
+        };
+
+        let manifest = Manifest::new(
+            &GeminiClientOptions::default(),
+            &context,
+            &scenes,
+            &[Some(partial), None],
+            "2026-10-16T09:30:00Z".to_string(),
+        );
+        let path = manifest::write(home, &manifest).expect("write manifest");
+        assert_eq!(path, home.join(manifest::MANIFEST_FILE_NAME));
+
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&path).expect("read manifest"))
+                .expect("parse manifest");
+        assert_eq!(written["mawaku_version"], env!("CARGO_PKG_VERSION"));
+        assert_eq!(written["created_at"], "2026-10-16T09:30:00Z");
+        assert_eq!(written["parameters"]["aspect_ratio"], "auto");
+        assert_eq!(
+            written["parameters"]["image_model"],
+            mawaku_gemini::DEFAULT_IMG_MODEL_VERSION
+        );
+        let kyoto = &written["scenes"][0];
+        assert_eq!(kyoto["location"], "Kyoto");
+        assert_eq!(kyoto["season"], "autumn");
+        assert_eq!(kyoto["status"], "partial");
+        assert_eq!(kyoto["prompt"], "Kyoto prompt");
+        assert_eq!(kyoto["files"], serde_json::json!(["kyoto-p1.png"]));
+        assert_eq!(kyoto["failed_predictions"], 1);
+        assert_eq!(written["scenes"][1]["status"], "failed");
+        assert!(written["scenes"][1]["prompt"].is_null());
+    });
+}
+
+#[test]
+fn theme_is_appended_to_the_prompt_and_file_names() {
+    let cli = Cli::try_parse_from([
+        "mawaku",
+        "--location",
+        "Kyoto",
+        "--season",
+        "autumn",
+        "--theme",
+        "japandi",
+    ])
+    .expect("parse theme");
+    assert_eq!(cli.theme, Some(Theme::Japandi));
+    assert!(Cli::try_parse_from(["mawaku", "--theme", "baroque"]).is_err());
+
+    with_isolated_home(|_| {
+        let context = run(cli);
+        let name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        assert_eq!(name_context.base(), "mawaku-kyoto-autumn-japandi");
+
+        let instructions = build_general_instructions(&context);
+        assert!(instructions.contains("Kyoto"));
+        assert!(instructions.ends_with(&Theme::Japandi.directive()));
+
+        let prompt = build_scene_prompt(&context, None, None);
+        assert!(prompt.contains(&Theme::Japandi.directive()));
+        assert!(prompt.contains("Season: autumn"));
+    });
+}
+
+#[test]
+fn cached_images_are_reused_for_an_identical_prompt() {
+    with_isolated_home(|home| {
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(home.join("outputs")),
+            image_cache: Some(ImageCache::new(home.join("image-cache"))),
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let fake = FakeGemini::new(
+            r#"{"predictions": [{"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"}]}"#,
+        );
+
+        let first = render_scene(&fake, &fake, &context, &image_name_context, false);
+        let second = render_scene(&fake, &fake, &context, &image_name_context, false);
+        assert_eq!(fake.image_prompts.lock().unwrap().len(), 1);
+        let (first_paths, second_paths) = (first.saved_paths(), second.saved_paths());
+        assert_eq!(second_paths.len(), 1);
+        assert_ne!(first_paths, second_paths);
+        assert_eq!(
+            fs::read(&second_paths[0]).expect("read reused image"),
+            b"hello"
+        );
+
+        let refreshed = RunContext {
+            refresh_cache: true,
+            ..context
+        };
+        render_scene(&fake, &fake, &refreshed, &image_name_context, false);
+        assert_eq!(fake.image_prompts.lock().unwrap().len(), 2);
+    });
+
+    let cli = Cli::try_parse_from(["mawaku", "--cache-images"]).expect("parse cache images");
+    assert!(cli.cache_images);
+}
+
+#[test]
+fn also_avatar_requests_a_square_image_with_an_avatar_name() {
+    with_isolated_home(|home| {
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(home.join("outputs")),
+            aspect_ratio: Some(AspectRatioArg::Fixed("16:9")),
+            also_avatar: true,
+            ..RunContext::default()
+        };
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let fake = FakeGemini::new(
+            r#"{"predictions": [{"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"}]}"#,
+        );
+
+        let outcome = render_scene(&fake, &fake, &context, &image_name_context, false);
+
+        let requests = fake.image_prompts.lock().unwrap().clone();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].1.as_deref(), Some("16:9"));
+        assert!(!requests[0].0.contains(AVATAR_DIRECTIVE));
+        assert_eq!(requests[1].1.as_deref(), Some(AVATAR_ASPECT_RATIO));
+        assert!(requests[1].0.ends_with(AVATAR_DIRECTIVE));
+
+        let names: Vec<String> = outcome
+            .saved_paths()
+            .iter()
+            .filter_map(|path| path.file_name())
+            .map(|name| name.to_string_lossy().into_owned())
+            .collect();
+        assert_eq!(names.len(), 2);
+        assert!(!names[0].contains("-avatar-"));
+        assert!(names[1].starts_with("mawaku-hakone-avatar-p1-"));
+    });
+
+    let error = Cli::try_parse_from(["mawaku", "--also-avatar", "--stdout-image"])
+        .expect_err("avatar needs files");
+    assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+}
+
+#[test]
+fn seed_sweep_parses_inclusive_ranges() {
+    let sweep: SeedSweep = "3..5".parse().expect("parse sweep");
+    assert_eq!(sweep.seeds().collect::<Vec<_>>(), vec![3, 4, 5]);
+    assert_eq!(sweep.len(), 3);
+    assert_eq!(sweep.to_string(), "3..5");
+    assert_eq!(" 7 .. 7 ".parse::<SeedSweep>().map(SeedSweep::len), Ok(1));
+
+    for invalid in ["5..3", "1-5", "a..b", "..5", "1..-2"] {
+        assert!(invalid.parse::<SeedSweep>().is_err(), "{invalid}");
+    }
+}
+
+#[test]
+fn seed_sweep_is_capped_unless_the_limit_is_raised() {
+    let sweep: SeedSweep = "1..20".parse().expect("parse sweep");
+    let error = sweep
+        .check_limit(DEFAULT_SEED_SWEEP_LIMIT)
+        .expect_err("sweep over the default limit");
+    assert!(error.contains("--seed-sweep-limit 20"), "{error}");
+    assert_eq!(sweep.check_limit(20), Ok(()));
+
+    let cli = Cli::try_parse_from(["mawaku", "--seed-sweep", "1..3"]).expect("parse sweep flag");
+    assert_eq!(cli.seed_sweep_limit, DEFAULT_SEED_SWEEP_LIMIT);
+    let error = Cli::try_parse_from([
+        "mawaku",
+        "--location",
+        "Hakone",
+        "--batch",
+        "--seed-sweep",
+        "1..3",
+    ])
+    .expect_err("sweep renders a single scene");
+    assert_eq!(error.kind(), clap::error::ErrorKind::ArgumentConflict);
+}
+
+#[test]
+fn seed_sweep_reuses_one_prompt_and_names_files_by_seed() {
+    with_isolated_home(|home| {
+        let context = RunContext {
+            location: "Hakone".to_string(),
+            locations: vec!["Hakone".to_string()],
+            image_output_dir: Some(home.join("outputs")),
+            ..RunContext::default()
+        };
+        let sweep = "41..43".parse().expect("parse sweep");
+        let image_name_context = image_name_context_for(&context, DEFAULT_FILE_NAME_PREFIX);
+        let fake = FakeGemini::new(
+            r#"{"predictions": [{"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"}]}"#,
+        );
+
+        let (prompt, outcomes) =
+            render_seed_sweep(&fake, &fake, &context, &image_name_context, sweep);
+
+        assert_eq!(*fake.seeds.lock().unwrap(), vec![41, 42, 43]);
+        let requests = fake.image_prompts.lock().unwrap().clone();
+        assert_eq!(requests.len(), 3);
+        assert!(requests.iter().all(|(sent, _)| *sent == prompt));
+        let names: Vec<String> = outcomes
+            .iter()
+            .flat_map(SceneOutcome::saved_paths)
+            .filter_map(|path| {
+                path.file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+            })
+            .collect();
+        assert_eq!(names.len(), 3);
+        for (name, seed) in names.iter().zip([41, 42, 43]) {
+            assert!(
+                name.starts_with(&format!("mawaku-hakone-seed{seed}-p1-")),
+                "{name}"
+            );
+        }
+    });
+}
+
+#[test]
+fn seeded_requests_get_their_own_image_cache_key() {
+    let context = RunContext::default();
+    let seeded = RunContext {
+        seed: Some(7),
+        ..RunContext::default()
+    };
+    assert_ne!(
+        cache::image_key(&context, "prompt", Some("16:9")),
+        cache::image_key(&seeded, "prompt", Some("16:9"))
+    );
+    assert_eq!(
+        cache::image_key(&context, "prompt", Some("16:9")),
+        ImageCache::key("prompt", &[Some("16:9"), None, None, None])
+    );
+}
+
+#[test]
+fn estimate_counts_requests_and_prices_them_without_calling_gemini() {
+    let scene = RunContext {
+        location: "Hakone".to_string(),
+        aspect_ratio: Some(AspectRatioArg::Fixed("16:9")),
+        ..RunContext::default()
+    };
+    let avatar_scene = RunContext {
+        also_avatar: true,
+        skip_description: true,
+        ..scene.clone()
+    };
+    let options = GeminiClientOptions::default();
+
+    let estimate = CostEstimate::new(&PriceTable::default(), &options, &[scene, avatar_scene]);
+
+    assert_eq!(estimate.image_requests, 3);
+    assert_eq!(estimate.images, 9);
+    assert_eq!(estimate.text_requests, 1);
+    let image_cost = 3.0 * estimate_cost(&options.image_model, options.sample_count, Some("16:9"));
+    let text_cost = PriceTable::default()
+        .text_cost(
+            &options.text_model,
+            DESCRIPTION_INPUT_TOKENS_ESTIMATE,
+            DESCRIPTION_OUTPUT_TOKENS_ESTIMATE,
+        )
+        .expect("text price");
+    assert!((estimate.cost - (image_cost + text_cost)).abs() < 1e-9);
+    assert!(estimate.unpriced_models.is_empty());
+    assert_eq!(
+        estimate.lines()[0],
+        format!("Estimated cost: {:.2} USD", estimate.cost)
+    );
+}
+
+#[test]
+fn estimate_skips_the_description_without_a_location() {
+    let scene = RunContext {
+        aspect_ratio: Some(AspectRatioArg::Fixed("16:9")),
+        ..RunContext::default()
+    };
+    let options = GeminiClientOptions::default();
+
+    let estimate = CostEstimate::new(&PriceTable::default(), &options, &[scene]);
+
+    assert_eq!(estimate.image_requests, 1);
+    assert_eq!(estimate.text_requests, 0);
+    let image_cost = estimate_cost(&options.image_model, options.sample_count, Some("16:9"));
+    assert!((estimate.cost - image_cost).abs() < 1e-9);
+}
+
+#[test]
+fn estimate_flags_models_without_a_price() {
+    let scene = RunContext {
+        upscale: Some(UpscaleFactor::X2),
+        seed_sweep: Some("1..2".parse().expect("parse sweep")),
+        skip_description: true,
+        ..RunContext::default()
+    };
+    let options = GeminiClientOptions::default();
+
+    let estimate = CostEstimate::new(&PriceTable::default(), &options, &[scene]);
+
+    // Two seeds, each one generation plus one upscale per image.
+    assert_eq!(
+        estimate.image_requests,
+        2 * (1 + u64::from(options.sample_count))
+    );
+    assert!(estimate.unpriced_models.contains(&options.upscale_model));
+    assert!(
+        estimate
+            .lines()
+            .iter()
+            .any(|line| line.contains(&options.upscale_model))
+    );
+    assert!(Cli::try_parse_from(["mawaku", "--estimate", "--prompt-only"]).is_err());
+}
+
+#[test]
+fn interactive_asks_only_for_missing_scene_flags() {
+    let mut cli = Cli::try_parse_from(["mawaku", "--interactive", "--time-of-day", "dusk"])
+        .expect("parse interactive");
+    let mut input = io::Cursor::new("\n  Hakone  \n\n");
+    let mut output = Vec::new();
+
+    interactive::fill_missing(&mut cli, &mut input, &mut output, true).expect("answers");
+
+    assert_eq!(cli.location, vec!["Hakone".to_string()]);
+    assert_eq!(cli.season, None);
+    assert_eq!(cli.time_of_day.as_deref(), Some("dusk"));
+    let transcript = String::from_utf8(output).expect("utf-8 prompts");
+    assert_eq!(
+        transcript,
+        "Location: A location is required.\nLocation: Season (optional): "
+    );
+}
+
+#[test]
+fn interactive_stops_when_input_ends() {
+    let mut cli = Cli::default();
+    let mut input = io::Cursor::new("");
+    let error = interactive::fill_missing(&mut cli, &mut input, &mut Vec::new(), true)
+        .expect_err("no location");
+    assert!(error.contains("no answer"), "{error}");
+    assert!(cli.location.is_empty());
+
+    let mut cli = Cli {
+        location: vec!["Oslo".to_string()],
+        season_preset: Some(SeasonPreset::Winter),
+        time_of_day: Some("night".to_string()),
+        ..Cli::default()
+    };
+    let mut output = Vec::new();
+    interactive::fill_missing(&mut cli, &mut io::Cursor::new(""), &mut output, true)
+        .expect("nothing to ask");
+    assert!(output.is_empty());
 }
 
 #[test]
-fn build_structured_prompt_falls_back_to_placeholders() {
-    let prompt = build_structured_prompt("Guide the render artistically.", None, None, Some("   "));
+fn interactive_needs_a_terminal_only_when_something_is_missing() {
+    let mut cli = Cli::try_parse_from([
+        "mawaku",
+        "--interactive",
+        "--location",
+        "Oslo",
+        "--season",
+        "winter",
+        "--time-of-day",
+        "night",
+    ])
+    .expect("parse interactive");
+    let mut output = Vec::new();
+    interactive::fill_missing(&mut cli, &mut io::Cursor::new(""), &mut output, false)
+        .expect("all fields provided");
+    assert!(output.is_empty());
 
-    assert!(prompt.contains("Guide the render artistically."));
-    assert!(prompt.contains("Ambiance: Unspecified"));
-    assert!(prompt.contains("Items: Unspecified"));
-    assert!(prompt.contains("Keywords: Unspecified"));
-    assert!(prompt.contains("Season: Unspecified"));
-    assert!(prompt.contains("Time of day: Unspecified"));
+    let mut cli = Cli::try_parse_from(["mawaku", "--interactive", "--location", "Oslo"])
+        .expect("parse interactive");
+    let mut output = Vec::new();
+    let error = interactive::fill_missing(&mut cli, &mut io::Cursor::new("\n"), &mut output, false)
+        .expect_err("season is missing");
+    assert!(error.contains("needs a terminal"), "{error}");
+    assert!(output.is_empty());
 }
@@ -1,9 +1,11 @@
 use std::{
+    collections::BTreeMap,
     fs,
     path::{Path, PathBuf},
 };
 
 use directories::BaseDirs;
+use mawaku_utils::{DEFAULT_FILE_NAME_PREFIX, prefix_slug, trimmed_or_none};
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use toml::Value;
@@ -20,7 +22,36 @@ Camera angle: professional webcam-style vantage facing into the room while hover
 Camera height: slightly above seated eye level, matching a real highly positioned webcam’s perspective. \
 Camera location: prefer a corner vantage that reveals depth. \
 The scene should feel like the believable background behind someone on a video call.";
+/// Name of the environment variable holding the Gemini API key, not the key
+/// itself. A stored key lives in `[gemini_api]` as `api_key`.
 pub const DEFAULT_GEMINI_API_KEY_ENV_VAR: &str = "GEMINI_API_KEY";
+pub const DEFAULT_DESCRIPTION_CACHE_TTL_SECS: u64 = 7 * 24 * 60 * 60;
+/// Saved images smaller than this are reported as likely failed generations.
+pub const DEFAULT_MIN_IMAGE_BYTES: usize = 10 * 1024;
+/// Aspect ratios accepted by Imagen.
+pub const SUPPORTED_ASPECT_RATIOS: &[&str] = &["1:1", "3:4", "4:3", "9:16", "16:9"];
+/// Spinner styles accepted by the `spinner` setting and `--spinner`.
+pub const SUPPORTED_SPINNER_STYLES: &[&str] = &["ascii", "dots", "none"];
+/// Harm categories accepted as keys of `gemini_api.safety_settings`.
+pub const SUPPORTED_HARM_CATEGORIES: &[&str] = &[
+    "harassment",
+    "hate_speech",
+    "sexually_explicit",
+    "dangerous_content",
+];
+/// Thresholds accepted as values of `gemini_api.safety_settings` and by
+/// `--safety-threshold`.
+pub const SUPPORTED_SAFETY_THRESHOLDS: &[&str] = &[
+    "block_none",
+    "block_only_high",
+    "block_medium_and_above",
+    "block_low_and_above",
+    "off",
+];
+/// Prefixes of the model families usable for `gemini_api.image_model`.
+pub const KNOWN_IMAGE_MODEL_PREFIXES: &[&str] = &["imagen-"];
+/// Prefixes of the model families usable for `gemini_api.text_model`.
+pub const KNOWN_TEXT_MODEL_PREFIXES: &[&str] = &["gemini-"];
 
 #[derive(Debug, Error)]
 pub enum ConfigError {
@@ -32,21 +63,199 @@ pub enum ConfigError {
     Deserialize(#[from] toml::de::Error),
     #[error("failed to serialize configuration: {0}")]
     Serialize(#[from] toml::ser::Error),
+    #[error("invalid {key} `{value}` in configuration (expected {expected})")]
+    InvalidValue {
+        key: &'static str,
+        value: String,
+        expected: String,
+    },
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct Config {
+    /// Replaces [`DEFAULT_PROMPT`] as the base art direction when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prompt_template: Option<String>,
     pub gemini_api: GeminiApiConfig,
     /// Stored at the root of `config.toml` for backward compatibility with
     /// earlier Mawaku versions that only understood this top-level key.
     pub image_output_dir: String,
+    /// Save images under `<image_output_dir>/YYYY/MM/DD/` instead of directly
+    /// in `image_output_dir`.
+    pub organize_by_date: bool,
+    /// First segment of every saved file name, so several projects can share
+    /// an output directory without their images intermingling.
+    pub file_name_prefix: String,
+    /// Progress spinner shown while an image generates: `ascii`, `dots`, or
+    /// `none`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub spinner: Option<String>,
+    /// Season used when `--season` and `--season-preset` are not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_season: Option<String>,
+    /// Time of day used when `--time-of-day` is not given.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_time_of_day: Option<String>,
+    pub image: ImageConfig,
+    pub cache: CacheConfig,
+    /// Short names expanded to full location strings, e.g.
+    /// `hakone = "Hakone, Japan, near the hot springs"`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aliases: BTreeMap<String, String>,
+    /// Price overrides for `--estimate`, for when Google's pricing changes.
+    #[serde(skip_serializing_if = "PricingConfig::is_empty")]
+    pub pricing: PricingConfig,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImageConfig {
+    /// Things generated images should avoid, e.g. `["logos", "watermarks"]`.
+    pub negative_keywords: Vec<String>,
+    /// Warn when a saved image is smaller than `min_image_bytes` or has
+    /// degenerate dimensions.
+    pub check_payloads: bool,
+    pub min_image_bytes: usize,
+}
+
+impl Default for ImageConfig {
+    fn default() -> Self {
+        Self {
+            negative_keywords: Vec::new(),
+            check_payloads: true,
+            min_image_bytes: DEFAULT_MIN_IMAGE_BYTES,
+        }
+    }
+}
+
+impl ImageConfig {
+    /// The non-blank negative keywords joined into a single negative prompt,
+    /// or `None` when there are none.
+    pub fn negative_prompt(&self) -> Option<String> {
+        let keywords: Vec<&str> = self
+            .negative_keywords
+            .iter()
+            .map(|keyword| keyword.trim())
+            .filter(|keyword| !keyword.is_empty())
+            .collect();
+        (!keywords.is_empty()).then(|| keywords.join(", "))
+    }
+
+    /// The byte threshold for payload checks, or `None` when they are off.
+    pub fn payload_check_threshold(&self) -> Option<usize> {
+        self.check_payloads.then_some(self.min_image_bytes)
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CacheConfig {
+    /// How long a cached place description stays valid. `0` disables the cache.
+    pub description_ttl_secs: u64,
+    /// Reuse the images generated earlier for an identical prompt and
+    /// parameters instead of calling Imagen again.
+    pub images: bool,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            description_ttl_secs: DEFAULT_DESCRIPTION_CACHE_TTL_SECS,
+            images: false,
+        }
+    }
+}
+
+/// Prices replacing the built-in table used by `--estimate`. Models left out
+/// keep their built-in price.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PricingConfig {
+    /// Label printed with estimates; prices are not converted. Defaults to
+    /// `USD`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub currency: Option<String>,
+    /// Per image model, e.g.
+    /// `"imagen-4.0-generate-001" = { per_image = 0.04 }`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub images: BTreeMap<String, ImagePriceConfig>,
+    /// Per text model, e.g.
+    /// `"gemini-2.5-flash" = { input_per_million = 0.3, output_per_million = 2.5 }`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub text: BTreeMap<String, TokenPriceConfig>,
+}
+
+impl PricingConfig {
+    pub fn is_empty(&self) -> bool {
+        self.currency.is_none() && self.images.is_empty() && self.text.is_empty()
+    }
+
+    fn prices(&self) -> impl Iterator<Item = f64> + '_ {
+        let images = self.images.values().flat_map(|price| {
+            std::iter::once(price.per_image).chain(price.aspect_ratios.values().copied())
+        });
+        let text = self
+            .text
+            .values()
+            .flat_map(|price| [price.input_per_million, price.output_per_million]);
+        images.chain(text)
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ImagePriceConfig {
+    pub per_image: f64,
+    /// Prices for aspect ratios billed differently from `per_image`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub aspect_ratios: BTreeMap<String, f64>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TokenPriceConfig {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(default)]
 pub struct GeminiApiConfig {
     pub api_key_env_var: String,
+    /// API key stored with `--set-gemini-api-key`. Used instead of the
+    /// environment variable when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_key: Option<String>,
+    /// Optional proxy URL for Gemini requests. When unset, the standard
+    /// `HTTPS_PROXY` environment variable is honoured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub proxy: Option<String>,
+    /// Extra HTTP headers attached to every Gemini request.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub extra_headers: BTreeMap<String, String>,
+    /// Imagen model used for image generation instead of the built-in default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image_model: Option<String>,
+    /// Gemini model used for place descriptions instead of the built-in default.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub text_model: Option<String>,
+    /// Default aspect ratio of generated images, or `auto`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub aspect_ratio: Option<String>,
+    /// Space Gemini requests to stay under this many per minute, e.g. to
+    /// keep long batches within quota. Unset or `0` means unlimited.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+    /// Cap on the tokens Gemini may spend on each text response, such as a
+    /// place description. Unset or `0` leaves the model's limit.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_output_tokens: Option<u32>,
+    /// Safety threshold per harm category for place descriptions, e.g.
+    /// `dangerous_content = "block_medium_and_above"`. Categories left out
+    /// use `block_only_high`.
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub safety_settings: BTreeMap<String, String>,
 }
 
 impl GeminiApiConfig {
@@ -57,13 +266,151 @@ impl GeminiApiConfig {
             self.api_key_env_var.as_str()
         }
     }
+
+    /// The stored API key, ignoring a blank value.
+    pub fn stored_api_key(&self) -> Option<&str> {
+        self.api_key
+            .as_deref()
+            .map(str::trim)
+            .filter(|key| !key.is_empty())
+    }
+}
+
+impl Config {
+    /// Check values that must come from a known set, so typos surface at
+    /// startup rather than as an opaque API error.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`ConfigError::InvalidValue`] naming the first offending key.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let gemini_api = &self.gemini_api;
+        if let Some(ratio) = gemini_api.aspect_ratio.as_deref() {
+            let ratio = ratio.trim();
+            if !ratio.eq_ignore_ascii_case("auto") && !SUPPORTED_ASPECT_RATIOS.contains(&ratio) {
+                return Err(ConfigError::InvalidValue {
+                    key: "gemini_api.aspect_ratio",
+                    value: ratio.to_string(),
+                    expected: format!("auto or one of {}", SUPPORTED_ASPECT_RATIOS.join(", ")),
+                });
+            }
+        }
+        validate_model(
+            "gemini_api.image_model",
+            gemini_api.image_model.as_deref(),
+            KNOWN_IMAGE_MODEL_PREFIXES,
+        )?;
+        validate_model(
+            "gemini_api.text_model",
+            gemini_api.text_model.as_deref(),
+            KNOWN_TEXT_MODEL_PREFIXES,
+        )?;
+        for (category, threshold) in &gemini_api.safety_settings {
+            let name = choice_name(category);
+            if !SUPPORTED_HARM_CATEGORIES
+                .contains(&name.strip_prefix("harm_category_").unwrap_or(&name))
+            {
+                return Err(ConfigError::InvalidValue {
+                    key: "gemini_api.safety_settings",
+                    value: category.clone(),
+                    expected: format!("a category among {}", SUPPORTED_HARM_CATEGORIES.join(", ")),
+                });
+            }
+            if !SUPPORTED_SAFETY_THRESHOLDS.contains(&choice_name(threshold).as_str()) {
+                return Err(ConfigError::InvalidValue {
+                    key: "gemini_api.safety_settings",
+                    value: threshold.clone(),
+                    expected: format!("one of {}", SUPPORTED_SAFETY_THRESHOLDS.join(", ")),
+                });
+            }
+        }
+        if let Some(style) = self.spinner.as_deref() {
+            let style = style.trim();
+            if !SUPPORTED_SPINNER_STYLES
+                .iter()
+                .any(|supported| supported.eq_ignore_ascii_case(style))
+            {
+                return Err(ConfigError::InvalidValue {
+                    key: "spinner",
+                    value: style.to_string(),
+                    expected: format!("one of {}", SUPPORTED_SPINNER_STYLES.join(", ")),
+                });
+            }
+        }
+        if let Some(price) = self
+            .pricing
+            .prices()
+            .find(|price| !price.is_finite() || *price < 0.0)
+        {
+            return Err(ConfigError::InvalidValue {
+                key: "pricing",
+                value: price.to_string(),
+                expected: "a price of zero or more".to_string(),
+            });
+        }
+        if prefix_slug(&self.file_name_prefix).is_none() {
+            return Err(ConfigError::InvalidValue {
+                key: "file_name_prefix",
+                value: self.file_name_prefix.clone(),
+                expected: "a prefix containing letters or digits".to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    /// The file name prefix as it appears in file names: slugified into one
+    /// word by [`prefix_slug`], or
+    /// [`DEFAULT_FILE_NAME_PREFIX`] when it has no usable characters.
+    pub fn file_name_prefix(&self) -> String {
+        prefix_slug(&self.file_name_prefix).unwrap_or_else(|| DEFAULT_FILE_NAME_PREFIX.to_string())
+    }
+
+    /// The configured prompt template, or [`DEFAULT_PROMPT`] when unset or blank.
+    pub fn prompt_template(&self) -> &str {
+        match self.prompt_template.as_deref() {
+            Some(template) if !template.trim().is_empty() => template,
+            _ => DEFAULT_PROMPT,
+        }
+    }
+
+    /// The configured default season, trimmed, or `None` when unset or blank.
+    pub fn default_season(&self) -> Option<&str> {
+        trimmed_or_none(self.default_season.as_deref())
+    }
+
+    /// The configured default time of day, trimmed, or `None` when unset or
+    /// blank.
+    pub fn default_time_of_day(&self) -> Option<&str> {
+        trimmed_or_none(self.default_time_of_day.as_deref())
+    }
+
+    /// The location stored under the alias `name`, matched ignoring case, or
+    /// `None` when there is no such alias or its location is blank.
+    pub fn alias(&self, name: &str) -> Option<&str> {
+        let name = name.trim();
+        self.aliases
+            .iter()
+            .find(|(alias, _)| alias.trim().eq_ignore_ascii_case(name))
+            .map(|(_, location)| location.trim())
+            .filter(|location| !location.is_empty())
+    }
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
+            prompt_template: None,
             gemini_api: GeminiApiConfig::default(),
             image_output_dir: default_image_output_dir().unwrap_or_else(|_| ".".to_string()),
+            organize_by_date: false,
+            file_name_prefix: DEFAULT_FILE_NAME_PREFIX.to_string(),
+            spinner: None,
+            default_season: None,
+            default_time_of_day: None,
+            image: ImageConfig::default(),
+            cache: CacheConfig::default(),
+            aliases: BTreeMap::new(),
+            pricing: PricingConfig::default(),
         }
     }
 }
@@ -72,6 +419,15 @@ impl Default for GeminiApiConfig {
     fn default() -> Self {
         Self {
             api_key_env_var: DEFAULT_GEMINI_API_KEY_ENV_VAR.to_string(),
+            api_key: None,
+            proxy: None,
+            extra_headers: BTreeMap::new(),
+            safety_settings: BTreeMap::new(),
+            image_model: None,
+            text_model: None,
+            aspect_ratio: None,
+            requests_per_minute: None,
+            max_output_tokens: None,
         }
     }
 }
@@ -86,7 +442,11 @@ pub fn load_or_init() -> Result<LoadOutcome, ConfigError> {
         let mut should_rewrite = false;
 
         if let Value::Table(ref mut table) = value {
-            if table.remove("default_prompt").is_some() {
+            // Earlier releases stored the template as `default_prompt`.
+            if let Some(legacy_prompt) = table.remove("default_prompt") {
+                if !table.contains_key("prompt_template") {
+                    table.insert("prompt_template".to_string(), legacy_prompt);
+                }
                 should_rewrite = true;
             }
 
@@ -127,6 +487,25 @@ pub fn load_or_init() -> Result<LoadOutcome, ConfigError> {
                     should_rewrite = true;
                 }
             }
+
+            if !table.contains_key("file_name_prefix") {
+                table.insert(
+                    "file_name_prefix".to_string(),
+                    Value::String(DEFAULT_FILE_NAME_PREFIX.to_string()),
+                );
+                should_rewrite = true;
+            }
+
+            // Write the keyword list out so users can see where to add entries.
+            let image = table
+                .entry("image")
+                .or_insert_with(|| Value::Table(Default::default()));
+            if let Value::Table(image) = image
+                && !image.contains_key("negative_keywords")
+            {
+                image.insert("negative_keywords".to_string(), Value::Array(Vec::new()));
+                should_rewrite = true;
+            }
         }
 
         let is_image_dir_missing_or_invalid = match value.get("image_output_dir") {
@@ -136,6 +515,7 @@ pub fn load_or_init() -> Result<LoadOutcome, ConfigError> {
         };
 
         let mut config: Config = value.try_into()?;
+        config.validate()?;
         let expected_dir = default_image_output_dir_for(&path);
 
         let empty_field = config.image_output_dir.trim().is_empty();
@@ -149,10 +529,25 @@ pub fn load_or_init() -> Result<LoadOutcome, ConfigError> {
             save(&config, &path)?;
         }
 
+        // Expand only the in-memory value so the file keeps the portable form.
+        let home = BaseDirs::new().map(|dirs| dirs.home_dir().to_path_buf());
+        let (image_output_dir, undefined) =
+            expand_path_vars(&config.image_output_dir, home.as_deref(), |name| {
+                std::env::var(name).ok()
+            });
+        config.image_output_dir = image_output_dir;
+        let warnings = undefined
+            .into_iter()
+            .map(|name| {
+                format!("image_output_dir references undefined `{name}`; it is used as written.")
+            })
+            .collect();
+
         Ok(LoadOutcome {
             config,
             path,
             created: false,
+            warnings,
         })
     } else {
         ensure_parent_exists(&path)?;
@@ -165,23 +560,163 @@ pub fn load_or_init() -> Result<LoadOutcome, ConfigError> {
             config,
             path,
             created: true,
+            warnings: Vec::new(),
         })
     }
 }
 
+/// Apply `change` to the configuration stored at `path` and save it.
+///
+/// The file is re-read rather than taking a loaded [`Config`], because
+/// [`load_or_init`] expands `image_output_dir` in memory only and saving that
+/// value would replace the portable form.
+pub fn update(path: &Path, change: impl FnOnce(&mut Config)) -> Result<(), ConfigError> {
+    let contents = fs::read_to_string(path)?;
+    let mut config: Config = toml::from_str(&contents)?;
+    change(&mut config);
+    save(&config, path)
+}
+
 /// Persist the given Mawaku configuration to disk at the provided path.
+///
+/// On Unix, a file holding a stored API key is readable by its owner only.
 pub fn save(config: &Config, path: &Path) -> Result<(), ConfigError> {
     ensure_parent_exists(path)?;
     let serialized = toml::to_string_pretty(config)?;
-    fs::write(path, serialized)?;
+    if config.gemini_api.stored_api_key().is_some() {
+        write_private(path, &serialized)?;
+    } else {
+        fs::write(path, serialized)?;
+    }
     Ok(())
 }
 
+/// Write `contents` to `path` with `0600` permissions, tightening them on an
+/// existing file before anything is written.
+#[cfg(unix)]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    use std::io::Write;
+    use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(true)
+        .mode(0o600)
+        .open(path)?;
+    file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    file.write_all(contents.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, contents: &str) -> std::io::Result<()> {
+    fs::write(path, contents)
+}
+
 #[derive(Debug)]
 pub struct LoadOutcome {
     pub config: Config,
     pub path: PathBuf,
     pub created: bool,
+    /// Problems that did not stop the config from loading, such as undefined
+    /// variables in `image_output_dir`.
+    pub warnings: Vec<String>,
+}
+
+/// Expand a leading `~` to `home` and `$VAR` or `${VAR}` references through
+/// `lookup`. References that cannot be resolved are kept as written and their
+/// names (or `~`) returned alongside the expanded text.
+fn expand_path_vars(
+    input: &str,
+    home: Option<&Path>,
+    lookup: impl Fn(&str) -> Option<String>,
+) -> (String, Vec<String>) {
+    let mut output = String::with_capacity(input.len());
+    let mut undefined = Vec::new();
+
+    let mut rest = input;
+    if rest == "~" || rest.starts_with("~/") || rest.starts_with("~\\") {
+        match home {
+            Some(home) => {
+                output.push_str(&home.to_string_lossy());
+                rest = &rest[1..];
+            }
+            None => undefined.push("~".to_string()),
+        }
+    }
+
+    while let Some(dollar) = rest.find('$') {
+        output.push_str(&rest[..dollar]);
+        let after = &rest[dollar + 1..];
+        let (name, reference_len) = match after.strip_prefix('{') {
+            Some(braced) => match braced.find('}') {
+                Some(close) => (&braced[..close], close + 2),
+                None => ("", 0),
+            },
+            None => {
+                let len = after
+                    .find(|ch: char| !(ch.is_ascii_alphanumeric() || ch == '_'))
+                    .unwrap_or(after.len());
+                (&after[..len], len)
+            }
+        };
+        let is_name = name
+            .chars()
+            .next()
+            .is_some_and(|first| first.is_ascii_alphabetic() || first == '_')
+            && name
+                .chars()
+                .all(|ch| ch.is_ascii_alphanumeric() || ch == '_');
+        if !is_name {
+            output.push('$');
+            rest = after;
+            continue;
+        }
+
+        let reference = &rest[dollar..dollar + 1 + reference_len];
+        match lookup(name) {
+            Some(value) => output.push_str(&value),
+            None => {
+                output.push_str(reference);
+                if !undefined.iter().any(|known| known == name) {
+                    undefined.push(name.to_string());
+                }
+            }
+        }
+        rest = &after[reference_len..];
+    }
+    output.push_str(rest);
+
+    (output, undefined)
+}
+
+/// `value` lowercased with hyphens as underscores, so `BLOCK-ONLY-HIGH` and
+/// `block_only_high` name the same choice.
+fn choice_name(value: &str) -> String {
+    value.trim().to_ascii_lowercase().replace('-', "_")
+}
+
+fn validate_model(
+    key: &'static str,
+    model: Option<&str>,
+    prefixes: &[&str],
+) -> Result<(), ConfigError> {
+    let Some(model) = model.map(str::trim) else {
+        return Ok(());
+    };
+    if prefixes.iter().any(|prefix| model.starts_with(prefix)) {
+        return Ok(());
+    }
+    let expected = prefixes
+        .iter()
+        .map(|prefix| format!("{prefix}*"))
+        .collect::<Vec<_>>()
+        .join(" or ");
+    Err(ConfigError::InvalidValue {
+        key,
+        value: model.to_string(),
+        expected: format!("a model named {expected}"),
+    })
 }
 
 fn ensure_parent_exists(path: &Path) -> Result<(), ConfigError> {
@@ -191,6 +726,16 @@ fn ensure_parent_exists(path: &Path) -> Result<(), ConfigError> {
     Ok(())
 }
 
+/// Directory used for cached API responses such as place descriptions.
+pub fn cache_directory() -> Result<PathBuf, ConfigError> {
+    Ok(config_directory()?.join("cache"))
+}
+
+/// File recording the scene flags of the previous run.
+pub fn last_run_path() -> Result<PathBuf, ConfigError> {
+    Ok(config_directory()?.join("last_run.json"))
+}
+
 fn config_file_path() -> Result<PathBuf, ConfigError> {
     Ok(config_directory()?.join("config.toml"))
 }
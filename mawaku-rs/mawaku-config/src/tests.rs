@@ -24,6 +24,23 @@ fn config_default_sets_image_output_dir() {
     assert!(!config.image_output_dir.trim().is_empty());
 }
 
+#[test]
+fn config_default_sets_description_cache_ttl() {
+    let config = Config::default();
+    assert_eq!(
+        config.cache.description_ttl_secs,
+        DEFAULT_DESCRIPTION_CACHE_TTL_SECS
+    );
+}
+
+#[test]
+fn cache_directory_lives_under_config_directory() {
+    with_isolated_home(|home| {
+        let dir = cache_directory().expect("resolve cache directory");
+        assert_eq!(dir, home.join(".mawaku").join("cache"));
+    });
+}
+
 #[test]
 fn load_or_init_creates_file_with_default_gemini_api_env_var() {
     with_isolated_home(|_| {
@@ -73,6 +90,8 @@ gemini_api_key = "super-secret"
         let contents = fs::read_to_string(&path).expect("read config");
         assert!(contents.contains(&format!("image_output_dir = \"{expected_dir}\"")));
         assert!(!contents.contains("default_prompt"));
+        assert!(contents.contains("prompt_template = \"Test\""));
+        assert_eq!(outcome.config.prompt_template(), "Test");
         assert!(!contents.contains("gemini_api_key"));
         assert!(contents.contains("[gemini_api]"));
     });
@@ -106,6 +125,190 @@ staging = "CUSTOM_GEMINI"
     });
 }
 
+#[test]
+fn load_or_init_reads_gemini_network_settings() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let path = config_dir.join("config.toml");
+        fs::write(
+            &path,
+            r#"
+image_output_dir = "/tmp/mawaku"
+[gemini_api]
+api_key_env_var = "GEMINI_API_KEY"
+proxy = "http://proxy.internal:3128"
+[gemini_api.extra_headers]
+X-Org-Id = "acme"
+"#,
+        )
+        .expect("write config");
+
+        let outcome = load_or_init().expect("load config");
+        assert_eq!(
+            outcome.config.gemini_api.proxy.as_deref(),
+            Some("http://proxy.internal:3128")
+        );
+        assert_eq!(
+            outcome
+                .config
+                .gemini_api
+                .extra_headers
+                .get("X-Org-Id")
+                .map(String::as_str),
+            Some("acme")
+        );
+    });
+}
+
+#[test]
+fn load_or_init_reads_organize_by_date() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "image_output_dir = \"/tmp/mawaku\"\norganize_by_date = true\n",
+        )
+        .expect("write config");
+
+        let outcome = load_or_init().expect("load config");
+        assert!(outcome.config.organize_by_date);
+        assert!(!Config::default().organize_by_date);
+    });
+}
+
+#[test]
+fn load_or_init_backfills_file_name_prefix() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let path = config_dir.join("config.toml");
+        fs::write(&path, "image_output_dir = \"/tmp/mawaku\"\n").expect("write config");
+
+        let outcome = load_or_init().expect("load config");
+        assert_eq!(outcome.config.file_name_prefix(), DEFAULT_FILE_NAME_PREFIX);
+        let contents = fs::read_to_string(&path).expect("read config");
+        assert!(contents.contains("file_name_prefix = \"mawaku\""));
+
+        fs::write(
+            &path,
+            "image_output_dir = \"/tmp/mawaku\"\nfile_name_prefix = \"Client Work\"\n",
+        )
+        .expect("write prefix");
+        let outcome = load_or_init().expect("load prefix");
+        assert_eq!(outcome.config.file_name_prefix(), "clientwork");
+
+        fs::write(
+            &path,
+            "image_output_dir = \"/tmp/mawaku\"\nfile_name_prefix = \"***\"\n",
+        )
+        .expect("write invalid prefix");
+        let error = load_or_init().expect_err("prefix without letters");
+        assert!(error.to_string().contains("file_name_prefix"));
+    });
+}
+
+#[test]
+fn load_or_init_backfills_negative_keywords() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let path = config_dir.join("config.toml");
+        fs::write(&path, "image_output_dir = \"/tmp/mawaku\"\n").expect("write config");
+
+        let outcome = load_or_init().expect("load config");
+        assert!(outcome.config.image.negative_keywords.is_empty());
+        assert_eq!(outcome.config.image.negative_prompt(), None);
+        assert_eq!(
+            outcome.config.image.payload_check_threshold(),
+            Some(DEFAULT_MIN_IMAGE_BYTES)
+        );
+
+        let contents = fs::read_to_string(&path).expect("read config");
+        let parsed: Value = contents.parse().expect("config is valid TOML");
+        assert_eq!(
+            parsed
+                .get("image")
+                .and_then(|image| image.get("negative_keywords"))
+                .and_then(Value::as_array)
+                .map(Vec::len),
+            Some(0)
+        );
+    });
+}
+
+#[test]
+fn negative_prompt_joins_non_blank_keywords() {
+    let image = ImageConfig {
+        negative_keywords: vec![
+            "logos".to_string(),
+            "  ".to_string(),
+            " watermarks ".to_string(),
+        ],
+        ..ImageConfig::default()
+    };
+    assert_eq!(
+        image.negative_prompt().as_deref(),
+        Some("logos, watermarks")
+    );
+}
+
+#[test]
+fn expand_path_vars_resolves_home_and_variables() {
+    let home = Path::new("/home/ada");
+    let lookup = |name: &str| match name {
+        "HOME" => Some("/home/ada".to_string()),
+        "PROJECT" => Some("mawaku".to_string()),
+        _ => None,
+    };
+
+    assert_eq!(
+        expand_path_vars("$HOME/Pictures/${PROJECT}", Some(home), lookup),
+        ("/home/ada/Pictures/mawaku".to_string(), Vec::new())
+    );
+    assert_eq!(
+        expand_path_vars("~/Pictures", Some(home), lookup),
+        ("/home/ada/Pictures".to_string(), Vec::new())
+    );
+    assert_eq!(
+        expand_path_vars("/srv/$MISSING/${MISSING}/cost$5", Some(home), lookup),
+        (
+            "/srv/$MISSING/${MISSING}/cost$5".to_string(),
+            vec!["MISSING".to_string()]
+        )
+    );
+    assert_eq!(
+        expand_path_vars("~user/images", None, lookup),
+        ("~user/images".to_string(), Vec::new())
+    );
+}
+
+#[test]
+fn load_or_init_expands_image_output_dir_in_memory_only() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let path = config_dir.join("config.toml");
+        fs::write(
+            &path,
+            "image_output_dir = \"$HOME/Pictures/$MAWAKU_UNSET_TEST_VAR\"\n",
+        )
+        .expect("write config");
+
+        let outcome = load_or_init().expect("load config");
+        assert_eq!(
+            outcome.config.image_output_dir,
+            format!("{}/Pictures/$MAWAKU_UNSET_TEST_VAR", home.display())
+        );
+        assert_eq!(outcome.warnings.len(), 1);
+        assert!(outcome.warnings[0].contains("MAWAKU_UNSET_TEST_VAR"));
+
+        let contents = fs::read_to_string(&path).expect("read config");
+        assert!(contents.contains("image_output_dir = \"$HOME/Pictures/$MAWAKU_UNSET_TEST_VAR\""));
+    });
+}
+
 fn with_isolated_home<F>(func: F)
 where
     F: FnOnce(&Path),
@@ -183,3 +386,228 @@ fn set_env(key: &str, value: &OsStr) {
 fn remove_env(key: &str) {
     unsafe { std::env::remove_var(key) };
 }
+
+#[test]
+fn load_or_init_reads_optional_scene_defaults() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let path = config_dir.join("config.toml");
+
+        fs::write(&path, "image_output_dir = \"/tmp/mawaku\"\n").expect("write config");
+        let outcome = load_or_init().expect("load config without defaults");
+        assert_eq!(outcome.config.default_season(), None);
+        assert_eq!(outcome.config.default_time_of_day(), None);
+
+        fs::write(
+            &path,
+            "image_output_dir = \"/tmp/mawaku\"\ndefault_season = \"  \"\ndefault_time_of_day = \" evening \"\n",
+        )
+        .expect("write config");
+        let outcome = load_or_init().expect("load config with defaults");
+        assert_eq!(outcome.config.default_season(), None);
+        assert_eq!(outcome.config.default_time_of_day(), Some("evening"));
+    });
+}
+
+#[test]
+fn prompt_template_falls_back_to_default_prompt() {
+    let mut config = Config::default();
+    assert_eq!(config.prompt_template(), DEFAULT_PROMPT);
+
+    config.prompt_template = Some("   ".to_string());
+    assert_eq!(config.prompt_template(), DEFAULT_PROMPT);
+
+    config.prompt_template = Some("Moody loft".to_string());
+    assert_eq!(config.prompt_template(), "Moody loft");
+}
+
+#[test]
+fn load_or_init_rejects_unsupported_aspect_ratio() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        fs::write(
+            config_dir.join("config.toml"),
+            "image_output_dir = \"/tmp/mawaku\"\n[gemini_api]\naspect_ratio = \"17:8\"\n",
+        )
+        .expect("write config");
+
+        match load_or_init() {
+            Err(ConfigError::InvalidValue { key, value, .. }) => {
+                assert_eq!(key, "gemini_api.aspect_ratio");
+                assert_eq!(value, "17:8");
+            }
+            other => panic!("expected invalid aspect ratio, got {other:?}"),
+        }
+    });
+}
+
+#[test]
+fn validate_checks_model_prefixes() {
+    let mut config = Config::default();
+    config.gemini_api.image_model = Some("imagen-4.0-generate-001".to_string());
+    config.gemini_api.text_model = Some("gemini-2.5-pro".to_string());
+    config.gemini_api.aspect_ratio = Some("Auto".to_string());
+    assert!(config.validate().is_ok());
+
+    config.gemini_api.image_model = Some("dall-e-3".to_string());
+    let error = config.validate().expect_err("bogus image model");
+    assert!(
+        error
+            .to_string()
+            .contains("gemini_api.image_model `dall-e-3`")
+    );
+
+    config.gemini_api.image_model = None;
+    config.gemini_api.text_model = Some("imagen-4.0-generate-001".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::InvalidValue {
+            key: "gemini_api.text_model",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn validate_checks_spinner_style() {
+    let mut config = Config {
+        spinner: Some("Dots".to_string()),
+        ..Config::default()
+    };
+    assert!(config.validate().is_ok());
+
+    config.spinner = Some("sparkles".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::InvalidValue { key: "spinner", .. })
+    ));
+}
+
+#[test]
+fn validate_checks_safety_settings() {
+    let mut config = Config::default();
+    config.gemini_api.safety_settings.insert(
+        "HARM_CATEGORY_HARASSMENT".to_string(),
+        "BLOCK_NONE".to_string(),
+    );
+    config.gemini_api.safety_settings.insert(
+        "dangerous-content".to_string(),
+        "block-medium-and-above".to_string(),
+    );
+    assert!(config.validate().is_ok());
+
+    config
+        .gemini_api
+        .safety_settings
+        .insert("violence".to_string(), "off".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::InvalidValue { key: "gemini_api.safety_settings", value, .. }) if value == "violence"
+    ));
+
+    config.gemini_api.safety_settings.remove("violence");
+    config
+        .gemini_api
+        .safety_settings
+        .insert("hate_speech".to_string(), "lenient".to_string());
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::InvalidValue { key: "gemini_api.safety_settings", value, .. }) if value == "lenient"
+    ));
+}
+
+#[test]
+fn pricing_overrides_parse_and_reject_negative_prices() {
+    let config: Config = toml::from_str(
+        r#"
+image_output_dir = "."
+
+[pricing]
+currency = "EUR"
+
+[pricing.images]
+"imagen-4.0-generate-001" = { per_image = 0.05, aspect_ratios = { "16:9" = 0.06 } }
+
+[pricing.text]
+"gemini-2.5-flash" = { input_per_million = 0.3, output_per_million = 2.5 }
+"#,
+    )
+    .expect("parse pricing");
+    assert_eq!(config.pricing.currency.as_deref(), Some("EUR"));
+    let image = &config.pricing.images["imagen-4.0-generate-001"];
+    assert_eq!(image.per_image, 0.05);
+    assert_eq!(image.aspect_ratios["16:9"], 0.06);
+    assert_eq!(
+        config.pricing.text["gemini-2.5-flash"].output_per_million,
+        2.5
+    );
+    assert!(config.validate().is_ok());
+    assert!(Config::default().pricing.is_empty());
+
+    let mut config = config;
+    config
+        .pricing
+        .text
+        .get_mut("gemini-2.5-flash")
+        .expect("text price")
+        .input_per_million = -1.0;
+    assert!(matches!(
+        config.validate(),
+        Err(ConfigError::InvalidValue { key: "pricing", .. })
+    ));
+}
+
+#[test]
+fn alias_matches_names_ignoring_case() {
+    let mut config = Config::default();
+    config.aliases.insert(
+        "Hakone".to_string(),
+        "Hakone, Japan, near the hot springs".to_string(),
+    );
+    config.aliases.insert("blank".to_string(), "  ".to_string());
+
+    assert_eq!(
+        config.alias(" hakone "),
+        Some("Hakone, Japan, near the hot springs")
+    );
+    assert_eq!(config.alias("blank"), None);
+    assert_eq!(config.alias("Kyoto"), None);
+}
+
+#[test]
+fn update_stores_api_key_and_keeps_portable_output_dir() {
+    with_isolated_home(|home| {
+        let config_dir = home.join(".mawaku");
+        fs::create_dir_all(&config_dir).expect("create config dir");
+        let path = config_dir.join("config.toml");
+        fs::write(&path, "image_output_dir = \"~/Pictures/mawaku\"\n").expect("write config");
+
+        let outcome = load_or_init().expect("load config");
+        assert!(outcome.config.gemini_api.stored_api_key().is_none());
+
+        update(&outcome.path, |config| {
+            config.gemini_api.api_key = Some("stored-key".to_string());
+        })
+        .expect("update config");
+
+        let contents = fs::read_to_string(&path).expect("read config");
+        assert!(contents.contains("api_key = \"stored-key\""));
+        assert!(contents.contains("image_output_dir = \"~/Pictures/mawaku\""));
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path)
+                .expect("stat config")
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+        let outcome = load_or_init().expect("reload config");
+        assert_eq!(
+            outcome.config.gemini_api.stored_api_key(),
+            Some("stored-key")
+        );
+    });
+}
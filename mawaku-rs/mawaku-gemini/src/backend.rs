@@ -0,0 +1,154 @@
+use crate::{
+    DescribedPlace, GeminiClient, GeminiError, Orientation, PlaceDescription, PredictPrediction,
+    PredictResponse, RetryAttempt, UpscaleFactor,
+};
+
+/// Produces images from prompts.
+///
+/// [`GeminiClient`] is the real implementation; tests can supply canned
+/// responses instead of calling the API.
+pub trait ImageGenerator: Send + Sync {
+    fn generate(&self, prompt: &str) -> Result<PredictResponse, GeminiError>;
+
+    /// Generate with a specific aspect ratio. Implementations without
+    /// aspect-ratio support fall back to [`Self::generate`].
+    fn generate_with_aspect_ratio(
+        &self,
+        prompt: &str,
+        aspect_ratio: &str,
+    ) -> Result<PredictResponse, GeminiError> {
+        let _ = aspect_ratio;
+        self.generate(prompt)
+    }
+
+    /// Generate with `aspect_ratio` when given, calling `on_retry` before each
+    /// retry. Implementations that never retry ignore the callback.
+    fn generate_reporting_retries(
+        &self,
+        prompt: &str,
+        aspect_ratio: Option<&str>,
+        on_retry: &dyn Fn(RetryAttempt),
+    ) -> Result<PredictResponse, GeminiError> {
+        let _ = on_retry;
+        match aspect_ratio {
+            Some(aspect_ratio) => self.generate_with_aspect_ratio(prompt, aspect_ratio),
+            None => self.generate(prompt),
+        }
+    }
+
+    /// Like [`Self::generate_reporting_retries`] with a fixed `seed`, so the
+    /// same prompt and seed reproduce the same images. Implementations
+    /// without seed support ignore it.
+    fn generate_seeded(
+        &self,
+        prompt: &str,
+        aspect_ratio: Option<&str>,
+        seed: u32,
+        on_retry: &dyn Fn(RetryAttempt),
+    ) -> Result<PredictResponse, GeminiError> {
+        let _ = seed;
+        self.generate_reporting_retries(prompt, aspect_ratio, on_retry)
+    }
+
+    /// Upscale a prediction. Returns `Ok(None)` when there is nothing to
+    /// upscale, which is the default for implementations without upscaling.
+    fn upscale(
+        &self,
+        prediction: &PredictPrediction,
+        factor: UpscaleFactor,
+    ) -> Result<Option<PredictResponse>, GeminiError> {
+        let _ = (prediction, factor);
+        Ok(None)
+    }
+}
+
+/// Text-model calls used to enrich image prompts.
+pub trait TextGenerator: Send + Sync {
+    /// Describe a place blending every entry in `locations`, along with
+    /// why generation stopped.
+    fn describe_place(
+        &self,
+        locations: &[&str],
+        season: &str,
+        time_of_day: Option<&str>,
+        weather: Option<&str>,
+    ) -> Result<DescribedPlace, GeminiError>;
+
+    /// Judge whether the place reads as landscape or portrait.
+    fn classify_orientation(
+        &self,
+        locations: &[&str],
+        description: Option<&PlaceDescription>,
+    ) -> Result<Orientation, GeminiError>;
+}
+
+impl ImageGenerator for GeminiClient {
+    fn generate(&self, prompt: &str) -> Result<PredictResponse, GeminiError> {
+        self.generate_image(prompt)
+    }
+
+    fn generate_with_aspect_ratio(
+        &self,
+        prompt: &str,
+        aspect_ratio: &str,
+    ) -> Result<PredictResponse, GeminiError> {
+        self.clone()
+            .with_aspect_ratio(aspect_ratio)
+            .generate_image(prompt)
+    }
+
+    fn generate_reporting_retries(
+        &self,
+        prompt: &str,
+        aspect_ratio: Option<&str>,
+        on_retry: &dyn Fn(RetryAttempt),
+    ) -> Result<PredictResponse, GeminiError> {
+        match aspect_ratio {
+            Some(aspect_ratio) => self
+                .clone()
+                .with_aspect_ratio(aspect_ratio)
+                .generate_image_reporting_retries(prompt, on_retry),
+            None => self.generate_image_reporting_retries(prompt, on_retry),
+        }
+    }
+
+    fn generate_seeded(
+        &self,
+        prompt: &str,
+        aspect_ratio: Option<&str>,
+        seed: u32,
+        on_retry: &dyn Fn(RetryAttempt),
+    ) -> Result<PredictResponse, GeminiError> {
+        self.clone()
+            .with_seed(seed)
+            .generate_reporting_retries(prompt, aspect_ratio, on_retry)
+    }
+
+    fn upscale(
+        &self,
+        prediction: &PredictPrediction,
+        factor: UpscaleFactor,
+    ) -> Result<Option<PredictResponse>, GeminiError> {
+        self.upscale_prediction(prediction, factor)
+    }
+}
+
+impl TextGenerator for GeminiClient {
+    fn describe_place(
+        &self,
+        locations: &[&str],
+        season: &str,
+        time_of_day: Option<&str>,
+        weather: Option<&str>,
+    ) -> Result<DescribedPlace, GeminiError> {
+        self.generate_blended_place_description(locations, season, time_of_day, weather)
+    }
+
+    fn classify_orientation(
+        &self,
+        locations: &[&str],
+        description: Option<&PlaceDescription>,
+    ) -> Result<Orientation, GeminiError> {
+        GeminiClient::classify_orientation(self, locations, description)
+    }
+}
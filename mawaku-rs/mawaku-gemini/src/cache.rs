@@ -0,0 +1,221 @@
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::{Orientation, PlaceDescription, PredictPrediction, PredictResponse};
+
+/// On-disk cache of place descriptions keyed by the scene inputs.
+///
+/// Each entry is stored as a small JSON file named after a stable hash of the
+/// location, season, time of day, and weather. Entries older than the
+/// configured TTL are treated as misses.
+#[derive(Debug, Clone)]
+pub struct PlaceDescriptionCache {
+    dir: PathBuf,
+    ttl: Duration,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CacheEntry {
+    created_at: u64,
+    description: PlaceDescription,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    orientation: Option<Orientation>,
+}
+
+impl PlaceDescriptionCache {
+    pub fn new(dir: impl Into<PathBuf>, ttl: Duration) -> Self {
+        Self {
+            dir: dir.into(),
+            ttl,
+        }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Build the cache key for a set of scene inputs.
+    ///
+    /// Inputs are trimmed and lowercased so `"Hakone "` and `"hakone"` share
+    /// an entry.
+    pub fn key(
+        location: &str,
+        season: Option<&str>,
+        time_of_day: Option<&str>,
+        weather: Option<&str>,
+    ) -> String {
+        let parts = [Some(location), season, time_of_day, weather]
+            .map(|part| part.unwrap_or_default().trim().to_lowercase());
+        format!("{:016x}", fnv1a_64(parts.join("\u{1f}").as_bytes()))
+    }
+
+    /// Return the cached description for `key` when present and not expired.
+    pub fn get(&self, key: &str) -> Option<PlaceDescription> {
+        self.read_entry(key).map(|entry| entry.description)
+    }
+
+    /// Return the orientation cached alongside the description for `key`.
+    pub fn orientation(&self, key: &str) -> Option<Orientation> {
+        self.read_entry(key)?.orientation
+    }
+
+    /// Store `description` under `key`, replacing any previous entry.
+    pub fn put(&self, key: &str, description: &PlaceDescription) -> io::Result<()> {
+        self.write_entry(
+            key,
+            &CacheEntry {
+                created_at: now_secs(),
+                description: description.clone(),
+                orientation: None,
+            },
+        )
+    }
+
+    /// Record `orientation` next to the description cached under `key`.
+    ///
+    /// Does nothing when no live entry exists for `key`; the entry keeps its
+    /// original creation time.
+    pub fn put_orientation(&self, key: &str, orientation: Orientation) -> io::Result<()> {
+        match self.read_entry(key) {
+            Some(mut entry) => {
+                entry.orientation = Some(orientation);
+                self.write_entry(key, &entry)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn read_entry(&self, key: &str) -> Option<CacheEntry> {
+        let contents = fs::read_to_string(self.entry_path(key)).ok()?;
+        let entry: CacheEntry = serde_json::from_str(&contents).ok()?;
+        let age = now_secs().saturating_sub(entry.created_at);
+        if age > self.ttl.as_secs() {
+            return None;
+        }
+        Some(entry)
+    }
+
+    fn write_entry(&self, key: &str, entry: &CacheEntry) -> io::Result<()> {
+        fs::create_dir_all(&self.dir)?;
+        let serialized = serde_json::to_string_pretty(entry)?;
+        fs::write(self.entry_path(key), serialized)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("place-{key}.json"))
+    }
+}
+
+/// On-disk cache of Imagen responses keyed by the final prompt and request
+/// parameters.
+///
+/// Imagen is non-deterministic, so a hit reuses an earlier result rather than
+/// reproducing it. Entries never expire.
+#[derive(Debug, Clone)]
+pub struct ImageCache {
+    dir: PathBuf,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ImageCacheEntry {
+    created_at: u64,
+    predictions: Vec<CachedPrediction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct CachedPrediction {
+    bytes_base64_encoded: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    mime_type: Option<String>,
+}
+
+impl ImageCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Build the cache key for `prompt` and the `parameters` that shape the
+    /// images, such as the aspect ratio and model.
+    ///
+    /// Unlike [`PlaceDescriptionCache::key`], the prompt is hashed exactly as
+    /// sent.
+    pub fn key(prompt: &str, parameters: &[Option<&str>]) -> String {
+        let mut parts = vec![prompt];
+        parts.extend(parameters.iter().map(|part| part.unwrap_or_default()));
+        format!("{:016x}", fnv1a_64(parts.join("\u{1f}").as_bytes()))
+    }
+
+    /// Return the predictions cached under `key`, if any.
+    pub fn get(&self, key: &str) -> Option<PredictResponse> {
+        let contents = fs::read_to_string(self.entry_path(key)).ok()?;
+        let entry: ImageCacheEntry = serde_json::from_str(&contents).ok()?;
+        if entry.predictions.is_empty() {
+            return None;
+        }
+        Some(PredictResponse {
+            predictions: entry
+                .predictions
+                .into_iter()
+                .map(|prediction| PredictPrediction {
+                    bytes_base64_encoded: Some(prediction.bytes_base64_encoded),
+                    mime_type: prediction.mime_type,
+                })
+                .collect(),
+            echoed_prompt: String::new(),
+        })
+    }
+
+    /// Store the predictions of `response` that carry image bytes under
+    /// `key`. Nothing is written when there are none.
+    pub fn put(&self, key: &str, response: &PredictResponse) -> io::Result<()> {
+        let predictions: Vec<CachedPrediction> = response
+            .predictions
+            .iter()
+            .filter_map(|prediction| {
+                Some(CachedPrediction {
+                    bytes_base64_encoded: prediction.bytes_base64_encoded.clone()?,
+                    mime_type: prediction.mime_type.clone(),
+                })
+            })
+            .collect();
+        if predictions.is_empty() {
+            return Ok(());
+        }
+
+        fs::create_dir_all(&self.dir)?;
+        let serialized = serde_json::to_string(&ImageCacheEntry {
+            created_at: now_secs(),
+            predictions,
+        })?;
+        fs::write(self.entry_path(key), serialized)
+    }
+
+    fn entry_path(&self, key: &str) -> PathBuf {
+        self.dir.join(format!("images-{key}.json"))
+    }
+}
+
+// FNV-1a keeps cache file names stable across Rust releases, unlike
+// `DefaultHasher`.
+fn fnv1a_64(bytes: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0000_0100_0000_01b3;
+    bytes.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+fn now_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,85 @@
+use reqwest::blocking::Request;
+use serde_json::Value;
+use std::fmt;
+use std::sync::Arc;
+
+/// Shown instead of secrets in request dumps.
+pub const REDACTED: &str = "[redacted]";
+
+/// Headers whose values are never printed.
+const SECRET_HEADERS: &[&str] = &["x-goog-api-key", "authorization", "proxy-authorization"];
+
+/// JSON fields holding image bytes, which are summarized by length instead
+/// of printed.
+const BINARY_FIELDS: &[&str] = &["bytesBase64Encoded"];
+
+/// Callback receiving each request, formatted by [`format_request_dump`],
+/// before it is sent. The caller decides where dumps go; the library never
+/// prints them itself.
+#[derive(Clone)]
+pub struct RequestDumper(Arc<dyn Fn(&str) + Send + Sync>);
+
+impl RequestDumper {
+    pub fn new(dump: impl Fn(&str) + Send + Sync + 'static) -> Self {
+        Self(Arc::new(dump))
+    }
+
+    pub fn dump(&self, request: &str) {
+        (self.0)(request)
+    }
+}
+
+impl fmt::Debug for RequestDumper {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("RequestDumper(..)")
+    }
+}
+
+/// Render `request` as its method and URL, headers, and pretty-printed JSON
+/// body, for `--dump-request`. Secret headers and any occurrence of
+/// `api_key` are replaced with [`REDACTED`].
+pub fn format_request_dump(request: &Request, api_key: &str) -> String {
+    let mut lines = vec![format!("{} {}", request.method(), request.url())];
+    for (name, value) in request.headers() {
+        let value = if SECRET_HEADERS.contains(&name.as_str()) {
+            REDACTED.to_string()
+        } else {
+            String::from_utf8_lossy(value.as_bytes()).into_owned()
+        };
+        lines.push(format!("{name}: {value}"));
+    }
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        lines.push(String::new());
+        lines.push(match serde_json::from_slice::<Value>(body) {
+            Ok(mut json) => {
+                elide_binary_fields(&mut json);
+                serde_json::to_string_pretty(&json)
+                    .unwrap_or_else(|_| String::from_utf8_lossy(body).into_owned())
+            }
+            Err(_) => String::from_utf8_lossy(body).into_owned(),
+        });
+    }
+
+    let dump = lines.join("\n");
+    match api_key.trim() {
+        "" => dump,
+        api_key => dump.replace(api_key, REDACTED),
+    }
+}
+
+fn elide_binary_fields(value: &mut Value) {
+    match value {
+        Value::Object(fields) => {
+            for (name, field) in fields.iter_mut() {
+                match field {
+                    Value::String(bytes) if BINARY_FIELDS.contains(&name.as_str()) => {
+                        *field = Value::String(format!("<{} base64 characters>", bytes.len()));
+                    }
+                    _ => elide_binary_fields(field),
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(elide_binary_fields),
+        _ => {}
+    }
+}
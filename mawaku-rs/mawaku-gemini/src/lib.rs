@@ -1,74 +1,668 @@
-use reqwest::blocking::Client;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use mawaku_config::{SUPPORTED_HARM_CATEGORIES, SUPPORTED_SAFETY_THRESHOLDS};
+use mawaku_utils::{decode_base64, format_context_line, list_or_unspecified};
+use reqwest::Proxy;
+use reqwest::blocking::{Client, RequestBuilder, Response};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::num::NonZeroU32;
+use std::time::Duration;
 use thiserror::Error;
 
+mod backend;
+mod cache;
+mod dump;
+mod pricing;
+mod rate_limit;
+mod schema;
+
+pub use backend::{ImageGenerator, TextGenerator};
+pub use cache::{ImageCache, PlaceDescriptionCache};
+pub use dump::{REDACTED, RequestDumper, format_request_dump};
+pub use mawaku_config::DEFAULT_PROMPT;
+pub use mawaku_utils::{normalized, trimmed_or_none};
+pub use pricing::{
+    DEFAULT_CURRENCY, DESCRIPTION_INPUT_TOKENS_ESTIMATE, DESCRIPTION_OUTPUT_TOKENS_ESTIMATE,
+    ImagePrice, ORIENTATION_INPUT_TOKENS_ESTIMATE, ORIENTATION_OUTPUT_TOKENS_ESTIMATE, PriceTable,
+    TokenPrice, estimate_cost,
+};
+pub use rate_limit::RateLimiter;
+pub use schema::{Schema, SchemaType, ToSchema};
+
 pub const DEFAULT_IMG_MODEL_VERSION: &str = "imagen-4.0-ultra-generate-001";
 pub const DEFAULT_TEXT_MODEL_VERSION: &str = "gemini-2.5-flash";
+pub const DEFAULT_EDIT_MODEL_VERSION: &str = "imagen-3.0-capability-001";
+pub const DEFAULT_UPSCALE_MODEL_VERSION: &str = "imagen-4.0-upscale-preview";
 pub const DEFAULT_SAMPLE_COUNT: u32 = 3;
 pub const DEFAULT_ASPECT_RATIO: &str = "16:9";
+/// Aspect ratio of the square avatar generated alongside a background.
+pub const AVATAR_ASPECT_RATIO: &str = "1:1";
+/// Appended by [`build_avatar_prompt`] to narrow a scene down to one motif.
+pub const AVATAR_DIRECTIVE: &str = "Square avatar: frame a single central motif from this scene, close up and centered, on a simple uncluttered backdrop that stays recognizable at small sizes.";
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(30);
+/// `User-Agent` sent with every request unless overridden. Applications
+/// should set [`GeminiClientOptions::user_agent`] to their own name and
+/// version.
+pub const DEFAULT_USER_AGENT: &str = concat!("mawaku-gemini/", env!("CARGO_PKG_VERSION"));
+/// Extra attempts made after a timeout or a transient HTTP status.
+pub const DEFAULT_MAX_RETRIES: u32 = 2;
+/// Wait before the first retry; it doubles for each later one.
+pub const DEFAULT_RETRY_DELAY: Duration = Duration::from_secs(1);
+/// HTTP statuses worth retrying: rate limiting and temporary server errors.
+pub const RETRYABLE_STATUS_CODES: &[u16] = &[429, 500, 502, 503, 504];
+/// Longest image prompt, in characters, accepted before a request is sent.
+pub const DEFAULT_MAX_PROMPT_LEN: usize = 4000;
+/// `finishReason` of a text candidate that ended normally.
+pub const NORMAL_FINISH_REASON: &str = "STOP";
+/// `finishReason` of a text candidate cut off by
+/// [`GeminiClientOptions::max_output_tokens`].
+pub const MAX_TOKENS_FINISH_REASON: &str = "MAX_TOKENS";
+/// Headers managed by the client itself; entries with these names in
+/// [`GeminiClientOptions::extra_headers`] are ignored.
+pub const RESERVED_HEADERS: &[&str] = &["x-goog-api-key", "content-type"];
 
-fn normalized(input: &str) -> Option<&str> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() {
-        None
-    } else {
-        Some(trimmed)
+/// Curated seasonal prompt fragments, keyed by [`SeasonPreset`].
+pub const SEASON_PRESET_FRAGMENTS: &[(SeasonPreset, &str)] = &[
+    (
+        SeasonPreset::Spring,
+        "soft fresh daylight, blossoming trees and new green leaves visible through the window",
+    ),
+    (
+        SeasonPreset::Summer,
+        "bright warm sunlight, lush full foliage and clear skies visible through the window",
+    ),
+    (
+        SeasonPreset::Autumn,
+        "warm amber light, fallen leaves visible through the window",
+    ),
+    (
+        SeasonPreset::Winter,
+        "cool crisp light, snow-covered scenery visible through the window and a cosy warm interior",
+    ),
+];
+
+/// A season with a curated prompt fragment, as an alternative to free-form
+/// season text.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SeasonPreset {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl SeasonPreset {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Spring => "spring",
+            Self::Summer => "summer",
+            Self::Autumn => "autumn",
+            Self::Winter => "winter",
+        }
+    }
+
+    /// The curated prompt fragment from [`SEASON_PRESET_FRAGMENTS`].
+    pub fn fragment(self) -> &'static str {
+        SEASON_PRESET_FRAGMENTS
+            .iter()
+            .find(|(preset, _)| *preset == self)
+            .map(|(_, fragment)| *fragment)
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for SeasonPreset {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for SeasonPreset {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "spring" => Ok(Self::Spring),
+            "summer" => Ok(Self::Summer),
+            "autumn" | "fall" => Ok(Self::Autumn),
+            "winter" => Ok(Self::Winter),
+            other => Err(format!(
+                "unsupported season preset `{other}` (expected spring, summer, autumn, or winter)"
+            )),
+        }
+    }
+}
+
+/// Curated interior style fragments, keyed by [`Theme`].
+pub const THEME_FRAGMENTS: &[(Theme, &str)] = &[
+    (
+        Theme::Cozy,
+        "warm layered lighting, soft textiles, wooden furniture and a few healthy plants",
+    ),
+    (
+        Theme::Minimalist,
+        "clean lines, uncluttered surfaces, a restrained neutral palette and generous empty space",
+    ),
+    (
+        Theme::Industrial,
+        "exposed brick, raw concrete, black steel fixtures and warm filament bulbs",
+    ),
+    (
+        Theme::Scandinavian,
+        "light oak, white walls, wool throws and soft natural daylight",
+    ),
+    (
+        Theme::Japandi,
+        "low wooden furniture, muted earth tones, linen and paper textures, and calm Japanese-Scandinavian restraint",
+    ),
+];
+
+/// An interior style with a curated prompt fragment, chosen with `--theme`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    Cozy,
+    Minimalist,
+    Industrial,
+    Scandinavian,
+    Japandi,
+}
+
+impl Theme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Cozy => "cozy",
+            Self::Minimalist => "minimalist",
+            Self::Industrial => "industrial",
+            Self::Scandinavian => "scandinavian",
+            Self::Japandi => "japandi",
+        }
+    }
+
+    /// The curated prompt fragment from [`THEME_FRAGMENTS`].
+    pub fn fragment(self) -> &'static str {
+        THEME_FRAGMENTS
+            .iter()
+            .find(|(theme, _)| *theme == self)
+            .map(|(_, fragment)| *fragment)
+            .unwrap_or_default()
+    }
+
+    /// The sentence appended to the prompt, e.g. `Interior style: ...`.
+    pub fn directive(self) -> String {
+        PromptLocale::English
+            .extra_directive("Interior style", self.fragment())
+            .unwrap_or_default()
+    }
+}
+
+impl std::fmt::Display for Theme {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for Theme {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "cozy" | "cosy" => Ok(Self::Cozy),
+            "minimalist" => Ok(Self::Minimalist),
+            "industrial" => Ok(Self::Industrial),
+            "scandinavian" => Ok(Self::Scandinavian),
+            "japandi" => Ok(Self::Japandi),
+            other => Err(format!(
+                "unsupported theme `{other}` (expected cozy, minimalist, industrial, scandinavian, or japandi)"
+            )),
+        }
+    }
+}
+
+/// Substitute `{location}`, `{season}`, `{time_of_day}`, and `{weather}` in
+/// `template` with the trimmed values.
+///
+/// Missing values become empty strings and unknown `{...}` sequences are left
+/// untouched. Returns `None` when the template contains none of the known
+/// placeholders, so callers can fall back to [`craft_prompt`].
+pub fn fill_prompt_template(
+    template: &str,
+    location: &str,
+    season: Option<&str>,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+) -> Option<String> {
+    let value_for = |name: &str| match name {
+        "location" => Some(normalized(location).unwrap_or_default()),
+        "season" => Some(season.and_then(normalized).unwrap_or_default()),
+        "time_of_day" => Some(time_of_day.and_then(normalized).unwrap_or_default()),
+        "weather" => Some(weather.and_then(normalized).unwrap_or_default()),
+        _ => None,
+    };
+
+    let mut output = String::with_capacity(template.len());
+    let mut rest = template;
+    let mut substituted = false;
+    while let Some(open) = rest.find('{') {
+        output.push_str(&rest[..open]);
+        let after_open = &rest[open + 1..];
+        match after_open
+            .find('}')
+            .and_then(|close| value_for(&after_open[..close]).map(|value| (close, value)))
+        {
+            Some((close, value)) => {
+                output.push_str(value);
+                rest = &after_open[close + 1..];
+                substituted = true;
+            }
+            None => {
+                output.push('{');
+                rest = after_open;
+            }
+        }
     }
+    output.push_str(rest);
+
+    substituted.then(|| output.trim().to_string())
 }
 
 /// Build a descriptive prompt for Gemini based on contextual inputs.
 ///
 /// The `base_prompt` establishes the overall art direction, while the
-/// location, season, and time-of-day arguments provide scene-specific
+/// location, season, time-of-day, and weather arguments provide scene-specific
 /// details. Empty strings are ignored so callers can pass user-provided
-/// values without additional validation.
+/// values without additional validation. When `base_prompt` contains
+/// placeholders (see [`fill_prompt_template`]) the values are substituted in
+/// place instead of being appended as sentences.
 pub fn craft_prompt(
     base_prompt: &str,
     location: &str,
     season: Option<&str>,
     time_of_day: Option<&str>,
+    weather: Option<&str>,
+) -> String {
+    craft_prompt_with_extras(base_prompt, location, season, time_of_day, weather, &[])
+}
+
+/// Build a prompt like [`craft_prompt`], appending additional scene directives.
+///
+/// Each `(label, value)` pair in `extras` becomes a `"{label}: {value}."`
+/// segment after the built-in ones, in order. Pairs with an empty label or
+/// value are skipped.
+pub fn craft_prompt_with_extras(
+    base_prompt: &str,
+    location: &str,
+    season: Option<&str>,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+    extras: &[(&str, &str)],
+) -> String {
+    craft_prompt_with_locations(
+        base_prompt,
+        &[location],
+        season,
+        time_of_day,
+        weather,
+        extras,
+    )
+}
+
+/// Build a prompt like [`craft_prompt_with_extras`] for one or more locations.
+///
+/// Several locations produce a directive for a scene that harmoniously blends
+/// all of them. Template placeholders receive the locations joined with
+/// [`join_locations`].
+pub fn craft_prompt_with_locations(
+    base_prompt: &str,
+    locations: &[&str],
+    season: Option<&str>,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+    extras: &[(&str, &str)],
+) -> String {
+    craft_prompt_with_locale(
+        base_prompt,
+        locations,
+        season,
+        time_of_day,
+        weather,
+        extras,
+        PromptLocale::default(),
+    )
+}
+
+/// Build a prompt like [`craft_prompt_with_locations`] with the appended
+/// sentences written in `locale`. Only Mawaku's own wording is translated;
+/// `base_prompt` and the scene values are used as given.
+pub fn craft_prompt_with_locale(
+    base_prompt: &str,
+    locations: &[&str],
+    season: Option<&str>,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+    extras: &[(&str, &str)],
+    locale: PromptLocale,
 ) -> String {
     let mut segments: Vec<String> = Vec::new();
 
-    if let Some(base) = normalized(base_prompt) {
-        segments.push(base.to_string());
+    let joined = locale.join_locations(locations);
+    if let Some(filled) = fill_prompt_template(base_prompt, &joined, season, time_of_day, weather) {
+        if !filled.is_empty() {
+            segments.push(filled);
+        }
+    } else {
+        if let Some(base) = normalized(base_prompt) {
+            segments.push(base.to_string());
+        }
+        segments.extend(
+            [
+                locale.locations_directive(locations),
+                season.and_then(|season| locale.season_directive(season)),
+                time_of_day.and_then(|time| locale.time_of_day_directive(time)),
+                weather.and_then(|weather| locale.weather_directive(weather)),
+            ]
+            .into_iter()
+            .flatten(),
+        );
     }
 
-    if let Some(loc) = normalized(location) {
-        segments.push(format!(
-            "Set the scene in {loc} and showcase the atmosphere from a cosy, lived-in interior perspective."
-        ));
+    segments.extend(
+        extras
+            .iter()
+            .filter_map(|(label, value)| locale.extra_directive(label, value)),
+    );
+
+    if segments.is_empty() {
+        String::new()
+    } else {
+        segments.join(" ")
     }
+}
+
+/// Assemble the full image prompt: `general_instructions`, the place
+/// description (or `Unspecified` placeholders), the scene timing, and the
+/// [`Orientation::directive`] for `aspect_ratio` unless it is square, as
+/// separate paragraphs. This is the prompt the CLI sends to Imagen.
+pub fn build_structured_prompt(
+    general_instructions: &str,
+    description: Option<&PlaceDescription>,
+    season: Option<&str>,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+    aspect_ratio: Option<&str>,
+) -> String {
+    let mut sections = Vec::new();
 
-    if let Some(season_value) = season.and_then(normalized) {
-        segments.push(format!("It is {season_value}."));
+    let instructions = general_instructions.trim();
+    if !instructions.is_empty() {
+        sections.push(instructions.to_string());
     }
 
-    if let Some(time_value) = time_of_day.and_then(normalized) {
-        segments.push(format!("Capture the lighting of {time_value}."));
+    let place_section = match description {
+        Some(details) => {
+            let ambiance =
+                trimmed_or_none(Some(details.ambiance.as_str())).unwrap_or("Unspecified");
+            let items = list_or_unspecified(&details.items);
+            let keywords = list_or_unspecified(&details.keywords);
+            format!(
+                "Complete place description:\nUse one or many of these details:\nAmbiance: {}\nItems: {}\nKeywords: {}",
+                ambiance, items, keywords
+            )
+        }
+        None => {
+            "Complete place description:\nUse one or many of these details:\nAmbiance: Unspecified\nItems: Unspecified\nKeywords: Unspecified"
+                .to_string()
+        }
+    };
+    sections.push(place_section);
+
+    let timing_section = format!(
+        "Scene timing:\n{}\n{}\n{}",
+        format_context_line("Season", season),
+        format_context_line("Time of day", time_of_day),
+        format_context_line("Weather", weather),
+    );
+    sections.push(timing_section);
+
+    if let Some(orientation) = aspect_ratio.and_then(Orientation::from_aspect_ratio) {
+        sections.push(orientation.directive().to_string());
     }
 
-    if segments.is_empty() {
-        String::new()
+    sections.join("\n\n")
+}
+
+/// Turn a scene prompt built for [`AVATAR_ASPECT_RATIO`] into a square avatar
+/// prompt by appending [`AVATAR_DIRECTIVE`] as its last paragraph.
+pub fn build_avatar_prompt(scene_prompt: &str) -> String {
+    let scene_prompt = scene_prompt.trim();
+    if scene_prompt.is_empty() {
+        AVATAR_DIRECTIVE.to_string()
     } else {
-        segments.join(" ")
+        format!("{scene_prompt}\n\n{AVATAR_DIRECTIVE}")
+    }
+}
+
+/// Join the non-empty locations as `A`, `A and B`, or `A, B, and C`.
+pub fn join_locations(locations: &[&str]) -> String {
+    PromptLocale::English.join_locations(locations)
+}
+
+/// Language of the sentences [`craft_prompt_with_locale`] appends to the
+/// base prompt.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PromptLocale {
+    #[default]
+    English,
+    Japanese,
+}
+
+impl PromptLocale {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::English => "en",
+            Self::Japanese => "ja",
+        }
+    }
+
+    /// Join the non-empty locations, e.g. `A, B, and C` or `A、B、C`.
+    pub fn join_locations(self, locations: &[&str]) -> String {
+        let locations: Vec<&str> = locations.iter().filter_map(|loc| normalized(loc)).collect();
+        match (self, locations.as_slice()) {
+            (_, []) => String::new(),
+            (_, [only]) => (*only).to_string(),
+            (Self::English, [first, second]) => format!("{first} and {second}"),
+            (Self::English, [rest @ .., last]) => format!("{}, and {last}", rest.join(", ")),
+            (Self::Japanese, [first, second]) => format!("{first}と{second}"),
+            (Self::Japanese, many) => many.join("、"),
+        }
+    }
+
+    /// The location sentence: a single-place directive, or a blending
+    /// directive for several places. `None` when every location is blank.
+    pub fn locations_directive(self, locations: &[&str]) -> Option<String> {
+        let present = locations
+            .iter()
+            .filter(|loc| normalized(loc).is_some())
+            .count();
+        let joined = self.join_locations(locations);
+        let directive = match (self, present) {
+            (_, 0) => return None,
+            (Self::English, 1) => format!(
+                "Set the scene in {joined} and showcase the atmosphere from a cosy, lived-in interior perspective."
+            ),
+            (Self::English, _) => format!(
+                "Create a scene that harmoniously blends {joined} and showcase the atmosphere from a cosy, lived-in interior perspective."
+            ),
+            (Self::Japanese, 1) => format!(
+                "舞台は{joined}とし、居心地が良く生活感のある室内からの視点でその雰囲気を表現してください。"
+            ),
+            (Self::Japanese, _) => format!(
+                "{joined}を調和よく融合させたシーンを作り、居心地が良く生活感のある室内からの視点でその雰囲気を表現してください。"
+            ),
+        };
+        Some(directive)
+    }
+
+    pub fn season_directive(self, season: &str) -> Option<String> {
+        let season = normalized(season)?;
+        Some(match self {
+            Self::English => format!("It is {season}."),
+            Self::Japanese => format!("季節は{season}です。"),
+        })
+    }
+
+    pub fn time_of_day_directive(self, time_of_day: &str) -> Option<String> {
+        let time = normalized(time_of_day)?;
+        Some(match self {
+            Self::English => format!("Capture the lighting of {time}."),
+            Self::Japanese => format!("{time}の光を捉えてください。"),
+        })
+    }
+
+    pub fn weather_directive(self, weather: &str) -> Option<String> {
+        let weather = normalized(weather)?;
+        Some(match self {
+            Self::English => format!("The weather outside is {weather}."),
+            Self::Japanese => format!("外の天気は{weather}です。"),
+        })
+    }
+
+    /// A `label: value` segment from `extras`; `None` when either is blank.
+    pub fn extra_directive(self, label: &str, value: &str) -> Option<String> {
+        let (label, value) = (normalized(label)?, normalized(value)?);
+        Some(match self {
+            Self::English => format!("{label}: {value}."),
+            Self::Japanese => format!("{label}：{value}。"),
+        })
+    }
+}
+
+impl std::fmt::Display for PromptLocale {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl std::str::FromStr for PromptLocale {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "en" | "english" => Ok(Self::English),
+            "ja" | "japanese" => Ok(Self::Japanese),
+            other => Err(format!(
+                "unsupported prompt locale `{other}` (expected en or ja)"
+            )),
+        }
     }
 }
 
+/// The art direction [`craft_prompt`] starts from when no custom template is
+/// configured.
+pub fn default_prompt() -> &'static str {
+    DEFAULT_PROMPT
+}
+
+/// The sentence [`craft_prompt`] adds for a single location, or `None` when
+/// `location` is blank.
+pub fn location_directive(location: &str) -> Option<String> {
+    locations_directive(&[location])
+}
+
+/// The sentence [`craft_prompt_with_locations`] adds for `locations`: a
+/// single-place directive, or a blending directive for several places.
+/// Returns `None` when every location is blank.
+pub fn locations_directive(locations: &[&str]) -> Option<String> {
+    PromptLocale::English.locations_directive(locations)
+}
+
+/// The sentence [`craft_prompt`] adds for `season`, or `None` when blank.
+pub fn season_directive(season: &str) -> Option<String> {
+    PromptLocale::English.season_directive(season)
+}
+
+/// The sentence [`craft_prompt`] adds for `time_of_day`, or `None` when blank.
+pub fn time_of_day_directive(time_of_day: &str) -> Option<String> {
+    PromptLocale::English.time_of_day_directive(time_of_day)
+}
+
+/// The sentence [`craft_prompt`] adds for `weather`, or `None` when blank.
+pub fn weather_directive(weather: &str) -> Option<String> {
+    PromptLocale::English.weather_directive(weather)
+}
+
 #[derive(Debug, Error)]
 pub enum GeminiError {
     #[error("Gemini API key is missing")]
     MissingApiKey,
+    #[error("Gemini returned no candidates")]
+    NoCandidates,
+    #[error("invalid response schema: {0}")]
+    InvalidSchema(String),
+    #[error("could not read an orientation from Gemini's answer `{0}`")]
+    UnrecognizedOrientation(String),
+    #[error("prompt is empty")]
+    EmptyPrompt,
+    #[error("Gemini blocked the prompt ({reason})")]
+    Blocked { reason: String },
+    #[error("prompt is {len} characters long; the maximum is {max}")]
+    PromptTooLong { len: usize, max: usize },
+    #[error(
+        "Gemini rejected the API key (HTTP {status}); check the key and that the Generative Language API is enabled for its project, or create a new key at https://aistudio.google.com/apikey"
+    )]
+    Unauthorized { status: u16 },
     #[error(transparent)]
     Http(#[from] reqwest::Error),
     #[error("Failed to parse JSON response: {0}")]
     JsonParse(#[from] serde_json::Error),
+    #[error(
+        "Gemini stopped generating early ({finish_reason}) and the response is incomplete: {source}"
+    )]
+    StoppedEarly {
+        finish_reason: String,
+        source: serde_json::Error,
+    },
+    #[error("invalid proxy URL {proxy}")]
+    InvalidProxy {
+        proxy: String,
+        #[source]
+        source: reqwest::Error,
+    },
+    #[error("invalid Gemini base URL {url}")]
+    InvalidBaseUrl { url: String },
+    #[error(
+        "refusing to send the API key over plain HTTP to {url}; use https, or http only for localhost"
+    )]
+    InsecureBaseUrl { url: String },
+    #[error("invalid HTTP header {name}")]
+    InvalidHeader { name: String },
+    #[error("failed to connect through proxy {proxy}")]
+    ProxyConnect {
+        proxy: String,
+        #[source]
+        source: reqwest::Error,
+    },
+}
+
+impl GeminiError {
+    /// Whether the API could not be reached at all, for example because DNS
+    /// resolution failed, the connection was refused, or the configured proxy
+    /// was unreachable.
+    pub fn is_offline(&self) -> bool {
+        matches!(self, Self::Http(source) if source.is_connect())
+            || matches!(self, Self::ProxyConnect { .. })
+    }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct PredictResponse {
     #[serde(default)]
     pub predictions: Vec<PredictPrediction>,
+    /// The exact prompt submitted to Imagen for this response. Filled in by
+    /// the client rather than parsed from the API payload.
+    #[serde(skip)]
+    pub echoed_prompt: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -79,6 +673,48 @@ pub struct PredictPrediction {
     pub mime_type: Option<String>,
 }
 
+/// A prediction whose base64 payload has already been decoded.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedImage {
+    pub bytes: Vec<u8>,
+    pub mime_type: Option<String>,
+}
+
+/// Images decoded from a [`PredictResponse`], plus one warning per prediction
+/// that was skipped because it had no bytes or could not be decoded.
+#[derive(Debug, Default)]
+pub struct DecodedImages {
+    pub images: Vec<DecodedImage>,
+    pub warnings: Vec<String>,
+}
+
+impl PredictResponse {
+    /// Decode every prediction's image bytes, skipping empty or malformed ones.
+    pub fn decode_images(&self) -> DecodedImages {
+        let mut decoded = DecodedImages::default();
+        for (index, prediction) in self.predictions.iter().enumerate() {
+            let display_index = index + 1;
+            let Some(encoded) = prediction.bytes_base64_encoded.as_deref() else {
+                decoded.warnings.push(format!(
+                    "prediction #{display_index} did not include encoded image bytes"
+                ));
+                continue;
+            };
+
+            match decode_base64(encoded) {
+                Ok(bytes) => decoded.images.push(DecodedImage {
+                    bytes,
+                    mime_type: prediction.mime_type.clone(),
+                }),
+                Err(error) => decoded.warnings.push(format!(
+                    "prediction #{display_index} could not be decoded ({error})"
+                )),
+            }
+        }
+        decoded
+    }
+}
+
 #[derive(Debug, Serialize)]
 struct PredictRequest<'a> {
     instances: Vec<Instance<'a>>,
@@ -96,257 +732,1680 @@ struct Parameters {
     sample_count: u32,
     #[serde(rename = "aspectRatio", skip_serializing_if = "Option::is_none")]
     aspect_ratio: Option<String>,
+    #[serde(rename = "negativePrompt", skip_serializing_if = "Option::is_none")]
+    negative_prompt: Option<String>,
+    #[serde(rename = "personGeneration", skip_serializing_if = "Option::is_none")]
+    person_generation: Option<PersonGeneration>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    seed: Option<u32>,
+    /// `false` when the caller opted out of Imagen's SynthID watermark;
+    /// omitted otherwise so the API default applies.
+    #[serde(rename = "addWatermark", skip_serializing_if = "Option::is_none")]
+    add_watermark: Option<bool>,
 }
 
-// Text generation request structures matching Gemini API format
-#[derive(Debug, Serialize)]
-struct TextRequest<'a> {
-    contents: Vec<Content<'a>>,
-    #[serde(rename = "generationConfig", skip_serializing_if = "Option::is_none")]
-    generation_config: Option<GenerationConfig>,
+/// Whether Imagen may draw people, sent as `personGeneration`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PersonGeneration {
+    /// No people at all, matching Mawaku's empty-room backgrounds.
+    #[default]
+    DontAllow,
+    AllowAdult,
+    AllowAll,
 }
 
-#[derive(Debug, Serialize)]
-struct GenerationConfig {
-    #[serde(rename = "responseMimeType")]
-    response_mime_type: String,
-    #[serde(rename = "responseSchema")]
-    response_schema: ResponseSchema,
+impl PersonGeneration {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::DontAllow => "dont_allow",
+            Self::AllowAdult => "allow_adult",
+            Self::AllowAll => "allow_all",
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct ResponseSchema {
-    #[serde(rename = "type")]
-    schema_type: String,
-    properties: serde_json::Value,
-    #[serde(rename = "propertyOrdering", skip_serializing_if = "Option::is_none")]
-    property_ordering: Option<Vec<String>>,
+impl std::fmt::Display for PersonGeneration {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct Content<'a> {
-    parts: Vec<Part<'a>>,
+impl std::str::FromStr for PersonGeneration {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().replace('-', "_").as_str() {
+            "dont_allow" => Ok(Self::DontAllow),
+            "allow_adult" => Ok(Self::AllowAdult),
+            "allow_all" => Ok(Self::AllowAll),
+            other => Err(format!(
+                "unsupported person generation `{other}` (expected dont_allow, allow_adult, or allow_all)"
+            )),
+        }
+    }
 }
 
-#[derive(Debug, Serialize)]
-struct Part<'a> {
-    text: &'a str,
+/// Harm category filtered in text responses, sent in `safetySettings`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum HarmCategory {
+    #[serde(rename = "HARM_CATEGORY_HARASSMENT")]
+    Harassment,
+    #[serde(rename = "HARM_CATEGORY_HATE_SPEECH")]
+    HateSpeech,
+    #[serde(rename = "HARM_CATEGORY_SEXUALLY_EXPLICIT")]
+    SexuallyExplicit,
+    #[serde(rename = "HARM_CATEGORY_DANGEROUS_CONTENT")]
+    DangerousContent,
 }
 
-// Text generation response structures
-#[derive(Debug, Deserialize)]
-pub struct GenerateContentResponse {
-    #[serde(default)]
-    pub candidates: Vec<Candidate>,
+impl HarmCategory {
+    /// Every category, in the order of [`SUPPORTED_HARM_CATEGORIES`].
+    pub const ALL: [Self; 4] = [
+        Self::Harassment,
+        Self::HateSpeech,
+        Self::SexuallyExplicit,
+        Self::DangerousContent,
+    ];
+
+    /// The name used in the configuration, from [`SUPPORTED_HARM_CATEGORIES`].
+    pub fn as_str(self) -> &'static str {
+        SUPPORTED_HARM_CATEGORIES[self as usize]
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct Candidate {
-    pub content: ContentResponse,
-    #[serde(rename = "finishReason")]
-    pub finish_reason: Option<String>,
+const _: () = assert!(HarmCategory::ALL.len() == SUPPORTED_HARM_CATEGORIES.len());
+
+impl std::fmt::Display for HarmCategory {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct ContentResponse {
-    pub parts: Vec<PartResponse>,
+impl std::str::FromStr for HarmCategory {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_ascii_lowercase().replace('-', "_");
+        let value = value.strip_prefix("harm_category_").unwrap_or(&value);
+        Self::ALL
+            .into_iter()
+            .find(|category| category.as_str() == value)
+            .ok_or_else(|| {
+                format!(
+                    "unsupported harm category `{value}` (expected one of {})",
+                    SUPPORTED_HARM_CATEGORIES.join(", ")
+                )
+            })
+    }
 }
 
-#[derive(Debug, Deserialize)]
-pub struct PartResponse {
-    pub text: String,
+/// How readily Gemini blocks a text response in one [`HarmCategory`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum SafetyThreshold {
+    BlockNone,
+    /// Block only content with a high probability of harm, so descriptions
+    /// of ordinary places are not cut short.
+    #[default]
+    BlockOnlyHigh,
+    BlockMediumAndAbove,
+    BlockLowAndAbove,
+    Off,
 }
 
-// Place description structured output
-#[derive(Debug, Serialize, Deserialize)]
-pub struct PlaceDescription {
-    pub ambiance: String,
-    pub items: Vec<String>,
-    pub keywords: Vec<String>,
+impl SafetyThreshold {
+    /// Every threshold, in the order of [`SUPPORTED_SAFETY_THRESHOLDS`].
+    pub const ALL: [Self; 5] = [
+        Self::BlockNone,
+        Self::BlockOnlyHigh,
+        Self::BlockMediumAndAbove,
+        Self::BlockLowAndAbove,
+        Self::Off,
+    ];
+
+    /// The name used in the configuration, from
+    /// [`SUPPORTED_SAFETY_THRESHOLDS`].
+    pub fn as_str(self) -> &'static str {
+        SUPPORTED_SAFETY_THRESHOLDS[self as usize]
+    }
 }
 
-impl std::fmt::Display for PlaceDescription {
+const _: () = assert!(SafetyThreshold::ALL.len() == SUPPORTED_SAFETY_THRESHOLDS.len());
+
+impl std::fmt::Display for SafetyThreshold {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        writeln!(f, "Ambiance: {}", self.ambiance)?;
-        writeln!(f, "Items: {}", self.items.join(", "))?;
-        write!(f, "Keywords: {}", self.keywords.join(", "))
+        f.write_str(self.as_str())
     }
 }
 
-impl<'a> PredictRequest<'a> {
-    fn new(prompt: &'a str, sample_count: u32, aspect_ratio: Option<String>) -> Self {
-        Self {
-            instances: vec![Instance { prompt }],
-            parameters: Parameters {
-                sample_count,
-                aspect_ratio,
-            },
-        }
+impl std::str::FromStr for SafetyThreshold {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim().to_ascii_lowercase().replace('-', "_");
+        Self::ALL
+            .into_iter()
+            .find(|threshold| threshold.as_str() == value)
+            .ok_or_else(|| {
+                format!(
+                    "unsupported safety threshold `{value}` (expected one of {})",
+                    SUPPORTED_SAFETY_THRESHOLDS.join(", ")
+                )
+            })
     }
 }
 
-impl<'a> TextRequest<'a> {
-    fn new(text: &'a str) -> Self {
-        Self {
-            contents: vec![Content {
-                parts: vec![Part { text }],
-            }],
-            generation_config: None,
-        }
-    }
+/// One entry of the `safetySettings` array sent with text requests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SafetySetting {
+    pub category: HarmCategory,
+    pub threshold: SafetyThreshold,
+}
 
-    fn with_schema(text: &'a str, generation_config: GenerationConfig) -> Self {
-        Self {
-            contents: vec![Content {
-                parts: vec![Part { text }],
-            }],
-            generation_config: Some(generation_config),
-        }
+impl SafetySetting {
+    /// `threshold` applied to every [`HarmCategory`].
+    pub fn all(threshold: SafetyThreshold) -> Vec<Self> {
+        HarmCategory::ALL
+            .into_iter()
+            .map(|category| Self {
+                category,
+                threshold,
+            })
+            .collect()
     }
 }
 
-fn image_endpoint_url() -> String {
-    format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{model_version}:predict",
-        model_version = DEFAULT_IMG_MODEL_VERSION
-    )
+/// How much [`GeminiClient::upscale_image`] enlarges an image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UpscaleFactor {
+    X2,
+    X4,
 }
 
-fn text_endpoint_url() -> String {
-    format!(
-        "https://generativelanguage.googleapis.com/v1beta/models/{model_version}:generateContent",
-        model_version = DEFAULT_TEXT_MODEL_VERSION
-    )
+impl UpscaleFactor {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::X2 => "x2",
+            Self::X4 => "x4",
+        }
+    }
 }
 
-/// Submit an image generation request to Gemini's Imagen 4 API.
-///
-/// The request targets Gemini's hosted Imagen 4 endpoint. Future iterations can
-/// expose configuration hooks for model selection and regional routing.
-///
-/// # Errors
-///
-/// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty or
-/// whitespace only. Network and HTTP errors are surfaced via `reqwest`.
-pub fn generate_image(api_key: &str, prompt: &str) -> Result<PredictResponse, GeminiError> {
-    if api_key.trim().is_empty() {
-        return Err(GeminiError::MissingApiKey);
+impl std::fmt::Display for UpscaleFactor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
     }
+}
 
-    let client = Client::new();
-    let url = image_endpoint_url();
-    let request_body = PredictRequest::new(
-        prompt,
-        DEFAULT_SAMPLE_COUNT,
-        Some(DEFAULT_ASPECT_RATIO.to_string()),
-    );
+impl std::str::FromStr for UpscaleFactor {
+    type Err = String;
 
-    let response = client
-        .post(url)
-        .header("x-goog-api-key", api_key)
-        .json(&request_body)
-        .send()?;
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "x2" | "2" => Ok(Self::X2),
+            "x4" | "4" => Ok(Self::X4),
+            other => Err(format!(
+                "unsupported upscale factor `{other}` (expected x2 or x4)"
+            )),
+        }
+    }
+}
 
-    let response = response.error_for_status()?;
-    let parsed = response.json::<PredictResponse>()?;
-    Ok(parsed)
+/// Whether a place reads better framed wide or tall.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Orientation {
+    Landscape,
+    Portrait,
 }
 
-/// Submit a text generation request to Gemini's API.
-///
-/// # Errors
-///
-/// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty or
-/// whitespace only. Network and HTTP errors are surfaced via `reqwest`.
-pub fn generate_text(api_key: &str, prompt: &str) -> Result<GenerateContentResponse, GeminiError> {
-    if api_key.trim().is_empty() {
-        return Err(GeminiError::MissingApiKey);
+impl Orientation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Self::Landscape => "landscape",
+            Self::Portrait => "portrait",
+        }
+    }
+
+    /// The Imagen aspect ratio used for this orientation.
+    pub fn aspect_ratio(self) -> &'static str {
+        match self {
+            Self::Landscape => DEFAULT_ASPECT_RATIO,
+            Self::Portrait => "3:4",
+        }
+    }
+
+    /// The orientation framed by an aspect ratio such as `9:16`, or `None`
+    /// for square or unparsable ratios.
+    pub fn from_aspect_ratio(aspect_ratio: &str) -> Option<Self> {
+        let (width, height) = aspect_ratio.trim().split_once(':')?;
+        let width: u32 = width.trim().parse().ok()?;
+        let height: u32 = height.trim().parse().ok()?;
+        match width.cmp(&height) {
+            std::cmp::Ordering::Greater => Some(Self::Landscape),
+            std::cmp::Ordering::Less => Some(Self::Portrait),
+            std::cmp::Ordering::Equal => None,
+        }
     }
 
-    let client = Client::new();
-    let url = text_endpoint_url();
-    let request_body = TextRequest::new(prompt);
+    /// The prompt sentence reinforcing this framing.
+    pub fn directive(self) -> &'static str {
+        match self {
+            Self::Landscape => "Compose this as a wide landscape image.",
+            Self::Portrait => "Compose this as a tall vertical image.",
+        }
+    }
 
-    let response = client
-        .post(url)
-        .header("x-goog-api-key", api_key)
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()?;
+    /// Read the orientation out of a free-form classification answer.
+    ///
+    /// Returns `None` when the answer mentions neither or both orientations.
+    pub fn from_answer(answer: &str) -> Option<Self> {
+        let answer = answer.to_ascii_lowercase();
+        match (answer.contains("landscape"), answer.contains("portrait")) {
+            (true, false) => Some(Self::Landscape),
+            (false, true) => Some(Self::Portrait),
+            _ => None,
+        }
+    }
+}
 
-    let response = response.error_for_status()?;
-    let parsed = response.json::<GenerateContentResponse>()?;
-    Ok(parsed)
+impl std::fmt::Display for Orientation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.as_str())
+    }
 }
 
-pub fn generate_place_description(
-    location: &str,
-    season: &str,
-    api_key: &str,
-) -> Result<PlaceDescription, GeminiError> {
-    if api_key.trim().is_empty() {
-        return Err(GeminiError::MissingApiKey);
+// Upscale request structures matching the Imagen upscale API
+#[derive(Debug, Serialize)]
+struct UpscaleRequest<'a> {
+    instances: Vec<UpscaleInstance<'a>>,
+    parameters: UpscaleParameters,
+}
+
+#[derive(Debug, Serialize)]
+struct UpscaleInstance<'a> {
+    prompt: &'a str,
+    image: EncodedImage,
+}
+
+#[derive(Debug, Serialize)]
+struct UpscaleParameters {
+    mode: &'static str,
+    #[serde(rename = "upscaleConfig")]
+    upscale_config: UpscaleConfig,
+    #[serde(rename = "sampleCount")]
+    sample_count: u32,
+}
+
+#[derive(Debug, Serialize)]
+struct UpscaleConfig {
+    #[serde(rename = "upscaleFactor")]
+    upscale_factor: &'static str,
+}
+
+// Image editing (inpainting) request structures matching the Imagen edit API
+#[derive(Debug, Serialize)]
+struct EditRequest<'a> {
+    instances: Vec<EditInstance<'a>>,
+    parameters: EditParameters,
+}
+
+#[derive(Debug, Serialize)]
+struct EditInstance<'a> {
+    prompt: &'a str,
+    #[serde(rename = "referenceImages")]
+    reference_images: Vec<ReferenceImage>,
+}
+
+#[derive(Debug, Serialize)]
+struct ReferenceImage {
+    #[serde(rename = "referenceType")]
+    reference_type: &'static str,
+    #[serde(rename = "referenceId")]
+    reference_id: u32,
+    #[serde(rename = "referenceImage")]
+    reference_image: EncodedImage,
+    #[serde(rename = "maskImageConfig", skip_serializing_if = "Option::is_none")]
+    mask_image_config: Option<MaskImageConfig>,
+}
+
+#[derive(Debug, Serialize)]
+struct EncodedImage {
+    #[serde(rename = "bytesBase64Encoded")]
+    bytes_base64_encoded: String,
+}
+
+#[derive(Debug, Serialize)]
+struct MaskImageConfig {
+    #[serde(rename = "maskMode")]
+    mask_mode: &'static str,
+}
+
+#[derive(Debug, Serialize)]
+struct EditParameters {
+    #[serde(rename = "editMode")]
+    edit_mode: &'static str,
+    #[serde(rename = "sampleCount")]
+    sample_count: u32,
+}
+
+// Text generation request structures matching Gemini API format
+#[derive(Debug, Serialize)]
+struct TextRequest<'a> {
+    contents: Vec<Content<'a>>,
+    #[serde(rename = "generationConfig", skip_serializing_if = "Option::is_none")]
+    generation_config: Option<GenerationConfig>,
+    #[serde(rename = "safetySettings", skip_serializing_if = "Vec::is_empty")]
+    safety_settings: Vec<SafetySetting>,
+}
+
+#[derive(Debug, Default, Serialize)]
+struct GenerationConfig {
+    #[serde(rename = "responseMimeType", skip_serializing_if = "Option::is_none")]
+    response_mime_type: Option<String>,
+    #[serde(rename = "responseSchema", skip_serializing_if = "Option::is_none")]
+    response_schema: Option<Schema>,
+    #[serde(rename = "candidateCount", skip_serializing_if = "Option::is_none")]
+    candidate_count: Option<u32>,
+    #[serde(rename = "maxOutputTokens", skip_serializing_if = "Option::is_none")]
+    max_output_tokens: Option<u32>,
+}
+
+#[derive(Debug, Serialize)]
+struct Content<'a> {
+    parts: Vec<Part<'a>>,
+}
+
+#[derive(Debug, Serialize)]
+struct Part<'a> {
+    text: &'a str,
+}
+
+// Text generation response structures
+#[derive(Debug, Deserialize)]
+pub struct GenerateContentResponse {
+    #[serde(default)]
+    pub candidates: Vec<Candidate>,
+    /// Safety ratings and block reason for the prompt. Present even on
+    /// successful calls, so it can explain softened or terse responses.
+    #[serde(rename = "promptFeedback", default)]
+    pub prompt_feedback: Option<PromptFeedback>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PromptFeedback {
+    #[serde(rename = "blockReason")]
+    pub block_reason: Option<String>,
+    #[serde(rename = "safetyRatings", default)]
+    pub safety_ratings: Vec<SafetyRating>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct SafetyRating {
+    pub category: String,
+    pub probability: String,
+    #[serde(default)]
+    pub blocked: bool,
+}
+
+/// A model listed by the `ListModels` endpoint.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ModelInfo {
+    /// Resource name, e.g. `models/gemini-2.5-flash`.
+    pub name: String,
+    #[serde(default)]
+    pub display_name: String,
+    /// API methods the model accepts, e.g. `predict` or `generateContent`.
+    #[serde(default)]
+    pub supported_generation_methods: Vec<String>,
+}
+
+impl ModelInfo {
+    /// The model name without its `models/` prefix, as used in
+    /// [`GeminiClientOptions`].
+    pub fn id(&self) -> &str {
+        self.name.strip_prefix("models/").unwrap_or(&self.name)
+    }
+
+    /// Whether Mawaku can use the model: it generates images through
+    /// `predict` or text through `generateContent`.
+    pub fn is_image_or_text_capable(&self) -> bool {
+        self.supported_generation_methods
+            .iter()
+            .any(|method| method == "predict" || method == "generateContent")
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListModelsResponse {
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
+    #[serde(default)]
+    pub next_page_token: Option<String>,
+}
+
+impl GenerateContentResponse {
+    /// Why Gemini refused the prompt, when it did.
+    pub fn block_reason(&self) -> Option<&str> {
+        self.prompt_feedback
+            .as_ref()
+            .and_then(|feedback| feedback.block_reason.as_deref())
+    }
+
+    /// Why the first candidate ended, e.g. `STOP` or `MAX_TOKENS`.
+    pub fn finish_reason(&self) -> Option<&str> {
+        self.candidates
+            .first()
+            .and_then(|candidate| candidate.finish_reason.as_deref())
+    }
+
+    /// The text of every candidate, in order, with each candidate's parts
+    /// concatenated.
+    pub fn candidate_texts(&self) -> Vec<String> {
+        self.candidates
+            .iter()
+            .map(|candidate| {
+                candidate
+                    .content
+                    .parts
+                    .iter()
+                    .map(|part| part.text.as_str())
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Candidate {
+    /// Missing when the candidate was blocked before producing content.
+    #[serde(default)]
+    pub content: ContentResponse,
+    #[serde(rename = "finishReason")]
+    pub finish_reason: Option<String>,
+}
+
+impl Candidate {
+    /// Whether the candidate ended for a reason other than
+    /// [`NORMAL_FINISH_REASON`], such as [`MAX_TOKENS_FINISH_REASON`] or
+    /// `SAFETY`.
+    pub fn stopped_early(&self) -> bool {
+        stopped_early(self.finish_reason.as_deref())
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ContentResponse {
+    #[serde(default)]
+    pub parts: Vec<PartResponse>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PartResponse {
+    pub text: String,
+}
+
+/// A place description and the reason Gemini stopped generating it.
+#[derive(Debug, Clone)]
+pub struct DescribedPlace {
+    pub description: PlaceDescription,
+    /// [`NORMAL_FINISH_REASON`] for a complete answer; `None` when Gemini did
+    /// not say.
+    pub finish_reason: Option<String>,
+}
+
+impl DescribedPlace {
+    /// Whether Gemini stopped before finishing, so the description may be
+    /// truncated or thin.
+    pub fn stopped_early(&self) -> bool {
+        stopped_early(self.finish_reason.as_deref())
+    }
+}
+
+fn stopped_early(finish_reason: Option<&str>) -> bool {
+    finish_reason.is_some_and(|reason| reason != NORMAL_FINISH_REASON)
+}
+
+// Place description structured output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PlaceDescription {
+    pub ambiance: String,
+    pub items: Vec<String>,
+    pub keywords: Vec<String>,
+}
+
+impl std::fmt::Display for PlaceDescription {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Ambiance: {}", self.ambiance)?;
+        writeln!(f, "Items: {}", self.items.join(", "))?;
+        write!(f, "Keywords: {}", self.keywords.join(", "))
+    }
+}
+
+impl<'a> PredictRequest<'a> {
+    fn new(
+        prompt: &'a str,
+        sample_count: u32,
+        aspect_ratio: Option<String>,
+        negative_prompt: Option<String>,
+    ) -> Self {
+        Self {
+            instances: vec![Instance { prompt }],
+            parameters: Parameters {
+                sample_count,
+                aspect_ratio,
+                negative_prompt,
+                person_generation: None,
+                seed: None,
+                add_watermark: None,
+            },
+        }
+    }
+
+    fn with_person_generation(mut self, person_generation: Option<PersonGeneration>) -> Self {
+        self.parameters.person_generation = person_generation;
+        self
+    }
+
+    fn with_seed(mut self, seed: Option<u32>) -> Self {
+        self.parameters.seed = seed;
+        self
+    }
+
+    fn with_watermark_disabled(mut self, disabled: bool) -> Self {
+        self.parameters.add_watermark = disabled.then_some(false);
+        self
+    }
+}
+
+impl<'a> EditRequest<'a> {
+    fn inpaint(prompt: &'a str, base_image: &[u8], mask: &[u8], sample_count: u32) -> Self {
+        Self {
+            instances: vec![EditInstance {
+                prompt,
+                reference_images: vec![
+                    ReferenceImage {
+                        reference_type: "REFERENCE_TYPE_RAW",
+                        reference_id: 1,
+                        reference_image: EncodedImage {
+                            bytes_base64_encoded: BASE64_STANDARD.encode(base_image),
+                        },
+                        mask_image_config: None,
+                    },
+                    ReferenceImage {
+                        reference_type: "REFERENCE_TYPE_MASK",
+                        reference_id: 2,
+                        reference_image: EncodedImage {
+                            bytes_base64_encoded: BASE64_STANDARD.encode(mask),
+                        },
+                        mask_image_config: Some(MaskImageConfig {
+                            mask_mode: "MASK_MODE_USER_PROVIDED",
+                        }),
+                    },
+                ],
+            }],
+            parameters: EditParameters {
+                edit_mode: "EDIT_MODE_INPAINT_INSERTION",
+                sample_count,
+            },
+        }
+    }
+}
+
+impl UpscaleRequest<'static> {
+    fn new(encoded_image: String, factor: UpscaleFactor) -> Self {
+        Self {
+            instances: vec![UpscaleInstance {
+                prompt: "",
+                image: EncodedImage {
+                    bytes_base64_encoded: encoded_image,
+                },
+            }],
+            parameters: UpscaleParameters {
+                mode: "upscale",
+                upscale_config: UpscaleConfig {
+                    upscale_factor: factor.as_str(),
+                },
+                sample_count: 1,
+            },
+        }
+    }
+}
+
+impl<'a> TextRequest<'a> {
+    fn new(text: &'a str) -> Self {
+        Self {
+            contents: vec![Content {
+                parts: vec![Part { text }],
+            }],
+            generation_config: None,
+            safety_settings: Vec::new(),
+        }
+    }
+
+    fn with_schema(text: &'a str, generation_config: GenerationConfig) -> Self {
+        Self {
+            contents: vec![Content {
+                parts: vec![Part { text }],
+            }],
+            generation_config: Some(generation_config),
+            safety_settings: Vec::new(),
+        }
+    }
+
+    fn with_safety_settings(mut self, safety_settings: &[SafetySetting]) -> Self {
+        self.safety_settings = safety_settings.to_vec();
+        self
+    }
+
+    /// Cap the response length; `None` leaves any existing cap in place.
+    fn with_max_output_tokens(mut self, max_output_tokens: Option<u32>) -> Self {
+        if let Some(max_output_tokens) = max_output_tokens {
+            self.generation_config
+                .get_or_insert_with(GenerationConfig::default)
+                .max_output_tokens = Some(max_output_tokens);
+        }
+        self
+    }
+}
+
+fn scene_context_clause(season: &str, time_of_day: Option<&str>, weather: Option<&str>) -> String {
+    let mut clause = String::new();
+
+    if let Some(season_value) = normalized(season) {
+        clause.push_str(&format!(" in {season_value}"));
     }
 
-    let prompt = format!(
-        "Describe the place called {location} in the {season}. Provide a general ambiance description, \
+    if let Some(time_value) = time_of_day.and_then(normalized) {
+        clause.push_str(&format!(" during the {time_value}"));
+    }
+
+    if let Some(weather_value) = weather.and_then(normalized) {
+        clause.push_str(&format!(" while the weather is {weather_value}"));
+    }
+
+    clause
+}
+
+fn place_description_prompt(
+    locations: &[&str],
+    season: &str,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+) -> String {
+    let context = scene_context_clause(season, time_of_day, weather);
+    let subject = match locations
+        .iter()
+        .filter(|loc| normalized(loc).is_some())
+        .count()
+    {
+        0 | 1 => format!("the place called {}", join_locations(locations)),
+        _ => format!(
+            "a place that harmoniously blends {}",
+            join_locations(locations)
+        ),
+    };
+    format!(
+        "Describe {subject}{context}. Provide a general ambiance description \
+         that reflects this season and time of day, \
          a list of potential items that might be found in a cozy interior view of this place, \
          a list of popular features or attractions associated with this location, \
          and a list of keywords that capture the essence of this location."
+    )
+}
+
+fn orientation_prompt(locations: &[&str], description: Option<&PlaceDescription>) -> String {
+    let mut prompt = format!(
+        "Would a cozy interior view of {} be framed better as a wide landscape \
+         image or a tall portrait image?",
+        join_locations(locations)
     );
+    if let Some(description) = description {
+        prompt.push_str(&format!("\n\nPlace description:\n{description}"));
+    }
+    prompt.push_str("\n\nAnswer with a single word: landscape or portrait.");
+    prompt
+}
+
+/// Scheme and host of the public Gemini API.
+pub const DEFAULT_BASE_URL: &str = "https://generativelanguage.googleapis.com";
+
+/// Environment variable overriding [`DEFAULT_BASE_URL`], e.g. to point the
+/// client at a local mock server.
+pub const BASE_URL_ENV_VAR: &str = "MAWAKU_GEMINI_BASE_URL";
+
+fn image_endpoint_url(base_url: &str, model: &str) -> String {
+    format!("{}/{model}:predict", models_endpoint_url(base_url))
+}
+
+fn models_endpoint_url(base_url: &str) -> String {
+    format!("{base_url}/v1beta/models")
+}
+
+fn text_endpoint_url(base_url: &str, model: &str) -> String {
+    format!("{}/{model}:generateContent", models_endpoint_url(base_url))
+}
+
+/// Default settings applied by [`GeminiClient`].
+#[derive(Debug, Clone)]
+pub struct GeminiClientOptions {
+    pub image_model: String,
+    pub edit_model: String,
+    pub upscale_model: String,
+    pub text_model: String,
+    pub sample_count: u32,
+    pub aspect_ratio: Option<String>,
+    /// Things image generation should avoid, sent as Imagen's
+    /// `negativePrompt`. Imagen 4 models no longer accept that parameter,
+    /// so for them it is appended to the prompt as an `Avoid:` sentence.
+    pub negative_prompt: Option<String>,
+    /// Sent as Imagen's `personGeneration`; `None` leaves the API default.
+    pub person_generation: Option<PersonGeneration>,
+    /// Sent as Imagen's `seed`, so the same prompt and seed reproduce the
+    /// same images. `None` lets Imagen pick a random one. Only Vertex AI
+    /// documents `seed`, see [`GeminiClient::honors_seed`].
+    pub seed: Option<u32>,
+    /// Send `addWatermark: false` to turn off Imagen's SynthID watermark.
+    /// Vertex AI only applies [`Self::seed`] to unwatermarked images.
+    pub disable_watermark: bool,
+    /// Per-category filters sent with every text request; empty leaves the
+    /// API defaults.
+    pub safety_settings: Vec<SafetySetting>,
+    /// Sent as `maxOutputTokens` with every text request to cap response
+    /// length. A response cut off by the cap reports a `MAX_TOKENS` finish
+    /// reason. `None` leaves the model's limit.
+    pub max_output_tokens: Option<u32>,
+    pub timeout: Duration,
+    /// Retries after a timeout or a status in [`RETRYABLE_STATUS_CODES`].
+    /// `0` disables retrying.
+    pub max_retries: u32,
+    /// Wait before the first retry, doubled for each later one.
+    pub retry_delay: Duration,
+    /// Space requests, retries included, to stay under this many per minute.
+    /// `None` sends them as fast as they come.
+    pub requests_per_minute: Option<NonZeroU32>,
+    /// Image prompts longer than this many characters are rejected locally
+    /// with [`GeminiError::PromptTooLong`].
+    pub max_prompt_len: usize,
+    /// Route every request through this proxy URL. When `None`, the standard
+    /// `HTTPS_PROXY`/`https_proxy` environment variables are honoured.
+    pub proxy: Option<String>,
+    /// Send requests to this scheme and host instead of [`DEFAULT_BASE_URL`],
+    /// keeping the `/v1beta/models/...` paths. When `None`, the
+    /// [`BASE_URL_ENV_VAR`] environment variable is honoured.
+    pub base_url: Option<String>,
+    /// Additional headers attached to every request, e.g. an `X-Org-Id`
+    /// required by an API gateway. The reserved `x-goog-api-key` and
+    /// `Content-Type` headers cannot be overridden and are skipped.
+    pub extra_headers: HashMap<String, String>,
+    /// `User-Agent` header identifying Mawaku to proxies and to Google.
+    pub user_agent: String,
+    /// Receives every request before it is sent, formatted by
+    /// [`format_request_dump`] with the API key redacted.
+    pub dump_requests: Option<RequestDumper>,
+}
 
-    // Build the schema for structured output
-    let schema_properties = serde_json::json!({
-        "ambiance": { "type": "STRING" },
-        "items": {
-            "type": "ARRAY",
-            "items": { "type": "STRING" }
-        },
-        "keywords": {
-            "type": "ARRAY",
-            "items": { "type": "STRING" }
-        }
-    });
-
-    let generation_config = GenerationConfig {
-        response_mime_type: "application/json".to_string(),
-        response_schema: ResponseSchema {
-            schema_type: "OBJECT".to_string(),
-            properties: schema_properties,
-            property_ordering: Some(vec![
-                "ambiance".to_string(),
-                "items".to_string(),
-                "keywords".to_string(),
-            ]),
-        },
+impl Default for GeminiClientOptions {
+    fn default() -> Self {
+        Self {
+            image_model: DEFAULT_IMG_MODEL_VERSION.to_string(),
+            edit_model: DEFAULT_EDIT_MODEL_VERSION.to_string(),
+            upscale_model: DEFAULT_UPSCALE_MODEL_VERSION.to_string(),
+            text_model: DEFAULT_TEXT_MODEL_VERSION.to_string(),
+            sample_count: DEFAULT_SAMPLE_COUNT,
+            aspect_ratio: Some(DEFAULT_ASPECT_RATIO.to_string()),
+            negative_prompt: None,
+            person_generation: Some(PersonGeneration::DontAllow),
+            seed: None,
+            disable_watermark: false,
+            safety_settings: SafetySetting::all(SafetyThreshold::default()),
+            max_output_tokens: None,
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            max_retries: DEFAULT_MAX_RETRIES,
+            retry_delay: DEFAULT_RETRY_DELAY,
+            requests_per_minute: None,
+            max_prompt_len: DEFAULT_MAX_PROMPT_LEN,
+            proxy: None,
+            base_url: None,
+            extra_headers: HashMap::new(),
+            user_agent: DEFAULT_USER_AGENT.to_string(),
+            dump_requests: None,
+        }
+    }
+}
+
+/// A reusable handle to the Gemini API.
+///
+/// The API key is validated once when the client is built and the underlying
+/// HTTP client is shared across requests.
+#[derive(Debug, Clone)]
+pub struct GeminiClient {
+    api_key: String,
+    http: Client,
+    options: GeminiClientOptions,
+    proxy: Option<String>,
+    base_url: String,
+    extra_headers: HeaderMap,
+    /// Shared by clones of the client, so their requests count together.
+    rate_limiter: Option<RateLimiter>,
+}
+
+impl GeminiClient {
+    /// Create a client with [`GeminiClientOptions::default`].
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty
+    /// or whitespace only.
+    pub fn new(api_key: &str) -> Result<Self, GeminiError> {
+        Self::with_options(api_key, GeminiClientOptions::default())
+    }
+
+    /// Create a client with custom model and request settings.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty
+    /// or whitespace only, [`GeminiError::InvalidProxy`] when the configured
+    /// proxy URL cannot be parsed, [`GeminiError::InvalidBaseUrl`] when the
+    /// base URL is not an `http` or `https` URL, [`GeminiError::InsecureBaseUrl`]
+    /// when it uses `http` for a host other than localhost,
+    /// [`GeminiError::InvalidHeader`]
+    /// when an extra header or the user agent is malformed, or an HTTP error if
+    /// the client cannot be built.
+    pub fn with_options(api_key: &str, options: GeminiClientOptions) -> Result<Self, GeminiError> {
+        let api_key = normalized(api_key).ok_or(GeminiError::MissingApiKey)?;
+        let extra_headers = build_extra_headers(&options.extra_headers)?;
+        let base_url = resolve_base_url(options.base_url.as_deref())?;
+        let user_agent = HeaderValue::from_str(options.user_agent.trim()).map_err(|_| {
+            GeminiError::InvalidHeader {
+                name: "User-Agent".to_string(),
+            }
+        })?;
+        let mut builder = Client::builder()
+            .timeout(options.timeout)
+            .user_agent(user_agent);
+
+        let explicit_proxy = options
+            .proxy
+            .as_deref()
+            .and_then(normalized)
+            .map(str::to_string);
+        if let Some(proxy) = explicit_proxy.as_deref() {
+            let proxy_config = Proxy::all(proxy).map_err(|source| GeminiError::InvalidProxy {
+                proxy: proxy.to_string(),
+                source,
+            })?;
+            builder = builder.proxy(proxy_config);
+        }
+
+        let http = builder.build()?;
+        let proxy = explicit_proxy.or_else(proxy_from_env);
+        let rate_limiter = options.requests_per_minute.map(RateLimiter::per_minute);
+
+        Ok(Self {
+            api_key: api_key.to_string(),
+            http,
+            options,
+            proxy,
+            base_url,
+            extra_headers,
+            rate_limiter,
+        })
+    }
+
+    pub fn options(&self) -> &GeminiClientOptions {
+        &self.options
+    }
+
+    /// Use `aspect_ratio` for subsequent image requests.
+    pub fn with_aspect_ratio(mut self, aspect_ratio: impl Into<String>) -> Self {
+        self.options.aspect_ratio = Some(aspect_ratio.into());
+        self
+    }
+
+    /// Use `seed` for subsequent image requests.
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.options.seed = Some(seed);
+        self
+    }
+
+    /// The proxy requests are routed through, if any.
+    pub fn proxy(&self) -> Option<&str> {
+        self.proxy.as_deref()
+    }
+
+    /// The scheme and host requests are sent to, without a trailing slash.
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Whether image requests go somewhere that documents Imagen's `seed`.
+    /// The Gemini API at [`DEFAULT_BASE_URL`] does not list it (only Vertex
+    /// AI does), so seeds are only trusted behind a custom base URL.
+    pub fn honors_seed(&self) -> bool {
+        self.base_url != DEFAULT_BASE_URL
+    }
+
+    /// Submit an image generation request to Gemini's Imagen 4 API.
+    ///
+    /// The prompt is trimmed before it is sent.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::EmptyPrompt`] or [`GeminiError::PromptTooLong`]
+    /// without contacting the API when the prompt is blank or exceeds
+    /// [`GeminiClientOptions::max_prompt_len`]. Network and HTTP errors are
+    /// surfaced via `reqwest`.
+    pub fn generate_image(&self, prompt: &str) -> Result<PredictResponse, GeminiError> {
+        self.request_image(prompt, None)
+    }
+
+    /// Like [`Self::generate_image`], calling `on_retry` before each retry so
+    /// callers can show that a slow request is being retried.
+    ///
+    /// # Errors
+    ///
+    /// See [`Self::generate_image`].
+    pub fn generate_image_reporting_retries(
+        &self,
+        prompt: &str,
+        on_retry: &dyn Fn(RetryAttempt),
+    ) -> Result<PredictResponse, GeminiError> {
+        self.request_image(prompt, Some(on_retry))
+    }
+
+    fn request_image(
+        &self,
+        prompt: &str,
+        on_retry: Option<&dyn Fn(RetryAttempt)>,
+    ) -> Result<PredictResponse, GeminiError> {
+        let prompt = normalized(prompt).ok_or(GeminiError::EmptyPrompt)?;
+        let url = image_endpoint_url(&self.base_url, &self.options.image_model);
+        let (prompt, negative_prompt) = apply_negative_prompt(
+            &self.options.image_model,
+            prompt,
+            self.options.negative_prompt.as_deref(),
+        );
+        // Check the length the API sees, after any folded-in negative prompt.
+        validate_prompt(&prompt, self.options.max_prompt_len)?;
+        let request_body = PredictRequest::new(
+            &prompt,
+            self.options.sample_count,
+            self.options.aspect_ratio.clone(),
+            negative_prompt,
+        )
+        .with_person_generation(self.options.person_generation)
+        .with_seed(self.options.seed)
+        .with_watermark_disabled(self.options.disable_watermark);
+
+        let response = self.send_with_retries(
+            self.http
+                .post(url)
+                .header("x-goog-api-key", &self.api_key)
+                .json(&request_body),
+            on_retry,
+        )?;
+
+        let response = response.error_for_status()?;
+        let mut parsed = response.json::<PredictResponse>()?;
+        parsed.echoed_prompt = prompt;
+        Ok(parsed)
+    }
+
+    /// Generate images for `prompt` and return their decoded bytes.
+    ///
+    /// Use [`Self::generate_image`] for the untouched API response.
+    ///
+    /// # Errors
+    ///
+    /// Network and HTTP errors are surfaced via `reqwest`.
+    pub fn generate_decoded_images(&self, prompt: &str) -> Result<DecodedImages, GeminiError> {
+        self.generate_image(prompt)
+            .map(|response| response.decode_images())
+    }
+
+    /// Refine an existing image by inpainting the region covered by `mask`.
+    ///
+    /// `base_image` and `mask` are raw image bytes (e.g. PNG); they are
+    /// base64-encoded before being sent. White pixels in the mask mark the
+    /// area Imagen should repaint according to `prompt`.
+    ///
+    /// # Errors
+    ///
+    /// Network and HTTP errors are surfaced via `reqwest`.
+    pub fn edit_image(
+        &self,
+        base_image: &[u8],
+        mask: &[u8],
+        prompt: &str,
+    ) -> Result<PredictResponse, GeminiError> {
+        let url = image_endpoint_url(&self.base_url, &self.options.edit_model);
+        let request_body =
+            EditRequest::inpaint(prompt, base_image, mask, self.options.sample_count);
+
+        let response = self.send(
+            self.http
+                .post(url)
+                .header("x-goog-api-key", &self.api_key)
+                .json(&request_body),
+        )?;
+
+        let response = response.error_for_status()?;
+        let mut parsed = response.json::<PredictResponse>()?;
+        parsed.echoed_prompt = prompt.to_string();
+        Ok(parsed)
+    }
+
+    /// Upscale raw image bytes by `factor`, returning the enlarged image.
+    ///
+    /// Upscaling is billed as a separate request and adds noticeable latency.
+    ///
+    /// # Errors
+    ///
+    /// Network and HTTP errors are surfaced via `reqwest`.
+    pub fn upscale_image(
+        &self,
+        image: &[u8],
+        factor: UpscaleFactor,
+    ) -> Result<PredictResponse, GeminiError> {
+        self.send_upscale_request(BASE64_STANDARD.encode(image), factor)
+    }
+
+    /// Upscale an image returned by a previous request without decoding it.
+    ///
+    /// Returns `Ok(None)` when the prediction carries no image bytes.
+    ///
+    /// # Errors
+    ///
+    /// Network and HTTP errors are surfaced via `reqwest`.
+    pub fn upscale_prediction(
+        &self,
+        prediction: &PredictPrediction,
+        factor: UpscaleFactor,
+    ) -> Result<Option<PredictResponse>, GeminiError> {
+        match prediction.bytes_base64_encoded.as_deref() {
+            Some(encoded) => self
+                .send_upscale_request(encoded.to_string(), factor)
+                .map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn send_upscale_request(
+        &self,
+        encoded_image: String,
+        factor: UpscaleFactor,
+    ) -> Result<PredictResponse, GeminiError> {
+        let url = image_endpoint_url(&self.base_url, &self.options.upscale_model);
+        let request_body = UpscaleRequest::new(encoded_image, factor);
+
+        let response = self.send(
+            self.http
+                .post(url)
+                .header("x-goog-api-key", &self.api_key)
+                .json(&request_body),
+        )?;
+
+        let response = response.error_for_status()?;
+        let parsed = response.json::<PredictResponse>()?;
+        Ok(parsed)
+    }
+
+    /// Submit a text generation request to Gemini's API.
+    ///
+    /// # Errors
+    ///
+    /// Network and HTTP errors are surfaced via `reqwest`.
+    pub fn generate_text(&self, prompt: &str) -> Result<GenerateContentResponse, GeminiError> {
+        self.send_text_request(TextRequest::new(prompt))
+    }
+
+    /// Ask Gemini whether the place blending `locations` reads as landscape or
+    /// portrait, using `description` for extra context when available.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::UnrecognizedOrientation`] when the answer names
+    /// neither orientation. Network and HTTP errors are surfaced via `reqwest`.
+    pub fn classify_orientation(
+        &self,
+        locations: &[&str],
+        description: Option<&PlaceDescription>,
+    ) -> Result<Orientation, GeminiError> {
+        let prompt = orientation_prompt(locations, description);
+        let answer = self
+            .generate_text(&prompt)?
+            .candidate_texts()
+            .into_iter()
+            .next()
+            .ok_or(GeminiError::NoCandidates)?;
+        Orientation::from_answer(&answer)
+            .ok_or_else(|| GeminiError::UnrecognizedOrientation(answer.trim().to_string()))
+    }
+
+    /// Request JSON output matching `schema` and deserialize the first
+    /// candidate into `T`.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::InvalidSchema`] without contacting the API when
+    /// `schema` breaks Gemini's rules, [`GeminiError::NoCandidates`] when the
+    /// response is empty, and [`GeminiError::JsonParse`] when the candidate
+    /// does not match `T`.
+    pub fn generate_json<T: serde::de::DeserializeOwned>(
+        &self,
+        prompt: &str,
+        schema: Schema,
+    ) -> Result<T, GeminiError> {
+        schema.validate()?;
+        let generation_config = GenerationConfig {
+            response_mime_type: Some("application/json".to_string()),
+            response_schema: Some(schema),
+            candidate_count: None,
+            max_output_tokens: None,
+        };
+        let request_body = TextRequest::with_schema(prompt, generation_config);
+        let text = self
+            .send_text_request(request_body)?
+            .candidate_texts()
+            .into_iter()
+            .next()
+            .ok_or(GeminiError::NoCandidates)?;
+        Ok(serde_json::from_str(&text)?)
+    }
+
+    /// Ask for `count` alternative completions of `prompt` and return the
+    /// text of each candidate.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::NoCandidates`] when the response is empty.
+    /// Network and HTTP errors are surfaced via `reqwest`.
+    pub fn generate_text_candidates(
+        &self,
+        prompt: &str,
+        count: u32,
+    ) -> Result<Vec<String>, GeminiError> {
+        let generation_config = GenerationConfig {
+            response_mime_type: None,
+            response_schema: None,
+            candidate_count: Some(count.max(1)),
+            max_output_tokens: None,
+        };
+        let request_body = TextRequest::with_schema(prompt, generation_config);
+        let texts = self.send_text_request(request_body)?.candidate_texts();
+        if texts.is_empty() {
+            return Err(GeminiError::NoCandidates);
+        }
+        Ok(texts)
+    }
+
+    /// Ask Gemini for a structured description of `location`.
+    ///
+    /// The season, time of day, and weather steer both the prompt and the
+    /// ambiance field of the response schema so the returned description
+    /// matches the lighting and mood of the requested scene (e.g. evening vs.
+    /// midday).
+    pub fn generate_place_description(
+        &self,
+        location: &str,
+        season: &str,
+        time_of_day: Option<&str>,
+        weather: Option<&str>,
+    ) -> Result<DescribedPlace, GeminiError> {
+        self.generate_blended_place_description(&[location], season, time_of_day, weather)
+    }
+
+    /// Ask Gemini for a structured description of a place blending every
+    /// entry in `locations`, like [`Self::generate_place_description`].
+    ///
+    /// Only a single candidate is requested, and the first one is returned
+    /// with its finish reason.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::NoCandidates`] when Gemini returns no candidate
+    /// and [`GeminiError::StoppedEarly`] when it stopped before completing the
+    /// JSON.
+    pub fn generate_blended_place_description(
+        &self,
+        locations: &[&str],
+        season: &str,
+        time_of_day: Option<&str>,
+        weather: Option<&str>,
+    ) -> Result<DescribedPlace, GeminiError> {
+        let response =
+            self.send_place_description_request(locations, season, time_of_day, weather, 1)?;
+        let description = parse_place_descriptions(&response)?
+            .into_iter()
+            .next()
+            .ok_or(GeminiError::NoCandidates)?;
+        Ok(DescribedPlace {
+            description,
+            finish_reason: response.finish_reason().map(str::to_string),
+        })
+    }
+
+    /// Request `count` alternative place descriptions and parse every
+    /// candidate, in the order Gemini returned them.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::NoCandidates`] when the response is empty and
+    /// [`GeminiError::JsonParse`] when a candidate is not valid JSON, or
+    /// [`GeminiError::StoppedEarly`] when that candidate was cut short.
+    pub fn generate_place_description_candidates(
+        &self,
+        locations: &[&str],
+        season: &str,
+        time_of_day: Option<&str>,
+        weather: Option<&str>,
+        count: u32,
+    ) -> Result<Vec<PlaceDescription>, GeminiError> {
+        let response =
+            self.send_place_description_request(locations, season, time_of_day, weather, count)?;
+        parse_place_descriptions(&response)
+    }
+
+    fn send_place_description_request(
+        &self,
+        locations: &[&str],
+        season: &str,
+        time_of_day: Option<&str>,
+        weather: Option<&str>,
+        count: u32,
+    ) -> Result<GenerateContentResponse, GeminiError> {
+        let prompt = place_description_prompt(locations, season, time_of_day, weather);
+        let location = join_locations(locations);
+
+        // Build the schema for structured output
+        let schema = Schema::object()
+            .property(
+                "ambiance",
+                Schema::string().description(format!(
+                    "Overall ambiance of {location}{}",
+                    scene_context_clause(season, time_of_day, weather)
+                )),
+            )
+            .property("items", Vec::<String>::schema())
+            .property("keywords", Vec::<String>::schema());
+
+        let generation_config = GenerationConfig {
+            response_mime_type: Some("application/json".to_string()),
+            response_schema: Some(schema),
+            candidate_count: (count > 1).then_some(count),
+            max_output_tokens: None,
+        };
+
+        let request_body = TextRequest::with_schema(&prompt, generation_config);
+        self.send_text_request(request_body)
+    }
+
+    /// List every model the API key can access, following pagination.
+    ///
+    /// # Errors
+    ///
+    /// Network and HTTP errors are surfaced via `reqwest`.
+    pub fn list_models(&self) -> Result<Vec<ModelInfo>, GeminiError> {
+        let mut models = Vec::new();
+        let mut page_token: Option<String> = None;
+        loop {
+            let mut request = self
+                .http
+                .get(models_endpoint_url(&self.base_url))
+                .header("x-goog-api-key", &self.api_key)
+                .query(&[("pageSize", "1000")]);
+            if let Some(token) = &page_token {
+                request = request.query(&[("pageToken", token.as_str())]);
+            }
+
+            let response = self.send(request)?.error_for_status()?;
+            let page = response.json::<ListModelsResponse>()?;
+            models.extend(page.models);
+            match page.next_page_token.filter(|token| !token.is_empty()) {
+                Some(token) => page_token = Some(token),
+                None => return Ok(models),
+            }
+        }
+    }
+
+    /// Send `request_body` with the configured safety settings and output
+    /// token cap.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::Blocked`] when Gemini refused the prompt without
+    /// returning any candidate.
+    fn send_text_request(
+        &self,
+        request_body: TextRequest<'_>,
+    ) -> Result<GenerateContentResponse, GeminiError> {
+        let url = text_endpoint_url(&self.base_url, &self.options.text_model);
+        let request_body = request_body
+            .with_safety_settings(&self.options.safety_settings)
+            .with_max_output_tokens(self.options.max_output_tokens);
+
+        let response = self.send(
+            self.http
+                .post(url)
+                .header("x-goog-api-key", &self.api_key)
+                .header("Content-Type", "application/json")
+                .json(&request_body),
+        )?;
+
+        let response = response.error_for_status()?;
+        let parsed = response.json::<GenerateContentResponse>()?;
+        if parsed.candidates.is_empty()
+            && let Some(reason) = parsed.block_reason()
+        {
+            return Err(GeminiError::Blocked {
+                reason: reason.to_string(),
+            });
+        }
+        Ok(parsed)
+    }
+
+    fn send(&self, request: RequestBuilder) -> Result<Response, GeminiError> {
+        self.send_with_retries(request, None)
+    }
+
+    /// Send `request`, retrying timeouts and [`RETRYABLE_STATUS_CODES`] up to
+    /// [`GeminiClientOptions::max_retries`] times. Every attempt first waits
+    /// for the rate limiter, if any. The last response is returned as is, so
+    /// callers still see the final error status.
+    fn send_with_retries(
+        &self,
+        request: RequestBuilder,
+        on_retry: Option<&dyn Fn(RetryAttempt)>,
+    ) -> Result<Response, GeminiError> {
+        let max_retries = self.options.max_retries;
+        let mut pending = request.headers(self.extra_headers.clone());
+        if let Some(dumper) = &self.options.dump_requests
+            && let Some(request) = pending.try_clone().and_then(|request| request.build().ok())
+        {
+            dumper.dump(&format_request_dump(&request, &self.api_key));
+        }
+        let mut retry = 0;
+        loop {
+            let next = if retry < max_retries {
+                pending.try_clone()
+            } else {
+                None
+            };
+            if let Some(rate_limiter) = &self.rate_limiter {
+                rate_limiter.acquire();
+            }
+            let result = pending.send();
+            let retryable = match &result {
+                Ok(response) => is_retryable_status(response.status().as_u16()),
+                Err(source) => source.is_timeout(),
+            };
+            match next {
+                Some(next) if retryable => {
+                    retry += 1;
+                    let attempt = RetryAttempt {
+                        retry,
+                        max_retries,
+                        delay: retry_delay(self.options.retry_delay, retry),
+                    };
+                    if let Some(on_retry) = on_retry {
+                        on_retry(attempt);
+                    }
+                    std::thread::sleep(attempt.delay);
+                    pending = next;
+                }
+                _ => {
+                    if let Ok(response) = &result
+                        && is_auth_failure(response.status().as_u16())
+                    {
+                        return Err(GeminiError::Unauthorized {
+                            status: response.status().as_u16(),
+                        });
+                    }
+                    return result.map_err(|source| match self.proxy.as_deref() {
+                        Some(proxy) if source.is_connect() => GeminiError::ProxyConnect {
+                            proxy: proxy.to_string(),
+                            source,
+                        },
+                        _ => GeminiError::Http(source),
+                    });
+                }
+            }
+        }
+    }
+}
+
+/// A retry the client is about to make, reported through
+/// [`GeminiClient::generate_image_reporting_retries`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RetryAttempt {
+    /// 1 for the first retry.
+    pub retry: u32,
+    pub max_retries: u32,
+    /// How long the client waits before sending it.
+    pub delay: Duration,
+}
+
+fn is_retryable_status(status: u16) -> bool {
+    RETRYABLE_STATUS_CODES.contains(&status)
+}
+
+/// 401 and 403 mean the key is invalid or not allowed to call the API.
+fn is_auth_failure(status: u16) -> bool {
+    matches!(status, 401 | 403)
+}
+
+/// `base` doubled for each retry after the first.
+fn retry_delay(base: Duration, retry: u32) -> Duration {
+    base.saturating_mul(1 << retry.saturating_sub(1).min(16))
+}
+
+fn parse_place_descriptions(
+    response: &GenerateContentResponse,
+) -> Result<Vec<PlaceDescription>, GeminiError> {
+    let texts = response.candidate_texts();
+    if texts.is_empty() {
+        return Err(GeminiError::NoCandidates);
+    }
+
+    texts
+        .iter()
+        .zip(&response.candidates)
+        .map(|(text, candidate)| {
+            serde_json::from_str(json_payload(text)).map_err(|source| {
+                match candidate.finish_reason.as_deref() {
+                    Some(reason) if candidate.stopped_early() => GeminiError::StoppedEarly {
+                        finish_reason: reason.to_string(),
+                        source,
+                    },
+                    _ => GeminiError::JsonParse(source),
+                }
+            })
+        })
+        .collect()
+}
+
+/// The JSON object inside `text`, without the ```` ```json ```` fences or
+/// surrounding prose Gemini sometimes adds despite the JSON response type.
+/// Text without an object is returned trimmed, so parsing reports the error.
+fn json_payload(text: &str) -> &str {
+    let text = text.trim();
+    match (text.find('{'), text.rfind('}')) {
+        (Some(start), Some(end)) if start < end => &text[start..=end],
+        _ => text,
+    }
+}
+
+fn validate_prompt(prompt: &str, max_len: usize) -> Result<&str, GeminiError> {
+    let prompt = normalized(prompt).ok_or(GeminiError::EmptyPrompt)?;
+    let len = prompt.chars().count();
+    if len > max_len {
+        return Err(GeminiError::PromptTooLong { len, max: max_len });
+    }
+    Ok(prompt)
+}
+
+fn build_extra_headers(headers: &HashMap<String, String>) -> Result<HeaderMap, GeminiError> {
+    let mut map = HeaderMap::new();
+
+    for (name, value) in headers {
+        let invalid = || GeminiError::InvalidHeader { name: name.clone() };
+        let header_name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid())?;
+        if RESERVED_HEADERS.contains(&header_name.as_str()) {
+            continue;
+        }
+        let header_value = HeaderValue::from_str(value.trim()).map_err(|_| invalid())?;
+        map.insert(header_name, header_value);
+    }
+
+    Ok(map)
+}
+
+/// Whether `model` accepts Imagen's `negativePrompt` parameter, which Imagen
+/// 4 models reject.
+fn supports_negative_prompt(model: &str) -> bool {
+    !model.starts_with("imagen-4")
+}
+
+/// The prompt and `negativePrompt` to send to `model`. Models without
+/// negative prompt support get it appended to the prompt as an instruction.
+fn apply_negative_prompt(
+    model: &str,
+    prompt: &str,
+    negative_prompt: Option<&str>,
+) -> (String, Option<String>) {
+    let negative_prompt = negative_prompt.and_then(normalized);
+    match negative_prompt {
+        Some(negative) if !supports_negative_prompt(model) => {
+            (format!("{prompt}\n\nAvoid: {negative}."), None)
+        }
+        negative => (prompt.to_string(), negative.map(str::to_string)),
+    }
+}
+
+/// The base URL from `explicit`, then [`BASE_URL_ENV_VAR`], then
+/// [`DEFAULT_BASE_URL`], with any trailing slash removed. Plain `http` is
+/// only accepted for loopback hosts, since every request carries the API key.
+fn resolve_base_url(explicit: Option<&str>) -> Result<String, GeminiError> {
+    let configured = explicit
+        .and_then(normalized)
+        .map(str::to_string)
+        .or_else(|| {
+            std::env::var(BASE_URL_ENV_VAR)
+                .ok()
+                .and_then(|value| normalized(&value).map(str::to_string))
+        });
+    let Some(configured) = configured else {
+        return Ok(DEFAULT_BASE_URL.to_string());
     };
+    let base_url = configured.trim_end_matches('/').to_string();
+    match reqwest::Url::parse(&base_url) {
+        Ok(url) if url.scheme() == "https" && url.has_host() => Ok(base_url),
+        Ok(url) if url.scheme() == "http" && url.has_host() => {
+            if is_loopback(&url) {
+                Ok(base_url)
+            } else {
+                Err(GeminiError::InsecureBaseUrl { url: configured })
+            }
+        }
+        _ => Err(GeminiError::InvalidBaseUrl { url: configured }),
+    }
+}
 
-    let client = Client::new();
-    let url = text_endpoint_url();
-    let request_body = TextRequest::with_schema(&prompt, generation_config);
+fn is_loopback(url: &reqwest::Url) -> bool {
+    let Some(host) = url.host_str() else {
+        return false;
+    };
+    match host.trim_matches(['[', ']']).parse::<std::net::IpAddr>() {
+        Ok(ip) => ip.is_loopback(),
+        Err(_) => host.eq_ignore_ascii_case("localhost"),
+    }
+}
 
-    let response = client
-        .post(url)
-        .header("x-goog-api-key", api_key)
-        .header("Content-Type", "application/json")
-        .json(&request_body)
-        .send()?;
+fn proxy_from_env() -> Option<String> {
+    ["HTTPS_PROXY", "https_proxy"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find_map(|value| normalized(&value).map(str::to_string))
+}
 
-    let response = response.error_for_status()?;
-    let parsed = response.json::<GenerateContentResponse>()?;
+/// Submit an image generation request to Gemini's Imagen 4 API.
+///
+/// Convenience wrapper around [`GeminiClient::generate_image`] with default
+/// options.
+///
+/// # Errors
+///
+/// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty or
+/// whitespace only. Network and HTTP errors are surfaced via `reqwest`.
+pub fn generate_image(api_key: &str, prompt: &str) -> Result<PredictResponse, GeminiError> {
+    GeminiClient::new(api_key)?.generate_image(prompt)
+}
 
-    // Extract the JSON text from the first candidate's first part
-    let json_text = parsed
-        .candidates
-        .first()
-        .and_then(|c| c.content.parts.first())
-        .map(|p| p.text.as_str())
-        .unwrap_or("{}");
+/// Inpaint the masked region of `base_image` according to `prompt`.
+///
+/// Convenience wrapper around [`GeminiClient::edit_image`] with default
+/// options.
+///
+/// # Errors
+///
+/// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty or
+/// whitespace only. Network and HTTP errors are surfaced via `reqwest`.
+pub fn edit_image(
+    api_key: &str,
+    base_image: &[u8],
+    mask: &[u8],
+    prompt: &str,
+) -> Result<PredictResponse, GeminiError> {
+    GeminiClient::new(api_key)?.edit_image(base_image, mask, prompt)
+}
 
-    // Parse the JSON into PlaceDescription
-    let place_description: PlaceDescription = serde_json::from_str(json_text)?;
+/// Upscale raw image bytes by `factor`.
+///
+/// Convenience wrapper around [`GeminiClient::upscale_image`] with default
+/// options.
+///
+/// # Errors
+///
+/// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty or
+/// whitespace only. Network and HTTP errors are surfaced via `reqwest`.
+pub fn upscale_image(
+    api_key: &str,
+    image: &[u8],
+    factor: UpscaleFactor,
+) -> Result<PredictResponse, GeminiError> {
+    GeminiClient::new(api_key)?.upscale_image(image, factor)
+}
 
-    Ok(place_description)
+/// Submit a text generation request to Gemini's API.
+///
+/// Convenience wrapper around [`GeminiClient::generate_text`] with default
+/// options.
+///
+/// # Errors
+///
+/// Returns [`GeminiError::MissingApiKey`] when the provided API key is empty or
+/// whitespace only. Network and HTTP errors are surfaced via `reqwest`.
+pub fn generate_text(api_key: &str, prompt: &str) -> Result<GenerateContentResponse, GeminiError> {
+    GeminiClient::new(api_key)?.generate_text(prompt)
+}
+
+/// Ask Gemini for a structured description of `location`.
+///
+/// Convenience wrapper around [`GeminiClient::generate_place_description`]
+/// with default options.
+pub fn generate_place_description(
+    location: &str,
+    season: &str,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+    api_key: &str,
+) -> Result<DescribedPlace, GeminiError> {
+    GeminiClient::new(api_key)?.generate_place_description(location, season, time_of_day, weather)
 }
 
 #[cfg(test)]
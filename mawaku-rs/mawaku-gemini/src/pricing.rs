@@ -0,0 +1,159 @@
+use mawaku_config::PricingConfig;
+use std::collections::BTreeMap;
+
+/// Currency of the built-in prices.
+pub const DEFAULT_CURRENCY: &str = "USD";
+
+/// Tokens assumed for the prompt of one place description request.
+pub const DESCRIPTION_INPUT_TOKENS_ESTIMATE: u64 = 150;
+/// Tokens assumed for one place description, thinking included, when no
+/// `maxOutputTokens` cap is set.
+pub const DESCRIPTION_OUTPUT_TOKENS_ESTIMATE: u64 = 1_000;
+/// Tokens assumed for the prompt of one orientation request, which carries
+/// the place description.
+pub const ORIENTATION_INPUT_TOKENS_ESTIMATE: u64 = 300;
+/// Tokens assumed for the one-word orientation answer.
+pub const ORIENTATION_OUTPUT_TOKENS_ESTIMATE: u64 = 10;
+
+/// Built-in Imagen prices per generated image, in USD.
+const IMAGE_PRICES: &[(&str, f64)] = &[
+    ("imagen-3.0-generate-002", 0.03),
+    ("imagen-4.0-fast-generate-001", 0.02),
+    ("imagen-4.0-generate-001", 0.04),
+    ("imagen-4.0-ultra-generate-001", 0.06),
+];
+
+/// Built-in Gemini prices per million input and output tokens, in USD.
+const TEXT_PRICES: &[(&str, f64, f64)] = &[
+    ("gemini-2.0-flash", 0.10, 0.40),
+    ("gemini-2.5-flash", 0.30, 2.50),
+    ("gemini-2.5-flash-lite", 0.10, 0.40),
+    ("gemini-2.5-pro", 1.25, 10.00),
+];
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct ImagePrice {
+    pub per_image: f64,
+    /// Prices for aspect ratios billed differently from `per_image`.
+    pub aspect_ratios: BTreeMap<String, f64>,
+}
+
+impl ImagePrice {
+    pub fn for_aspect_ratio(&self, aspect_ratio: Option<&str>) -> f64 {
+        aspect_ratio
+            .and_then(|ratio| self.aspect_ratios.get(ratio.trim()))
+            .copied()
+            .unwrap_or(self.per_image)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TokenPrice {
+    pub input_per_million: f64,
+    pub output_per_million: f64,
+}
+
+impl TokenPrice {
+    pub fn cost(self, input_tokens: u64, output_tokens: u64) -> f64 {
+        (input_tokens as f64 * self.input_per_million
+            + output_tokens as f64 * self.output_per_million)
+            / 1_000_000.0
+    }
+}
+
+/// Prices per model behind cost estimates. The defaults reflect Google's
+/// published pricing when this version was released; override them with
+/// [`Self::with_config`] when it changes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PriceTable {
+    /// Label for the prices; nothing is converted.
+    pub currency: String,
+    pub images: BTreeMap<String, ImagePrice>,
+    pub text: BTreeMap<String, TokenPrice>,
+}
+
+impl Default for PriceTable {
+    fn default() -> Self {
+        Self {
+            currency: DEFAULT_CURRENCY.to_string(),
+            images: IMAGE_PRICES
+                .iter()
+                .map(|(model, per_image)| {
+                    let price = ImagePrice {
+                        per_image: *per_image,
+                        aspect_ratios: BTreeMap::new(),
+                    };
+                    (model.to_string(), price)
+                })
+                .collect(),
+            text: TEXT_PRICES
+                .iter()
+                .map(|(model, input_per_million, output_per_million)| {
+                    let price = TokenPrice {
+                        input_per_million: *input_per_million,
+                        output_per_million: *output_per_million,
+                    };
+                    (model.to_string(), price)
+                })
+                .collect(),
+        }
+    }
+}
+
+impl PriceTable {
+    /// This table with the prices and currency from the `[pricing]`
+    /// configuration taking precedence.
+    pub fn with_config(mut self, config: &PricingConfig) -> Self {
+        if let Some(currency) = config
+            .currency
+            .as_deref()
+            .map(str::trim)
+            .filter(|currency| !currency.is_empty())
+        {
+            self.currency = currency.to_string();
+        }
+        for (model, price) in &config.images {
+            let price = ImagePrice {
+                per_image: price.per_image,
+                aspect_ratios: price.aspect_ratios.clone(),
+            };
+            self.images.insert(model.trim().to_string(), price);
+        }
+        for (model, price) in &config.text {
+            let price = TokenPrice {
+                input_per_million: price.input_per_million,
+                output_per_million: price.output_per_million,
+            };
+            self.text.insert(model.trim().to_string(), price);
+        }
+        self
+    }
+
+    /// Cost of one image request returning `sample_count` images, or `None`
+    /// when `model` has no price.
+    pub fn image_cost(
+        &self,
+        model: &str,
+        sample_count: u32,
+        aspect_ratio: Option<&str>,
+    ) -> Option<f64> {
+        let price = self.images.get(model.trim())?;
+        Some(price.for_aspect_ratio(aspect_ratio) * f64::from(sample_count))
+    }
+
+    /// Cost of one text request, or `None` when `model` has no price.
+    pub fn text_cost(&self, model: &str, input_tokens: u64, output_tokens: u64) -> Option<f64> {
+        self.text
+            .get(model.trim())
+            .map(|price| price.cost(input_tokens, output_tokens))
+    }
+}
+
+/// Estimated cost in USD of one image request returning `sample_count`
+/// images, using the built-in [`PriceTable`]. Models without a price
+/// cost `0.0`; use [`PriceTable::image_cost`] to tell them apart.
+pub fn estimate_cost(model: &str, sample_count: u32, aspect_ratio: Option<&str>) -> f64 {
+    PriceTable::default()
+        .image_cost(model, sample_count, aspect_ratio)
+        .unwrap_or_default()
+}
@@ -0,0 +1,57 @@
+use std::num::NonZeroU32;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// Token bucket spacing requests evenly at a requests-per-minute rate.
+///
+/// The bucket holds a single token, so requests never burst above the rate
+/// and a per-minute quota holds over any window. Clones share one bucket,
+/// which lets concurrent batch workers queue behind each other.
+#[derive(Debug, Clone)]
+pub struct RateLimiter {
+    bucket: Arc<Mutex<Bucket>>,
+    interval: Duration,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    /// When the next request may be sent.
+    next_free: Option<Instant>,
+}
+
+impl RateLimiter {
+    pub fn per_minute(requests: NonZeroU32) -> Self {
+        Self {
+            bucket: Arc::new(Mutex::new(Bucket { next_free: None })),
+            interval: Duration::from_secs(60) / requests.get(),
+        }
+    }
+
+    /// Time between two requests.
+    pub fn interval(&self) -> Duration {
+        self.interval
+    }
+
+    /// Claim the next slot at or after `now`, returning how long to wait
+    /// before using it.
+    pub(crate) fn reserve(&self, now: Instant) -> Duration {
+        let mut bucket = self
+            .bucket
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner());
+        let slot = bucket.next_free.map_or(now, |next_free| next_free.max(now));
+        bucket.next_free = Some(slot + self.interval);
+        slot - now
+    }
+
+    /// Block until a request may be sent, returning how long that took.
+    /// Time already spent elsewhere, such as a retry backoff, counts toward
+    /// the wait.
+    pub fn acquire(&self) -> Duration {
+        let wait = self.reserve(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+        wait
+    }
+}
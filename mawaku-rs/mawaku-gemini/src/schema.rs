@@ -0,0 +1,192 @@
+use std::collections::BTreeMap;
+
+use serde::Serialize;
+
+use crate::GeminiError;
+
+/// Types accepted in a Gemini `responseSchema`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "UPPERCASE")]
+pub enum SchemaType {
+    String,
+    Integer,
+    Number,
+    Boolean,
+    Array,
+    Object,
+}
+
+/// A structured-output schema in the subset of OpenAPI that Gemini accepts.
+///
+/// Build one with the type constructors, e.g.
+/// `Schema::object().property("warmth", Schema::number())`, or derive it from
+/// a Rust type through [`ToSchema`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct Schema {
+    #[serde(rename = "type")]
+    schema_type: SchemaType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    nullable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    items: Option<Box<Schema>>,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    properties: BTreeMap<String, Schema>,
+    #[serde(rename = "propertyOrdering", skip_serializing_if = "Vec::is_empty")]
+    property_ordering: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    required: Vec<String>,
+}
+
+impl Schema {
+    fn of(schema_type: SchemaType) -> Self {
+        Self {
+            schema_type,
+            description: None,
+            nullable: false,
+            items: None,
+            properties: BTreeMap::new(),
+            property_ordering: Vec::new(),
+            required: Vec::new(),
+        }
+    }
+
+    pub fn string() -> Self {
+        Self::of(SchemaType::String)
+    }
+
+    pub fn integer() -> Self {
+        Self::of(SchemaType::Integer)
+    }
+
+    pub fn number() -> Self {
+        Self::of(SchemaType::Number)
+    }
+
+    pub fn boolean() -> Self {
+        Self::of(SchemaType::Boolean)
+    }
+
+    pub fn array(items: Schema) -> Self {
+        Self {
+            items: Some(Box::new(items)),
+            ..Self::of(SchemaType::Array)
+        }
+    }
+
+    /// An object with no properties yet; add them with [`Self::property`].
+    pub fn object() -> Self {
+        Self::of(SchemaType::Object)
+    }
+
+    pub fn schema_type(&self) -> SchemaType {
+        self.schema_type
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    pub fn nullable(mut self) -> Self {
+        self.nullable = true;
+        self
+    }
+
+    /// Add a required property. Properties are emitted in the order they are
+    /// added through `propertyOrdering`; adding a name again replaces its
+    /// schema and keeps its position.
+    pub fn property(mut self, name: impl Into<String>, schema: Schema) -> Self {
+        let name = name.into();
+        if !self.required.contains(&name) {
+            self.required.push(name.clone());
+        }
+        self.insert_property(name, schema);
+        self
+    }
+
+    /// Add a property the model may leave out.
+    pub fn optional_property(mut self, name: impl Into<String>, schema: Schema) -> Self {
+        let name = name.into();
+        self.required.retain(|required| *required != name);
+        self.insert_property(name, schema);
+        self
+    }
+
+    fn insert_property(&mut self, name: String, schema: Schema) {
+        if self.properties.insert(name.clone(), schema).is_none() {
+            self.property_ordering.push(name);
+        }
+    }
+
+    /// Check the rules Gemini enforces on top of the type vocabulary: arrays
+    /// need an item schema, objects need at least one property, and nothing
+    /// else may carry either.
+    ///
+    /// # Errors
+    ///
+    /// Returns [`GeminiError::InvalidSchema`] naming the offending path, e.g.
+    /// `schema.lighting.warmth`.
+    pub fn validate(&self) -> Result<(), GeminiError> {
+        self.validate_at("schema")
+            .map_err(GeminiError::InvalidSchema)
+    }
+
+    fn validate_at(&self, path: &str) -> Result<(), String> {
+        match self.schema_type {
+            SchemaType::Array => match self.items.as_deref() {
+                Some(items) => items.validate_at(&format!("{path}[]"))?,
+                None => return Err(format!("{path}: ARRAY needs an item schema")),
+            },
+            SchemaType::Object if self.properties.is_empty() => {
+                return Err(format!("{path}: OBJECT needs at least one property"));
+            }
+            SchemaType::Object => {
+                for (name, property) in &self.properties {
+                    property.validate_at(&format!("{path}.{name}"))?;
+                }
+            }
+            scalar => {
+                if self.items.is_some() || !self.properties.is_empty() {
+                    return Err(format!(
+                        "{path}: {scalar:?} cannot have items or properties"
+                    ));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Rust types with a direct Gemini schema equivalent.
+pub trait ToSchema {
+    fn schema() -> Schema;
+}
+
+macro_rules! impl_to_schema {
+    ($constructor:ident: $($ty:ty),+) => {
+        $(impl ToSchema for $ty {
+            fn schema() -> Schema {
+                Schema::$constructor()
+            }
+        })+
+    };
+}
+
+impl_to_schema!(string: String, &str, char);
+impl_to_schema!(integer: i8, i16, i32, i64, u8, u16, u32, u64, isize, usize);
+impl_to_schema!(number: f32, f64);
+impl_to_schema!(boolean: bool);
+
+impl<T: ToSchema> ToSchema for Vec<T> {
+    fn schema() -> Schema {
+        Schema::array(T::schema())
+    }
+}
+
+impl<T: ToSchema> ToSchema for Option<T> {
+    fn schema() -> Schema {
+        T::schema().nullable()
+    }
+}
@@ -1,8 +1,9 @@
 use super::*;
+use std::collections::HashMap;
 
 #[test]
 fn serialize_request_matches_expected_shape() {
-    let request = PredictRequest::new("A cozy home office", DEFAULT_SAMPLE_COUNT, None);
+    let request = PredictRequest::new("A cozy home office", DEFAULT_SAMPLE_COUNT, None, None);
     let value = serde_json::to_value(request).expect("serialize request");
 
     let expected = serde_json::json!({
@@ -13,6 +14,182 @@ fn serialize_request_matches_expected_shape() {
     assert_eq!(value, expected);
 }
 
+#[test]
+fn serialize_request_includes_negative_prompt() {
+    let request = PredictRequest::new(
+        "A cozy home office",
+        1,
+        None,
+        Some("logos, watermarks".to_string()),
+    );
+    let value = serde_json::to_value(request).expect("serialize request");
+
+    assert_eq!(
+        value["parameters"]["negativePrompt"],
+        serde_json::json!("logos, watermarks")
+    );
+}
+
+#[test]
+fn imagen_4_gets_the_negative_prompt_in_the_prompt_text() {
+    assert_eq!(
+        apply_negative_prompt(
+            DEFAULT_IMG_MODEL_VERSION,
+            "A cozy home office",
+            Some(" logos, watermarks ")
+        ),
+        (
+            "A cozy home office\n\nAvoid: logos, watermarks.".to_string(),
+            None
+        )
+    );
+    assert_eq!(
+        apply_negative_prompt(
+            "imagen-3.0-generate-001",
+            "A cozy home office",
+            Some("logos, watermarks")
+        ),
+        (
+            "A cozy home office".to_string(),
+            Some("logos, watermarks".to_string())
+        )
+    );
+    assert_eq!(
+        apply_negative_prompt(DEFAULT_IMG_MODEL_VERSION, "A cozy home office", Some("  ")),
+        ("A cozy home office".to_string(), None)
+    );
+}
+
+#[test]
+fn serialize_request_includes_seed_only_when_set() {
+    let request = PredictRequest::new("A cozy home office", 1, None, None).with_seed(Some(42));
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert_eq!(value["parameters"]["seed"], serde_json::json!(42));
+    assert!(value["parameters"].get("addWatermark").is_none());
+
+    let request = PredictRequest::new("A cozy home office", 1, None, None).with_seed(None);
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert!(value["parameters"].get("seed").is_none());
+    assert!(value["parameters"].get("addWatermark").is_none());
+
+    let client = GeminiClient::new("secret").expect("client").with_seed(7);
+    assert_eq!(client.options().seed, Some(7));
+}
+
+#[test]
+fn watermark_is_only_disabled_on_request() {
+    let request = PredictRequest::new("A cozy home office", 1, None, None)
+        .with_seed(Some(42))
+        .with_watermark_disabled(true);
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert_eq!(
+        value["parameters"]["addWatermark"],
+        serde_json::json!(false)
+    );
+
+    let request =
+        PredictRequest::new("A cozy home office", 1, None, None).with_watermark_disabled(false);
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert!(value["parameters"].get("addWatermark").is_none());
+    assert!(!GeminiClientOptions::default().disable_watermark);
+}
+
+#[test]
+fn seeds_are_only_trusted_behind_a_custom_base_url() {
+    let options = GeminiClientOptions {
+        base_url: Some(DEFAULT_BASE_URL.to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+    assert!(!client.honors_seed());
+
+    let options = GeminiClientOptions {
+        base_url: Some("http://127.0.0.1:8080".to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+    assert!(client.honors_seed());
+}
+
+#[test]
+fn serialize_request_includes_person_generation() {
+    let request = PredictRequest::new("A cozy home office", 1, None, None)
+        .with_person_generation(Some(PersonGeneration::DontAllow));
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert_eq!(
+        value["parameters"]["personGeneration"],
+        serde_json::json!("dont_allow")
+    );
+
+    assert_eq!(
+        GeminiClientOptions::default().person_generation,
+        Some(PersonGeneration::DontAllow)
+    );
+    assert_eq!(
+        "allow-adult".parse::<PersonGeneration>(),
+        Ok(PersonGeneration::AllowAdult)
+    );
+    assert_eq!(PersonGeneration::AllowAll.to_string(), "allow_all");
+    assert!("everyone".parse::<PersonGeneration>().is_err());
+}
+
+#[test]
+fn serialize_edit_request_encodes_reference_images() {
+    let request = EditRequest::inpaint("Add a plant", b"hello", b"mask", 2);
+    let value = serde_json::to_value(request).expect("serialize request");
+
+    let instance = &value["instances"][0];
+    assert_eq!(instance["prompt"], "Add a plant");
+    let references = instance["referenceImages"]
+        .as_array()
+        .expect("reference images array");
+    assert_eq!(references.len(), 2);
+    assert_eq!(references[0]["referenceType"], "REFERENCE_TYPE_RAW");
+    assert_eq!(
+        references[0]["referenceImage"]["bytesBase64Encoded"],
+        "aGVsbG8="
+    );
+    assert!(references[0].get("maskImageConfig").is_none());
+    assert_eq!(references[1]["referenceType"], "REFERENCE_TYPE_MASK");
+    assert_eq!(
+        references[1]["maskImageConfig"]["maskMode"],
+        "MASK_MODE_USER_PROVIDED"
+    );
+    assert_eq!(
+        value["parameters"],
+        serde_json::json!({"editMode": "EDIT_MODE_INPAINT_INSERTION", "sampleCount": 2})
+    );
+}
+
+#[test]
+fn serialize_upscale_request_matches_expected_shape() {
+    let request = UpscaleRequest::new("aGVsbG8=".to_string(), UpscaleFactor::X4);
+    let value = serde_json::to_value(request).expect("serialize request");
+
+    let expected = serde_json::json!({
+        "instances": [{"prompt": "", "image": {"bytesBase64Encoded": "aGVsbG8="}}],
+        "parameters": {
+            "mode": "upscale",
+            "upscaleConfig": {"upscaleFactor": "x4"},
+            "sampleCount": 1
+        },
+    });
+    assert_eq!(value, expected);
+}
+
+#[test]
+fn upscale_factor_parses_from_str() {
+    assert_eq!("x2".parse::<UpscaleFactor>(), Ok(UpscaleFactor::X2));
+    assert_eq!(" X4 ".parse::<UpscaleFactor>(), Ok(UpscaleFactor::X4));
+    assert!("x3".parse::<UpscaleFactor>().is_err());
+}
+
+#[test]
+fn edit_image_rejects_empty_api_key() {
+    let error = edit_image("", b"base", b"mask", "prompt").expect_err("missing key");
+    assert!(matches!(error, GeminiError::MissingApiKey));
+}
+
 #[test]
 fn craft_prompt_builds_contextual_description() {
     let prompt = craft_prompt(
@@ -20,20 +197,129 @@ fn craft_prompt_builds_contextual_description() {
         "Lisbon, Portugal",
         Some("spring"),
         Some("golden hour"),
+        Some("light rain"),
     );
 
     assert!(prompt.contains("Base instructions."));
     assert!(prompt.contains("Lisbon, Portugal"));
     assert!(prompt.contains("spring"));
     assert!(prompt.contains("golden hour"));
+    assert!(prompt.contains("light rain"));
 }
 
 #[test]
 fn craft_prompt_ignores_empty_inputs() {
-    let prompt = craft_prompt("  ", "   ", Some("  "), Some(""));
+    let prompt = craft_prompt("  ", "   ", Some("  "), Some(""), Some(" "));
     assert!(prompt.is_empty());
 }
 
+#[test]
+fn craft_prompt_with_locale_translates_only_appended_sentences() {
+    let prompt = craft_prompt_with_locale(
+        "Photo.",
+        &["京都", "Oslo"],
+        Some("秋"),
+        Some(" 夕暮れ "),
+        Some("小雨"),
+        &[("Mood", "calm")],
+        PromptLocale::Japanese,
+    );
+
+    assert_eq!(
+        prompt,
+        "Photo. 京都とOsloを調和よく融合させたシーンを作り、居心地が良く生活感のある室内からの視点でその雰囲気を表現してください。 季節は秋です。 夕暮れの光を捉えてください。 外の天気は小雨です。 Mood：calm。"
+    );
+    assert_eq!("ja".parse::<PromptLocale>(), Ok(PromptLocale::Japanese));
+    assert!("fr".parse::<PromptLocale>().is_err());
+}
+
+#[test]
+fn every_locale_skips_empty_inputs() {
+    for locale in [PromptLocale::English, PromptLocale::Japanese] {
+        let prompt = craft_prompt_with_locale(
+            "  ",
+            &["   ", ""],
+            Some("  "),
+            Some(""),
+            Some(" "),
+            &[("Mood", " "), (" ", "calm")],
+            locale,
+        );
+        assert_eq!(prompt, "", "{locale}");
+    }
+    assert_eq!(
+        craft_prompt_with_locale(
+            "Base.",
+            &["Kyoto"],
+            Some("spring"),
+            None,
+            None,
+            &[],
+            PromptLocale::English,
+        ),
+        craft_prompt("Base.", "Kyoto", Some("spring"), None, None)
+    );
+}
+
+#[test]
+fn normalized_matches_trimmed_or_none() {
+    for input in ["", "   ", "\t\n", " Kyoto ", "Oslo", "\u{3000}dusk\u{3000}"] {
+        assert_eq!(normalized(input), trimmed_or_none(Some(input)), "{input:?}");
+    }
+    assert_eq!(normalized("  Kyoto\n"), Some("Kyoto"));
+    assert_eq!(normalized(" \t "), None);
+    assert_eq!(trimmed_or_none(None), None);
+}
+
+#[test]
+fn prompt_building_blocks_compose_into_craft_prompt() {
+    let prompt = craft_prompt(
+        default_prompt(),
+        "Kyoto",
+        Some("spring"),
+        Some("dawn"),
+        Some("light rain"),
+    );
+    let composed = [
+        Some(default_prompt().to_string()),
+        location_directive("Kyoto"),
+        season_directive("spring"),
+        time_of_day_directive("dawn"),
+        weather_directive("light rain"),
+    ]
+    .into_iter()
+    .flatten()
+    .collect::<Vec<_>>()
+    .join(" ");
+
+    assert_eq!(prompt, composed);
+    assert_eq!(default_prompt(), DEFAULT_PROMPT);
+    assert_eq!(location_directive("  "), None);
+    assert_eq!(
+        locations_directive(&["Kyoto", "Oslo"]).as_deref(),
+        Some(
+            "Create a scene that harmoniously blends Kyoto and Oslo and showcase the atmosphere from a cosy, lived-in interior perspective."
+        )
+    );
+}
+
+#[test]
+fn craft_prompt_with_extras_appends_labelled_segments() {
+    let prompt = craft_prompt_with_extras(
+        "Base instructions.",
+        "Kyoto, Japan",
+        Some("autumn"),
+        None,
+        None,
+        &[("Weather", "light rain"), ("Style", "  "), ("", "ukiyo-e")],
+    );
+
+    assert!(prompt.starts_with("Base instructions."));
+    assert!(prompt.ends_with("It is autumn. Weather: light rain."));
+    assert!(!prompt.contains("Style"));
+    assert!(!prompt.contains("ukiyo-e"));
+}
+
 #[test]
 fn empty_api_key_is_rejected() {
     let error = generate_image("   ", "workspace").expect_err("missing key");
@@ -45,7 +331,476 @@ fn endpoint_uses_defaults() {
     let expected = format!(
         "https://generativelanguage.googleapis.com/v1beta/models/{DEFAULT_IMG_MODEL_VERSION}:predict"
     );
-    assert_eq!(image_endpoint_url(), expected);
+    assert_eq!(
+        image_endpoint_url(DEFAULT_BASE_URL, DEFAULT_IMG_MODEL_VERSION),
+        expected
+    );
+}
+
+#[test]
+fn endpoints_keep_their_path_under_a_custom_base_url() {
+    let base_url = "http://127.0.0.1:8080";
+    assert_eq!(
+        image_endpoint_url(base_url, "imagen"),
+        "http://127.0.0.1:8080/v1beta/models/imagen:predict"
+    );
+    assert_eq!(
+        text_endpoint_url(base_url, "gemini"),
+        "http://127.0.0.1:8080/v1beta/models/gemini:generateContent"
+    );
+    assert_eq!(
+        models_endpoint_url(base_url),
+        "http://127.0.0.1:8080/v1beta/models"
+    );
+}
+
+#[test]
+fn client_strips_trailing_slash_from_base_url() {
+    let options = GeminiClientOptions {
+        base_url: Some("http://localhost:9000/".to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+    assert_eq!(client.base_url(), "http://localhost:9000");
+}
+
+#[test]
+fn client_requires_https_for_remote_base_urls() {
+    for base_url in [
+        "http://localhost:9000",
+        "http://127.0.0.1:8080",
+        "http://[::1]:8080",
+    ] {
+        let options = GeminiClientOptions {
+            base_url: Some(base_url.to_string()),
+            ..GeminiClientOptions::default()
+        };
+        GeminiClient::with_options("secret", options).expect("loopback http is allowed");
+    }
+
+    let options = GeminiClientOptions {
+        base_url: Some("http://gemini.example.com".to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let error = GeminiClient::with_options("secret", options).expect_err("remote http rejected");
+    assert!(
+        matches!(&error, GeminiError::InsecureBaseUrl { url } if url == "http://gemini.example.com"),
+        "{error:?}"
+    );
+}
+
+#[test]
+fn client_rejects_base_url_without_http_scheme() {
+    for base_url in ["localhost:9000", "ftp://example.com", "not a url"] {
+        let options = GeminiClientOptions {
+            base_url: Some(base_url.to_string()),
+            ..GeminiClientOptions::default()
+        };
+        let error = GeminiClient::with_options("secret", options).expect_err("invalid base URL");
+        assert!(
+            matches!(&error, GeminiError::InvalidBaseUrl { url } if url == base_url),
+            "{base_url}: {error:?}"
+        );
+    }
+}
+
+#[test]
+fn request_dump_redacts_the_api_key_and_elides_image_bytes() {
+    let request_body = PredictRequest::new("a quiet harbour", 2, Some("16:9".to_string()), None);
+    let request = Client::new()
+        .post("https://example.test/v1beta/models/imagen:predict?key=secret-key")
+        .header("x-goog-api-key", "secret-key")
+        .header("X-Org-Id", "studio")
+        .json(&request_body)
+        .build()
+        .expect("build request");
+
+    let dump = format_request_dump(&request, "secret-key");
+    assert!(!dump.contains("secret-key"), "{dump}");
+    assert!(dump.starts_with(&format!(
+        "POST https://example.test/v1beta/models/imagen:predict?key={REDACTED}"
+    )));
+    assert!(dump.contains(&format!("x-goog-api-key: {REDACTED}")));
+    assert!(dump.contains("x-org-id: studio"));
+    assert!(dump.contains("\"sampleCount\": 2"), "{dump}");
+    assert!(dump.contains("\"aspectRatio\": \"16:9\""), "{dump}");
+
+    let request = Client::new()
+        .post("https://example.test/v1beta/models/imagen:predict")
+        .json(&UpscaleRequest::new("aW1n".repeat(100), UpscaleFactor::X2))
+        .build()
+        .expect("build request");
+    let dump = format_request_dump(&request, "secret-key");
+    assert!(dump.contains("<400 base64 characters>"), "{dump}");
+    assert!(!dump.contains("aW1naW1n"));
+}
+
+#[test]
+fn request_dumps_go_to_the_caller_supplied_dumper() {
+    use std::net::TcpListener;
+    use std::sync::{Arc, Mutex};
+
+    // Bind and drop a listener for a port that is known to be closed.
+    let address = TcpListener::bind("127.0.0.1:0")
+        .and_then(|listener| listener.local_addr())
+        .expect("reserve a closed port");
+    let dumps = Arc::new(Mutex::new(Vec::new()));
+    let collected = Arc::clone(&dumps);
+    let options = GeminiClientOptions {
+        base_url: Some(format!("http://{address}")),
+        max_retries: 0,
+        dump_requests: Some(RequestDumper::new(move |request| {
+            collected.lock().expect("dumps").push(request.to_string());
+        })),
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+
+    assert!(client.generate_image("a quiet harbour").is_err());
+    let dumps = dumps.lock().expect("dumps");
+    assert_eq!(dumps.len(), 1);
+    assert!(dumps[0].contains("a quiet harbour"));
+    assert!(!dumps[0].contains("secret"));
+}
+
+/// Answer a single Imagen request with one prediction. The handle yields the
+/// request line and the JSON body that was sent.
+fn serve_prediction() -> (
+    std::net::SocketAddr,
+    std::thread::JoinHandle<(String, serde_json::Value)>,
+) {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+    let address = listener.local_addr().expect("mock server address");
+    let server = std::thread::spawn(move || {
+        let (stream, _) = listener.accept().expect("accept request");
+        let mut reader = BufReader::new(stream);
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).expect("read request");
+        let mut content_length = 0;
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).expect("read header");
+            if header.trim().is_empty() {
+                break;
+            }
+            if let Some((name, value)) = header.split_once(':')
+                && name.eq_ignore_ascii_case("content-length")
+            {
+                content_length = value.trim().parse().expect("content length");
+            }
+        }
+        let mut request_body = vec![0; content_length];
+        std::io::Read::read_exact(&mut reader, &mut request_body).expect("read body");
+        let body = r#"{"predictions":[{"bytesBase64Encoded":"aW1n","mimeType":"image/png"}]}"#;
+        write!(
+            reader.get_mut(),
+            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+            body.len()
+        )
+        .expect("write response");
+        let request_body = serde_json::from_slice(&request_body).expect("request body is JSON");
+        (request_line, request_body)
+    });
+    (address, server)
+}
+
+#[test]
+fn image_requests_are_sent_to_the_custom_base_url() {
+    let (address, server) = serve_prediction();
+    let options = GeminiClientOptions {
+        base_url: Some(format!("http://{address}")),
+        max_retries: 0,
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+    let response = client
+        .generate_image("a quiet harbour")
+        .expect("mock response");
+
+    let (request_line, _) = server.join().expect("mock server");
+    assert_eq!(
+        request_line.trim(),
+        format!("POST /v1beta/models/{DEFAULT_IMG_MODEL_VERSION}:predict HTTP/1.1")
+    );
+    assert_eq!(
+        response.predictions[0].bytes_base64_encoded.as_deref(),
+        Some("aW1n")
+    );
+}
+
+#[test]
+fn image_responses_echo_the_prompt_that_was_sent() {
+    let (address, server) = serve_prediction();
+    let options = GeminiClientOptions {
+        base_url: Some(format!("http://{address}")),
+        max_retries: 0,
+        negative_prompt: Some("people".to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+    let response = client
+        .generate_image("  a quiet harbour  ")
+        .expect("mock response");
+
+    let (_, request_body) = server.join().expect("mock server");
+    let sent = request_body["instances"][0]["prompt"]
+        .as_str()
+        .expect("prompt in request");
+    // Imagen 4 has no negativePrompt field, so it is folded into the prompt.
+    assert_eq!(sent, "a quiet harbour\n\nAvoid: people.");
+    assert_eq!(response.echoed_prompt, sent);
+}
+
+/// Answer one request per status in `statuses`, in order, then stop. The
+/// handle yields how many requests were served.
+fn serve_statuses(
+    statuses: &'static [u16],
+) -> (std::net::SocketAddr, std::thread::JoinHandle<usize>) {
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    let listener = TcpListener::bind("127.0.0.1:0").expect("bind mock server");
+    let address = listener.local_addr().expect("mock server address");
+    let server = std::thread::spawn(move || {
+        for status in statuses {
+            let (stream, _) = listener.accept().expect("accept request");
+            let mut reader = BufReader::new(stream);
+            let mut content_length = 0;
+            loop {
+                let mut header = String::new();
+                reader.read_line(&mut header).expect("read header");
+                if header.trim().is_empty() {
+                    break;
+                }
+                if let Some((name, value)) = header.split_once(':')
+                    && name.eq_ignore_ascii_case("content-length")
+                {
+                    content_length = value.trim().parse().expect("content length");
+                }
+            }
+            let mut request_body = vec![0; content_length];
+            std::io::Read::read_exact(&mut reader, &mut request_body).expect("read body");
+            let body = match status {
+                200 => r#"{"predictions":[{"bytesBase64Encoded":"aW1n","mimeType":"image/png"}]}"#,
+                _ => r#"{"error":{"message":"try again later"}}"#,
+            };
+            write!(
+                reader.get_mut(),
+                "HTTP/1.1 {status} Mock\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                body.len()
+            )
+            .expect("write response");
+        }
+        statuses.len()
+    });
+    (address, server)
+}
+
+#[test]
+fn transient_statuses_are_retried_until_a_request_succeeds() {
+    let (address, server) = serve_statuses(&[503, 200]);
+    let options = GeminiClientOptions {
+        max_retries: 2,
+        retry_delay: Duration::ZERO,
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+    let attempts = std::sync::Mutex::new(Vec::new());
+    let on_retry = |attempt: RetryAttempt| attempts.lock().expect("attempts").push(attempt.retry);
+
+    let response = client
+        .send_with_retries(
+            client.http.post(format!("http://{address}/predict")),
+            Some(&on_retry),
+        )
+        .expect("second attempt succeeds");
+
+    assert_eq!(server.join().expect("mock server"), 2);
+    assert_eq!(*attempts.lock().expect("attempts"), vec![1]);
+    let parsed = response
+        .json::<PredictResponse>()
+        .expect("parse mock response");
+    assert_eq!(
+        parsed.predictions[0].bytes_base64_encoded.as_deref(),
+        Some("aW1n")
+    );
+}
+
+#[test]
+fn retries_stop_after_max_retries_with_the_last_status() {
+    let (address, server) = serve_statuses(&[503, 502, 503]);
+    let options = GeminiClientOptions {
+        max_retries: 2,
+        retry_delay: Duration::ZERO,
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("client");
+
+    let response = client
+        .send_with_retries(client.http.post(format!("http://{address}/predict")), None)
+        .expect("the last response is returned");
+
+    assert_eq!(server.join().expect("mock server"), 3);
+    assert_eq!(response.status().as_u16(), 503);
+}
+
+#[test]
+fn client_rejects_empty_api_key() {
+    let error = GeminiClient::new(" \t ").expect_err("missing key");
+    assert!(matches!(error, GeminiError::MissingApiKey));
+}
+
+#[test]
+fn client_rejects_invalid_proxy() {
+    let options = GeminiClientOptions {
+        proxy: Some("not a url".to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let error = GeminiClient::with_options("secret", options).expect_err("invalid proxy");
+    assert!(matches!(error, GeminiError::InvalidProxy { proxy, .. } if proxy == "not a url"));
+}
+
+#[test]
+fn client_reports_proxy_connection_failures() {
+    let options = GeminiClientOptions {
+        proxy: Some("http://127.0.0.1:9".to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("build client");
+    assert_eq!(client.proxy(), Some("http://127.0.0.1:9"));
+
+    let error = client
+        .generate_text("hello")
+        .expect_err("proxy unreachable");
+    assert!(matches!(error, GeminiError::ProxyConnect { .. }));
+}
+
+#[test]
+fn extra_headers_skip_reserved_names() {
+    let headers = HashMap::from([
+        ("X-Org-Id".to_string(), "acme".to_string()),
+        ("X-Goog-Api-Key".to_string(), "override".to_string()),
+        ("content-type".to_string(), "text/plain".to_string()),
+    ]);
+
+    let map = build_extra_headers(&headers).expect("valid headers");
+    assert_eq!(map.len(), 1);
+    assert_eq!(
+        map.get("x-org-id").and_then(|v| v.to_str().ok()),
+        Some("acme")
+    );
+}
+
+#[test]
+fn client_rejects_invalid_extra_header() {
+    let options = GeminiClientOptions {
+        extra_headers: HashMap::from([("bad header".to_string(), "value".to_string())]),
+        ..GeminiClientOptions::default()
+    };
+    let error = GeminiClient::with_options("secret", options).expect_err("invalid header");
+    assert!(matches!(error, GeminiError::InvalidHeader { name } if name == "bad header"));
+}
+
+#[test]
+fn client_rejects_invalid_user_agent() {
+    let options = GeminiClientOptions {
+        user_agent: "mawaku\ncli".to_string(),
+        ..GeminiClientOptions::default()
+    };
+    let error = GeminiClient::with_options("secret", options).expect_err("invalid user agent");
+    assert!(matches!(error, GeminiError::InvalidHeader { name } if name == "User-Agent"));
+}
+
+#[test]
+fn default_user_agent_identifies_mawaku() {
+    assert_eq!(
+        GeminiClientOptions::default().user_agent,
+        format!("mawaku-gemini/{}", env!("CARGO_PKG_VERSION"))
+    );
+}
+
+#[test]
+fn rate_limiter_spaces_requests_evenly() {
+    let limiter = RateLimiter::per_minute(NonZeroU32::new(30).expect("non-zero"));
+    assert_eq!(limiter.interval(), Duration::from_secs(2));
+
+    let start = std::time::Instant::now();
+    assert_eq!(limiter.reserve(start), Duration::ZERO);
+    let shared = limiter.clone();
+    assert_eq!(shared.reserve(start), Duration::from_secs(2));
+    assert_eq!(
+        limiter.reserve(start + Duration::from_secs(1)),
+        Duration::from_secs(3)
+    );
+    // A long pause, such as a retry backoff, leaves the next slot free.
+    assert_eq!(
+        limiter.reserve(start + Duration::from_secs(60)),
+        Duration::ZERO
+    );
+}
+
+#[test]
+fn client_uses_default_options() {
+    let client = GeminiClient::new("secret").expect("build client");
+    let options = client.options();
+    assert_eq!(options.image_model, DEFAULT_IMG_MODEL_VERSION);
+    assert_eq!(options.text_model, DEFAULT_TEXT_MODEL_VERSION);
+    assert_eq!(options.sample_count, DEFAULT_SAMPLE_COUNT);
+    assert_eq!(options.aspect_ratio.as_deref(), Some(DEFAULT_ASPECT_RATIO));
+    assert_eq!(options.timeout, DEFAULT_REQUEST_TIMEOUT);
+    assert_eq!(options.max_retries, DEFAULT_MAX_RETRIES);
+    assert_eq!(options.requests_per_minute, None);
+}
+
+#[test]
+fn retries_back_off_on_transient_statuses() {
+    assert_eq!(
+        retry_delay(Duration::from_secs(1), 1),
+        Duration::from_secs(1)
+    );
+    assert_eq!(
+        retry_delay(Duration::from_secs(1), 3),
+        Duration::from_secs(4)
+    );
+    assert!(is_retryable_status(429));
+    assert!(is_retryable_status(503));
+    assert!(!is_retryable_status(400));
+    assert!(!is_retryable_status(200));
+}
+
+#[test]
+fn auth_failures_are_reported_as_unauthorized() {
+    assert!(is_auth_failure(401));
+    assert!(is_auth_failure(403));
+    assert!(!is_auth_failure(404));
+    assert!(!is_auth_failure(500));
+
+    let message = GeminiError::Unauthorized { status: 403 }.to_string();
+    assert!(message.contains("HTTP 403"));
+    assert!(message.contains("Generative Language API"));
+    assert!(message.contains("https://aistudio.google.com/apikey"));
+}
+
+#[test]
+fn default_retry_reporting_falls_back_to_plain_generation() {
+    struct Canned;
+
+    impl ImageGenerator for Canned {
+        fn generate(&self, prompt: &str) -> Result<PredictResponse, GeminiError> {
+            Ok(PredictResponse {
+                predictions: Vec::new(),
+                echoed_prompt: prompt.to_string(),
+            })
+        }
+    }
+
+    let response = Canned
+        .generate_reporting_retries("A loft", Some("1:1"), &|_| panic!("no retries expected"))
+        .expect("generate");
+    assert_eq!(response.echoed_prompt, "A loft");
 }
 
 #[test]
@@ -67,33 +822,21 @@ fn parses_prediction_payload_with_base64() {
     let prediction = &response.predictions[0];
     assert_eq!(prediction.bytes_base64_encoded.as_deref(), Some("aGVsbG8="));
     assert_eq!(prediction.mime_type.as_deref(), Some("image/png"));
+    assert!(response.echoed_prompt.is_empty());
 }
 
 #[test]
 fn text_request_with_schema_serializes_correctly() {
-    let schema_properties = serde_json::json!({
-        "ambiance": { "type": "STRING" },
-        "items": {
-            "type": "ARRAY",
-            "items": { "type": "STRING" }
-        },
-        "keywords": {
-            "type": "ARRAY",
-            "items": { "type": "STRING" }
-        }
-    });
+    let schema = Schema::object()
+        .property("ambiance", Schema::string())
+        .property("items", Vec::<String>::schema())
+        .property("keywords", Vec::<String>::schema());
 
     let generation_config = GenerationConfig {
-        response_mime_type: "application/json".to_string(),
-        response_schema: ResponseSchema {
-            schema_type: "OBJECT".to_string(),
-            properties: schema_properties,
-            property_ordering: Some(vec![
-                "ambiance".to_string(),
-                "items".to_string(),
-                "keywords".to_string(),
-            ]),
-        },
+        response_mime_type: Some("application/json".to_string()),
+        response_schema: Some(schema),
+        candidate_count: None,
+        max_output_tokens: None,
     };
 
     let request = TextRequest::with_schema("Test prompt", generation_config);
@@ -110,11 +853,60 @@ fn text_request_with_schema_serializes_correctly() {
         "OBJECT"
     );
     assert!(value["generationConfig"]["responseSchema"]["properties"]["ambiance"].is_object());
+    assert_eq!(
+        value["generationConfig"]["responseSchema"]["propertyOrdering"],
+        serde_json::json!(["ambiance", "items", "keywords"])
+    );
+    assert!(value["generationConfig"].get("candidateCount").is_none());
+    assert!(value["generationConfig"].get("maxOutputTokens").is_none());
 }
 
 #[test]
-fn place_description_parses_from_json() {
-    let json = r#"
+fn max_output_tokens_is_added_to_any_generation_config() {
+    let request = TextRequest::new("Describe Hakone").with_max_output_tokens(Some(256));
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert_eq!(value["generationConfig"]["maxOutputTokens"], 256);
+
+    let generation_config = GenerationConfig {
+        response_mime_type: Some("application/json".to_string()),
+        ..GenerationConfig::default()
+    };
+    let request = TextRequest::with_schema("Describe Hakone", generation_config)
+        .with_max_output_tokens(Some(128));
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert_eq!(value["generationConfig"]["maxOutputTokens"], 128);
+    assert_eq!(
+        value["generationConfig"]["responseMimeType"],
+        "application/json"
+    );
+
+    let request = TextRequest::new("Describe Hakone").with_max_output_tokens(None);
+    let value = serde_json::to_value(request).expect("serialize request");
+    assert!(value.get("generationConfig").is_none());
+}
+
+#[test]
+fn place_description_prompt_includes_scene_timing() {
+    let prompt =
+        place_description_prompt(&["Hakone, Japan"], "autumn", Some("evening"), Some("fog"));
+
+    assert!(prompt.starts_with(
+        "Describe the place called Hakone, Japan in autumn during the evening while the weather is fog."
+    ));
+}
+
+#[test]
+fn place_description_prompt_skips_missing_context() {
+    let prompt = place_description_prompt(&["Hakone, Japan"], "any season", None, Some("  "));
+
+    assert!(prompt.starts_with("Describe the place called Hakone, Japan in any season."));
+    assert!(!prompt.contains("during the"));
+    assert!(!prompt.contains("weather is"));
+}
+
+#[test]
+fn place_description_parses_from_json() {
+    let json = r#"
     {
         "ambiance": "Warm and inviting",
         "items": ["wooden chair", "bookshelf", "plant"],
@@ -144,3 +936,802 @@ fn place_description_displays_formatted() {
     assert!(formatted.contains("Items: chair, table"));
     assert!(formatted.contains("Keywords: cozy, rustic"));
 }
+
+fn unique_cache_dir(label: &str) -> std::path::PathBuf {
+    std::env::temp_dir().join(format!(
+        "mawaku-gemini-cache-test-{label}-{}",
+        std::process::id()
+    ))
+}
+
+#[test]
+fn place_description_cache_round_trips_entries() {
+    let dir = unique_cache_dir("round-trip");
+    let cache = PlaceDescriptionCache::new(&dir, std::time::Duration::from_secs(60));
+    let key = PlaceDescriptionCache::key("Hakone, Japan", Some("autumn"), None, None);
+    let description = PlaceDescription {
+        ambiance: "Misty".to_string(),
+        items: vec!["lantern".to_string()],
+        keywords: vec!["onsen".to_string()],
+    };
+
+    assert!(cache.get(&key).is_none());
+    cache.put(&key, &description).expect("write cache entry");
+    let cached = cache.get(&key).expect("cache hit");
+    assert_eq!(cached.ambiance, "Misty");
+    assert_eq!(cached.items, vec!["lantern".to_string()]);
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn image_cache_round_trips_predictions_with_bytes() {
+    let dir = unique_cache_dir("images");
+    let cache = ImageCache::new(&dir);
+    let key = ImageCache::key("A calm office", &[Some("16:9"), None]);
+    assert_ne!(key, ImageCache::key("A calm office", &[Some("9:16"), None]));
+    assert_ne!(key, ImageCache::key("a calm office", &[Some("16:9"), None]));
+
+    let response: PredictResponse = serde_json::from_str(
+        r#"{"predictions": [
+            {"bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png"},
+            {"mimeType": "image/png"}
+        ]}"#,
+    )
+    .expect("parse response");
+
+    assert!(cache.get(&key).is_none());
+    cache.put(&key, &response).expect("write cache entry");
+    let cached = cache.get(&key).expect("cache hit");
+    assert_eq!(cached.predictions.len(), 1);
+    assert_eq!(
+        cached.predictions[0].bytes_base64_encoded.as_deref(),
+        Some("aGVsbG8=")
+    );
+    assert_eq!(
+        cached.predictions[0].mime_type.as_deref(),
+        Some("image/png")
+    );
+
+    let empty: PredictResponse =
+        serde_json::from_str(r#"{"predictions": []}"#).expect("parse empty response");
+    let empty_key = ImageCache::key("Nothing", &[]);
+    cache.put(&empty_key, &empty).expect("skip empty entry");
+    assert!(cache.get(&empty_key).is_none());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn place_description_cache_expires_entries() {
+    let dir = unique_cache_dir("expired");
+    let cache = PlaceDescriptionCache::new(&dir, std::time::Duration::ZERO);
+    let key = PlaceDescriptionCache::key("Lisbon", None, None, None);
+    std::fs::create_dir_all(&dir).expect("create cache dir");
+    std::fs::write(
+        dir.join(format!("place-{key}.json")),
+        r#"{"created_at": 0, "description": {"ambiance": "", "items": [], "keywords": []}}"#,
+    )
+    .expect("write stale entry");
+
+    assert!(cache.get(&key).is_none());
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn place_description_cache_key_normalizes_inputs() {
+    let first = PlaceDescriptionCache::key(" Hakone ", Some("Autumn"), None, Some(""));
+    let second = PlaceDescriptionCache::key("hakone", Some("autumn"), Some(" "), None);
+    let other = PlaceDescriptionCache::key("hakone", Some("winter"), None, None);
+    assert_eq!(first, second);
+    assert_ne!(first, other);
+}
+
+#[test]
+fn decode_images_skips_predictions_without_valid_bytes() {
+    let json = r#"{
+        "predictions": [
+            { "bytesBase64Encoded": "aGVsbG8=", "mimeType": "image/png" },
+            { "mimeType": "image/png" },
+            { "bytesBase64Encoded": "not base64!", "mimeType": "image/jpeg" }
+        ]
+    }"#;
+    let response: PredictResponse = serde_json::from_str(json).expect("parse response");
+
+    let decoded = response.decode_images();
+
+    assert_eq!(
+        decoded.images,
+        vec![DecodedImage {
+            bytes: b"hello".to_vec(),
+            mime_type: Some("image/png".to_string()),
+        }]
+    );
+    assert_eq!(decoded.warnings.len(), 2);
+    assert!(decoded.warnings[0].contains("#2"));
+    assert!(decoded.warnings[1].contains("#3"));
+}
+
+#[test]
+fn generate_image_rejects_blank_prompt_before_sending() {
+    let client = GeminiClient::new("key").expect("client");
+    let error = client.generate_image("   \n ").expect_err("blank prompt");
+    assert!(matches!(error, GeminiError::EmptyPrompt));
+}
+
+#[test]
+fn generate_image_rejects_overlong_prompt_before_sending() {
+    let options = GeminiClientOptions {
+        max_prompt_len: 10,
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("key", options).expect("client");
+
+    let error = client
+        .generate_image("  a cozy loft in Hakone  ")
+        .expect_err("prompt too long");
+    assert!(matches!(
+        error,
+        GeminiError::PromptTooLong { len: 21, max: 10 }
+    ));
+}
+
+#[test]
+fn generate_image_counts_the_folded_negative_prompt_against_the_limit() {
+    let options = GeminiClientOptions {
+        max_prompt_len: 30,
+        negative_prompt: Some("text, watermark".to_string()),
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("key", options).expect("client");
+
+    // 21 characters fit on their own; "\n\nAvoid: text, watermark." adds 25.
+    let error = client
+        .generate_image("a cozy loft in Hakone")
+        .expect_err("folded prompt too long");
+    assert!(matches!(
+        error,
+        GeminiError::PromptTooLong { len: 46, max: 30 }
+    ));
+}
+
+#[test]
+fn validate_prompt_trims_and_counts_characters() {
+    assert_eq!(validate_prompt("  été  ", 3).expect("fits"), "été");
+    assert!(validate_prompt("été!", 3).is_err());
+}
+
+#[test]
+fn fill_prompt_template_substitutes_known_placeholders() {
+    let filled = fill_prompt_template(
+        "A loft in {location} during {season} at {time_of_day}, {weather} outside. Keep {props} tidy.",
+        " Hakone ",
+        Some("spring"),
+        Some("dusk"),
+        None,
+    )
+    .expect("template has placeholders");
+
+    assert_eq!(
+        filled,
+        "A loft in Hakone during spring at dusk,  outside. Keep {props} tidy."
+    );
+}
+
+#[test]
+fn fill_prompt_template_returns_none_without_placeholders() {
+    assert!(fill_prompt_template("A {cozy} loft {", "Hakone", None, None, None).is_none());
+}
+
+#[test]
+fn craft_prompt_uses_template_placeholders_instead_of_appending() {
+    let prompt = craft_prompt(
+        "Interior facing {location} in {season}.",
+        "Lisbon",
+        Some("autumn"),
+        Some("dusk"),
+        None,
+    );
+
+    assert_eq!(prompt, "Interior facing Lisbon in autumn.");
+}
+
+#[test]
+fn every_season_preset_has_a_fragment() {
+    for preset in [
+        SeasonPreset::Spring,
+        SeasonPreset::Summer,
+        SeasonPreset::Autumn,
+        SeasonPreset::Winter,
+    ] {
+        assert!(!preset.fragment().is_empty(), "{preset} has no fragment");
+    }
+    assert_eq!(
+        SeasonPreset::Autumn.fragment(),
+        "warm amber light, fallen leaves visible through the window"
+    );
+}
+
+#[test]
+fn season_preset_parses_names() {
+    assert_eq!("Autumn".parse(), Ok(SeasonPreset::Autumn));
+    assert_eq!("fall".parse(), Ok(SeasonPreset::Autumn));
+    assert!("monsoon".parse::<SeasonPreset>().is_err());
+}
+
+#[test]
+fn every_theme_has_a_fragment_and_parses_its_name() {
+    for theme in [
+        Theme::Cozy,
+        Theme::Minimalist,
+        Theme::Industrial,
+        Theme::Scandinavian,
+        Theme::Japandi,
+    ] {
+        assert!(!theme.fragment().is_empty(), "{theme} has no fragment");
+        assert_eq!(theme.as_str().parse(), Ok(theme));
+    }
+    assert_eq!(
+        Theme::Industrial.directive(),
+        "Interior style: exposed brick, raw concrete, black steel fixtures and warm filament bulbs."
+    );
+    assert_eq!("Cosy".parse(), Ok(Theme::Cozy));
+    assert!("baroque".parse::<Theme>().is_err());
+}
+
+#[test]
+fn join_locations_lists_non_empty_entries() {
+    assert_eq!(join_locations(&[]), "");
+    assert_eq!(join_locations(&[" Kyoto ", ""]), "Kyoto");
+    assert_eq!(join_locations(&["Kyoto", "Oslo"]), "Kyoto and Oslo");
+    assert_eq!(
+        join_locations(&["Kyoto", "Oslo", "Lima"]),
+        "Kyoto, Oslo, and Lima"
+    );
+}
+
+#[test]
+fn craft_prompt_with_locations_blends_multiple_places() {
+    let prompt = craft_prompt_with_locations(
+        "Base.",
+        &["Kyoto temple", "Scandinavian cabin"],
+        None,
+        None,
+        None,
+        &[],
+    );
+
+    assert_eq!(
+        prompt,
+        "Base. Create a scene that harmoniously blends Kyoto temple and Scandinavian cabin and showcase the atmosphere from a cosy, lived-in interior perspective."
+    );
+}
+
+#[test]
+fn place_description_prompt_blends_multiple_places() {
+    let prompt = place_description_prompt(
+        &["Kyoto temple", "Scandinavian cabin"],
+        "winter",
+        None,
+        None,
+    );
+    assert!(prompt.starts_with(
+        "Describe a place that harmoniously blends Kyoto temple and Scandinavian cabin in winter."
+    ));
+}
+
+#[test]
+fn parses_prompt_feedback_from_text_response() {
+    let json = r#"{
+        "candidates": [],
+        "promptFeedback": {
+            "blockReason": "SAFETY",
+            "safetyRatings": [
+                { "category": "HARM_CATEGORY_DANGEROUS_CONTENT", "probability": "MEDIUM", "blocked": true },
+                { "category": "HARM_CATEGORY_HARASSMENT", "probability": "NEGLIGIBLE" }
+            ]
+        }
+    }"#;
+
+    let response: GenerateContentResponse = serde_json::from_str(json).expect("parse response");
+    let feedback = response
+        .prompt_feedback
+        .clone()
+        .expect("prompt feedback present");
+    assert_eq!(feedback.block_reason.as_deref(), Some("SAFETY"));
+    assert_eq!(feedback.safety_ratings.len(), 2);
+    assert!(feedback.safety_ratings[0].blocked);
+    assert_eq!(response.block_reason(), Some("SAFETY"));
+    assert_eq!(feedback.safety_ratings[1].probability, "NEGLIGIBLE");
+    assert!(!feedback.safety_ratings[1].blocked);
+
+    let response: GenerateContentResponse =
+        serde_json::from_str(r#"{ "candidates": [] }"#).expect("parse without feedback");
+    assert!(response.prompt_feedback.is_none());
+    assert!(response.block_reason().is_none());
+}
+
+#[test]
+fn text_request_includes_safety_settings() {
+    let request = TextRequest::new("Describe Kyoto")
+        .with_safety_settings(&SafetySetting::all(SafetyThreshold::BlockOnlyHigh));
+    let value = serde_json::to_value(request).expect("serialize request");
+
+    let settings = value["safetySettings"].as_array().expect("safety settings");
+    assert_eq!(settings.len(), HarmCategory::ALL.len());
+    assert_eq!(
+        settings[0],
+        serde_json::json!({
+            "category": "HARM_CATEGORY_HARASSMENT",
+            "threshold": "BLOCK_ONLY_HIGH",
+        })
+    );
+
+    let value = serde_json::to_value(TextRequest::new("Describe Kyoto")).expect("serialize");
+    assert!(value.get("safetySettings").is_none());
+
+    assert_eq!(
+        GeminiClientOptions::default().safety_settings,
+        SafetySetting::all(SafetyThreshold::BlockOnlyHigh)
+    );
+    assert_eq!(
+        "BLOCK-MEDIUM-AND-ABOVE".parse::<SafetyThreshold>(),
+        Ok(SafetyThreshold::BlockMediumAndAbove)
+    );
+    assert_eq!(
+        "HARM_CATEGORY_HATE_SPEECH".parse::<HarmCategory>(),
+        Ok(HarmCategory::HateSpeech)
+    );
+    assert!("block_some".parse::<SafetyThreshold>().is_err());
+    assert!("violence".parse::<HarmCategory>().is_err());
+}
+
+#[test]
+fn candidate_texts_join_parts_for_every_candidate() {
+    let json = r#"{
+        "candidates": [
+            { "content": { "parts": [ { "text": "{\"ambiance\": " }, { "text": "\"calm\", \"items\": [], \"keywords\": []}" } ] } },
+            { "content": { "parts": [ { "text": "{\"ambiance\": \"busy\", \"items\": [\"lamp\"], \"keywords\": []}" } ] } },
+            { "finishReason": "SAFETY" }
+        ]
+    }"#;
+    let response: GenerateContentResponse = serde_json::from_str(json).expect("parse response");
+
+    let texts = response.candidate_texts();
+    assert_eq!(texts.len(), 3);
+    assert_eq!(texts[2], "");
+
+    let response: GenerateContentResponse = serde_json::from_str(&json.replace(
+        r#",
+            { "finishReason": "SAFETY" }"#,
+        "",
+    ))
+    .expect("parse response without blocked candidate");
+    let descriptions = parse_place_descriptions(&response).expect("parse descriptions");
+    assert_eq!(descriptions.len(), 2);
+    assert_eq!(descriptions[0].ambiance, "calm");
+    assert_eq!(descriptions[1].items, vec!["lamp".to_string()]);
+}
+
+#[test]
+fn place_descriptions_tolerate_code_fences_and_prose() {
+    let response_for = |texts: &[&str]| -> GenerateContentResponse {
+        let candidates: Vec<_> = texts
+            .iter()
+            .map(|text| serde_json::json!({ "content": { "parts": [{ "text": text }] } }))
+            .collect();
+        serde_json::from_value(serde_json::json!({ "candidates": candidates }))
+            .expect("build response")
+    };
+    let fenced = "```json\n{\"ambiance\": \"calm\", \"items\": [], \"keywords\": [\"tea\"]}\n```";
+    let chatty = "Here is the description:\n{\"ambiance\": \"busy\", \"items\": [], \"keywords\": []}\nEnjoy!";
+
+    let descriptions =
+        parse_place_descriptions(&response_for(&[fenced, chatty])).expect("parse cleaned JSON");
+    assert_eq!(descriptions[0].ambiance, "calm");
+    assert_eq!(descriptions[0].keywords, vec!["tea".to_string()]);
+    assert_eq!(descriptions[1].ambiance, "busy");
+
+    let error = parse_place_descriptions(&response_for(&["```json\nnot JSON\n```"]))
+        .expect_err("fenced text without JSON");
+    assert!(matches!(error, GeminiError::JsonParse(_)));
+}
+
+#[test]
+fn truncated_descriptions_report_the_finish_reason() {
+    let response: GenerateContentResponse = serde_json::from_value(serde_json::json!({
+        "candidates": [{
+            "content": { "parts": [{ "text": "{\"ambiance\": \"calm, with" }] },
+            "finishReason": "MAX_TOKENS"
+        }]
+    }))
+    .expect("build response");
+    assert_eq!(response.finish_reason(), Some("MAX_TOKENS"));
+    assert!(response.candidates[0].stopped_early());
+
+    let error = parse_place_descriptions(&response).expect_err("truncated JSON");
+    assert!(
+        matches!(error, GeminiError::StoppedEarly { finish_reason, .. } if finish_reason == "MAX_TOKENS")
+    );
+
+    let described = DescribedPlace {
+        description: PlaceDescription {
+            ambiance: "calm".to_string(),
+            items: Vec::new(),
+            keywords: Vec::new(),
+        },
+        finish_reason: Some(NORMAL_FINISH_REASON.to_string()),
+    };
+    assert!(!described.stopped_early());
+    assert!(
+        !DescribedPlace {
+            finish_reason: None,
+            ..described
+        }
+        .stopped_early()
+    );
+}
+
+#[test]
+fn empty_candidates_are_a_typed_error() {
+    let response: GenerateContentResponse =
+        serde_json::from_str(r#"{ "candidates": [] }"#).expect("parse response");
+    let error = parse_place_descriptions(&response).expect_err("no candidates");
+    assert!(matches!(error, GeminiError::NoCandidates));
+}
+
+#[test]
+fn place_description_cache_stores_orientation_with_entry() {
+    let dir = unique_cache_dir("orientation");
+    let cache = PlaceDescriptionCache::new(&dir, std::time::Duration::from_secs(60));
+    let key = PlaceDescriptionCache::key("Reading room", None, None, None);
+    let description = PlaceDescription {
+        ambiance: "Quiet".to_string(),
+        items: vec![],
+        keywords: vec![],
+    };
+
+    cache
+        .put_orientation(&key, Orientation::Portrait)
+        .expect("no entry is a no-op");
+    assert!(cache.orientation(&key).is_none());
+
+    cache.put(&key, &description).expect("write cache entry");
+    assert!(cache.orientation(&key).is_none());
+    cache
+        .put_orientation(&key, Orientation::Portrait)
+        .expect("write orientation");
+    assert_eq!(cache.orientation(&key), Some(Orientation::Portrait));
+    assert_eq!(cache.get(&key).expect("cache hit").ambiance, "Quiet");
+
+    std::fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn orientation_reads_classification_answers() {
+    assert_eq!(
+        Orientation::from_answer("Landscape."),
+        Some(Orientation::Landscape)
+    );
+    assert_eq!(
+        Orientation::from_answer(" portrait\n"),
+        Some(Orientation::Portrait)
+    );
+    assert_eq!(Orientation::from_answer("landscape or portrait"), None);
+    assert_eq!(Orientation::from_answer("square"), None);
+    assert_eq!(Orientation::Landscape.aspect_ratio(), DEFAULT_ASPECT_RATIO);
+    assert_eq!(Orientation::Portrait.aspect_ratio(), "3:4");
+}
+
+#[test]
+fn orientation_prompt_includes_description_when_available() {
+    let description = PlaceDescription {
+        ambiance: "Vaulted ceilings".to_string(),
+        items: vec!["ladder".to_string()],
+        keywords: vec![],
+    };
+
+    let bare = orientation_prompt(&["Trinity College Library"], None);
+    assert!(bare.contains("Trinity College Library"));
+    assert!(!bare.contains("Place description"));
+    assert!(bare.ends_with("landscape or portrait."));
+
+    let detailed = orientation_prompt(&["Trinity College Library"], Some(&description));
+    assert!(detailed.contains("Ambiance: Vaulted ceilings"));
+}
+
+#[test]
+fn schema_serializes_scalar_types() {
+    let cases = [
+        (Schema::string(), "STRING"),
+        (Schema::integer(), "INTEGER"),
+        (Schema::number(), "NUMBER"),
+        (Schema::boolean(), "BOOLEAN"),
+    ];
+    for (schema, expected) in cases {
+        let value = serde_json::to_value(&schema).expect("serialize schema");
+        assert_eq!(value, serde_json::json!({ "type": expected }));
+        assert!(schema.validate().is_ok());
+    }
+}
+
+#[test]
+fn schema_serializes_nested_objects() {
+    let schema = Schema::object()
+        .property("suggested_color_count", u32::schema())
+        .property(
+            "lighting",
+            Schema::object()
+                .property("warmth", f64::schema().description("0 is cool, 1 is warm"))
+                .property("intensity", f32::schema()),
+        )
+        .optional_property("has_window", Option::<bool>::schema())
+        .property("palette", Vec::<String>::schema());
+
+    assert!(schema.validate().is_ok());
+    let value = serde_json::to_value(&schema).expect("serialize schema");
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "type": "OBJECT",
+            "properties": {
+                "suggested_color_count": { "type": "INTEGER" },
+                "lighting": {
+                    "type": "OBJECT",
+                    "properties": {
+                        "warmth": { "type": "NUMBER", "description": "0 is cool, 1 is warm" },
+                        "intensity": { "type": "NUMBER" }
+                    },
+                    "propertyOrdering": ["warmth", "intensity"],
+                    "required": ["warmth", "intensity"]
+                },
+                "has_window": { "type": "BOOLEAN", "nullable": true },
+                "palette": { "type": "ARRAY", "items": { "type": "STRING" } }
+            },
+            "propertyOrdering": ["suggested_color_count", "lighting", "has_window", "palette"],
+            "required": ["suggested_color_count", "lighting", "palette"]
+        })
+    );
+}
+
+#[test]
+fn schema_validation_reports_offending_path() {
+    let empty = Schema::object().property("lighting", Schema::object());
+    match empty.validate() {
+        Err(GeminiError::InvalidSchema(message)) => {
+            assert_eq!(
+                message,
+                "schema.lighting: OBJECT needs at least one property"
+            );
+        }
+        other => panic!("expected InvalidSchema, got {other:?}"),
+    }
+    assert_eq!(Schema::object().schema_type(), SchemaType::Object);
+}
+
+#[test]
+fn schema_property_replaces_an_existing_entry() {
+    let schema = Schema::object()
+        .property("warmth", Schema::string())
+        .property("intensity", Schema::number())
+        .property("warmth", Schema::number())
+        .optional_property("intensity", Schema::integer());
+
+    let value = serde_json::to_value(&schema).expect("serialize schema");
+    assert_eq!(
+        value,
+        serde_json::json!({
+            "type": "OBJECT",
+            "properties": {
+                "warmth": { "type": "NUMBER" },
+                "intensity": { "type": "INTEGER" }
+            },
+            "propertyOrdering": ["warmth", "intensity"],
+            "required": ["warmth"]
+        })
+    );
+}
+
+#[test]
+fn parses_list_models_response() {
+    let json = r#"{
+        "models": [
+            {
+                "name": "models/imagen-4.0-generate-001",
+                "displayName": "Imagen 4",
+                "supportedGenerationMethods": ["predict"]
+            },
+            {
+                "name": "models/gemini-2.5-flash",
+                "displayName": "Gemini 2.5 Flash",
+                "supportedGenerationMethods": ["generateContent", "countTokens"]
+            },
+            { "name": "models/text-embedding-004", "supportedGenerationMethods": ["embedContent"] }
+        ],
+        "nextPageToken": "page-2"
+    }"#;
+
+    let response: ListModelsResponse = serde_json::from_str(json).expect("parse models");
+    assert_eq!(response.next_page_token.as_deref(), Some("page-2"));
+    let ids: Vec<&str> = response.models.iter().map(ModelInfo::id).collect();
+    assert_eq!(
+        ids,
+        [
+            "imagen-4.0-generate-001",
+            "gemini-2.5-flash",
+            "text-embedding-004"
+        ]
+    );
+    assert_eq!(response.models[0].display_name, "Imagen 4");
+    assert!(response.models[0].is_image_or_text_capable());
+    assert!(response.models[1].is_image_or_text_capable());
+    assert!(!response.models[2].is_image_or_text_capable());
+
+    let empty: ListModelsResponse = serde_json::from_str("{}").expect("parse empty page");
+    assert!(empty.models.is_empty());
+    assert!(empty.next_page_token.is_none());
+}
+
+#[test]
+fn refused_connections_count_as_offline() {
+    let source = reqwest::blocking::Client::new()
+        .get("http://127.0.0.1:1/")
+        .send()
+        .expect_err("nothing listens on port 1");
+    assert!(GeminiError::Http(source).is_offline());
+    assert!(!GeminiError::Unauthorized { status: 401 }.is_offline());
+    assert!(!GeminiError::NoCandidates.is_offline());
+}
+
+#[test]
+fn unreachable_proxies_count_as_offline() {
+    let options = GeminiClientOptions {
+        proxy: Some("http://127.0.0.1:1".to_string()),
+        max_retries: 0,
+        ..GeminiClientOptions::default()
+    };
+    let client = GeminiClient::with_options("secret", options).expect("build client");
+
+    let error = client
+        .generate_image("a cozy loft in Hakone")
+        .expect_err("nothing listens on port 1");
+    assert!(matches!(error, GeminiError::ProxyConnect { .. }));
+    assert!(error.is_offline());
+}
+
+#[test]
+fn build_structured_prompt_formats_sections() {
+    let description = PlaceDescription {
+        ambiance: "Warm mountain inn with panoramic views".to_string(),
+        items: vec!["tatami mats".to_string(), "shoji screens".to_string()],
+        keywords: vec!["serene".to_string(), "onsen".to_string()],
+    };
+
+    let prompt = build_structured_prompt(
+        "Follow the art direction guidelines.",
+        Some(&description),
+        Some("spring"),
+        Some("dusk"),
+        Some("light snow"),
+        Some("9:16"),
+    );
+
+    assert!(prompt.contains("Follow the art direction guidelines."));
+    assert!(prompt.contains("Complete place description:"));
+    assert!(prompt.contains("Ambiance: Warm mountain inn with panoramic views"));
+    assert!(prompt.contains("Items: tatami mats, shoji screens"));
+    assert!(prompt.contains("Keywords: serene, onsen"));
+    assert!(prompt.contains("Scene timing:"));
+    assert!(prompt.contains("Season: spring"));
+    assert!(prompt.contains("Time of day: dusk"));
+    assert!(prompt.contains("Weather: light snow"));
+    assert!(prompt.ends_with("\n\nCompose this as a tall vertical image."));
+}
+
+#[test]
+fn build_structured_prompt_falls_back_to_placeholders() {
+    let prompt = build_structured_prompt(
+        "Guide the render artistically.",
+        None,
+        None,
+        Some("   "),
+        None,
+        Some("1:1"),
+    );
+
+    assert!(prompt.contains("Guide the render artistically."));
+    assert!(prompt.contains("Ambiance: Unspecified"));
+    assert!(prompt.contains("Items: Unspecified"));
+    assert!(prompt.contains("Keywords: Unspecified"));
+    assert!(prompt.contains("Season: Unspecified"));
+    assert!(prompt.contains("Time of day: Unspecified"));
+    assert!(prompt.contains("Weather: Unspecified"));
+    assert!(!prompt.contains("Compose this as"));
+}
+
+#[test]
+fn build_avatar_prompt_appends_the_motif_directive() {
+    let scene = build_structured_prompt(
+        "Cozy reading nook.",
+        None,
+        None,
+        None,
+        None,
+        Some(AVATAR_ASPECT_RATIO),
+    );
+    let prompt = build_avatar_prompt(&scene);
+
+    assert!(prompt.starts_with("Cozy reading nook."));
+    assert!(prompt.ends_with(&format!("\n\n{AVATAR_DIRECTIVE}")));
+    assert!(!prompt.contains("Compose this as"));
+    assert_eq!(build_avatar_prompt("  "), AVATAR_DIRECTIVE);
+}
+
+#[test]
+fn orientation_follows_aspect_ratio() {
+    assert_eq!(
+        Orientation::from_aspect_ratio("16:9"),
+        Some(Orientation::Landscape)
+    );
+    assert_eq!(
+        Orientation::from_aspect_ratio(" 3:4 "),
+        Some(Orientation::Portrait)
+    );
+    assert_eq!(Orientation::from_aspect_ratio("1:1"), None);
+    assert_eq!(Orientation::from_aspect_ratio("wide"), None);
+    assert_eq!(
+        Orientation::Landscape.directive(),
+        "Compose this as a wide landscape image."
+    );
+}
+
+#[test]
+fn estimate_cost_multiplies_the_image_price_by_sample_count() {
+    let cost = estimate_cost(DEFAULT_IMG_MODEL_VERSION, 3, Some("16:9"));
+    assert!((cost - 0.18).abs() < 1e-9, "{cost}");
+    assert_eq!(estimate_cost("imagen-unknown", 3, None), 0.0);
+    assert_eq!(
+        PriceTable::default().image_cost("imagen-unknown", 3, None),
+        None
+    );
+    assert_eq!(PriceTable::default().currency, DEFAULT_CURRENCY);
+}
+
+#[test]
+fn price_table_applies_configured_overrides() {
+    let mut config = mawaku_config::PricingConfig {
+        currency: Some("EUR".to_string()),
+        ..mawaku_config::PricingConfig::default()
+    };
+    config.images.insert(
+        "imagen-4.0-generate-001".to_string(),
+        mawaku_config::ImagePriceConfig {
+            per_image: 0.05,
+            aspect_ratios: [("16:9".to_string(), 0.08)].into_iter().collect(),
+        },
+    );
+    config.text.insert(
+        "gemini-custom".to_string(),
+        mawaku_config::TokenPriceConfig {
+            input_per_million: 1.0,
+            output_per_million: 4.0,
+        },
+    );
+
+    let table = PriceTable::default().with_config(&config);
+    assert_eq!(table.currency, "EUR");
+    assert_eq!(
+        table.image_cost("imagen-4.0-generate-001", 2, Some("1:1")),
+        Some(0.1)
+    );
+    assert_eq!(
+        table.image_cost("imagen-4.0-generate-001", 1, Some("16:9")),
+        Some(0.08)
+    );
+    assert_eq!(
+        table.text_cost("gemini-custom", 1_000_000, 500_000),
+        Some(3.0)
+    );
+    assert!(table.text_cost(DEFAULT_TEXT_MODEL_VERSION, 1, 1).is_some());
+}
@@ -0,0 +1,134 @@
+use image::{Rgba, RgbaImage};
+
+const GLYPH_WIDTH: u32 = 5;
+const GLYPH_HEIGHT: u32 = 7;
+const GLYPH_SPACING: u32 = 1;
+const BOX_ALPHA: f32 = 0.55;
+const TEXT_ALPHA: f32 = 0.9;
+
+/// Burn `caption` into the bottom-left corner of `canvas`.
+///
+/// The text is drawn with a built-in 5x7 bitmap font on a semi-transparent
+/// dark box.
+pub(crate) fn draw_caption(canvas: &mut RgbaImage, caption: &str) {
+    let text = caption.trim();
+    if text.is_empty() {
+        return;
+    }
+
+    let scale = (canvas.height() / 270).max(1);
+    let padding = 3 * scale;
+    let advance = (GLYPH_WIDTH + GLYPH_SPACING) * scale;
+    let glyph_count = text.chars().count() as u32;
+    let text_width = (glyph_count * advance).saturating_sub(GLYPH_SPACING * scale);
+    let box_width = (text_width + 2 * padding).min(canvas.width());
+    let box_height = (GLYPH_HEIGHT * scale + 2 * padding).min(canvas.height());
+    let margin = 2 * padding;
+    let box_x = margin.min(canvas.width() - box_width);
+    let box_y = canvas.height() - box_height - margin.min(canvas.height() - box_height);
+
+    fill_rect(
+        canvas,
+        box_x,
+        box_y,
+        box_width,
+        box_height,
+        Rgba([0, 0, 0, 255]),
+        BOX_ALPHA,
+    );
+
+    let origin_y = box_y + padding;
+    for (index, ch) in text.chars().enumerate() {
+        let origin_x = box_x + padding + index as u32 * advance;
+        for (row, bits) in glyph(ch).iter().enumerate() {
+            for column in 0..GLYPH_WIDTH {
+                if bits & (1 << (GLYPH_WIDTH - 1 - column)) != 0 {
+                    fill_rect(
+                        canvas,
+                        origin_x + column * scale,
+                        origin_y + row as u32 * scale,
+                        scale,
+                        scale,
+                        Rgba([255, 255, 255, 255]),
+                        TEXT_ALPHA,
+                    );
+                }
+            }
+        }
+    }
+}
+
+fn fill_rect(
+    canvas: &mut RgbaImage,
+    x: u32,
+    y: u32,
+    width: u32,
+    height: u32,
+    color: Rgba<u8>,
+    alpha: f32,
+) {
+    let x_end = (x + width).min(canvas.width());
+    let y_end = (y + height).min(canvas.height());
+    for py in y..y_end {
+        for px in x..x_end {
+            let pixel = canvas.get_pixel_mut(px, py);
+            for channel in 0..3 {
+                let blended =
+                    f32::from(pixel[channel]) * (1.0 - alpha) + f32::from(color[channel]) * alpha;
+                pixel[channel] = blended.round() as u8;
+            }
+        }
+    }
+}
+
+fn glyph(ch: char) -> [u8; 7] {
+    match ch.to_ascii_uppercase() {
+        'A' => [0x0E, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'B' => [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E],
+        'C' => [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E],
+        'D' => [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C],
+        'E' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F],
+        'F' => [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10],
+        'G' => [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F],
+        'H' => [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11],
+        'I' => [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        'J' => [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C],
+        'K' => [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11],
+        'L' => [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F],
+        'M' => [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11],
+        'N' => [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11],
+        'O' => [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'P' => [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10],
+        'Q' => [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D],
+        'R' => [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11],
+        'S' => [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E],
+        'T' => [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04],
+        'U' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E],
+        'V' => [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04],
+        'W' => [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A],
+        'X' => [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11],
+        'Y' => [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04],
+        'Z' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F],
+        '0' => [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E],
+        '1' => [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E],
+        '2' => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F],
+        '3' => [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E],
+        '4' => [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02],
+        '5' => [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E],
+        '6' => [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E],
+        '7' => [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08],
+        '8' => [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E],
+        '9' => [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C],
+        ' ' => [0x00; 7],
+        '.' => [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C],
+        ',' => [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08],
+        '-' => [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00],
+        ':' => [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00],
+        '|' => [0x04; 7],
+        '/' => [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00],
+        '(' => [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02],
+        ')' => [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08],
+        '\'' => [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00],
+        _ => [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04],
+    }
+}
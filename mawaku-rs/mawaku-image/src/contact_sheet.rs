@@ -0,0 +1,61 @@
+use image::imageops::{self, FilterType};
+use image::{DynamicImage, ImageFormat as CodecFormat, Rgba, RgbaImage};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use crate::ImageSaveError;
+use crate::transform::encode;
+
+const THUMBNAIL_SIZE: u32 = 256;
+const CELL_PADDING: u32 = 8;
+const BACKGROUND: Rgba<u8> = Rgba([24, 24, 24, 255]);
+
+/// Compose the images at `paths` into a near-square grid of thumbnails and
+/// return the PNG-encoded sheet.
+pub(crate) fn compose(paths: &[PathBuf]) -> Result<Vec<u8>, ImageSaveError> {
+    let thumbnails = paths
+        .iter()
+        .map(|path| load_thumbnail(path))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let (columns, rows) = grid_dimensions(thumbnails.len());
+    let cell = THUMBNAIL_SIZE + CELL_PADDING;
+    let mut sheet = RgbaImage::from_pixel(
+        columns * cell + CELL_PADDING,
+        rows * cell + CELL_PADDING,
+        BACKGROUND,
+    );
+
+    for (index, thumbnail) in thumbnails.iter().enumerate() {
+        let index = index as u32;
+        let column = index % columns;
+        let row = index / columns;
+        // Centre each thumbnail within its cell so mixed aspect ratios line up.
+        let x = CELL_PADDING + column * cell + (THUMBNAIL_SIZE - thumbnail.width()) / 2;
+        let y = CELL_PADDING + row * cell + (THUMBNAIL_SIZE - thumbnail.height()) / 2;
+        imageops::overlay(&mut sheet, thumbnail, i64::from(x), i64::from(y));
+    }
+
+    encode(&DynamicImage::ImageRgba8(sheet), CodecFormat::Png)
+}
+
+/// Columns and rows for a near-square grid holding `count` cells.
+pub(crate) fn grid_dimensions(count: usize) -> (u32, u32) {
+    let count = count.max(1) as u32;
+    let columns = (1..=count)
+        .find(|columns| columns * columns >= count)
+        .unwrap_or(count);
+    let rows = count.div_ceil(columns);
+    (columns, rows)
+}
+
+fn load_thumbnail(path: &Path) -> Result<RgbaImage, ImageSaveError> {
+    let bytes = fs::read(path).map_err(|source| ImageSaveError::Io {
+        path: path.to_path_buf(),
+        source,
+    })?;
+    let decoded = image::load_from_memory(&bytes).map_err(ImageSaveError::Image)?;
+    Ok(decoded
+        .resize(THUMBNAIL_SIZE, THUMBNAIL_SIZE, FilterType::Triangle)
+        .to_rgba8())
+}
@@ -0,0 +1,183 @@
+//! Read pixel dimensions from encoded image headers without decoding pixels.
+
+use crate::ImageSaveError;
+use std::fmt;
+use std::str::FromStr;
+
+/// Width and height of an image, in pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageDimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl fmt::Display for ImageDimensions {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+/// A target `width:height` ratio, such as `16:9`, for center-cropping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AspectRatio {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// A rectangle inside an image, from its top-left corner.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CropRegion {
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl AspectRatio {
+    /// The largest region of an image of `dimensions` with this ratio,
+    /// centered on the image. Returns `None` when the image already has the
+    /// ratio, to the nearest pixel.
+    pub fn center_crop(self, dimensions: ImageDimensions) -> Option<CropRegion> {
+        let (width, height) = (u64::from(dimensions.width), u64::from(dimensions.height));
+        let (ratio_width, ratio_height) = (u64::from(self.width), u64::from(self.height));
+        if width == 0 || height == 0 {
+            return None;
+        }
+        // Compare width / height with ratio_width / ratio_height without
+        // floating point.
+        if width * ratio_height > height * ratio_width {
+            let cropped = ((height * ratio_width + ratio_height / 2) / ratio_height).max(1);
+            (cropped < width).then(|| CropRegion {
+                x: ((width - cropped) / 2) as u32,
+                y: 0,
+                width: cropped as u32,
+                height: dimensions.height,
+            })
+        } else {
+            let cropped = ((width * ratio_height + ratio_width / 2) / ratio_width).max(1);
+            (cropped < height).then(|| CropRegion {
+                x: 0,
+                y: ((height - cropped) / 2) as u32,
+                width: dimensions.width,
+                height: cropped as u32,
+            })
+        }
+    }
+}
+
+impl fmt::Display for AspectRatio {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.width, self.height)
+    }
+}
+
+impl FromStr for AspectRatio {
+    type Err = ImageSaveError;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let invalid = || ImageSaveError::InvalidAspectRatio(value.to_string());
+        let (width, height) = value.trim().split_once(':').ok_or_else(invalid)?;
+        let width = width.trim().parse::<u32>().map_err(|_| invalid())?;
+        let height = height.trim().parse::<u32>().map_err(|_| invalid())?;
+        if width == 0 || height == 0 {
+            return Err(invalid());
+        }
+        Ok(Self { width, height })
+    }
+}
+
+/// Measure PNG, JPEG, GIF, or WebP bytes. Returns `None` for other formats or
+/// truncated headers.
+pub fn image_dimensions(bytes: &[u8]) -> Option<ImageDimensions> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        png_dimensions(bytes)
+    } else if bytes.starts_with(&[0xFF, 0xD8]) {
+        jpeg_dimensions(bytes)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(ImageDimensions {
+            width: u32::from(le_u16(bytes, 6)?),
+            height: u32::from(le_u16(bytes, 8)?),
+        })
+    } else if bytes.starts_with(b"RIFF") && bytes.get(8..12) == Some(b"WEBP") {
+        webp_dimensions(bytes)
+    } else {
+        None
+    }
+}
+
+fn png_dimensions(bytes: &[u8]) -> Option<ImageDimensions> {
+    if bytes.get(12..16)? != b"IHDR" {
+        return None;
+    }
+    Some(ImageDimensions {
+        width: be_u32(bytes, 16)?,
+        height: be_u32(bytes, 20)?,
+    })
+}
+
+fn jpeg_dimensions(bytes: &[u8]) -> Option<ImageDimensions> {
+    let mut offset = 2;
+    loop {
+        // Skip fill bytes before the marker code.
+        while *bytes.get(offset)? == 0xFF && *bytes.get(offset + 1)? == 0xFF {
+            offset += 1;
+        }
+        if *bytes.get(offset)? != 0xFF {
+            return None;
+        }
+        let marker = *bytes.get(offset + 1)?;
+        let length = usize::from(be_u16(bytes, offset + 2)?);
+        // SOF0-SOF15, excluding DHT (C4), JPG (C8), and DAC (CC).
+        if matches!(marker, 0xC0..=0xCF) && !matches!(marker, 0xC4 | 0xC8 | 0xCC) {
+            return Some(ImageDimensions {
+                height: u32::from(be_u16(bytes, offset + 5)?),
+                width: u32::from(be_u16(bytes, offset + 7)?),
+            });
+        }
+        offset += 2 + length;
+    }
+}
+
+fn webp_dimensions(bytes: &[u8]) -> Option<ImageDimensions> {
+    match bytes.get(12..16)? {
+        b"VP8 " => Some(ImageDimensions {
+            width: u32::from(le_u16(bytes, 26)? & 0x3FFF),
+            height: u32::from(le_u16(bytes, 28)? & 0x3FFF),
+        }),
+        b"VP8L" => {
+            let bits = u32::from_le_bytes(bytes.get(21..25)?.try_into().ok()?);
+            Some(ImageDimensions {
+                width: (bits & 0x3FFF) + 1,
+                height: ((bits >> 14) & 0x3FFF) + 1,
+            })
+        }
+        b"VP8X" => Some(ImageDimensions {
+            width: le_u24(bytes, 24)? + 1,
+            height: le_u24(bytes, 27)? + 1,
+        }),
+        _ => None,
+    }
+}
+
+fn be_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn be_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        bytes.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn le_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        bytes.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le_u24(bytes: &[u8], offset: usize) -> Option<u32> {
+    let raw = bytes.get(offset..offset + 3)?;
+    Some(u32::from(raw[0]) | (u32::from(raw[1]) << 8) | (u32::from(raw[2]) << 16))
+}
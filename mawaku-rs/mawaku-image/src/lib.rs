@@ -1,10 +1,63 @@
-use base64::Engine;
-use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use mawaku_utils::{decode_base64, today_partition};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::time::{SystemTime, UNIX_EPOCH};
 use thiserror::Error;
 
+#[cfg(feature = "image")]
+mod caption;
+#[cfg(feature = "image")]
+mod contact_sheet;
+mod dimensions;
+mod metadata;
+mod plausibility;
+mod sink;
+#[cfg(feature = "image")]
+mod transform;
+
+pub use dimensions::{AspectRatio, CropRegion, ImageDimensions, image_dimensions};
+pub use plausibility::{MIN_IMAGE_SIDE, SuspiciousImage, check_image};
+pub use sink::{FilesystemSink, ImageSink, StoredAt};
+
+pub const DEFAULT_JPEG_QUALITY: u8 = 90;
+
+/// Largest Gaussian blur sigma accepted. Stronger blurs wash the scene out
+/// and take seconds per image.
+pub const MAX_BLUR_SIGMA: f32 = 50.0;
+
+/// Encoding applied when re-encoding a saved image.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageFormat {
+    Png,
+    /// JPEG with a quality between 1 and 100.
+    Jpeg {
+        quality: u8,
+    },
+    Webp,
+}
+
+impl ImageFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Png => "png",
+            Self::Jpeg { .. } => "jpg",
+            Self::Webp => "webp",
+        }
+    }
+}
+
+/// Where images are written when no output directory is given.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DefaultDir {
+    /// The directory containing the running executable.
+    CurrentExe,
+    /// The process's current working directory.
+    #[default]
+    CurrentWorkingDir,
+    /// The system temporary directory.
+    TempDir,
+}
+
 #[derive(Debug, Error)]
 pub enum ImageSaveError {
     #[error("image payload is empty")]
@@ -15,46 +68,203 @@ pub enum ImageSaveError {
     ResolveApplicationDirectory(std::io::Error),
     #[error("application directory has no parent directory")]
     InvalidApplicationDirectory,
+    #[error("failed to resolve current working directory: {0}")]
+    ResolveWorkingDirectory(std::io::Error),
     #[error("failed to write image to {path}")]
     Io {
         path: PathBuf,
         #[source]
         source: std::io::Error,
     },
+    #[error("failed to store image as {name}")]
+    Sink {
+        name: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
+    #[error("invalid aspect ratio `{0}` (expected W:H with positive numbers, e.g. 16:9)")]
+    InvalidAspectRatio(String),
+    #[error("blur sigma must be greater than 0 and at most {MAX_BLUR_SIGMA}, got {0}")]
+    InvalidBlurSigma(f32),
+    #[error("embedding metadata is only supported for PNG and JPEG images")]
+    MetadataUnsupported,
+    #[error("failed to encode image metadata: {0}")]
+    Metadata(#[source] png::EncodingError),
+    #[error("{0} requires building mawaku-image with the `image` feature")]
+    FeatureUnavailable(&'static str),
+    #[cfg(feature = "image")]
+    #[error("failed to process image: {0}")]
+    Image(#[source] image::ImageError),
 }
 
 #[derive(Debug, Default)]
 pub struct SaveImageOptions<'a> {
     pub file_stem: Option<&'a str>,
     pub mime_type: Option<&'a str>,
+    /// Ignored by [`save_base64_image_to`], where the sink decides.
     pub output_dir: Option<&'a Path>,
+    /// Directory used when `output_dir` is `None`.
+    pub default_dir: DefaultDir,
+    /// Text burned into the bottom-left corner of the image. Requires the
+    /// `image` feature; when `None` the decoded bytes are written unchanged.
+    pub caption: Option<&'a str>,
+    /// Center-crop the image to this ratio before any other processing;
+    /// images that already have it are left alone. Requires the `image`
+    /// feature.
+    pub crop_to_aspect: Option<AspectRatio>,
+    /// Gaussian blur sigma applied before the caption is drawn, for a soft
+    /// "bokeh" background. Requires the `image` feature; see
+    /// [`check_blur_sigma`] for the accepted range.
+    pub blur: Option<f32>,
+    /// Re-encode the image in this format (adjusting the extension). Requires
+    /// the `image` feature; when `None` the original encoding is kept.
+    pub target_format: Option<ImageFormat>,
+    /// Key/value pairs embedded in the saved file: PNG text chunks, or the
+    /// EXIF `UserComment` of a JPEG. Other formats are saved without
+    /// it, see [`SavedImage::metadata_skipped`].
+    pub metadata: &'a [(&'a str, &'a str)],
+    /// Nest the file under `<output_dir>/YYYY/MM/DD/` for the current UTC date.
+    pub organize_by_date: bool,
+    /// Also write the untouched base64 payload to `<stem>.b64` next to the
+    /// image, before decoding, so the exact API response can be reproduced.
+    pub save_raw: bool,
 }
 
+/// A file written by [`save_base64_image`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SavedImage {
+    /// Where the image was stored, as reported by the [`ImageSink`]: the
+    /// exact file path for [`FilesystemSink`], see [`StoredAt::into_path_buf`].
+    pub path: PathBuf,
+    /// Pixel dimensions read from the written bytes, or `None` when the
+    /// format could not be measured.
+    pub dimensions: Option<ImageDimensions>,
+    /// Size of the written file in bytes.
+    pub byte_len: usize,
+    /// The `.b64` file holding the raw payload, when
+    /// [`SaveImageOptions::save_raw`] was set.
+    pub raw_path: Option<PathBuf>,
+    /// Set when [`SaveImageOptions::metadata`] was given but the image is in
+    /// a format that cannot hold it, such as WebP or GIF; the image was saved
+    /// without metadata.
+    pub metadata_skipped: bool,
+}
+
+impl SavedImage {
+    /// Flag the image when it is smaller than `min_bytes` or has degenerate
+    /// dimensions, which usually means the generation failed silently.
+    pub fn check(&self, min_bytes: usize) -> Option<SuspiciousImage> {
+        check_image(self.byte_len, self.dimensions, min_bytes)
+    }
+}
+
+/// Decode `encoded` and write it to `options.output_dir`, or to the
+/// [`DefaultDir`] when none is given.
+///
+/// Missing directories are created only once there is a file to write, so a
+/// payload that fails to decode leaves no empty directory behind.
 pub fn save_base64_image(
     encoded: &str,
     options: SaveImageOptions<'_>,
-) -> Result<PathBuf, ImageSaveError> {
+) -> Result<SavedImage, ImageSaveError> {
     if encoded.trim().is_empty() {
         return Err(ImageSaveError::EmptyPayload);
     }
 
-    let output_dir = resolve_output_dir(options.output_dir)?;
+    let output_dir = resolve_output_dir(options.output_dir, options.default_dir)?;
+    save_base64_image_to(encoded, &FilesystemSink::new(output_dir), options)
+}
+
+/// Decode `encoded` and hand the file, plus the raw payload when
+/// [`SaveImageOptions::save_raw`] is set, to `sink`.
+pub fn save_base64_image_to(
+    encoded: &str,
+    sink: &dyn ImageSink,
+    options: SaveImageOptions<'_>,
+) -> Result<SavedImage, ImageSaveError> {
+    if encoded.trim().is_empty() {
+        return Err(ImageSaveError::EmptyPayload);
+    }
+
+    let prefix = if options.organize_by_date {
+        let partition = today_partition();
+        let components = partition
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>();
+        format!("{}/", components.join("/"))
+    } else {
+        String::new()
+    };
+    let extension = options
+        .target_format
+        .map(ImageFormat::extension)
+        .unwrap_or_else(|| extension_from_mime(options.mime_type));
+    let file_stem = match options.file_stem {
+        Some(stem) => stem.to_string(),
+        None => format!("mawaku-image-{}", timestamp_suffix()),
+    };
+
+    // Reject bad options before anything, even the raw payload, is written.
+    check_options(&options)?;
+
+    let raw_path = if options.save_raw {
+        let location = sink.write(&format!("{prefix}{file_stem}.b64"), encoded.as_bytes())?;
+        Some(location.into_path_buf())
+    } else {
+        None
+    };
+
+    let bytes = decode_base64(encoded).map_err(ImageSaveError::Decode)?;
+    let bytes = if needs_transform(&options) {
+        transform_image(
+            &bytes,
+            options.crop_to_aspect,
+            options.blur,
+            options.caption,
+            options.target_format,
+        )?
+    } else {
+        bytes
+    };
+    let metadata_skipped = !options.metadata.is_empty() && !metadata::supports(&bytes);
+    let bytes = if metadata_skipped {
+        bytes
+    } else {
+        metadata::embed(bytes, options.metadata)?
+    };
+
+    let location = sink.write(&format!("{prefix}{file_stem}.{extension}"), &bytes)?;
+
+    Ok(SavedImage {
+        path: location.into_path_buf(),
+        dimensions: image_dimensions(&bytes),
+        byte_len: bytes.len(),
+        raw_path,
+        metadata_skipped,
+    })
+}
+
+/// Compose the saved images at `paths` into a single grid of thumbnails and
+/// write it as `{file_stem}.png`, in the current working directory when
+/// `output_dir` is `None`. Requires the `image` feature.
+pub fn save_contact_sheet(
+    paths: &[PathBuf],
+    file_stem: &str,
+    output_dir: Option<&Path>,
+) -> Result<PathBuf, ImageSaveError> {
+    if paths.is_empty() {
+        return Err(ImageSaveError::EmptyPayload);
+    }
+
+    let bytes = compose_contact_sheet(paths)?;
+    let output_dir = resolve_output_dir(output_dir, DefaultDir::default())?;
     fs::create_dir_all(&output_dir).map_err(|source| ImageSaveError::Io {
         path: output_dir.clone(),
         source,
     })?;
 
-    let extension = extension_from_mime(options.mime_type);
-    let file_name = match options.file_stem {
-        Some(stem) => format!("{stem}.{extension}"),
-        None => format!("mawaku-image-{}.{}", timestamp_suffix(), extension),
-    };
-
-    let path = output_dir.join(file_name);
-    let bytes = BASE64_STANDARD
-        .decode(encoded)
-        .map_err(ImageSaveError::Decode)?;
-
+    let path = output_dir.join(format!("{file_stem}.png"));
     fs::write(&path, &bytes).map_err(|source| ImageSaveError::Io {
         path: path.clone(),
         source,
@@ -63,17 +273,111 @@ pub fn save_base64_image(
     Ok(path)
 }
 
-fn resolve_output_dir(dir: Option<&Path>) -> Result<PathBuf, ImageSaveError> {
+#[cfg(feature = "image")]
+fn compose_contact_sheet(paths: &[PathBuf]) -> Result<Vec<u8>, ImageSaveError> {
+    contact_sheet::compose(paths)
+}
+
+#[cfg(not(feature = "image"))]
+fn compose_contact_sheet(_paths: &[PathBuf]) -> Result<Vec<u8>, ImageSaveError> {
+    Err(ImageSaveError::FeatureUnavailable(
+        "building contact sheets",
+    ))
+}
+
+/// Accept `sigma` when it is a usable blur strength: finite, positive, and
+/// at most [`MAX_BLUR_SIGMA`].
+pub fn check_blur_sigma(sigma: f32) -> Result<f32, ImageSaveError> {
+    if sigma.is_finite() && sigma > 0.0 && sigma <= MAX_BLUR_SIGMA {
+        Ok(sigma)
+    } else {
+        Err(ImageSaveError::InvalidBlurSigma(sigma))
+    }
+}
+
+fn needs_transform(options: &SaveImageOptions<'_>) -> bool {
+    options.crop_to_aspect.is_some()
+        || options.blur.is_some()
+        || options.caption.is_some()
+        || options.target_format.is_some()
+}
+
+fn check_options(options: &SaveImageOptions<'_>) -> Result<(), ImageSaveError> {
+    if let Some(sigma) = options.blur {
+        check_blur_sigma(sigma)?;
+    }
+    #[cfg(not(feature = "image"))]
+    if needs_transform(options) {
+        return Err(unavailable_transform(
+            options.crop_to_aspect,
+            options.blur,
+            options.caption,
+        ));
+    }
+    Ok(())
+}
+
+#[cfg(feature = "image")]
+fn transform_image(
+    bytes: &[u8],
+    crop_to_aspect: Option<AspectRatio>,
+    blur: Option<f32>,
+    caption: Option<&str>,
+    target_format: Option<ImageFormat>,
+) -> Result<Vec<u8>, ImageSaveError> {
+    transform::transform(bytes, crop_to_aspect, blur, caption, target_format)
+}
+
+#[cfg(not(feature = "image"))]
+fn transform_image(
+    _bytes: &[u8],
+    crop_to_aspect: Option<AspectRatio>,
+    blur: Option<f32>,
+    caption: Option<&str>,
+    _target_format: Option<ImageFormat>,
+) -> Result<Vec<u8>, ImageSaveError> {
+    Err(unavailable_transform(crop_to_aspect, blur, caption))
+}
+
+#[cfg(not(feature = "image"))]
+fn unavailable_transform(
+    crop_to_aspect: Option<AspectRatio>,
+    blur: Option<f32>,
+    caption: Option<&str>,
+) -> ImageSaveError {
+    if crop_to_aspect.is_some() {
+        ImageSaveError::FeatureUnavailable("cropping images")
+    } else if blur.is_some() {
+        ImageSaveError::FeatureUnavailable("blurring images")
+    } else if caption.is_some() {
+        ImageSaveError::FeatureUnavailable("captioning images")
+    } else {
+        ImageSaveError::FeatureUnavailable("converting image formats")
+    }
+}
+
+fn resolve_output_dir(
+    dir: Option<&Path>,
+    default_dir: DefaultDir,
+) -> Result<PathBuf, ImageSaveError> {
     if let Some(path) = dir {
         return Ok(path.to_path_buf());
     }
 
-    let current_exe =
-        std::env::current_exe().map_err(ImageSaveError::ResolveApplicationDirectory)?;
-    let parent = current_exe
-        .parent()
-        .ok_or(ImageSaveError::InvalidApplicationDirectory)?;
-    Ok(parent.to_path_buf())
+    match default_dir {
+        DefaultDir::CurrentExe => {
+            let current_exe =
+                std::env::current_exe().map_err(ImageSaveError::ResolveApplicationDirectory)?;
+            let parent = current_exe
+                .parent()
+                .ok_or(ImageSaveError::InvalidApplicationDirectory)?;
+            Ok(parent.to_path_buf())
+        }
+        DefaultDir::CurrentWorkingDir => {
+            std::env::current_dir().map_err(ImageSaveError::ResolveWorkingDirectory)
+        }
+        DefaultDir::TempDir => Ok(std::env::temp_dir()),
+    }
 }
 
 fn extension_from_mime(mime_type: Option<&str>) -> &'static str {
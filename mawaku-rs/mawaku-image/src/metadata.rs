@@ -0,0 +1,165 @@
+//! Embed generation parameters directly in encoded image bytes.
+//!
+//! PNG files receive one text chunk per entry (`tEXt` for Latin-1 text,
+//! `iTXt` otherwise), encoded by the `png` crate. JPEG files receive an EXIF
+//! `APP1` segment whose `UserComment` tag lists every entry as `key: value`
+//! lines, stored as UTF-16 behind the standard `UNICODE` character code.
+
+use std::io::Cursor;
+
+use png::text_metadata::{EncodableTextChunk, ITXtChunk, TEXtChunk};
+
+use crate::ImageSaveError;
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+/// Signature plus the IHDR chunk, which always carries 13 bytes of data.
+const PNG_HEADER_LEN: usize = PNG_SIGNATURE.len() + 4 + 4 + 13 + 4;
+const JPEG_SOI: &[u8] = &[0xFF, 0xD8];
+const MAX_SEGMENT_LEN: usize = u16::MAX as usize;
+
+const TAG_EXIF_IFD_POINTER: u16 = 0x8769;
+const TAG_USER_COMMENT: u16 = 0x9286;
+const TYPE_UNDEFINED: u16 = 7;
+const TYPE_LONG: u16 = 4;
+const UNICODE_CHARACTER_CODE: &[u8; 8] = b"UNICODE\0";
+
+/// Whether `bytes` are in a format [`embed`] can write metadata into.
+pub(crate) fn supports(bytes: &[u8]) -> bool {
+    bytes.starts_with(PNG_SIGNATURE) || bytes.starts_with(JPEG_SOI)
+}
+
+pub(crate) fn embed(bytes: Vec<u8>, entries: &[(&str, &str)]) -> Result<Vec<u8>, ImageSaveError> {
+    if entries.is_empty() {
+        return Ok(bytes);
+    }
+
+    if bytes.starts_with(PNG_SIGNATURE) {
+        embed_png(bytes, entries)
+    } else if bytes.starts_with(JPEG_SOI) {
+        Ok(embed_jpeg(bytes, entries))
+    } else {
+        Err(ImageSaveError::MetadataUnsupported)
+    }
+}
+
+fn embed_png(bytes: Vec<u8>, entries: &[(&str, &str)]) -> Result<Vec<u8>, ImageSaveError> {
+    // Text chunks go straight after IHDR, which must be the first chunk.
+    png::Decoder::new(Cursor::new(bytes.as_slice()))
+        .read_header_info()
+        .map_err(|_| ImageSaveError::MetadataUnsupported)?;
+
+    let mut chunks = Vec::new();
+    for (key, value) in entries {
+        let keyword = png_keyword(key);
+        if keyword.is_empty() {
+            continue;
+        }
+
+        let latin1 = value.chars().all(|ch| u32::from(ch) <= 0xFF);
+        let encoded = if latin1 {
+            TEXtChunk::new(keyword, *value).encode(&mut chunks)
+        } else {
+            ITXtChunk::new(keyword, *value).encode(&mut chunks)
+        };
+        encoded.map_err(ImageSaveError::Metadata)?;
+    }
+
+    let mut output = Vec::with_capacity(bytes.len() + chunks.len());
+    output.extend_from_slice(&bytes[..PNG_HEADER_LEN]);
+    output.extend_from_slice(&chunks);
+    output.extend_from_slice(&bytes[PNG_HEADER_LEN..]);
+    Ok(output)
+}
+
+/// PNG keywords are 1-79 printable Latin-1 characters.
+fn png_keyword(key: &str) -> String {
+    key.trim()
+        .chars()
+        .filter(|ch| matches!(u32::from(*ch), 0x20..=0x7E | 0xA1..=0xFF))
+        .take(79)
+        .collect()
+}
+
+fn embed_jpeg(bytes: Vec<u8>, entries: &[(&str, &str)]) -> Vec<u8> {
+    let comment = entries
+        .iter()
+        .map(|(key, value)| format!("{}: {}", key.trim(), value))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let segment = exif_segment(&comment);
+
+    // Keep a JFIF APP0 segment first when present, as most readers expect.
+    let mut insert_at = JPEG_SOI.len();
+    if bytes.get(2..4) == Some(&[0xFF, 0xE0])
+        && let Some(len) = bytes.get(4..6)
+    {
+        let app0_end = insert_at + 2 + u16::from_be_bytes([len[0], len[1]]) as usize;
+        if app0_end <= bytes.len() {
+            insert_at = app0_end;
+        }
+    }
+
+    let mut output = Vec::with_capacity(bytes.len() + segment.len());
+    output.extend_from_slice(&bytes[..insert_at]);
+    output.extend_from_slice(&segment);
+    output.extend_from_slice(&bytes[insert_at..]);
+    output
+}
+
+/// A big-endian TIFF structure with IFD0 pointing at an Exif IFD that holds
+/// a single `UserComment`, wrapped in an `APP1` segment.
+fn exif_segment(comment: &str) -> Vec<u8> {
+    const IFD_LEN: u32 = 2 + 12 + 4;
+    const EXIF_IFD_OFFSET: u32 = 8 + IFD_LEN;
+    const COMMENT_OFFSET: u32 = EXIF_IFD_OFFSET + IFD_LEN;
+    // The segment length counts itself (2), "Exif\0\0" (6), then the TIFF
+    // data up to the comment and its character code.
+    const OVERHEAD: usize = 2 + 6 + COMMENT_OFFSET as usize + UNICODE_CHARACTER_CODE.len();
+
+    let mut text = Vec::new();
+    for ch in comment.chars() {
+        let mut units = [0u16; 2];
+        let units = ch.encode_utf16(&mut units);
+        if OVERHEAD + text.len() + 2 * units.len() > MAX_SEGMENT_LEN {
+            break;
+        }
+        text.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+    }
+
+    let mut tiff = Vec::new();
+    tiff.extend_from_slice(b"MM\x00\x2A");
+    tiff.extend_from_slice(&8u32.to_be_bytes());
+    push_ifd(
+        &mut tiff,
+        TAG_EXIF_IFD_POINTER,
+        TYPE_LONG,
+        1,
+        EXIF_IFD_OFFSET,
+    );
+    let comment_len = (UNICODE_CHARACTER_CODE.len() + text.len()) as u32;
+    push_ifd(
+        &mut tiff,
+        TAG_USER_COMMENT,
+        TYPE_UNDEFINED,
+        comment_len,
+        COMMENT_OFFSET,
+    );
+    tiff.extend_from_slice(UNICODE_CHARACTER_CODE);
+    tiff.extend_from_slice(&text);
+
+    let mut segment = vec![0xFF, 0xE1];
+    segment.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+    segment.extend_from_slice(b"Exif\x00\x00");
+    segment.extend_from_slice(&tiff);
+    segment
+}
+
+/// Append an IFD holding one entry and no next IFD.
+fn push_ifd(tiff: &mut Vec<u8>, tag: u16, kind: u16, count: u32, value: u32) {
+    tiff.extend_from_slice(&1u16.to_be_bytes());
+    tiff.extend_from_slice(&tag.to_be_bytes());
+    tiff.extend_from_slice(&kind.to_be_bytes());
+    tiff.extend_from_slice(&count.to_be_bytes());
+    tiff.extend_from_slice(&value.to_be_bytes());
+    tiff.extend_from_slice(&0u32.to_be_bytes());
+}
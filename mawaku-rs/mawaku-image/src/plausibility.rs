@@ -0,0 +1,46 @@
+//! Flag saved images that decode but look like a failed generation.
+
+use crate::ImageDimensions;
+use std::fmt;
+
+/// Images whose width or height is below this many pixels are reported as
+/// degenerate.
+pub const MIN_IMAGE_SIDE: u32 = 64;
+
+/// Why an image looks like a failed generation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SuspiciousImage {
+    TooSmall { bytes: usize, min_bytes: usize },
+    DegenerateDimensions(ImageDimensions),
+}
+
+impl fmt::Display for SuspiciousImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::TooSmall { bytes, min_bytes } => {
+                write!(f, "only {bytes} bytes, expected at least {min_bytes}")
+            }
+            Self::DegenerateDimensions(dimensions) => {
+                write!(f, "degenerate dimensions {dimensions}")
+            }
+        }
+    }
+}
+
+/// Check an image of `byte_len` bytes against `min_bytes` and
+/// [`MIN_IMAGE_SIDE`]. Unmeasured dimensions are not held against it.
+pub fn check_image(
+    byte_len: usize,
+    dimensions: Option<ImageDimensions>,
+    min_bytes: usize,
+) -> Option<SuspiciousImage> {
+    if let Some(dimensions) = dimensions
+        && (dimensions.width < MIN_IMAGE_SIDE || dimensions.height < MIN_IMAGE_SIDE)
+    {
+        return Some(SuspiciousImage::DegenerateDimensions(dimensions));
+    }
+    (byte_len < min_bytes).then_some(SuspiciousImage::TooSmall {
+        bytes: byte_len,
+        min_bytes,
+    })
+}
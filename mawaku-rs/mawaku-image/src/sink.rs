@@ -0,0 +1,65 @@
+use crate::ImageSaveError;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Where an [`ImageSink`] stored a file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoredAt {
+    /// A local file, kept as a path so non-UTF-8 names survive.
+    Path(PathBuf),
+    /// A URL or object key, e.g. `s3://bucket/mawaku-image.png`.
+    Uri(String),
+}
+
+impl StoredAt {
+    /// The local path, or the URI as a path for sinks that store elsewhere.
+    pub fn into_path_buf(self) -> PathBuf {
+        match self {
+            Self::Path(path) => path,
+            Self::Uri(uri) => PathBuf::from(uri),
+        }
+    }
+}
+
+/// Destination for saved images and their raw payloads.
+///
+/// [`FilesystemSink`] writes to a local directory; other implementations can
+/// upload to object storage such as S3 or GCS.
+pub trait ImageSink: Send + Sync {
+    /// Store `bytes` under `name`, a `/`-separated relative name such as
+    /// `2024/05/01/mawaku-image.png`, and return where it ended up. Failures
+    /// outside the local filesystem are reported as [`ImageSaveError::Sink`].
+    fn write(&self, name: &str, bytes: &[u8]) -> Result<StoredAt, ImageSaveError>;
+}
+
+/// Writes images below a local directory, creating it as needed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilesystemSink {
+    dir: PathBuf,
+}
+
+impl FilesystemSink {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+}
+
+impl ImageSink for FilesystemSink {
+    fn write(&self, name: &str, bytes: &[u8]) -> Result<StoredAt, ImageSaveError> {
+        let path = self.dir.join(name);
+        let parent = path.parent().unwrap_or(&self.dir);
+        fs::create_dir_all(parent).map_err(|source| ImageSaveError::Io {
+            path: parent.to_path_buf(),
+            source,
+        })?;
+        fs::write(&path, bytes).map_err(|source| ImageSaveError::Io {
+            path: path.clone(),
+            source,
+        })?;
+        Ok(StoredAt::Path(path))
+    }
+}
@@ -1,4 +1,6 @@
 use super::*;
+use base64::Engine;
+use base64::engine::general_purpose::{STANDARD as BASE64_STANDARD, URL_SAFE_NO_PAD};
 use std::fs;
 use std::path::PathBuf;
 
@@ -21,9 +23,12 @@ fn saves_image_to_specified_directory() {
         file_stem: Some("custom-name"),
         mime_type: Some("image/png"),
         output_dir: Some(dir.as_path()),
+        ..SaveImageOptions::default()
     };
 
-    let path = save_base64_image("aGVsbG8=", options).expect("save image succeeds");
+    let path = save_base64_image("aGVsbG8=", options)
+        .expect("save image succeeds")
+        .path;
     assert_eq!(path, dir.join("custom-name.png"));
 
     let bytes = fs::read(&path).expect("read saved image");
@@ -34,14 +39,112 @@ fn saves_image_to_specified_directory() {
 }
 
 #[test]
-fn default_output_directory_uses_application_path() {
+fn saves_url_safe_payloads() {
+    let dir = unique_temp_dir();
+    let payload = [0xfb, 0xff, 0xbf, 0x00, 0x3e];
+    let encoded = URL_SAFE_NO_PAD.encode(payload);
+    assert!(encoded.contains('-') && encoded.contains('_'));
+    let options = SaveImageOptions {
+        file_stem: Some("url-safe"),
+        output_dir: Some(dir.as_path()),
+        ..SaveImageOptions::default()
+    };
+
+    let path = save_base64_image(&encoded, options)
+        .expect("save url-safe payload")
+        .path;
+    assert_eq!(fs::read(&path).expect("read saved image"), payload);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn save_raw_writes_base64_payload_next_to_image() {
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("raw"),
+        mime_type: Some("image/png"),
+        output_dir: Some(dir.as_path()),
+        save_raw: true,
+        ..SaveImageOptions::default()
+    };
+
+    let saved = save_base64_image("aGVsbG8=", options).expect("save image succeeds");
+    let raw_path = saved.raw_path.expect("raw payload written");
+    assert_eq!(raw_path, dir.join("raw.b64"));
+    assert_eq!(
+        fs::read_to_string(&raw_path).expect("read raw payload"),
+        "aGVsbG8="
+    );
+    assert_eq!(fs::read(&saved.path).expect("read saved image"), b"hello");
+
+    let options = SaveImageOptions {
+        file_stem: Some("plain"),
+        output_dir: Some(dir.as_path()),
+        ..SaveImageOptions::default()
+    };
+    let saved = save_base64_image("aGVsbG8=", options).expect("save image succeeds");
+    assert!(saved.raw_path.is_none());
+    assert!(!dir.join("plain.b64").exists());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn organize_by_date_nests_image_under_dated_directory() {
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("dated"),
+        mime_type: Some("image/png"),
+        output_dir: Some(dir.as_path()),
+        organize_by_date: true,
+        ..SaveImageOptions::default()
+    };
+
+    let path = save_base64_image("aGVsbG8=", options)
+        .expect("save image succeeds")
+        .path;
+    assert_eq!(path, dir.join(today_partition()).join("dated.png"));
+    assert!(path.is_file());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn output_dir_is_created_only_when_an_image_is_written() {
+    let dir = unique_temp_dir();
+    let output_dir = dir.join("run-42");
+    let options = || SaveImageOptions {
+        file_stem: Some("lazy"),
+        output_dir: Some(output_dir.as_path()),
+        organize_by_date: true,
+        ..SaveImageOptions::default()
+    };
+
+    assert!(save_base64_image("not base64!", options()).is_err());
+    assert!(!output_dir.exists());
+
+    let path = save_base64_image("aGVsbG8=", options())
+        .expect("save image succeeds")
+        .path;
+    assert!(path.is_file());
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn current_exe_default_dir_uses_application_path() {
     let options = SaveImageOptions {
         file_stem: Some("mawaku-test-default"),
         mime_type: Some("image/png"),
         output_dir: None,
+        default_dir: DefaultDir::CurrentExe,
+        ..SaveImageOptions::default()
     };
 
-    let path = save_base64_image("aGVsbG8=", options).expect("save image with default directory");
+    let path = save_base64_image("aGVsbG8=", options)
+        .expect("save image with default directory")
+        .path;
     let exe_dir = std::env::current_exe()
         .expect("determine current exe")
         .parent()
@@ -52,8 +155,708 @@ fn default_output_directory_uses_application_path() {
     fs::remove_file(&path).ok();
 }
 
+#[test]
+fn default_dir_falls_back_to_working_directory() {
+    assert_eq!(
+        SaveImageOptions::default().default_dir,
+        DefaultDir::CurrentWorkingDir
+    );
+    assert_eq!(
+        resolve_output_dir(None, DefaultDir::CurrentWorkingDir).expect("resolve cwd"),
+        std::env::current_dir().expect("current dir")
+    );
+    assert_eq!(
+        resolve_output_dir(None, DefaultDir::TempDir).expect("resolve temp dir"),
+        std::env::temp_dir()
+    );
+
+    let explicit = Path::new("/srv/backgrounds");
+    assert_eq!(
+        resolve_output_dir(Some(explicit), DefaultDir::TempDir).expect("explicit dir wins"),
+        explicit
+    );
+}
+
 #[test]
 fn empty_payload_is_rejected() {
     let error = save_base64_image("", SaveImageOptions::default()).expect_err("empty payload");
     assert!(matches!(error, ImageSaveError::EmptyPayload));
 }
+
+#[cfg(not(feature = "image"))]
+#[test]
+fn caption_requires_image_feature() {
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("captioned"),
+        output_dir: Some(dir.as_path()),
+        caption: Some("Hakone | spring"),
+        ..SaveImageOptions::default()
+    };
+
+    let error = save_base64_image("aGVsbG8=", options).expect_err("caption needs feature");
+    assert!(matches!(error, ImageSaveError::FeatureUnavailable(_)));
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(not(feature = "image"))]
+#[test]
+fn format_conversion_requires_image_feature() {
+    let options = SaveImageOptions {
+        target_format: Some(ImageFormat::Jpeg { quality: 80 }),
+        ..SaveImageOptions::default()
+    };
+
+    let error = save_base64_image("aGVsbG8=", options).expect_err("conversion needs feature");
+    assert!(matches!(error, ImageSaveError::FeatureUnavailable(_)));
+}
+
+#[cfg(not(feature = "image"))]
+#[test]
+fn contact_sheet_requires_image_feature() {
+    let paths = vec![PathBuf::from("missing.png")];
+    let error = save_contact_sheet(&paths, "sheet", None).expect_err("sheet needs feature");
+    assert!(matches!(error, ImageSaveError::FeatureUnavailable(_)));
+}
+
+#[cfg(not(feature = "image"))]
+#[test]
+fn blur_requires_image_feature() {
+    let options = SaveImageOptions {
+        blur: Some(4.0),
+        ..SaveImageOptions::default()
+    };
+
+    let error = save_base64_image("aGVsbG8=", options).expect_err("blur needs feature");
+    assert!(matches!(error, ImageSaveError::FeatureUnavailable(_)));
+}
+
+#[cfg(not(feature = "image"))]
+#[test]
+fn crop_requires_image_feature() {
+    let options = SaveImageOptions {
+        crop_to_aspect: Some(AspectRatio {
+            width: 16,
+            height: 9,
+        }),
+        ..SaveImageOptions::default()
+    };
+
+    let error = save_base64_image("aGVsbG8=", options).expect_err("crop needs feature");
+    assert!(matches!(error, ImageSaveError::FeatureUnavailable(_)));
+}
+
+#[test]
+fn aspect_ratio_parses_positive_width_and_height() {
+    let ratio: AspectRatio = " 16:9 ".parse().expect("parse ratio");
+    assert_eq!(
+        ratio,
+        AspectRatio {
+            width: 16,
+            height: 9
+        }
+    );
+    assert_eq!(ratio.to_string(), "16:9");
+    for value in ["", "16", "16:", ":9", "0:9", "16:0", "a:b", "-4:3", "4:3:1"] {
+        assert!(
+            matches!(
+                value.parse::<AspectRatio>(),
+                Err(ImageSaveError::InvalidAspectRatio(_))
+            ),
+            "{value}"
+        );
+    }
+}
+
+#[test]
+fn center_crop_keeps_the_middle_of_the_image() {
+    let square = AspectRatio {
+        width: 1,
+        height: 1,
+    };
+    let wide = ImageDimensions {
+        width: 1600,
+        height: 900,
+    };
+    assert_eq!(
+        square.center_crop(wide),
+        Some(CropRegion {
+            x: 350,
+            y: 0,
+            width: 900,
+            height: 900
+        })
+    );
+    let portrait = AspectRatio {
+        width: 3,
+        height: 4,
+    };
+    assert_eq!(
+        AspectRatio {
+            width: 16,
+            height: 9
+        }
+        .center_crop(ImageDimensions {
+            width: 900,
+            height: 1200
+        }),
+        Some(CropRegion {
+            x: 0,
+            y: 347,
+            width: 900,
+            height: 506
+        })
+    );
+    assert_eq!(
+        portrait.center_crop(ImageDimensions {
+            width: 896,
+            height: 1195
+        }),
+        None
+    );
+    assert_eq!(
+        AspectRatio {
+            width: 32,
+            height: 18
+        }
+        .center_crop(wide),
+        None
+    );
+}
+
+#[test]
+fn blur_sigma_must_be_positive_and_bounded() {
+    assert_eq!(check_blur_sigma(2.5).ok(), Some(2.5));
+    assert_eq!(check_blur_sigma(MAX_BLUR_SIGMA).ok(), Some(MAX_BLUR_SIGMA));
+    for sigma in [0.0, -1.0, MAX_BLUR_SIGMA + 1.0, f32::NAN, f32::INFINITY] {
+        assert!(
+            matches!(
+                check_blur_sigma(sigma),
+                Err(ImageSaveError::InvalidBlurSigma(_))
+            ),
+            "{sigma}"
+        );
+    }
+
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        output_dir: Some(dir.as_path()),
+        blur: Some(0.0),
+        ..SaveImageOptions::default()
+    };
+    let error = save_base64_image("aGVsbG8=", options).expect_err("invalid sigma");
+    assert!(matches!(error, ImageSaveError::InvalidBlurSigma(_)));
+    assert_eq!(fs::read_dir(&dir).expect("read dir").count(), 0);
+
+    // The raw payload is not written, nor its directory created, either.
+    let nested = dir.join("raw");
+    let options = SaveImageOptions {
+        output_dir: Some(nested.as_path()),
+        blur: Some(0.0),
+        save_raw: true,
+        ..SaveImageOptions::default()
+    };
+    let error = save_base64_image("aGVsbG8=", options).expect_err("invalid sigma");
+    assert!(matches!(error, ImageSaveError::InvalidBlurSigma(_)));
+    assert!(!nested.exists());
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn blur_softens_hard_edges() {
+    let mut image = image::RgbImage::from_pixel(32, 8, image::Rgb([0, 0, 0]));
+    for x in 16..32 {
+        for y in 0..8 {
+            image.put_pixel(x, y, image::Rgb([255, 255, 255]));
+        }
+    }
+    let bytes = transform::encode(
+        &image::DynamicImage::ImageRgb8(image),
+        image::ImageFormat::Png,
+    )
+    .expect("encode test png");
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("blurred"),
+        output_dir: Some(dir.as_path()),
+        blur: Some(3.0),
+        ..SaveImageOptions::default()
+    };
+
+    let saved = save_base64_image(&BASE64_STANDARD.encode(bytes), options).expect("save blurred");
+    let blurred = image::open(&saved.path).expect("open blurred").to_rgb8();
+    assert_eq!(blurred.dimensions(), (32, 8));
+    let edge = blurred.get_pixel(15, 4)[0];
+    assert!(edge > 0 && edge < 255, "edge pixel {edge}");
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn crop_to_aspect_center_crops_and_skips_matching_images() {
+    let dir = unique_temp_dir();
+    let square = AspectRatio {
+        width: 1,
+        height: 1,
+    };
+    let options = |file_stem| SaveImageOptions {
+        file_stem: Some(file_stem),
+        output_dir: Some(dir.as_path()),
+        crop_to_aspect: Some(square),
+        ..SaveImageOptions::default()
+    };
+
+    let cropped =
+        save_base64_image(&encoded_test_png(160, 90), options("cropped")).expect("save cropped");
+    assert_eq!(
+        cropped.dimensions,
+        Some(ImageDimensions {
+            width: 90,
+            height: 90
+        })
+    );
+    let unchanged =
+        save_base64_image(&encoded_test_png(64, 64), options("unchanged")).expect("save unchanged");
+    assert_eq!(
+        unchanged.dimensions,
+        Some(ImageDimensions {
+            width: 64,
+            height: 64
+        })
+    );
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(feature = "image")]
+fn encoded_test_png(width: u32, height: u32) -> String {
+    let image = image::RgbImage::from_pixel(width, height, image::Rgb([200, 200, 200]));
+    let bytes = transform::encode(
+        &image::DynamicImage::ImageRgb8(image),
+        image::ImageFormat::Png,
+    )
+    .expect("encode test png");
+    BASE64_STANDARD.encode(bytes)
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn caption_is_drawn_in_bottom_left_corner() {
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("captioned"),
+        mime_type: Some("image/png"),
+        output_dir: Some(dir.as_path()),
+        caption: Some("HAKONE"),
+        ..SaveImageOptions::default()
+    };
+
+    let path = save_base64_image(&encoded_test_png(200, 100), options)
+        .expect("save captioned")
+        .path;
+    let saved = image::open(&path).expect("open captioned image").to_rgb8();
+    assert_eq!(saved.dimensions(), (200, 100));
+    assert_eq!(saved.get_pixel(199, 0), &image::Rgb([200, 200, 200]));
+    let darkened =
+        (0..saved.height()).any(|y| (0..saved.width()).any(|x| saved.get_pixel(x, y)[0] < 200));
+    assert!(darkened);
+    assert!(saved.get_pixel(7, 90)[0] < 200);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn converts_png_to_jpeg_and_adjusts_extension() {
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("converted"),
+        mime_type: Some("image/png"),
+        output_dir: Some(dir.as_path()),
+        target_format: Some(ImageFormat::Jpeg { quality: 75 }),
+        ..SaveImageOptions::default()
+    };
+
+    let path = save_base64_image(&encoded_test_png(64, 32), options)
+        .expect("save converted")
+        .path;
+    assert_eq!(path, dir.join("converted.jpg"));
+    let bytes = fs::read(&path).expect("read converted image");
+    assert_eq!(
+        image::guess_format(&bytes).expect("detect format"),
+        image::ImageFormat::Jpeg
+    );
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn image_format_maps_to_extension() {
+    assert_eq!(ImageFormat::Png.extension(), "png");
+    assert_eq!(ImageFormat::Jpeg { quality: 90 }.extension(), "jpg");
+    assert_eq!(ImageFormat::Webp.extension(), "webp");
+}
+
+#[test]
+fn contact_sheet_rejects_empty_input() {
+    let error = save_contact_sheet(&[], "sheet", None).expect_err("no images");
+    assert!(matches!(error, ImageSaveError::EmptyPayload));
+}
+
+#[cfg(feature = "image")]
+#[test]
+fn contact_sheet_lays_out_predictions_in_near_square_grid() {
+    assert_eq!(contact_sheet::grid_dimensions(1), (1, 1));
+    assert_eq!(contact_sheet::grid_dimensions(2), (2, 1));
+    assert_eq!(contact_sheet::grid_dimensions(4), (2, 2));
+    assert_eq!(contact_sheet::grid_dimensions(5), (3, 2));
+
+    let dir = unique_temp_dir();
+    let paths = (1..=3)
+        .map(|index| {
+            let stem = format!("prediction-{index}");
+            let options = SaveImageOptions {
+                file_stem: Some(stem.as_str()),
+                mime_type: Some("image/png"),
+                output_dir: Some(dir.as_path()),
+                ..SaveImageOptions::default()
+            };
+            save_base64_image(&encoded_test_png(512, 288), options)
+                .expect("save prediction")
+                .path
+        })
+        .collect::<Vec<_>>();
+
+    let sheet = save_contact_sheet(&paths, "mawaku-test-sheet", Some(dir.as_path()))
+        .expect("save contact sheet");
+    assert_eq!(sheet, dir.join("mawaku-test-sheet.png"));
+
+    let decoded = image::open(&sheet).expect("decode contact sheet");
+    assert_eq!(decoded.width(), 2 * 264 + 8);
+    assert_eq!(decoded.height(), 2 * 264 + 8);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn embeds_text_chunks_after_png_header() {
+    // 1x1 grayscale PNG.
+    let png = BASE64_STANDARD
+        .decode("iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAAAAAA6fptVAAAACklEQVR4nGNgAAAAAgABSK+kcQAAAABJRU5ErkJggg==")
+        .expect("decode fixture");
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("tagged"),
+        mime_type: Some("image/png"),
+        output_dir: Some(dir.as_path()),
+        metadata: &[("Description", "Cozy loft"), ("Location", "São Paulo ☀")],
+        ..SaveImageOptions::default()
+    };
+
+    let path = save_base64_image(&BASE64_STANDARD.encode(&png), options)
+        .expect("save png")
+        .path;
+    let bytes = fs::read(&path).expect("read tagged png");
+
+    let text_chunk = b"tEXtDescription\0Cozy loft";
+    let text_at = bytes
+        .windows(text_chunk.len())
+        .position(|window| window == text_chunk)
+        .expect("tEXt chunk present");
+    assert_eq!(text_at, 33 + 4, "tEXt chunk follows IHDR");
+    let itxt = bytes
+        .windows(4)
+        .position(|window| window == b"iTXt")
+        .expect("iTXt chunk present for non Latin-1 text");
+    assert!(bytes[itxt..].starts_with(b"iTXtLocation\0\0\0\0\0S\xC3\xA3o Paulo"));
+    assert!(bytes.ends_with(&png[33..]));
+
+    let reader = png::Decoder::new(std::io::Cursor::new(bytes.as_slice()))
+        .read_info()
+        .expect("tagged png decodes");
+    let info = reader.info();
+    assert_eq!(info.uncompressed_latin1_text[0].text, "Cozy loft");
+    assert_eq!(
+        info.utf8_text[0].get_text().expect("iTXt text"),
+        "São Paulo ☀"
+    );
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn embeds_exif_user_comment_in_jpeg() {
+    let jpeg = [0xFF, 0xD8, 0xFF, 0xD9];
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        file_stem: Some("tagged"),
+        mime_type: Some("image/jpeg"),
+        output_dir: Some(dir.as_path()),
+        metadata: &[("Location", "São Paulo"), ("Season", "spring")],
+        ..SaveImageOptions::default()
+    };
+
+    let path = save_base64_image(&BASE64_STANDARD.encode(jpeg), options)
+        .expect("save jpeg")
+        .path;
+    let bytes = fs::read(&path).expect("read tagged jpeg");
+
+    assert_eq!(&bytes[..4], &[0xFF, 0xD8, 0xFF, 0xE1]);
+    let length = u16::from_be_bytes([bytes[4], bytes[5]]) as usize;
+    assert_eq!(&bytes[6..12], b"Exif\0\0");
+    assert_eq!(&bytes[4 + length..], &[0xFF, 0xD9]);
+
+    let tiff = &bytes[12..4 + length];
+    let read_u16 = |at: usize| u16::from_be_bytes([tiff[at], tiff[at + 1]]);
+    let read_u32 =
+        |at: usize| u32::from_be_bytes([tiff[at], tiff[at + 1], tiff[at + 2], tiff[at + 3]]);
+    assert_eq!(&tiff[..4], b"MM\0\x2A");
+    let ifd0 = read_u32(4) as usize;
+    assert_eq!(read_u16(ifd0 + 2), 0x8769, "IFD0 points at the Exif IFD");
+    let exif_ifd = read_u32(ifd0 + 10) as usize;
+    assert_eq!(read_u16(exif_ifd + 2), 0x9286, "Exif IFD holds UserComment");
+    let count = read_u32(exif_ifd + 6) as usize;
+    let offset = read_u32(exif_ifd + 10) as usize;
+    let comment = &tiff[offset..offset + count];
+    assert_eq!(&comment[..8], b"UNICODE\0");
+    let units: Vec<u16> = comment[8..]
+        .chunks(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect();
+    assert_eq!(
+        String::from_utf16(&units).expect("UTF-16 comment"),
+        "Location: São Paulo\nSeason: spring"
+    );
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn metadata_is_skipped_for_unsupported_formats() {
+    let dir = unique_temp_dir();
+    let options = SaveImageOptions {
+        mime_type: Some("image/webp"),
+        output_dir: Some(dir.as_path()),
+        metadata: &[("Location", "Hakone")],
+        ..SaveImageOptions::default()
+    };
+
+    let saved = save_base64_image("aGVsbG8=", options).expect("save without metadata");
+    assert!(saved.metadata_skipped);
+    assert_eq!(fs::read(&saved.path).expect("read saved image"), b"hello");
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn save_reports_dimensions_when_measurable() {
+    let dir = unique_temp_dir();
+    // 1x1 grayscale PNG.
+    let png = "iVBORw0KGgoAAAANSUhEUgAAAAEAAAABCAAAAAA6fptVAAAACklEQVR4nGNgAAAAAgABSK+kcQAAAABJRU5ErkJggg==";
+    let options = SaveImageOptions {
+        file_stem: Some("measured"),
+        mime_type: Some("image/png"),
+        output_dir: Some(dir.as_path()),
+        ..SaveImageOptions::default()
+    };
+    let saved = save_base64_image(png, options).expect("save png");
+    assert_eq!(
+        saved.dimensions,
+        Some(ImageDimensions {
+            width: 1,
+            height: 1
+        })
+    );
+
+    let options = SaveImageOptions {
+        file_stem: Some("opaque"),
+        output_dir: Some(dir.as_path()),
+        ..SaveImageOptions::default()
+    };
+    let saved = save_base64_image("aGVsbG8=", options).expect("save unknown bytes");
+    assert_eq!(saved.dimensions, None);
+    assert_eq!(saved.byte_len, 5);
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[test]
+fn check_image_flags_tiny_payloads_and_degenerate_dimensions() {
+    let full_hd = ImageDimensions {
+        width: 1920,
+        height: 1080,
+    };
+    assert_eq!(check_image(500_000, Some(full_hd), 10_240), None);
+    assert_eq!(
+        check_image(900, Some(full_hd), 10_240),
+        Some(SuspiciousImage::TooSmall {
+            bytes: 900,
+            min_bytes: 10_240
+        })
+    );
+    assert_eq!(check_image(900, None, 0), None);
+
+    let sliver = ImageDimensions {
+        width: 1920,
+        height: 1,
+    };
+    assert_eq!(
+        check_image(500_000, Some(sliver), 10_240).map(|issue| issue.to_string()),
+        Some("degenerate dimensions 1920x1".to_string())
+    );
+}
+
+#[test]
+fn image_dimensions_reads_jpeg_gif_and_webp_headers() {
+    // SOI, APP0 stub, SOF0 with height 1080 and width 1920.
+    let jpeg = [
+        0xFF, 0xD8, 0xFF, 0xE0, 0x00, 0x04, 0x00, 0x00, 0xFF, 0xC0, 0x00, 0x11, 0x08, 0x04, 0x38,
+        0x07, 0x80,
+    ];
+    assert_eq!(
+        image_dimensions(&jpeg).map(|dims| dims.to_string()),
+        Some("1920x1080".to_string())
+    );
+
+    let gif = b"GIF89a\x80\x02\xe0\x01";
+    assert_eq!(
+        image_dimensions(gif).map(|dims| dims.to_string()),
+        Some("640x480".to_string())
+    );
+
+    let mut webp = b"RIFF\0\0\0\0WEBPVP8X\0\0\0\0\0\0\0\0".to_vec();
+    webp.extend_from_slice(&[0x7F, 0x07, 0x00, 0x37, 0x04, 0x00]);
+    assert_eq!(
+        image_dimensions(&webp).map(|dims| dims.to_string()),
+        Some("1920x1080".to_string())
+    );
+
+    assert_eq!(image_dimensions(b"hello"), None);
+    assert_eq!(image_dimensions(&[0xFF, 0xD8, 0xFF]), None);
+}
+
+#[derive(Default)]
+struct RecordingSink {
+    writes: std::sync::Mutex<Vec<(String, Vec<u8>)>>,
+}
+
+impl ImageSink for RecordingSink {
+    fn write(&self, name: &str, bytes: &[u8]) -> Result<StoredAt, ImageSaveError> {
+        self.writes
+            .lock()
+            .expect("writes lock")
+            .push((name.to_string(), bytes.to_vec()));
+        Ok(StoredAt::Uri(format!("memory://{name}")))
+    }
+}
+
+/// A sink whose remote store is unreachable.
+struct UnavailableSink;
+
+impl ImageSink for UnavailableSink {
+    fn write(&self, name: &str, _bytes: &[u8]) -> Result<StoredAt, ImageSaveError> {
+        Err(ImageSaveError::Sink {
+            name: name.to_string(),
+            source: "bucket unavailable".into(),
+        })
+    }
+}
+
+#[test]
+fn sink_failures_are_reported_with_the_file_name() {
+    let options = SaveImageOptions {
+        file_stem: Some("scene"),
+        ..SaveImageOptions::default()
+    };
+
+    let error =
+        save_base64_image_to("aGVsbG8=", &UnavailableSink, options).expect_err("sink fails");
+    assert!(matches!(&error, ImageSaveError::Sink { name, .. } if name == "scene.png"));
+    assert_eq!(error.to_string(), "failed to store image as scene.png");
+    let source = std::error::Error::source(&error).expect("sink error source");
+    assert_eq!(source.to_string(), "bucket unavailable");
+}
+
+#[test]
+fn save_to_sink_hands_raw_payload_and_image_to_the_sink() {
+    let sink = RecordingSink::default();
+    let options = SaveImageOptions {
+        file_stem: Some("scene"),
+        mime_type: Some("image/jpeg"),
+        save_raw: true,
+        ..SaveImageOptions::default()
+    };
+
+    let saved = save_base64_image_to("aGVsbG8=", &sink, options).expect("save to sink");
+    assert_eq!(saved.path, PathBuf::from("memory://scene.jpg"));
+    assert_eq!(saved.raw_path, Some(PathBuf::from("memory://scene.b64")));
+    assert_eq!(saved.byte_len, 5);
+    assert_eq!(
+        *sink.writes.lock().expect("writes lock"),
+        vec![
+            ("scene.b64".to_string(), b"aGVsbG8=".to_vec()),
+            ("scene.jpg".to_string(), b"hello".to_vec()),
+        ]
+    );
+}
+
+#[test]
+fn save_to_sink_prefixes_names_with_date_partition() {
+    let sink = RecordingSink::default();
+    let options = SaveImageOptions {
+        file_stem: Some("scene"),
+        organize_by_date: true,
+        ..SaveImageOptions::default()
+    };
+
+    save_base64_image_to("aGVsbG8=", &sink, options).expect("save to sink");
+    let writes = sink.writes.lock().expect("writes lock");
+    let partition = today_partition();
+    let expected = format!(
+        "{}/scene.png",
+        partition
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/")
+    );
+    assert_eq!(writes[0].0, expected);
+}
+
+#[test]
+fn filesystem_sink_creates_nested_directories() {
+    let dir = unique_temp_dir();
+    let sink = FilesystemSink::new(&dir);
+
+    let location = sink
+        .write("2024/05/01/scene.png", b"hello")
+        .expect("write through sink");
+    let path = dir.join("2024/05/01/scene.png");
+    assert_eq!(location, StoredAt::Path(path.clone()));
+    assert_eq!(fs::read(&path).expect("read written file"), b"hello");
+
+    fs::remove_dir_all(&dir).ok();
+}
+
+#[cfg(unix)]
+#[test]
+fn saved_path_survives_non_utf8_directories() {
+    use std::os::unix::ffi::OsStrExt;
+
+    let root = unique_temp_dir();
+    let dir = root.join(std::ffi::OsStr::from_bytes(b"out-\xff"));
+    let options = SaveImageOptions {
+        file_stem: Some("scene"),
+        output_dir: Some(dir.as_path()),
+        ..SaveImageOptions::default()
+    };
+
+    let saved = save_base64_image("aGVsbG8=", options).expect("save image");
+    assert_eq!(saved.path, dir.join("scene.png"));
+    assert_eq!(fs::read(&saved.path).expect("read saved image"), b"hello");
+
+    fs::remove_dir_all(&root).ok();
+}
@@ -0,0 +1,76 @@
+use image::codecs::jpeg::JpegEncoder;
+use image::{DynamicImage, ImageFormat as CodecFormat};
+use std::io::Cursor;
+
+use crate::caption::draw_caption;
+use crate::{AspectRatio, ImageDimensions, ImageFormat, ImageSaveError};
+
+/// Decode `bytes`, apply the optional crop, blur, and caption, and re-encode
+/// either in the requested format or the original one.
+pub(crate) fn transform(
+    bytes: &[u8],
+    crop_to_aspect: Option<AspectRatio>,
+    blur: Option<f32>,
+    caption: Option<&str>,
+    target_format: Option<ImageFormat>,
+) -> Result<Vec<u8>, ImageSaveError> {
+    let source_format = image::guess_format(bytes).map_err(ImageSaveError::Image)?;
+    let mut decoded =
+        image::load_from_memory_with_format(bytes, source_format).map_err(ImageSaveError::Image)?;
+
+    // Crop first so the blur and caption apply to the final frame.
+    let dimensions = ImageDimensions {
+        width: decoded.width(),
+        height: decoded.height(),
+    };
+    if let Some(region) = crop_to_aspect.and_then(|ratio| ratio.center_crop(dimensions)) {
+        decoded = decoded.crop_imm(region.x, region.y, region.width, region.height);
+    }
+
+    // Blur before the caption so the caption stays crisp.
+    if let Some(sigma) = blur {
+        decoded = decoded.blur(sigma);
+    }
+
+    if let Some(caption) = caption {
+        let has_alpha = decoded.color().has_alpha();
+        let mut canvas = decoded.to_rgba8();
+        draw_caption(&mut canvas, caption);
+        decoded = DynamicImage::ImageRgba8(canvas);
+        if !has_alpha {
+            decoded = DynamicImage::ImageRgb8(decoded.to_rgb8());
+        }
+    }
+
+    match target_format {
+        Some(ImageFormat::Png) => encode(&decoded, CodecFormat::Png),
+        Some(ImageFormat::Webp) => encode(
+            &DynamicImage::ImageRgba8(decoded.to_rgba8()),
+            CodecFormat::WebP,
+        ),
+        Some(ImageFormat::Jpeg { quality }) => encode_jpeg(&decoded, quality),
+        None => encode(&decoded, source_format),
+    }
+}
+
+pub(crate) fn encode(image: &DynamicImage, format: CodecFormat) -> Result<Vec<u8>, ImageSaveError> {
+    if format == CodecFormat::Jpeg {
+        return encode_jpeg(image, crate::DEFAULT_JPEG_QUALITY);
+    }
+
+    let mut buffer = Cursor::new(Vec::new());
+    image
+        .write_to(&mut buffer, format)
+        .map_err(ImageSaveError::Image)?;
+    Ok(buffer.into_inner())
+}
+
+fn encode_jpeg(image: &DynamicImage, quality: u8) -> Result<Vec<u8>, ImageSaveError> {
+    let mut buffer = Vec::new();
+    let encoder = JpegEncoder::new_with_quality(&mut buffer, quality.clamp(1, 100));
+    image
+        .to_rgb8()
+        .write_with_encoder(encoder)
+        .map_err(ImageSaveError::Image)?;
+    Ok(buffer)
+}
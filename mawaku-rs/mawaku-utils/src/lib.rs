@@ -1,14 +1,28 @@
-use rand::{seq::SliceRandom, thread_rng};
+use base64::Engine;
+use base64::alphabet;
+use base64::engine::general_purpose::STANDARD as BASE64_STANDARD;
+use base64::engine::{DecodePaddingMode, GeneralPurpose, GeneralPurposeConfig};
+use rand::{Rng, thread_rng};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
 
 pub const DEFAULT_FILE_NAME_PREFIX: &str = "mawaku";
 pub const DEFAULT_RANDOM_SUFFIX_LENGTH: usize = 5;
 pub const COMPONENT_MAX_LEN: usize = 10;
-const SUFFIX_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+pub const DEFAULT_SUFFIX_ALPHABET: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZ0123456789";
+pub const MAX_FILE_STEM_ATTEMPTS: usize = 32;
+/// Appended to the file name prefix when no location is given.
+pub const ABSTRACT_FILE_NAME_SEGMENT: &str = "abstract";
 
 #[derive(Debug, Clone)]
 pub struct ImageNameBuilder {
     parts: Vec<String>,
     random_suffix_length: usize,
+    suffix_alphabet: Vec<u8>,
+    timestamp: bool,
+    component_max_len: usize,
 }
 
 impl ImageNameBuilder {
@@ -16,18 +30,54 @@ impl ImageNameBuilder {
         Self {
             parts: vec![prefix.into()],
             random_suffix_length: DEFAULT_RANDOM_SUFFIX_LENGTH,
+            suffix_alphabet: DEFAULT_SUFFIX_ALPHABET.to_vec(),
+            timestamp: false,
+            component_max_len: COMPONENT_MAX_LEN,
         }
     }
 
     pub fn with_random_suffix_length(mut self, length: usize) -> Self {
-        debug_assert!(length <= SUFFIX_ALPHABET.len());
         self.random_suffix_length = length;
         self
     }
 
+    /// Use a custom set of characters for the random suffix.
+    ///
+    /// Only ASCII letters and digits are kept, once each, so the suffix can
+    /// never add a separator, path, or extension to the file name. An
+    /// alphabet with none of them falls back to [`DEFAULT_SUFFIX_ALPHABET`].
+    pub fn with_alphabet(mut self, alphabet: &[u8]) -> Self {
+        let mut filtered = Vec::with_capacity(alphabet.len());
+        for &byte in alphabet {
+            if byte.is_ascii_alphanumeric() && !filtered.contains(&byte) {
+                filtered.push(byte);
+            }
+        }
+        self.suffix_alphabet = if filtered.is_empty() {
+            DEFAULT_SUFFIX_ALPHABET.to_vec()
+        } else {
+            filtered
+        };
+        self
+    }
+
+    /// Insert a `YYYYMMDD-HHMMSS` (UTC) segment before the random suffix so
+    /// generated files sort chronologically.
+    pub fn with_timestamp(mut self, enabled: bool) -> Self {
+        self.timestamp = enabled;
+        self
+    }
+
+    /// Cap each pushed component at `max_len` characters instead of
+    /// [`COMPONENT_MAX_LEN`]. Set this before pushing components.
+    pub fn with_component_max_len(mut self, max_len: usize) -> Self {
+        self.component_max_len = max_len;
+        self
+    }
+
     pub fn push_component(&mut self, value: Option<&str>) {
         if let Some(value) = value
-            && let Some(token) = component_token(value)
+            && let Some(token) = component_token_with_max_len(value, self.component_max_len)
         {
             self.parts.push(token);
         }
@@ -38,6 +88,9 @@ impl ImageNameBuilder {
         ImageNameContext {
             base,
             random_suffix_length: self.random_suffix_length,
+            suffix_alphabet: self.suffix_alphabet,
+            timestamp: self.timestamp,
+            component_max_len: self.component_max_len,
         }
     }
 }
@@ -46,6 +99,9 @@ impl ImageNameBuilder {
 pub struct ImageNameContext {
     base: String,
     random_suffix_length: usize,
+    suffix_alphabet: Vec<u8>,
+    timestamp: bool,
+    component_max_len: usize,
 }
 
 impl ImageNameContext {
@@ -60,17 +116,219 @@ impl ImageNameContext {
         builder.build()
     }
 
+    /// The prefix and components shared by every file stem in this context.
+    pub fn base(&self) -> &str {
+        &self.base
+    }
+
+    /// This context with `value` appended as one more component, for a
+    /// variant of the scene's images such as an avatar. It is capped at the
+    /// same length as the components the context was built with.
+    pub fn with_component(&self, value: &str) -> Self {
+        let mut context = self.clone();
+        if let Some(token) = component_token_with_max_len(value, self.component_max_len) {
+            context.base = format!("{}-{token}", context.base);
+        }
+        context
+    }
+
     pub fn file_stem(&self, index: usize) -> String {
-        let suffix = unique_suffix(self.random_suffix_length);
-        format!("{}-p{}-{}", self.base, index, suffix)
+        let suffix = unique_suffix(&self.suffix_alphabet, self.random_suffix_length);
+        if self.timestamp {
+            let stamp = format_timestamp(unix_timestamp_secs());
+            format!("{}-p{}-{}-{}", self.base, index, stamp, suffix)
+        } else {
+            format!("{}-p{}-{}", self.base, index, suffix)
+        }
     }
+
+    /// Generate a file stem that is not already present in `existing`.
+    ///
+    /// The random suffix is regenerated up to [`MAX_FILE_STEM_ATTEMPTS`]
+    /// times; `None` is returned if every attempt collided.
+    pub fn file_stem_avoiding(&self, index: usize, existing: &HashSet<String>) -> Option<String> {
+        (0..MAX_FILE_STEM_ATTEMPTS)
+            .map(|_| self.file_stem(index))
+            .find(|stem| !existing.contains(stem))
+    }
+}
+
+/// The naming context for a scene: `prefix` (followed by
+/// [`ABSTRACT_FILE_NAME_SEGMENT`] when no location is given), then one
+/// component per location, the season, the time of day, the weather, and the
+/// theme.
+pub fn build_image_name_context(
+    prefix: &str,
+    locations: &[&str],
+    season: Option<&str>,
+    time_of_day: Option<&str>,
+    weather: Option<&str>,
+    theme: Option<&str>,
+) -> ImageNameContext {
+    let has_location = locations
+        .iter()
+        .any(|location| trimmed_or_none(Some(location)).is_some());
+    let prefix = if has_location {
+        prefix.to_string()
+    } else {
+        format!("{prefix}-{ABSTRACT_FILE_NAME_SEGMENT}")
+    };
+    let mut builder = ImageNameBuilder::new(prefix);
+    for location in locations {
+        builder.push_component(Some(location));
+    }
+    builder.push_component(season);
+    builder.push_component(time_of_day);
+    builder.push_component(weather);
+    builder.push_component(theme);
+    builder.build()
+}
+
+/// The pieces of a file stem produced by [`ImageNameContext::file_stem`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParsedImageName {
+    pub prefix: String,
+    /// The components pushed with [`ImageNameBuilder::push_component`], in
+    /// order and as encoded: slugified, truncated, and with the words of a
+    /// multi-word component joined by `_` (such as `midnight_s`).
+    pub components: Vec<String>,
+    pub index: usize,
+    pub timestamp: Option<String>,
+    pub suffix: String,
+}
+
+impl ParsedImageName {
+    /// The prefix and components joined back together, as encoded in the stem.
+    pub fn base(&self) -> String {
+        std::iter::once(self.prefix.as_str())
+            .chain(self.components.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("-")
+    }
+}
+
+/// Split a file stem such as `mawaku-hakone_jap-spring-dusk-p1-AB3KZ` back
+/// into its prefix, components, prediction index, optional timestamp, and
+/// suffix.
+///
+/// The prediction index marker is located from the right so components that
+/// look like `p2` cannot be mistaken for it.
+pub fn parse_image_name(stem: &str) -> Option<ParsedImageName> {
+    let tokens: Vec<&str> = stem.split('-').collect();
+    if tokens.iter().any(|token| token.is_empty()) {
+        return None;
+    }
+
+    let marker = tokens
+        .iter()
+        .enumerate()
+        .rev()
+        .skip(1)
+        .find_map(|(position, token)| {
+            let trailing = tokens.len() - position - 1;
+            let index = token.strip_prefix('p')?.parse::<usize>().ok()?;
+            let timestamp = match trailing {
+                1 => None,
+                3 if is_timestamp(tokens[position + 1], tokens[position + 2]) => {
+                    Some(format!("{}-{}", tokens[position + 1], tokens[position + 2]))
+                }
+                _ => return None,
+            };
+            Some((position, index, timestamp))
+        })?;
+
+    let (position, index, timestamp) = marker;
+    if position == 0 {
+        return None;
+    }
+
+    Some(ParsedImageName {
+        prefix: tokens[0].to_string(),
+        components: tokens[1..position]
+            .iter()
+            .map(|token| token.to_string())
+            .collect(),
+        index,
+        timestamp,
+        suffix: tokens[tokens.len() - 1].to_string(),
+    })
+}
+
+fn is_timestamp(date: &str, time: &str) -> bool {
+    date.len() == 8
+        && time.len() == 6
+        && date
+            .chars()
+            .chain(time.chars())
+            .all(|ch| ch.is_ascii_digit())
+}
+
+/// Collect the file stems of every entry in `dir`.
+///
+/// A missing or unreadable directory yields an empty set.
+pub fn existing_file_stems(dir: &Path) -> HashSet<String> {
+    let Ok(entries) = fs::read_dir(dir) else {
+        return HashSet::new();
+    };
+
+    entries
+        .filter_map(Result::ok)
+        .filter_map(|entry| {
+            entry
+                .path()
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+        })
+        .collect()
 }
 
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "con", "prn", "aux", "nul", "com1", "com2", "com3", "com4", "com5", "com6", "com7", "com8",
+    "com9", "lpt1", "lpt2", "lpt3", "lpt4", "lpt5", "lpt6", "lpt7", "lpt8", "lpt9",
+];
+
 pub fn component_token(input: &str) -> Option<String> {
-    slugify(input).map(|slug| truncate_component(&slug))
+    component_token_with_max_len(input, COMPONENT_MAX_LEN)
+}
+
+/// Like [`component_token`], capped at `max_len` characters. Words inside
+/// the component are joined with `_`, so `-` only ever separates components
+/// and [`parse_image_name`] can recover them.
+pub fn component_token_with_max_len(input: &str, max_len: usize) -> Option<String> {
+    slugify(input)
+        .map(|slug| truncate_component_to(&slug, max_len).replace('-', "_"))
+        .map(|token| sanitize_component(&token))
 }
 
+/// Escape a slugified component that is a reserved Windows device name,
+/// such as `con` or `lpt1`, with a trailing underscore so it is a valid file
+/// name on every platform.
+pub fn sanitize_component(component: &str) -> String {
+    if is_windows_reserved_name(component) {
+        format!("{component}_")
+    } else {
+        component.to_string()
+    }
+}
+
+fn is_windows_reserved_name(base: &str) -> bool {
+    WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|reserved| reserved.eq_ignore_ascii_case(base))
+}
+
+/// Lowercase `input` and collapse everything but ASCII letters and digits
+/// into single hyphens.
+///
+/// With the `unicode` feature enabled, accented and non-Latin characters are
+/// transliterated to their closest ASCII equivalent first, so "São Paulo"
+/// becomes `sao-paulo` instead of `s-o-paulo`.
 pub fn slugify(input: &str) -> Option<String> {
+    #[cfg(feature = "unicode")]
+    let transliterated = deunicode::deunicode(input);
+    #[cfg(feature = "unicode")]
+    let input = transliterated.as_str();
+
     let mut slug = String::new();
     let mut last_was_separator = false;
 
@@ -93,12 +351,24 @@ pub fn slugify(input: &str) -> Option<String> {
     if slug.is_empty() { None } else { Some(slug) }
 }
 
+/// Slugify a file name prefix into a single hyphen-free word, so the prefix
+/// is always the first `-`-separated token of a file name and one prefix can
+/// never be mistaken for the start of another: "Client Work" becomes
+/// `clientwork`.
+pub fn prefix_slug(input: &str) -> Option<String> {
+    slugify(input).map(|slug| slug.replace('-', ""))
+}
+
 pub fn truncate_component(slug: &str) -> String {
-    if slug.len() <= COMPONENT_MAX_LEN {
+    truncate_component_to(slug, COMPONENT_MAX_LEN)
+}
+
+pub fn truncate_component_to(slug: &str, max_len: usize) -> String {
+    if slug.chars().count() <= max_len {
         return slug.to_string();
     }
 
-    let truncated: String = slug.chars().take(COMPONENT_MAX_LEN).collect();
+    let truncated: String = slug.chars().take(max_len).collect();
     let trimmed = truncated.trim_end_matches('-').to_string();
     if trimmed.is_empty() {
         truncated
@@ -107,25 +377,104 @@ pub fn truncate_component(slug: &str) -> String {
     }
 }
 
-fn unique_suffix(length: usize) -> String {
-    debug_assert!(length <= SUFFIX_ALPHABET.len());
+fn unique_suffix(alphabet: &[u8], length: usize) -> String {
+    if alphabet.is_empty() {
+        return String::new();
+    }
+
     let mut rng = thread_rng();
-    SUFFIX_ALPHABET
-        .choose_multiple(&mut rng, length)
-        .copied()
-        .map(char::from)
+    (0..length)
+        .map(|_| char::from(alphabet[rng.gen_range(0..alphabet.len())]))
         .collect()
 }
 
+/// Format seconds since the Unix epoch as a zero-padded `YYYYMMDD-HHMMSS`
+/// string in UTC.
+pub fn format_timestamp(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let seconds_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        seconds_of_day / 3_600,
+        (seconds_of_day % 3_600) / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// Format seconds since the Unix epoch as an ISO 8601 `YYYY-MM-DDTHH:MM:SSZ`
+/// string in UTC.
+pub fn format_iso8601(secs: u64) -> String {
+    let days = (secs / 86_400) as i64;
+    let seconds_of_day = secs % 86_400;
+    let (year, month, day) = civil_from_days(days);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        seconds_of_day / 3_600,
+        (seconds_of_day % 3_600) / 60,
+        seconds_of_day % 60
+    )
+}
+
+/// The `YYYY/MM/DD` subdirectory (UTC) for a moment given in seconds since
+/// the Unix epoch.
+pub fn date_partition(secs: u64) -> PathBuf {
+    let (year, month, day) = civil_from_days((secs / 86_400) as i64);
+    [
+        format!("{year:04}"),
+        format!("{month:02}"),
+        format!("{day:02}"),
+    ]
+    .iter()
+    .collect()
+}
+
+/// The [`date_partition`] for the current day.
+pub fn today_partition() -> PathBuf {
+    date_partition(unix_timestamp_secs())
+}
+
+/// Seconds since the Unix epoch, or `0` if the clock is set before it.
+pub fn unix_timestamp_secs() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+// Converts days since 1970-01-01 into a proleptic Gregorian (year, month, day).
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month, day)
+}
+
+/// `input` with surrounding whitespace removed, or `None` when nothing is
+/// left. Prompt builders use this to skip blank user values.
+pub fn normalized(input: &str) -> Option<&str> {
+    let trimmed = input.trim();
+    if trimmed.is_empty() {
+        None
+    } else {
+        Some(trimmed)
+    }
+}
+
+/// [`normalized`] for optional values.
 pub fn trimmed_or_none(input: Option<&str>) -> Option<&str> {
-    input.and_then(|value| {
-        let trimmed = value.trim();
-        if trimmed.is_empty() {
-            None
-        } else {
-            Some(trimmed)
-        }
-    })
+    input.and_then(normalized)
 }
 
 pub fn list_or_unspecified<I, S>(items: I) -> String
@@ -156,14 +505,68 @@ pub fn format_context_line(label: &str, value: Option<&str>) -> String {
     }
 }
 
+const PADDING_INDIFFERENT: GeneralPurposeConfig =
+    GeneralPurposeConfig::new().with_decode_padding_mode(DecodePaddingMode::Indifferent);
+/// Fallbacks tried by [`decode_base64`] after the standard engine, in order.
+const BASE64_FALLBACKS: [GeneralPurpose; 2] = [
+    GeneralPurpose::new(&alphabet::URL_SAFE, PADDING_INDIFFERENT),
+    GeneralPurpose::new(&alphabet::STANDARD, PADDING_INDIFFERENT),
+];
+
+/// Decode an image payload as standard base64, falling back to the URL-safe
+/// alphabet (`-` and `_`) and to unpadded input. When every engine fails, the
+/// standard engine's error is returned.
+pub fn decode_base64(encoded: &str) -> Result<Vec<u8>, base64::DecodeError> {
+    BASE64_STANDARD.decode(encoded).or_else(|error| {
+        BASE64_FALLBACKS
+            .iter()
+            .find_map(|engine| engine.decode(encoded).ok())
+            .ok_or(error)
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn decode_base64_accepts_url_safe_and_unpadded_payloads() {
+        let bytes = [0xfb, 0xff, 0xbf, 0x00];
+        assert_eq!(decode_base64("+/+/AA==").as_deref(), Ok(&bytes[..]));
+        assert_eq!(decode_base64("-_-_AA==").as_deref(), Ok(&bytes[..]));
+        assert_eq!(decode_base64("-_-_AA").as_deref(), Ok(&bytes[..]));
+        assert_eq!(decode_base64("+/+/AA").as_deref(), Ok(&bytes[..]));
+        assert!(decode_base64("not base64!").is_err());
+    }
+
     #[test]
     fn component_token_slugifies_input() {
         let token = component_token("Hakone, Japan");
-        assert_eq!(token.as_deref(), Some("hakone-jap"));
+        assert_eq!(token.as_deref(), Some("hakone_jap"));
+    }
+
+    #[test]
+    fn scene_name_context_orders_components_and_marks_abstract_scenes() {
+        let context = build_image_name_context(
+            "mawaku",
+            &["Kyoto", "Oslo"],
+            Some("autumn"),
+            Some("dusk"),
+            None,
+            Some("japandi"),
+        );
+        assert_eq!(context.base(), "mawaku-kyoto-oslo-autumn-dusk-japandi");
+
+        let context = build_image_name_context("desk", &["  "], None, None, Some("rain"), None);
+        assert_eq!(context.base(), "desk-abstract-rain");
+    }
+
+    #[test]
+    fn with_component_appends_a_variant_segment() {
+        let context = build_image_name_context("mawaku", &["Kyoto"], None, None, None, None);
+        let avatar = context.with_component("Avatar");
+        assert_eq!(avatar.base(), "mawaku-kyoto-avatar");
+        assert_eq!(context.with_component(" ! ").base(), "mawaku-kyoto");
     }
 
     #[test]
@@ -172,6 +575,75 @@ mod tests {
         assert_eq!(slug.as_deref(), Some("hakone-japan"));
     }
 
+    #[test]
+    fn prefix_slug_is_a_single_token() {
+        assert_eq!(prefix_slug("Client Work").as_deref(), Some("clientwork"));
+        assert_eq!(prefix_slug("mawaku").as_deref(), Some("mawaku"));
+        assert_eq!(prefix_slug("***"), None);
+    }
+
+    #[cfg(not(feature = "unicode"))]
+    #[test]
+    fn slugify_replaces_non_ascii_without_unicode_feature() {
+        assert_eq!(slugify("São Paulo").as_deref(), Some("s-o-paulo"));
+        assert_eq!(prefix_slug("São Paulo").as_deref(), Some("sopaulo"));
+    }
+
+    #[cfg(feature = "unicode")]
+    #[test]
+    fn slugify_transliterates_with_unicode_feature() {
+        assert_eq!(slugify("Kyōto").as_deref(), Some("kyoto"));
+        assert_eq!(slugify("São Paulo").as_deref(), Some("sao-paulo"));
+        assert_eq!(prefix_slug("São Paulo").as_deref(), Some("saopaulo"));
+    }
+
+    #[test]
+    fn component_token_escapes_windows_reserved_names() {
+        assert_eq!(component_token("CON").as_deref(), Some("con_"));
+        assert_eq!(component_token("lpt9").as_deref(), Some("lpt9_"));
+        assert_eq!(component_token("Console").as_deref(), Some("console"));
+    }
+
+    #[test]
+    fn sanitize_component_escapes_reserved_names() {
+        assert_eq!(sanitize_component("nul"), "nul_");
+        assert_eq!(sanitize_component("LPT1"), "LPT1_");
+        assert_eq!(sanitize_component("nullify"), "nullify");
+        assert_eq!(sanitize_component("con-island"), "con-island");
+    }
+
+    #[test]
+    fn builder_respects_custom_component_max_len() {
+        let mut builder =
+            ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX).with_component_max_len(13);
+        builder.push_component(Some("San Francisco, USA"));
+        builder.push_component(Some("Late Winter"));
+        let context = builder.build();
+        assert_eq!(context.base, "mawaku-san_francisco-late_winter");
+    }
+
+    #[test]
+    fn with_component_respects_custom_component_max_len() {
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_component_max_len(4)
+            .build();
+        assert_eq!(context.with_component("seed12345").base(), "mawaku-seed");
+
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_component_max_len(13)
+            .build();
+        assert_eq!(
+            context.with_component("Golden Retriever").base(),
+            "mawaku-golden_retrie"
+        );
+    }
+
+    #[test]
+    fn truncate_component_to_trims_trailing_hyphen() {
+        assert_eq!(truncate_component_to("san-francisco", 4), "san");
+        assert_eq!(truncate_component_to("san-francisco", 40), "san-francisco");
+    }
+
     #[test]
     fn builder_discards_empty_components() {
         let mut builder = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX);
@@ -191,4 +663,163 @@ mod tests {
             .expect("file stem contains random suffix");
         assert_eq!(suffix.len(), DEFAULT_RANDOM_SUFFIX_LENGTH);
     }
+
+    #[test]
+    fn file_stem_uses_custom_alphabet_and_length() {
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_alphabet(b"0123456789abcdef")
+            .with_random_suffix_length(48)
+            .build();
+        let stem = context.file_stem(1);
+        let (_, suffix) = stem
+            .rsplit_once('-')
+            .expect("file stem contains random suffix");
+        assert_eq!(suffix.len(), 48);
+        assert!(suffix.chars().all(|ch| ch.is_ascii_hexdigit()));
+        assert!(!suffix.chars().any(|ch| ch.is_ascii_uppercase()));
+    }
+
+    #[test]
+    fn alphabet_keeps_only_distinct_ascii_alphanumerics() {
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_alphabet("a-/.\u{e9}aa".as_bytes())
+            .with_random_suffix_length(6)
+            .build();
+        assert_eq!(context.file_stem(1), "mawaku-p1-aaaaaa");
+
+        let builder = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX).with_alphabet(b"AbAb9-9");
+        assert_eq!(builder.suffix_alphabet, b"Ab9");
+
+        let fallback = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX).with_alphabet(b"-./");
+        assert_eq!(fallback.suffix_alphabet, DEFAULT_SUFFIX_ALPHABET);
+    }
+
+    #[test]
+    fn suffix_samples_with_replacement() {
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_alphabet(b"Z")
+            .with_random_suffix_length(4)
+            .build();
+        assert_eq!(context.file_stem(1), "mawaku-p1-ZZZZ");
+    }
+
+    #[test]
+    fn file_stem_avoiding_skips_existing_names() {
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_alphabet(b"AB")
+            .with_random_suffix_length(1)
+            .build();
+        let existing: HashSet<String> = ["mawaku-p1-A".to_string()].into_iter().collect();
+        assert_eq!(
+            context.file_stem_avoiding(1, &existing).as_deref(),
+            Some("mawaku-p1-B")
+        );
+    }
+
+    #[test]
+    fn file_stem_avoiding_gives_up_when_exhausted() {
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_alphabet(b"A")
+            .with_random_suffix_length(1)
+            .build();
+        let existing: HashSet<String> = ["mawaku-p1-A".to_string()].into_iter().collect();
+        assert!(context.file_stem_avoiding(1, &existing).is_none());
+    }
+
+    #[test]
+    fn existing_file_stems_reads_directory() {
+        let dir =
+            std::env::temp_dir().join(format!("mawaku-utils-test-stems-{}", std::process::id()));
+        fs::create_dir_all(&dir).expect("create temp directory");
+        fs::write(dir.join("mawaku-p1-ABCDE.png"), b"").expect("write fixture");
+
+        let stems = existing_file_stems(&dir);
+        assert!(stems.contains("mawaku-p1-ABCDE"));
+        assert!(existing_file_stems(&dir.join("missing")).is_empty());
+
+        fs::remove_dir_all(&dir).ok();
+    }
+
+    #[test]
+    fn parse_image_name_round_trips_file_stem() {
+        let mut builder = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX);
+        builder.push_component(Some("Hakone, Japan"));
+        builder.push_component(Some("Spring"));
+        builder.push_component(Some("Midnight Sun"));
+        let context = builder.build();
+        let stem = context.file_stem(2);
+
+        let parsed = parse_image_name(&stem).expect("stem parses");
+        assert_eq!(parsed.prefix, DEFAULT_FILE_NAME_PREFIX);
+        assert_eq!(parsed.base(), context.base);
+        assert_eq!(parsed.base(), "mawaku-hakone_jap-spring-midnight_s");
+        assert_eq!(
+            parsed.components,
+            vec!["hakone_jap", "spring", "midnight_s"]
+        );
+        assert_eq!(parsed.index, 2);
+        assert_eq!(parsed.timestamp, None);
+        assert_eq!(parsed.suffix.len(), DEFAULT_RANDOM_SUFFIX_LENGTH);
+        assert!(stem.ends_with(&parsed.suffix));
+    }
+
+    #[test]
+    fn parse_image_name_handles_timestamp_and_index_like_components() {
+        let parsed =
+            parse_image_name("mawaku-p2-dusk-p10-20240301-000005-AB3KZ").expect("stem parses");
+        assert_eq!(parsed.components, vec!["p2", "dusk"]);
+        assert_eq!(parsed.index, 10);
+        assert_eq!(parsed.timestamp.as_deref(), Some("20240301-000005"));
+        assert_eq!(parsed.suffix, "AB3KZ");
+    }
+
+    #[test]
+    fn parse_image_name_rejects_unrelated_stems() {
+        assert!(parse_image_name("holiday-photo").is_none());
+        assert!(parse_image_name("p1-ABCDE").is_none());
+        assert!(parse_image_name("mawaku--p1-ABCDE").is_none());
+    }
+
+    #[test]
+    fn format_timestamp_pads_components() {
+        assert_eq!(format_timestamp(0), "19700101-000000");
+        assert_eq!(format_timestamp(1_709_251_205), "20240301-000005");
+        assert_eq!(format_timestamp(1_798_761_599), "20261231-235959");
+    }
+
+    #[test]
+    fn format_iso8601_uses_utc_calendar_fields() {
+        assert_eq!(format_iso8601(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_iso8601(1_709_251_205), "2024-03-01T00:00:05Z");
+    }
+
+    #[test]
+    fn date_partition_nests_year_month_day() {
+        assert_eq!(
+            date_partition(1_709_251_205),
+            Path::new("2024").join("03").join("01")
+        );
+        assert_eq!(date_partition(0), Path::new("1970").join("01").join("01"));
+    }
+
+    #[test]
+    fn file_stem_places_timestamp_before_suffix() {
+        let context = ImageNameBuilder::new(DEFAULT_FILE_NAME_PREFIX)
+            .with_timestamp(true)
+            .build();
+        let stem = context.file_stem(3);
+        let rest = stem
+            .strip_prefix("mawaku-p3-")
+            .expect("stem keeps prefix and index");
+        let (stamp, suffix) = rest.rsplit_once('-').expect("suffix separator");
+        let (date, time) = stamp.split_once('-').expect("timestamp separator");
+        assert_eq!(date.len(), 8);
+        assert_eq!(time.len(), 6);
+        assert!(
+            date.chars()
+                .chain(time.chars())
+                .all(|ch| ch.is_ascii_digit())
+        );
+        assert_eq!(suffix.len(), DEFAULT_RANDOM_SUFFIX_LENGTH);
+    }
 }